@@ -1,14 +1,41 @@
+/// Contains the counters and timers for the API calls.
+pub(crate) mod api_stats;
+
 /// Contains constant values which are used as arguments to functions and in log messages.
 pub(crate) mod constants;
 
+/// Contains the drain label which the storage Nodes are drained with for the upgrade.
+pub(crate) mod drain_label;
+
 /// Contains the error handling tooling.
 pub(crate) mod error;
 
+/// Contains the initialization of the upgrade-job's logging.
+pub(crate) mod logging;
+
+/// Contains the HTTP health probe endpoint for the upgrade-job's Pod.
+pub(crate) mod health;
+
 /// Contains tools to create Kubernetes API clients.
 pub(crate) mod kube_client;
 
 /// Contains macros.
 pub(crate) mod macros;
 
+/// Contains the discovery of the namespace of the install.
+pub(crate) mod namespace;
+
+/// Contains the Prometheus metrics endpoint for the upgrade's progress.
+pub(crate) mod metrics;
+
+/// Contains the in-flight state of the upgrade.
+pub(crate) mod progress;
+
+/// Contains the state which is shared by the whole upgrade run.
+pub(crate) mod run;
+
 /// Contains tools to create storage API clients.
 pub(crate) mod rest_client;
+
+/// Contains the timeline of the upgrade's events.
+pub(crate) mod timeline;
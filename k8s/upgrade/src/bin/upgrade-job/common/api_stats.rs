@@ -0,0 +1,176 @@
+use crate::common::timeline::{Timeline, TimelineEvent};
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    future::Future,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Mutex,
+    },
+    time::{Duration, Instant},
+};
+
+/// These are the names of the API calls which are counted and timed.
+pub(crate) const GET_NODE: &str = "get_node";
+pub(crate) const GET_NODES: &str = "get_nodes";
+pub(crate) const PUT_NODE_DRAIN: &str = "put_node_drain";
+pub(crate) const PUT_NODE_CORDON: &str = "put_node_cordon";
+pub(crate) const DELETE_NODE_CORDON: &str = "delete_node_cordon";
+pub(crate) const GET_VOLUMES: &str = "get_volumes";
+pub(crate) const PUT_VOLUME: &str = "put_volume";
+pub(crate) const DELETE_VOLUME: &str = "delete_volume";
+pub(crate) const GET_REPLICAS: &str = "get_replicas";
+pub(crate) const GET_POOLS: &str = "get_pools";
+pub(crate) const GET_SNAPSHOTS: &str = "get_snapshots";
+pub(crate) const LIST_PODS: &str = "list_pods";
+pub(crate) const DELETE_POD: &str = "delete_pod";
+pub(crate) const EVICT_POD: &str = "evict_pod";
+pub(crate) const GET_POD: &str = "get_pod";
+pub(crate) const GET_POD_LOGS: &str = "get_pod_logs";
+pub(crate) const CREATE_EVENT: &str = "create_event";
+pub(crate) const GET_CONFIG_MAP: &str = "get_config_map";
+pub(crate) const APPLY_CONFIG_MAP: &str = "apply_config_map";
+pub(crate) const LIST_DAEMONSETS: &str = "list_daemonsets";
+pub(crate) const LIST_DEPLOYMENTS: &str = "list_deployments";
+pub(crate) const PATCH_DEPLOYMENT: &str = "patch_deployment";
+pub(crate) const CREATE_ACCESS_REVIEW: &str = "create_access_review";
+pub(crate) const GET_K8S_NODE: &str = "get_k8s_node";
+pub(crate) const LIST_K8S_NODES: &str = "list_k8s_nodes";
+pub(crate) const PATCH_K8S_NODE: &str = "patch_k8s_node";
+pub(crate) const LIST_PERSISTENT_VOLUMES: &str = "list_persistent_volumes";
+pub(crate) const LIST_VOLUME_ATTACHMENTS: &str = "list_volume_attachments";
+pub(crate) const GET_LEASE: &str = "get_lease";
+pub(crate) const CREATE_LEASE: &str = "create_lease";
+pub(crate) const REPLACE_LEASE: &str = "replace_lease";
+
+/// These are the counts and the latencies of the API calls which were made with the API clients
+/// which share it. The Kubernetes and the storage REST API clients of an upgrade share one, so
+/// that it may be summarized in the upgrade report.
+#[derive(Default, Debug)]
+pub(crate) struct ApiStats {
+    /// The latencies of every API call, grouped by the name of the API call.
+    latencies: Mutex<BTreeMap<&'static str, Vec<Duration>>>,
+    /// The number of Kubernetes API calls which have been made so far.
+    kubernetes_calls: AtomicUsize,
+    /// The number of storage REST API calls which have been made so far.
+    rest_calls: AtomicUsize,
+    /// The timeline which every API call is recorded in.
+    timeline: Timeline,
+}
+
+impl ApiStats {
+    /// Creates the stats, which record every API call in 'timeline' too.
+    pub(crate) fn new(timeline: Timeline) -> Self {
+        Self {
+            timeline,
+            ..Default::default()
+        }
+    }
+
+    /// This awaits on a Kubernetes API call's Future and records its latency against the name of
+    /// the call.
+    pub(crate) async fn timed<F, T>(&self, api_call: &'static str, call: F) -> T
+    where
+        F: Future<Output = T>,
+    {
+        self.kubernetes_calls.fetch_add(1, Ordering::Relaxed);
+        self.record_latency(api_call, call).await
+    }
+
+    /// This awaits on a storage REST API call's Future and records its latency against the name
+    /// of the call.
+    pub(crate) async fn timed_rest<F, T>(&self, api_call: &'static str, call: F) -> T
+    where
+        F: Future<Output = T>,
+    {
+        self.rest_calls.fetch_add(1, Ordering::Relaxed);
+        self.record_latency(api_call, call).await
+    }
+
+    /// This awaits on an API call's Future and records its latency against the name of the call.
+    async fn record_latency<F, T>(&self, api_call: &'static str, call: F) -> T
+    where
+        F: Future<Output = T>,
+    {
+        let start = Instant::now();
+        let output = call.await;
+        let latency = start.elapsed();
+
+        if let Ok(mut latencies) = self.latencies.lock() {
+            latencies.entry(api_call).or_default().push(latency);
+        }
+        self.timeline.record(TimelineEvent::ApiCall {
+            api_call: api_call.to_string(),
+            latency_ms: millis(latency),
+        });
+
+        output
+    }
+
+    /// Returns the number of Kubernetes and storage REST API calls which have been made so far.
+    pub(crate) fn call_counts(&self) -> ApiCallCounts {
+        ApiCallCounts {
+            kubernetes: self.kubernetes_calls.load(Ordering::Relaxed),
+            rest: self.rest_calls.load(Ordering::Relaxed),
+        }
+    }
+
+    /// Generates the summary of all of the API calls which have been made so far.
+    pub(crate) fn summary(&self) -> Vec<ApiCallSummary> {
+        let latencies = match self.latencies.lock() {
+            Ok(latencies) => latencies,
+            Err(_) => return Vec::new(),
+        };
+
+        latencies
+            .iter()
+            .map(|(&api_call, latencies)| {
+                let mut sorted = latencies.clone();
+                sorted.sort();
+                ApiCallSummary {
+                    api_call,
+                    count: sorted.len(),
+                    total_ms: millis(sorted.iter().sum()),
+                    p50_ms: millis(percentile(&sorted, 50)),
+                    p90_ms: millis(percentile(&sorted, 90)),
+                    p99_ms: millis(percentile(&sorted, 99)),
+                    max_ms: millis(sorted.last().copied().unwrap_or_default()),
+                }
+            })
+            .collect()
+    }
+}
+
+/// This is the count and the aggregate latencies (in milliseconds) for one API call.
+#[derive(Serialize, Debug)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub(crate) struct ApiCallSummary {
+    api_call: &'static str,
+    count: usize,
+    total_ms: f64,
+    p50_ms: f64,
+    p90_ms: f64,
+    p99_ms: f64,
+    max_ms: f64,
+}
+
+/// This is the number of API calls which have been made so far, per API.
+#[derive(Serialize, Default, Debug)]
+pub(crate) struct ApiCallCounts {
+    pub(crate) kubernetes: usize,
+    pub(crate) rest: usize,
+}
+
+/// Nearest-rank percentile of a sorted list of latencies.
+fn percentile(sorted: &[Duration], percent: usize) -> Duration {
+    if sorted.is_empty() {
+        return Duration::ZERO;
+    }
+    let rank = (percent * sorted.len() + 99) / 100;
+    sorted[rank.clamp(1, sorted.len()) - 1]
+}
+
+/// Converts a Duration to a fractional number of milliseconds.
+fn millis(duration: Duration) -> f64 {
+    duration.as_secs_f64() * 1000.0
+}
@@ -11,13 +11,31 @@ pub(crate) const CORE_CHART_NAME: &str = "mayastor";
 /// This is the shared Pod label of the <helm-release>-io-engine DaemonSet.
 pub(crate) const IO_ENGINE_LABEL: &str = "app=io-engine";
 
+/// This is the name of the io-engine container in the io-engine Pods.
+pub(crate) const IO_ENGINE_CONTAINER_NAME: &str = "io-engine";
+
+/// This is the shared Pod label of the <helm-release>-csi-node DaemonSet.
+pub(crate) const CSI_NODE_LABEL: &str = "app=csi-node";
+
 /// This is the shared Pod label of the <helm-release>-agent-core Deployment.
 pub(crate) const AGENT_CORE_LABEL: &str = "app=agent-core";
 
 /// This is the shared label across the helm chart components which carries the chart version.
 pub(crate) const CHART_VERSION_LABEL_KEY: &str = "openebs.io/version";
 
-/// This is the label set on a storage API Node resource when a 'Node Drain' is issued.
+/// This is the annotation which pins a Kubernetes Node to the version it is on. The data-plane
+/// upgrade skips the Nodes which have it set to "true".
+pub(crate) const UPGRADE_SKIP_ANNOTATION_KEY: &str = "openebs.io/upgrade-skip";
+
+/// This is the annotation which carries the time at which the cordon for upgrade of a Kubernetes
+/// Node's storage Node expires, as an RFC 3339 timestamp, if the upgrade is run with a cordon TTL.
+pub(crate) const CORDON_EXPIRY_ANNOTATION_KEY: &str = "openebs.io/upgrade-cordon-expiry";
+
+/// This is the label which the DaemonSet controller sets on its Pods to identify the
+/// ControllerRevision (i.e. the Pod template) that the Pod was created from.
+pub(crate) const CONTROLLER_REVISION_HASH_LABEL_KEY: &str = "controller-revision-hash";
+
+/// This is the default label set on a storage API Node resource when a 'Node Drain' is issued.
 pub(crate) const DRAIN_FOR_UPGRADE: &str = "mayastor-upgrade";
 
 /// This is the allowed upgrade to-version/to-version-range for the Umbrella chart.
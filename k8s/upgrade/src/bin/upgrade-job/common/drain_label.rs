@@ -0,0 +1,43 @@
+/// These are the labels which an upgrade run drains and cordons the storage Nodes with, i.e. the
+/// drain label, and the extra drain labels which the Nodes are drained with alongside it, e.g. for
+/// external tooling which watches for them. The Nodes are uncordoned with the same labels which
+/// they were drained with.
+#[derive(Clone, Debug, Eq, PartialEq)]
+pub(crate) struct DrainLabels {
+    drain_label: String,
+    extra_drain_labels: Vec<String>,
+}
+
+impl DrainLabels {
+    /// Creates the DrainLabels with the drain label 'drain_label' and the extra drain labels
+    /// 'extra_drain_labels'. The drain label itself, and any repeated labels, are left out of the
+    /// extra drain labels.
+    pub(crate) fn new(drain_label: String, extra_drain_labels: Vec<String>) -> Self {
+        let mut extra_labels: Vec<String> = Vec::with_capacity(extra_drain_labels.len());
+        for label in extra_drain_labels {
+            if label.ne(&drain_label) && !extra_labels.contains(&label) {
+                extra_labels.push(label);
+            }
+        }
+        Self {
+            drain_label,
+            extra_drain_labels: extra_labels,
+        }
+    }
+
+    /// Returns the drain label of the upgrade run.
+    pub(crate) fn drain_label(&self) -> &str {
+        self.drain_label.as_str()
+    }
+
+    /// Returns the extra drain labels of the upgrade run.
+    pub(crate) fn extra_drain_labels(&self) -> &[String] {
+        self.extra_drain_labels.as_slice()
+    }
+
+    /// Returns true if 'label' is the drain label or one of the extra drain labels of the upgrade
+    /// run.
+    pub(crate) fn is_upgrade_drain_label(&self, label: &str) -> bool {
+        label.eq(self.drain_label()) || self.extra_drain_labels.iter().any(|extra| extra.eq(label))
+    }
+}
@@ -1,12 +1,12 @@
 use crate::{
     common::constants::{
-        CHART_VERSION_LABEL_KEY, CORE_CHART_NAME, PRODUCT, TO_UMBRELLA_SEMVER, UMBRELLA_CHART_NAME,
+        CORE_CHART_NAME, PRODUCT, TO_UMBRELLA_SEMVER, UMBRELLA_CHART_NAME,
         UMBRELLA_CHART_UPGRADE_DOCS_URL,
     },
     events::event_recorder::EventNote,
 };
 use snafu::Snafu;
-use std::path::PathBuf;
+use std::{path::PathBuf, time::Duration};
 use url::Url;
 
 /// For use with multiple fallible operations which may fail for different reasons, but are
@@ -28,6 +28,18 @@ pub(crate) enum Error {
         rest_endpoint: String,
     },
 
+    /// Error for when the storage REST API bearer token can't be read from its file.
+    #[snafu(display(
+        "Failed to read the {} REST API bearer token from {}: {}",
+        PRODUCT,
+        path.display(),
+        source
+    ))]
+    ReadRestTokenFile {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+
     /// Error for when Kubernetes API client generation fails.
     #[snafu(display("Failed to generate kubernetes client: {}", source))]
     K8sClientGeneration { source: kube_client::Error },
@@ -52,6 +64,19 @@ pub(crate) enum Error {
         rest_endpoint: Url,
     },
 
+    /// Error for when the storage REST API can't be reached, e.g. because the endpoint is wrong
+    /// or the api-rest service is down.
+    #[snafu(display(
+        "Failed to reach the {} REST API at {}: {}",
+        PRODUCT,
+        rest_endpoint,
+        source
+    ))]
+    RestEndpointUnreachable {
+        source: openapi::tower::client::Error<openapi::models::RestJsonError>,
+        rest_endpoint: Url,
+    },
+
     /// Error for when a Helm command fails.
     #[snafu(display(
         "Failed to run Helm command,\ncommand: {},\nargs: {:?},\ncommand_error: {}",
@@ -176,6 +201,28 @@ pub(crate) enum Error {
     #[snafu(display("{} is not a file", path.display()))]
     NotAFile { path: PathBuf },
 
+    /// Error for when the timeline file cannot be created.
+    #[snafu(display("Failed to create timeline file {}: {}", path.display(), source))]
+    CreateTimelineFile {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+
+    /// Error for when the progress Unix socket cannot be bound.
+    #[snafu(display("Failed to bind progress Unix socket {}: {}", path.display(), source))]
+    BindProgressSocket {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+
+    /// Error for when the metrics TCP port cannot be bound.
+    #[snafu(display("Failed to bind metrics port {}: {}", port, source))]
+    BindMetricsPort { source: std::io::Error, port: u16 },
+
+    /// Error for when the health probe TCP port cannot be bound.
+    #[snafu(display("Failed to bind health probe port {}: {}", port, source))]
+    BindHealthPort { source: std::io::Error, port: u16 },
+
     /// Error when reading a file.
     #[snafu(display("Failed to read from file {}: {}", filepath.display(), source))]
     ReadingFile {
@@ -230,6 +277,20 @@ pub(crate) enum Error {
         namespace: String,
     },
 
+    /// Error for when a Kubernetes API request for GET-ing a list of DaemonSets filtered by
+    /// label(s) fails.
+    #[snafu(display(
+        "Failed to list DaemonSets with label {} in namespace {}: {}",
+        label,
+        namespace,
+        source
+    ))]
+    ListDaemonSetsWithLabel {
+        source: kube::Error,
+        label: String,
+        namespace: String,
+    },
+
     /// Error for when a Pod does not have a PodSpec struct member.
     #[snafu(display("Failed get .spec from Pod {} in Namespace {}", name, namespace))]
     EmptyPodSpec { name: String, namespace: String },
@@ -250,6 +311,13 @@ pub(crate) enum Error {
     ))]
     EmptyPodUid { name: String, namespace: String },
 
+    /// Error for when a cordon request for a storage node fails.
+    #[snafu(display("Failed to cordon {} Node {}: {}", PRODUCT, node_id, source))]
+    StorageNodeCordon {
+        source: openapi::tower::client::Error<openapi::models::RestJsonError>,
+        node_id: String,
+    },
+
     /// Error for when an uncordon request for a storage node fails.
     #[snafu(display("Failed to uncordon {} Node {}: {}", PRODUCT, node_id, source))]
     StorageNodeUncordon {
@@ -265,6 +333,35 @@ pub(crate) enum Error {
         node: String,
     },
 
+    /// Error for when a Kubernetes API request for GET-ing an io-engine Pod on a Node fails.
+    #[snafu(display("Failed to get Pod {} on Node {}: {}", name, node, source))]
+    GetIoEnginePod {
+        source: kube::Error,
+        name: String,
+        node: String,
+    },
+
+    /// Error for when the force delete of a Pod which won't terminate fails.
+    #[snafu(display("Failed to force delete Pod {} from Node {}: {}", name, node, source))]
+    PodForceDelete {
+        source: kube::Error,
+        name: String,
+        node: String,
+    },
+
+    /// Error for when a deleted Pod is not gone within the timeout.
+    #[snafu(display(
+        "Timed out after {:?} waiting for the deleted Pod {} on Node {} to be gone",
+        timeout,
+        name,
+        node
+    ))]
+    PodDeletionTimeout {
+        name: String,
+        node: String,
+        timeout: Duration,
+    },
+
     /// Error for when listing storage nodes fails.
     #[snafu(display("Failed to list {} Nodes: {}", PRODUCT, source))]
     ListStorageNodes {
@@ -278,6 +375,10 @@ pub(crate) enum Error {
         node_id: String,
     },
 
+    /// Error for when a storage node is removed from the cluster while it's being upgraded.
+    #[snafu(display("{} Node {} was removed during its upgrade", PRODUCT, node_id))]
+    StorageNodeVanished { node_id: String },
+
     /// Error for when the storage node's Spec is empty.
     #[snafu(display("Failed to get {} Node {}", PRODUCT, node_id))]
     EmptyStorageNodeSpec { node_id: String },
@@ -408,9 +509,128 @@ pub(crate) enum Error {
         note: EventNote,
     },
 
-    /// Error for when there are too many io-engine Pods in one single node;
-    #[snafu(display("Too many io-engine Pods in Node '{}'", node_name))]
-    TooManyIoEnginePods { node_name: String },
+    /// Error for when there is more than one io-engine Pod from the same DaemonSet on a Node.
+    #[snafu(display(
+        "Too many io-engine Pods {:?} of DaemonSet '{}' in Node '{}'",
+        pods,
+        daemonset,
+        node_name
+    ))]
+    TooManyIoEnginePods {
+        node_name: String,
+        /// The name of the DaemonSet which owns the Pods.
+        daemonset: String,
+        /// The names and the phases of the DaemonSet's io-engine Pods on the Node.
+        pods: Vec<String>,
+    },
+
+    /// Error for when an io-engine Pod which is to be upgraded isn't owned by a DaemonSet, so
+    /// nothing would replace it once it's deleted.
+    #[snafu(display(
+        "io-engine Pod '{}' in Node '{}' isn't owned by a DaemonSet",
+        pod_name,
+        node_name
+    ))]
+    PodNotOwnedByDaemonSet { pod_name: String, node_name: String },
+
+    /// Error for when the io-engine Pods with the target version label were created from more
+    /// than one DaemonSet revision.
+    #[snafu(display(
+        "io-engine Pods with label {}={} belong to more than one DaemonSet revision: {:?}",
+        label_key,
+        version,
+        revisions
+    ))]
+    MultipleTargetRevisions {
+        label_key: String,
+        version: String,
+        revisions: Vec<String>,
+    },
+
+    /// Error for when the monitored labels of a Kubernetes Node have changed across the
+    /// restart of its io-engine Pod.
+    #[snafu(display(
+        "Monitored labels {:?} of Kubernetes Node '{}' changed during its upgrade",
+        labels,
+        node_name
+    ))]
+    NodeLabelsChanged {
+        node_name: String,
+        labels: Vec<String>,
+    },
+
+    /// Error for when the control-plane becomes unhealthy while a Node is being upgraded.
+    #[snafu(display(
+        "Control-plane became unhealthy during the upgrade of Node '{}'",
+        node_name
+    ))]
+    ControlPlaneUnhealthy { node_name: String },
+
+    /// Error for when too many Nodes fail to upgrade one after the other.
+    #[snafu(display(
+        "Aborted data-plane upgrade after {} consecutive Node failures: {:?}",
+        failures,
+        nodes
+    ))]
+    TooManyConsecutiveNodeFailures { failures: u32, nodes: Vec<String> },
+
+    /// Error for when some of the Nodes failed to upgrade, and the upgrade continued on errors.
+    #[snafu(display("Failed to upgrade the data-plane on Nodes {:?}", nodes))]
+    DataPlaneNodesFailed { nodes: Vec<String> },
+
+    /// Error for when there are degraded or faulted volumes before the data-plane upgrade.
+    #[snafu(display(
+        "Volumes {:?} are degraded or faulted, draining a Node could make them unavailable",
+        volumes
+    ))]
+    UnhealthyVolumesPresent { volumes: Vec<String> },
+
+    /// Error for when io-engine Pods are left on the source version at the end of the upgrade.
+    #[snafu(display(
+        "io-engine Pods {:?} are still on version {} after the data-plane upgrade",
+        pods,
+        version
+    ))]
+    NodesNotUpgraded { version: String, pods: Vec<String> },
+
+    /// Error for when a storage Node is cordoned by something other than the upgrade.
+    #[snafu(display(
+        "{} Node {} is cordoned by something other than the upgrade, labels: {:?}",
+        PRODUCT,
+        node_id,
+        labels
+    ))]
+    ExternallyCordonedNode {
+        node_id: String,
+        labels: Vec<String>,
+    },
+
+    /// Error for when storage Nodes are left cordoned at the end of the upgrade.
+    #[snafu(display("{} Nodes {:?} are left cordoned after the upgrade", PRODUCT, nodes))]
+    NodesLeftCordoned { nodes: Vec<String> },
+
+    /// Error for when the data-plane upgrade doesn't complete within its deadline.
+    #[snafu(display(
+        "Data-plane upgrade stopped, as it did not complete within the deadline of {:?}",
+        deadline
+    ))]
+    GlobalUpgradeDeadlineExceeded { deadline: Duration },
+
+    /// Error for when the data-plane upgrade is stopped by a termination signal.
+    #[snafu(display("Data-plane upgrade abandoned after receiving {}", signal))]
+    UpgradeAbandoned { signal: String },
+
+    /// Error for when the data-plane upgrade is stopped as it made no forward progress within
+    /// the idle timeout.
+    #[snafu(display(
+        "Data-plane upgrade stopped, as it made no forward progress for {:?}",
+        idle_timeout
+    ))]
+    UpgradeStalled { idle_timeout: Duration },
+
+    /// Error for when the data-plane upgrade is stopped by its cancellation token.
+    #[snafu(display("Data-plane upgrade cancelled"))]
+    UpgradeCancelled,
 
     /// Error for when the thin-provisioning options are absent, but still tried to fetch it.
     #[snafu(display("The agents.core.capacity yaml object is absent amongst the helm values"))]
@@ -424,11 +644,15 @@ pub(crate) enum Error {
     /// Error for when the no value for version label is found on the helm chart.
     #[snafu(display(
         "Failed to get the value of the {} label in Pod {} in Namespace {}",
-        CHART_VERSION_LABEL_KEY,
+        label_key,
         pod_name,
         namespace
     ))]
-    HelmChartVersionLabelHasNoValue { pod_name: String, namespace: String },
+    HelmChartVersionLabelHasNoValue {
+        label_key: String,
+        pod_name: String,
+        namespace: String,
+    },
 
     /// Error for when a pod does not have Namespace set on it.
     #[snafu(display(
@@ -471,14 +695,15 @@ pub(crate) enum Error {
         label_selector: String,
     },
 
-    /// Error for when the CHART_VERSION_LABEL_KEY is missing amongst the labels in a Deployment.
+    /// Error for when the chart version label is missing amongst the labels in a Deployment.
     #[snafu(display(
         "A label with the key {} was not found for Deployment {} in namespace {}",
-        CHART_VERSION_LABEL_KEY,
+        label_key,
         deployment_name,
         namespace
     ))]
     NoVersionLabelInDeployment {
+        label_key: String,
         deployment_name: String,
         namespace: String,
     },
@@ -501,6 +726,17 @@ pub(crate) enum Error {
     #[snafu(display("Invalid helm upgrade request"))]
     InvalidHelmUpgrade,
 
+    /// Error for when the data-plane upgrade's target version isn't newer than the source version.
+    #[snafu(display(
+        "Refusing to upgrade the data-plane from {} to {}: the target version is not newer",
+        from_version,
+        to_version
+    ))]
+    NotAnUpgrade {
+        from_version: String,
+        to_version: String,
+    },
+
     /// Error for when the helm upgrade's target version is lower the source version.
     #[snafu(display(
         "Failed to upgrade from {} to {}: upgrade to an earlier-released version is forbidden",
@@ -512,6 +748,14 @@ pub(crate) enum Error {
         to_version: String,
     },
 
+    /// Error for when the version being upgraded to is the same as the installed version, and
+    /// this isn't allowed.
+    #[snafu(display(
+        "Failed to upgrade: the installed version and the version being upgraded to are both {}",
+        version
+    ))]
+    SameFromAndToVersion { version: String },
+
     /// Error for when yq command execution fails.
     #[snafu(display(
         "Failed to run yq command,\ncommand: {},\nargs: {:?},\ncommand_error: {}",
@@ -525,6 +769,47 @@ pub(crate) enum Error {
         args: Vec<String>,
     },
 
+    /// Error for when a hook command could not be run.
+    #[snafu(display(
+        "Failed to run {} hook command,\ncommand: {},\ncommand_error: {}",
+        hook,
+        command,
+        source
+    ))]
+    HookCommandExec {
+        source: std::io::Error,
+        hook: String,
+        command: String,
+    },
+
+    /// Error for when a hook command exits with a non-zero status.
+    #[snafu(display(
+        "{} hook command failed,\ncommand: {},\nstatus: {},\nstd_err: {}",
+        hook,
+        command,
+        status,
+        std_err,
+    ))]
+    HookCommand {
+        hook: String,
+        command: String,
+        status: String,
+        std_err: String,
+    },
+
+    /// Error for when a hook command doesn't exit within the hook timeout.
+    #[snafu(display(
+        "Timed out after {:?} waiting for {} hook command to exit,\ncommand: {}",
+        timeout,
+        hook,
+        command,
+    ))]
+    HookCommandTimeout {
+        hook: String,
+        command: String,
+        timeout: Duration,
+    },
+
     /// Error for when the `yq -V` command returns an error.
     #[snafu(display(
         "`yq -V` command return an error,\ncommand: {},\narg: {},\nstd_err: {}",
@@ -609,7 +894,663 @@ pub(crate) enum Error {
         source: std::io::Error,
         path: PathBuf,
     },
+
+    /// Error for when a Kubernetes API request for listing PersistentVolumes fails.
+    #[snafu(display("Failed to list Kubernetes PersistentVolumes: {}", source))]
+    ListPersistentVolumes { source: kube::Error },
+
+    /// Error for when a Kubernetes API request for listing VolumeAttachments fails.
+    #[snafu(display("Failed to list Kubernetes VolumeAttachments: {}", source))]
+    ListVolumeAttachments { source: kube::Error },
+
+    /// Error for when a Kubernetes API request for GET-ing a Node fails.
+    #[snafu(display("Failed to GET Kubernetes Node {}: {}", node_name, source))]
+    GetK8sNode {
+        source: kube::Error,
+        node_name: String,
+    },
+
+    /// Error for when a Kubernetes API request for listing the Nodes fails.
+    #[snafu(display("Failed to list Kubernetes Nodes: {}", source))]
+    ListK8sNodes { source: kube::Error },
+
+    /// Error for when a Kubernetes API request for patching a Node fails.
+    #[snafu(display("Failed to patch Kubernetes Node {}: {}", node_name, source))]
+    PatchK8sNode {
+        source: kube::Error,
+        node_name: String,
+    },
+
+    /// Error for when a Kubernetes API request for GET-ing a ConfigMap fails.
+    #[snafu(display("Failed to GET Kubernetes ConfigMap {}: {}", name, source))]
+    GetConfigMap { source: kube::Error, name: String },
+
+    /// Error for when a timeout value in a ConfigMap is not a valid duration.
+    #[snafu(display(
+        "Failed to parse value '{}' of key '{}' in ConfigMap {} as a duration: {}",
+        value,
+        key,
+        config_map_name,
+        source
+    ))]
+    ParseTimeoutValue {
+        source: humantime::DurationError,
+        key: String,
+        value: String,
+        config_map_name: String,
+    },
+
+    /// Error for when a storage node drain does not complete within the timeout.
+    #[snafu(display(
+        "Timed out after {:?} waiting for {} Node {} to drain, last observed drain state: {}",
+        timeout,
+        PRODUCT,
+        node_id,
+        state
+    ))]
+    DrainTimeout {
+        node_id: String,
+        timeout: Duration,
+        state: String,
+    },
+
+    /// Error for when volume rebuilds do not complete within the timeout.
+    #[snafu(display(
+        "Timed out after {:?} waiting for volume rebuilds to complete before upgrading Node {}",
+        timeout,
+        node_name
+    ))]
+    RebuildTimeout {
+        node_name: String,
+        timeout: Duration,
+    },
+
+    /// Error for when a volume with replicas on a node does not get back to its configured replica
+    /// count within the timeout.
+    #[snafu(display(
+        "Volume {} with replicas on Node {} was not rebuilt to its configured replica count \
+        within {:?}",
+        volume,
+        node_name,
+        timeout
+    ))]
+    ReplicaCountNotRestored {
+        volume: String,
+        node_name: String,
+        timeout: Duration,
+    },
+
+    /// Error for when the VolumeAttachments of the volumes on a Node are not attached within the
+    /// timeout.
+    #[snafu(display(
+        "Timed out after {:?} waiting for VolumeAttachments {:?} of the volumes on Node {} to be \
+        attached",
+        timeout,
+        attachments,
+        node_name
+    ))]
+    VolumeAttachmentTimeout {
+        node_name: String,
+        timeout: Duration,
+        attachments: Vec<String>,
+    },
+
+    /// Error for when the storage Node is not reported as Online within the timeout.
+    #[snafu(display(
+        "Timed out after {:?} waiting for {} Node {} to be Online",
+        timeout,
+        PRODUCT,
+        node_id
+    ))]
+    StorageNodeOnlineTimeout { node_id: String, timeout: Duration },
+
+    /// Error for when the etcd health endpoint URI is invalid.
+    #[snafu(display("Invalid etcd health endpoint URI {}: {}", uri, source))]
+    EtcdHealthUri {
+        source: http::uri::InvalidUri,
+        uri: String,
+    },
+
+    /// Error for when the request to the etcd health endpoint fails.
+    #[snafu(display("Failed to check etcd health at {}: {}", uri, source))]
+    EtcdHealthRequest { source: hyper::Error, uri: String },
+
+    /// Error for when the etcd health endpoint doesn't respond within the timeout.
+    #[snafu(display(
+        "Timed out after {:?} waiting for the etcd health endpoint {} to respond",
+        timeout,
+        uri
+    ))]
+    EtcdHealthTimeout { uri: String, timeout: Duration },
+
+    /// Error for when the response of the etcd health endpoint can't be deserialized.
+    #[snafu(display("Failed to parse the etcd health response from {}: {}", uri, source))]
+    EtcdHealthResponse {
+        source: serde_json::Error,
+        uri: String,
+    },
+
+    /// Error for when etcd reports that its cluster is unhealthy.
+    #[snafu(display("etcd cluster is unhealthy, as reported by {}: '{}'", uri, reason))]
+    EtcdUnhealthy { uri: String, reason: String },
+
+    /// Error for when the test volume which verifies the control plane's function can't be
+    /// created.
+    #[snafu(display("Failed to create {} test Volume {}: {}", PRODUCT, volume_uuid, source))]
+    CreateTestVolume {
+        source: openapi::tower::client::Error<openapi::models::RestJsonError>,
+        volume_uuid: String,
+    },
+
+    /// Error for when the test volume which verifies the control plane's function can't be
+    /// deleted.
+    #[snafu(display("Failed to delete {} test Volume {}: {}", PRODUCT, volume_uuid, source))]
+    DeleteTestVolume {
+        source: openapi::tower::client::Error<openapi::models::RestJsonError>,
+        volume_uuid: String,
+    },
+
+    /// Error for when the io-engine Pod does not become Ready within the timeout.
+    #[snafu(display(
+        "Timed out after {:?} waiting for the io-engine Pod on Node {} to become Ready, \
+        io-engine Pods: {:?}",
+        timeout,
+        node_name,
+        pods
+    ))]
+    DataPlanePodNotReady {
+        node_name: String,
+        timeout: Duration,
+        /// The last observed phase and container statuses of the io-engine Pods on the Node.
+        pods: Vec<String>,
+    },
+
+    /// Error for when a deleted io-engine Pod is still terminating after the threshold.
+    #[snafu(display(
+        "io-engine Pod {} on Node {} is still terminating after {:?}",
+        pod_name,
+        node_name,
+        timeout
+    ))]
+    PodStuckTerminating {
+        pod_name: String,
+        node_name: String,
+        timeout: Duration,
+    },
+
+    /// Error for when an io-engine container restarts too many times while waiting for it to
+    /// become Ready.
+    #[snafu(display(
+        "Container '{}' of io-engine Pod '{}' on Node {} restarted {} times, last termination \
+        reason: {}",
+        container,
+        pod_name,
+        node_name,
+        restarts,
+        reason
+    ))]
+    DataPlanePodCrashLooping {
+        node_name: String,
+        pod_name: String,
+        container: String,
+        restarts: i32,
+        reason: String,
+    },
+
+    /// Error for when the control-plane Pods do not become Ready within the timeout.
+    #[snafu(display(
+        "Timed out after {:?} waiting for {} control-plane components {:?} to become Ready",
+        timeout,
+        PRODUCT,
+        components
+    ))]
+    ControlPlaneNotReady {
+        timeout: Duration,
+        components: Vec<String>,
+    },
+
+    /// Error for when a Node can't be drained without dropping the number of Nodes with a Ready
+    /// io-engine Pod below the minimum, for as long as the timeout.
+    #[snafu(display(
+        "Draining Node {} would leave {} Nodes with a Ready io-engine Pod, fewer than the \
+        minimum of {}, after waiting for {:?}",
+        node_name,
+        healthy_nodes,
+        min_healthy_nodes,
+        timeout
+    ))]
+    MinimumHealthyNodesViolated {
+        node_name: String,
+        healthy_nodes: usize,
+        min_healthy_nodes: usize,
+        timeout: Duration,
+    },
+
+    /// Error for when the storage REST API CA certificate can't be read from its file.
+    #[snafu(display(
+        "Failed to read the {} REST API CA certificate from {}: {}",
+        PRODUCT,
+        path.display(),
+        source
+    ))]
+    ReadRestCaCertificate {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+
+    /// Error for when the storage REST API CA certificate file doesn't hold a PEM-encoded
+    /// certificate.
+    #[snafu(display(
+        "The {} REST API CA certificate file {} doesn't hold a PEM-encoded certificate",
+        PRODUCT,
+        path.display()
+    ))]
+    InvalidRestCaCertificate { path: PathBuf },
+
+    /// Error for when more Nodes fail to upgrade than may be tolerated.
+    #[snafu(display(
+        "Aborted data-plane upgrade after {} Node failures, more than the maximum of {}: {:?}",
+        failures,
+        max_failures,
+        nodes
+    ))]
+    TooManyNodeFailures {
+        failures: usize,
+        max_failures: u32,
+        nodes: Vec<String>,
+    },
+
+    /// Error for when some of the listed io-engine Pods don't have a .spec or a .spec.nodeName.
+    #[snafu(display(
+        "Found io-engine Pods {:?} without a .spec or a .spec.nodeName in Namespace {}",
+        pods,
+        namespace
+    ))]
+    InvalidPodsInList {
+        namespace: String,
+        pods: Vec<String>,
+    },
+
+    /// Error for when the control-plane is on a later chart version than the data-plane upgrade's
+    /// target version.
+    #[snafu(display(
+        "The control-plane is on version {}, which is later than the data-plane target version \
+        {}, control-plane Pods: {:?}",
+        control_plane_version,
+        upgrade_to_version,
+        pods
+    ))]
+    ControlPlaneAheadOfTarget {
+        control_plane_version: String,
+        upgrade_to_version: String,
+        pods: Vec<String>,
+    },
+
+    /// Error for when a GET request for a list of volume snapshots fails.
+    #[snafu(display("Failed to list {} volume snapshots: {}", PRODUCT, source))]
+    ListVolumeSnapshots {
+        source: openapi::tower::client::Error<openapi::models::RestJsonError>,
+    },
+
+    /// Error for when the snapshots of the volumes on a Node are still being created or deleted
+    /// after the timeout.
+    #[snafu(display(
+        "Timed out after {:?} waiting for snapshots {:?} of the volumes on Node {} to complete",
+        timeout,
+        snapshots,
+        node_name
+    ))]
+    SnapshotInProgress {
+        node_name: String,
+        timeout: Duration,
+        snapshots: Vec<String>,
+    },
+
+    /// Error for when a storage Node is still draining with the upgrade drain label after the
+    /// timeout, so that it can't be uncordoned.
+    #[snafu(display(
+        "{} Node {} is still draining after {:?}, it could not be uncordoned",
+        PRODUCT,
+        node_id,
+        timeout
+    ))]
+    UncordonDrainInProgress { node_id: String, timeout: Duration },
+
+    /// Error for when the Pod templates of the io-engine DaemonSets don't carry the target chart
+    /// version, so that the deleted io-engine Pods would be re-created on the old version.
+    #[snafu(display(
+        "The io-engine DaemonSets {:?} are not updated to version {}, their Pods would not be \
+        upgraded",
+        daemonsets,
+        upgrade_to_version
+    ))]
+    DaemonSetNotUpdated {
+        upgrade_to_version: String,
+        daemonsets: Vec<String>,
+    },
+
+    /// Error for when the drain labels don't show up on a storage Node after it is drained with
+    /// them repeatedly.
+    #[snafu(display(
+        "The drain labels {:?} are not on {} Node {} after {} drain requests",
+        labels,
+        PRODUCT,
+        node_id,
+        attempts
+    ))]
+    DrainLabelNotApplied {
+        node_id: String,
+        labels: Vec<String>,
+        attempts: u32,
+    },
+
+    /// Error for when the kubeconfig file can't be read or parsed.
+    #[snafu(display("Failed to read the kubeconfig: {}", source))]
+    ReadKubeconfig {
+        source: kube::config::KubeconfigError,
+    },
+
+    /// Error for when the kubeconfig context is missing from the kubeconfig, or is invalid.
+    #[snafu(display("Failed to load the kubeconfig context '{}': {}", context, source))]
+    InvalidKubeconfigContext {
+        source: kube::config::KubeconfigError,
+        context: String,
+    },
+
+    /// Error for when the cluster isn't in the expected state after an upgrade.
+    #[snafu(display(
+        "The cluster is not fully upgraded to version {}: {:?}",
+        upgrade_to_version,
+        discrepancies
+    ))]
+    UpgradeVerificationFailed {
+        upgrade_to_version: String,
+        discrepancies: Vec<String>,
+    },
+
+    /// Error for when a Kubernetes API request to list the resources with a label across all of
+    /// the namespaces fails.
+    #[snafu(display(
+        "Failed to list the resources with label {} across the namespaces: {}",
+        label,
+        source
+    ))]
+    ListResourcesAcrossNamespaces { source: kube::Error, label: String },
+
+    /// Error for when the namespace of the install isn't set and it can't be discovered.
+    #[snafu(display(
+        "Failed to discover the {} namespace, no io-engine DaemonSet or agent-core Deployment \
+        was found, the namespace must be set",
+        PRODUCT
+    ))]
+    NamespaceNotDiscovered,
+
+    /// Error for when the namespace of the install isn't set and there is more than one
+    /// candidate namespace.
+    #[snafu(display(
+        "Found {} in more than one namespace {:?}, the namespace must be set",
+        PRODUCT,
+        namespaces
+    ))]
+    MultipleNamespacesDiscovered { namespaces: Vec<String> },
+
+    /// Error for when an API request is rejected as Forbidden, e.g. due to a missing RBAC rule.
+    #[snafu(display(
+        "Insufficient permissions to {} {}, check the upgrade-job's permissions: {}",
+        operation,
+        resource,
+        reason
+    ))]
+    InsufficientPermissions {
+        operation: String,
+        resource: String,
+        reason: String,
+    },
+
+    /// Error for when a SelfSubjectAccessReview Kubernetes API request fails.
+    #[snafu(display("Failed to review the upgrade-job's permissions: {}", source))]
+    CreateSelfSubjectAccessReview { source: kube::Error },
+
+    /// Error for when the upgrade-job's ServiceAccount lacks permissions which the data-plane
+    /// upgrade needs.
+    #[snafu(display(
+        "The upgrade-job's ServiceAccount lacks the permissions: {}",
+        permissions.join(", ")
+    ))]
+    MissingPermissions { permissions: Vec<String> },
+
+    /// Error for when a Pod-eviction Kubernetes API request fails.
+    #[snafu(display("Failed to evict Pod {} from Node {}: {}", name, node, source))]
+    PodEviction {
+        source: kube::Error,
+        name: String,
+        node: String,
+    },
+
+    /// Error for when a Pod's eviction is still blocked by a PodDisruptionBudget after the
+    /// timeout.
+    #[snafu(display(
+        "The eviction of Pod {} from Node {} is still blocked by a PodDisruptionBudget after \
+        {:?}: {}",
+        name,
+        node,
+        timeout,
+        reason
+    ))]
+    PodEvictionBlocked {
+        name: String,
+        node: String,
+        timeout: Duration,
+        reason: String,
+    },
+
+    /// Error for when volumes which were on a Node are Faulted after the Node's io-engine Pod is
+    /// restarted, e.g. because the new io-engine failed to import a pool.
+    #[snafu(display(
+        "Volumes {:?} which were on Node {} are Faulted after its upgrade",
+        volumes,
+        node_name
+    ))]
+    VolumeFaultedAfterUpgrade {
+        node_name: String,
+        volumes: Vec<String>,
+    },
+
+    /// Error for when the source version is earlier than the minimum version which the target
+    /// version may be upgraded from.
+    #[snafu(display(
+        "Failed to upgrade from {} to {}: the upgrade to {} is only supported from version {} or \
+        later",
+        from_version,
+        to_version,
+        to_version,
+        min_source_version
+    ))]
+    UnsupportedUpgradePath {
+        from_version: String,
+        to_version: String,
+        min_source_version: String,
+    },
+
+    /// Error for when a Deployment couldn't be patched, e.g. to restart its Pods.
+    #[snafu(display(
+        "Failed to patch Deployment {} in namespace {}: {}",
+        name,
+        namespace,
+        source
+    ))]
+    PatchDeployment {
+        source: kube::Error,
+        name: String,
+        namespace: String,
+    },
+
+    /// Error for when a GET request for a list of storage pools fails.
+    #[snafu(display("Failed to list {} pools: {}", PRODUCT, source))]
+    ListPools {
+        source: openapi::tower::client::Error<openapi::models::RestJsonError>,
+    },
+
+    /// Error for when a storage Node which is about to be upgraded has offline pools.
+    #[snafu(display(
+        "Node {} has offline pools {:?}, restarting its io-engine may leave volumes without a \
+        healthy replica",
+        node_name,
+        pools
+    ))]
+    PoolOffline {
+        node_name: String,
+        pools: Vec<String>,
+    },
+
+    /// Error in serializing the pre-upgrade state snapshot to JSON.
+    #[snafu(display("Failed to serialize the pre-upgrade state snapshot: {}", source))]
+    SerializeStateSnapshot { source: serde_json::Error },
+
+    /// Error for when the pre-upgrade state snapshot cannot be written to its file.
+    #[snafu(display(
+        "Failed to write the pre-upgrade state snapshot to {}: {}",
+        path.display(),
+        source
+    ))]
+    WriteStateSnapshot {
+        source: std::io::Error,
+        path: PathBuf,
+    },
+
+    /// Error for when no storage node matches a Kubernetes Node, neither by its name, nor by its
+    /// hostname label or its addresses.
+    #[snafu(display(
+        "Failed to map Kubernetes Node '{}' to a {} Node, none matches its name, its hostname \
+        label or its addresses",
+        node_name,
+        PRODUCT
+    ))]
+    NodeIdMappingFailed { node_name: String },
+
+    /// Error for when a GET request for the upgrade Lease fails.
+    #[snafu(display("Failed to GET the upgrade Lease {}: {}", name, source))]
+    GetLease { source: kube::Error, name: String },
+
+    /// Error for when the upgrade Lease cannot be created.
+    #[snafu(display("Failed to create the upgrade Lease {}: {}", name, source))]
+    CreateLease { source: kube::Error, name: String },
+
+    /// Error for when the upgrade Lease cannot be updated.
+    #[snafu(display("Failed to update the upgrade Lease {}: {}", name, source))]
+    ReplaceLease { source: kube::Error, name: String },
+
+    /// Error for when another upgrade-job holds the upgrade Lease.
+    #[snafu(display(
+        "Another upgrade is in progress, the upgrade Lease {} is held by '{}'",
+        name,
+        holder
+    ))]
+    ConcurrentUpgradeInProgress { name: String, holder: String },
+
+    /// Error for when the upgrade Lease is taken over by another holder, or expires, during the
+    /// data-plane upgrade.
+    #[snafu(display("The upgrade Lease was lost to '{}', stopping the upgrade", holder))]
+    UpgradeLeaseLost { holder: String },
+
+    /// Error for when the CSI node plugin Pods on a Node aren't Ready on the target version
+    /// within the timeout, after the Node's io-engine restart.
+    #[snafu(display(
+        "Timed out after {:?} waiting for the CSI node plugin Pods on Node {} to become Ready on \
+        the target version, CSI node plugin Pods: {:?}",
+        timeout,
+        node_name,
+        pods
+    ))]
+    CsiNodePluginNotReady {
+        node_name: String,
+        timeout: Duration,
+        /// The last observed phases of the CSI node plugin Pods on the Node.
+        pods: Vec<String>,
+    },
+
+    /// Error for when the missing replicas of the volumes which had replicas on a Node can't be
+    /// re-created, as no pool has room for them.
+    #[snafu(display(
+        "Failed to re-create the missing replicas of volumes {:?} after the upgrade of Node {}, \
+        no pool has room for them",
+        volumes,
+        node_name
+    ))]
+    ReplicaRelocationFailed {
+        node_name: String,
+        volumes: Vec<String>,
+    },
 }
 
 /// A wrapper type to remove repeated Result<T, Error> returns.
 pub(crate) type Result<T, E = Error> = std::result::Result<T, E>;
+
+/// These are the exit codes of the upgrade-job, so that automation may tell the kinds of failures
+/// apart, e.g. to retry the upgrade after a transient failure. Exit code 2 is left to the
+/// command line argument errors.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum UpgradeExitCode {
+    /// The upgrade completed.
+    Success,
+    /// The upgrade failed for any reason not covered by the other exit codes.
+    Failed,
+    /// The upgrade was stopped before it completed, e.g. by a termination signal, by its
+    /// deadline, by its idle timeout or by being cancelled. It may be resumed.
+    Interrupted,
+    /// The control-plane wasn't ready, or healthy, before a Node's upgrade.
+    ControlPlaneNotReady,
+    /// A storage Node's drain didn't complete within the timeout.
+    DrainTimeout,
+    /// The volume rebuilds didn't complete within the timeout.
+    RebuildTimeout,
+    /// The replacement io-engine Pods didn't become Ready.
+    PodNotReady,
+}
+
+impl UpgradeExitCode {
+    /// Returns the process exit code.
+    pub(crate) fn as_i32(self) -> i32 {
+        match self {
+            Self::Success => 0,
+            Self::Failed => 1,
+            Self::Interrupted => 3,
+            Self::ControlPlaneNotReady => 4,
+            Self::DrainTimeout => 5,
+            Self::RebuildTimeout => 6,
+            Self::PodNotReady => 7,
+        }
+    }
+}
+
+impl<T> From<&Result<T>> for UpgradeExitCode {
+    fn from(result: &Result<T>) -> Self {
+        match result {
+            Ok(_) => Self::Success,
+            Err(
+                Error::UpgradeAbandoned { .. }
+                | Error::GlobalUpgradeDeadlineExceeded { .. }
+                | Error::UpgradeStalled { .. }
+                | Error::UpgradeCancelled,
+            ) => Self::Interrupted,
+            Err(Error::ControlPlaneNotReady { .. } | Error::ControlPlaneUnhealthy { .. }) => {
+                Self::ControlPlaneNotReady
+            }
+            Err(Error::DrainTimeout { .. } | Error::UncordonDrainInProgress { .. }) => {
+                Self::DrainTimeout
+            }
+            Err(
+                Error::RebuildTimeout { .. }
+                | Error::ReplicaCountNotRestored { .. }
+                | Error::ReplicaRelocationFailed { .. },
+            ) => Self::RebuildTimeout,
+            Err(
+                Error::DataPlanePodNotReady { .. }
+                | Error::DataPlanePodCrashLooping { .. }
+                | Error::PodStuckTerminating { .. }
+                | Error::CsiNodePluginNotReady { .. },
+            ) => Self::PodNotReady,
+            Err(_) => Self::Failed,
+        }
+    }
+}
@@ -0,0 +1,167 @@
+use crate::common::error::{BindHealthPort, Result};
+use snafu::ResultExt;
+use std::{
+    net::SocketAddr,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::{TcpListener, TcpStream},
+    task::JoinHandle,
+};
+use tracing::{info, warn};
+
+/// The time a health probe connection is given to send its request and to receive the response,
+/// so that a slow or a stuck client doesn't hold on to the connection.
+const HEALTH_PROBE_CONNECTION_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// This is the last time the upgrade made forward progress, i.e. a phase of the upgrade started
+/// or a Node was upgraded. The API calls made within a phase aren't progress, e.g. a wait which
+/// polls for ever is stalled. The clones share the same time, so that the upgrade records
+/// progress in the one which the health probes and the idle timeout check.
+#[derive(Clone, Debug)]
+pub(crate) struct ForwardProgress {
+    last: Arc<Mutex<Instant>>,
+}
+
+impl Default for ForwardProgress {
+    fn default() -> Self {
+        Self {
+            last: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+}
+
+impl ForwardProgress {
+    /// Records that the upgrade is making forward progress. The deliberate waits, e.g. for the
+    /// maintenance window to open or for the upgrade to be resumed, record it too.
+    pub(crate) fn record(&self) {
+        *self.last.lock().unwrap_or_else(PoisonError::into_inner) = Instant::now();
+    }
+
+    /// Returns the time since the upgrade last made forward progress.
+    fn elapsed(&self) -> Duration {
+        self.last
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .elapsed()
+    }
+
+    /// This resolves once the upgrade has made no forward progress for 'idle_timeout', e.g. when
+    /// it is deadlocked in a way which the timeouts of the individual phases don't catch. The
+    /// returned value is the time since the last forward progress. This never resolves if there
+    /// is no idle timeout.
+    pub(crate) async fn stalled(&self, idle_timeout: Option<Duration>) -> Duration {
+        let Some(idle_timeout) = idle_timeout else {
+            return std::future::pending().await;
+        };
+
+        self.record();
+        let poll_interval = Duration::from_secs(10_u64).min(idle_timeout);
+        loop {
+            tokio::time::sleep(poll_interval).await;
+            let since = self.elapsed();
+            if since >= idle_timeout {
+                return since;
+            }
+        }
+    }
+}
+
+/// This is an HTTP health probe endpoint for the upgrade-job's Pod. '/healthz' responds with 200
+/// for as long as the upgrade has made forward progress within the stall threshold, and with 503
+/// otherwise, so that a liveness probe restarts a hung upgrade. '/readyz' responds with 200 once
+/// the endpoint is serving. The endpoint stops serving when this is dropped.
+pub(crate) struct HealthServer {
+    server: JoinHandle<()>,
+}
+
+impl HealthServer {
+    /// Binds the TCP port 'port' on all interfaces, and starts serving the health probes on it,
+    /// from the upgrade's 'forward_progress'. Each connection is served by a task of its own.
+    pub(crate) async fn serve(
+        port: u16,
+        stall_threshold: Duration,
+        forward_progress: ForwardProgress,
+    ) -> Result<Self> {
+        let address = SocketAddr::from(([0, 0, 0, 0], port));
+        let listener = TcpListener::bind(address)
+            .await
+            .context(BindHealthPort { port })?;
+        info!(%address, ?stall_threshold, "Serving the upgrade health probes");
+        forward_progress.record();
+
+        let server = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((stream, _)) => {
+                        let forward_progress = forward_progress.clone();
+                        tokio::spawn(async move {
+                            let response = respond(stream, stall_threshold, &forward_progress);
+                            if tokio::time::timeout(HEALTH_PROBE_CONNECTION_TIMEOUT, response)
+                                .await
+                                .is_err()
+                            {
+                                warn!(
+                                    timeout = ?HEALTH_PROBE_CONNECTION_TIMEOUT,
+                                    "Timed out serving health probe connection"
+                                );
+                            }
+                        });
+                    }
+                    Err(error) => warn!(%error, "Failed to accept health probe connection"),
+                }
+            }
+        });
+
+        Ok(Self { server })
+    }
+}
+
+impl Drop for HealthServer {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// Reads the health probe request from 'stream', and writes the response to it.
+async fn respond(
+    mut stream: TcpStream,
+    stall_threshold: Duration,
+    forward_progress: &ForwardProgress,
+) {
+    let mut request = [0_u8; 1024];
+    let read = stream.read(&mut request).await.unwrap_or_default();
+    let request = String::from_utf8_lossy(&request[..read]);
+    let path = request.split_whitespace().nth(1).unwrap_or_default();
+
+    let (status, body) = match path {
+        "/healthz" => {
+            let since = forward_progress.elapsed();
+            if since < stall_threshold {
+                ("200 OK", "ok".to_string())
+            } else {
+                warn!(
+                    ?since,
+                    "The upgrade has made no forward progress, failing the health probe"
+                );
+                (
+                    "503 Service Unavailable",
+                    format!("no forward progress for {since:?}"),
+                )
+            }
+        }
+        "/readyz" => ("200 OK", "ok".to_string()),
+        _ => ("404 Not Found", "not found".to_string()),
+    };
+    let response = format!(
+        "HTTP/1.1 {status}\r\nContent-Type: text/plain\r\n\
+        Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+        body.len()
+    );
+    if let Err(error) = stream.write_all(response.as_bytes()).await {
+        warn!(%error, "Failed to write health probe response");
+    }
+    let _ = stream.shutdown().await;
+}
@@ -1,18 +1,34 @@
-use crate::common::error::{K8sClientGeneration, KubeClientSetBuilderNs, Result};
+use crate::common::{
+    api_stats::ApiStats,
+    error::{
+        InvalidKubeconfigContext, K8sClientGeneration, KubeClientSetBuilderNs, ReadKubeconfig,
+        Result,
+    },
+};
 use k8s_openapi::{
     api::{
-        apps::v1::Deployment,
-        core::v1::{Namespace, Pod},
+        apps::v1::{DaemonSet, Deployment},
+        core::v1::{ConfigMap, Namespace, Node, PersistentVolume, Pod},
+        events::v1::Event,
+        storage::v1::VolumeAttachment,
     },
     apiextensions_apiserver::pkg::apis::apiextensions::v1::CustomResourceDefinition,
 };
-use kube::{api::Api, Client};
+use kube::{
+    api::Api,
+    config::{KubeConfigOptions, Kubeconfig},
+    Client, Config,
+};
 use snafu::ResultExt;
+use std::{path::PathBuf, sync::Arc};
 
 /// Builder for Kubernetes clients.
 #[derive(Default)]
 pub(crate) struct KubeClientSetBuilder {
     namespace: Option<String>,
+    kubeconfig: Option<PathBuf>,
+    context: Option<String>,
+    api_stats: Option<Arc<ApiStats>>,
 }
 
 impl KubeClientSetBuilder {
@@ -26,6 +42,28 @@ impl KubeClientSetBuilder {
         self
     }
 
+    /// Build Kubernetes API clients from the kubeconfig file at 'path', with the kubeconfig
+    /// context 'context'. The default kubeconfig is used if 'path' is None, and its current
+    /// context if 'context' is None. If neither is set, the in-cluster config is tried first.
+    #[must_use]
+    pub(crate) fn with_kubeconfig(
+        mut self,
+        path: Option<PathBuf>,
+        context: Option<String>,
+    ) -> Self {
+        self.kubeconfig = path;
+        self.context = context;
+        self
+    }
+
+    /// Record the counts and the latencies of the API calls in 'api_stats', which may be shared
+    /// with other API clients. The clients record them in stats of their own otherwise.
+    #[must_use]
+    pub(crate) fn with_api_stats(mut self, api_stats: Arc<ApiStats>) -> Self {
+        self.api_stats = Some(api_stats);
+        self
+    }
+
     // TODO: Make the builder option validations error out at compile-time, using std::compile_error
     // or something similar.
     /// Build the KubeClientSet.
@@ -33,13 +71,39 @@ impl KubeClientSetBuilder {
         // Namespace must be used.
         let namespace = self.namespace.ok_or(KubeClientSetBuilderNs.build())?;
 
-        let client = Client::try_default().await.context(K8sClientGeneration)?;
+        let client = match (self.kubeconfig, self.context) {
+            (None, None) => Client::try_default().await.context(K8sClientGeneration)?,
+            (kubeconfig, context) => {
+                let kubeconfig = match kubeconfig {
+                    Some(path) => Kubeconfig::read_from(path.as_path()),
+                    None => Kubeconfig::read(),
+                }
+                .context(ReadKubeconfig)?;
+                let options = KubeConfigOptions {
+                    context: context.clone(),
+                    ..Default::default()
+                };
+                let config = Config::from_custom_kubeconfig(kubeconfig, &options)
+                    .await
+                    .context(InvalidKubeconfigContext {
+                        context: context.unwrap_or_else(|| "current-context".to_string()),
+                    })?;
+                Client::try_from(config).context(K8sClientGeneration)?
+            }
+        };
         return Ok(KubeClientSet {
             client: client.clone(),
             pods_api: Api::namespaced(client.clone(), namespace.as_str()),
             namespaces_api: Api::all(client.clone()),
             deployments_api: Api::namespaced(client.clone(), namespace.as_str()),
+            configmaps_api: Api::namespaced(client.clone(), namespace.as_str()),
+            daemonsets_api: Api::namespaced(client.clone(), namespace.as_str()),
+            nodes_api: Api::all(client.clone()),
+            persistent_volumes_api: Api::all(client.clone()),
+            volume_attachments_api: Api::all(client.clone()),
+            events_api: Api::namespaced(client.clone(), namespace.as_str()),
             crd_api: Api::all(client),
+            api_stats: self.api_stats.unwrap_or_default(),
         });
     }
 }
@@ -50,7 +114,14 @@ pub(crate) struct KubeClientSet {
     pods_api: Api<Pod>,
     namespaces_api: Api<Namespace>,
     deployments_api: Api<Deployment>,
+    configmaps_api: Api<ConfigMap>,
+    daemonsets_api: Api<DaemonSet>,
+    nodes_api: Api<Node>,
+    persistent_volumes_api: Api<PersistentVolume>,
+    volume_attachments_api: Api<VolumeAttachment>,
+    events_api: Api<Event>,
     crd_api: Api<CustomResourceDefinition>,
+    api_stats: Arc<ApiStats>,
 }
 
 impl KubeClientSet {
@@ -73,11 +144,46 @@ impl KubeClientSet {
         &self.deployments_api
     }
 
+    /// Generate the ConfigMap api client.
+    pub(crate) fn configmaps_api(&self) -> &Api<ConfigMap> {
+        &self.configmaps_api
+    }
+
+    /// Generate the DaemonSet api client.
+    pub(crate) fn daemonsets_api(&self) -> &Api<DaemonSet> {
+        &self.daemonsets_api
+    }
+
+    /// Generate the Node api client.
+    pub(crate) fn nodes_api(&self) -> &Api<Node> {
+        &self.nodes_api
+    }
+
+    /// Generate the PersistentVolume api client.
+    pub(crate) fn persistent_volumes_api(&self) -> &Api<PersistentVolume> {
+        &self.persistent_volumes_api
+    }
+
+    /// Generate the VolumeAttachment api client.
+    pub(crate) fn volume_attachments_api(&self) -> &Api<VolumeAttachment> {
+        &self.volume_attachments_api
+    }
+
+    /// Generate the Event api client.
+    pub(crate) fn events_api(&self) -> &Api<Event> {
+        &self.events_api
+    }
+
     /// Generate the CustomResourceDefinition api client.
     pub(crate) fn crd_api(&self) -> &Api<CustomResourceDefinition> {
         &self.crd_api
     }
 
+    /// Get the stats which the API calls made with the clients are recorded in.
+    pub(crate) fn api_stats(&self) -> &Arc<ApiStats> {
+        &self.api_stats
+    }
+
     /// Get a clone of the kube::Client.
     pub(crate) fn client(&self) -> Client {
         self.client.clone()
@@ -0,0 +1,61 @@
+use clap::ValueEnum;
+use tracing_subscriber::EnvFilter;
+use utils::{
+    raw_version_str,
+    tracing_telemetry::{default_tracing_tags, init_tracing},
+};
+
+/// This decides how the upgrade-job's logs are formatted.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum LogFormat {
+    /// Human readable text.
+    #[default]
+    Text,
+    /// One JSON object per line, with the structured fields as keys, e.g. 'node.name', for log
+    /// aggregators to index.
+    Json,
+}
+
+/// This decides how much the data-plane upgrade's waits log while they poll, e.g. the waits for
+/// the volume rebuilds and for the io-engine Pods' readiness. The start and the end of each wait
+/// are logged at info regardless.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum WaitLogVerbosity {
+    /// Each poll's status is logged at trace.
+    Quiet,
+    /// Each poll's status is logged at debug.
+    #[default]
+    Normal,
+    /// Each poll's status is logged at info, along with the state of the Pods or the volumes
+    /// which are waited on.
+    Verbose,
+}
+
+impl WaitLogVerbosity {
+    /// This returns true if the waits log the state of the Pods or the volumes which they wait
+    /// on. Collecting that state may take extra API calls, so it is only done if it's logged.
+    pub(crate) fn logs_wait_state(self) -> bool {
+        self == WaitLogVerbosity::Verbose
+    }
+}
+
+/// Initialize logging components -- tracing. The log level is taken from RUST_LOG for the JSON
+/// format, and it is 'info' if RUST_LOG isn't set.
+pub(crate) fn init_logging(format: LogFormat) {
+    match format {
+        LogFormat::Text => {
+            let tags = default_tracing_tags(raw_version_str(), env!("CARGO_PKG_VERSION"));
+            init_tracing("upgrade-job", tags, None);
+        }
+        LogFormat::Json => {
+            tracing_subscriber::fmt()
+                .json()
+                .with_current_span(true)
+                .with_span_list(false)
+                .with_env_filter(
+                    EnvFilter::try_from_default_env().unwrap_or_else(|_| EnvFilter::new("info")),
+                )
+                .init();
+        }
+    }
+}
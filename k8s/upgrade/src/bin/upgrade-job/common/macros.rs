@@ -3,3 +3,17 @@
 macro_rules! vec_to_strings {
     ($($x:expr),*) => (vec![$($x.to_string()),*]);
 }
+
+/// Logs the status of a poll of one of the data-plane upgrade's waits, at the level which the
+/// wait log verbosity, the first argument, picks. The rest of the arguments are the same as the
+/// tracing macros'.
+#[macro_export]
+macro_rules! poll_status {
+    ($verbosity:expr, $($arg:tt)+) => {
+        match $verbosity {
+            $crate::common::logging::WaitLogVerbosity::Quiet => tracing::trace!($($arg)+),
+            $crate::common::logging::WaitLogVerbosity::Normal => tracing::debug!($($arg)+),
+            $crate::common::logging::WaitLogVerbosity::Verbose => tracing::info!($($arg)+),
+        }
+    };
+}
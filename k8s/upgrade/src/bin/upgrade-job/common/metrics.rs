@@ -0,0 +1,130 @@
+use crate::common::{
+    error::{BindMetricsPort, Result},
+    progress::{Progress, UpgradeProgress},
+};
+use snafu::ResultExt;
+use std::{fmt::Write, net::SocketAddr};
+use tokio::{
+    io::{AsyncReadExt, AsyncWriteExt},
+    net::TcpListener,
+    task::JoinHandle,
+};
+use tracing::{info, warn};
+
+/// This is a Prometheus metrics endpoint, which responds to every HTTP request with the
+/// progress of the data-plane upgrade in the Prometheus text format, and closes the connection.
+/// The endpoint stops serving when this is dropped.
+pub(crate) struct MetricsServer {
+    server: JoinHandle<()>,
+}
+
+impl MetricsServer {
+    /// Binds the TCP port 'port' on all interfaces, and starts serving the metrics, from
+    /// 'progress', on it.
+    pub(crate) async fn serve(port: u16, progress: UpgradeProgress) -> Result<Self> {
+        let address = SocketAddr::from(([0, 0, 0, 0], port));
+        let listener = TcpListener::bind(address)
+            .await
+            .context(BindMetricsPort { port })?;
+        info!(%address, "Serving the upgrade metrics");
+
+        let server = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((mut stream, _)) => {
+                        // The request is always answered with the metrics, whatever its path.
+                        let mut request = [0_u8; 1024];
+                        let _ = stream.read(&mut request).await;
+
+                        let body = render(&progress.current());
+                        let response = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: text/plain; version=0.0.4\r\n\
+                            Content-Length: {}\r\nConnection: close\r\n\r\n{body}",
+                            body.len()
+                        );
+                        if let Err(error) = stream.write_all(response.as_bytes()).await {
+                            warn!(%error, "Failed to write upgrade metrics");
+                        }
+                        let _ = stream.shutdown().await;
+                    }
+                    Err(error) => warn!(%error, "Failed to accept metrics connection"),
+                }
+            }
+        });
+
+        Ok(Self { server })
+    }
+}
+
+impl Drop for MetricsServer {
+    fn drop(&mut self) {
+        self.server.abort();
+    }
+}
+
+/// Renders the progress of the upgrade as Prometheus metrics.
+fn render(progress: &Progress) -> String {
+    let mut metrics = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, labels: &str, value: f64| {
+        let _ = writeln!(metrics, "# HELP {name} {help}");
+        let _ = writeln!(metrics, "# TYPE {name} {kind}");
+        let _ = writeln!(metrics, "{name}{labels} {value}");
+    };
+
+    metric(
+        "upgrade_job_nodes_total",
+        "gauge",
+        "The number of Nodes with io-engine Pods when the data-plane upgrade started.",
+        "",
+        progress.nodes_total as f64,
+    );
+    metric(
+        "upgrade_job_nodes_upgraded_total",
+        "counter",
+        "The number of Nodes whose data-plane has been upgraded.",
+        "",
+        progress.nodes_upgraded as f64,
+    );
+    metric(
+        "upgrade_job_current_node",
+        "gauge",
+        "The Node which is being upgraded, along with the phase of its upgrade.",
+        &format!(
+            "{{node=\"{}\",phase=\"{}\"}}",
+            escape(progress.node_name.as_deref().unwrap_or_default()),
+            escape(progress.phase.as_deref().unwrap_or_default())
+        ),
+        1.0,
+    );
+    metric(
+        "upgrade_job_rebuild_wait_seconds_total",
+        "counter",
+        "The time spent waiting for the volume rebuilds before the Nodes were drained.",
+        "",
+        progress.rebuild_wait_secs,
+    );
+    metric(
+        "upgrade_job_drain_wait_seconds_total",
+        "counter",
+        "The time spent waiting for the Nodes to drain.",
+        "",
+        progress.drain_wait_secs,
+    );
+    metric(
+        "upgrade_job_paused",
+        "gauge",
+        "This is 1 while the data-plane upgrade is paused.",
+        "",
+        u8::from(progress.paused) as f64,
+    );
+
+    metrics
+}
+
+/// Escapes a Prometheus label value.
+fn escape(value: &str) -> String {
+    value
+        .replace('\\', "\\\\")
+        .replace('"', "\\\"")
+        .replace('\n', "\\n")
+}
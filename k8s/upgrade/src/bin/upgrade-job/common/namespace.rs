@@ -0,0 +1,72 @@
+use crate::common::{
+    api_stats::{ApiStats, LIST_DAEMONSETS, LIST_DEPLOYMENTS},
+    constants::{AGENT_CORE_LABEL, IO_ENGINE_LABEL},
+    error::{
+        K8sClientGeneration, ListResourcesAcrossNamespaces, MultipleNamespacesDiscovered,
+        NamespaceNotDiscovered, Result,
+    },
+};
+use k8s_openapi::api::apps::v1::{DaemonSet, Deployment};
+use kube::{
+    api::{Api, ListParams},
+    Client, ResourceExt,
+};
+use snafu::ResultExt;
+use std::collections::BTreeSet;
+use tracing::info;
+
+/// Discovers the namespace of the install, from the namespaces of the io-engine DaemonSets and of
+/// the agent-core Deployments across the cluster. This fails if there are none, or if they're
+/// spread across more than one namespace, e.g. if there is more than one install. The API calls
+/// are recorded in 'api_stats'.
+pub(crate) async fn discover_namespace(api_stats: &ApiStats) -> Result<String> {
+    let client = Client::try_default().await.context(K8sClientGeneration)?;
+    let list_params = |label: &str| ListParams::default().labels(label);
+
+    let mut namespaces: BTreeSet<String> = BTreeSet::new();
+    let daemonsets = api_stats
+        .timed(
+            LIST_DAEMONSETS,
+            Api::<DaemonSet>::all(client.clone()).list(&list_params(IO_ENGINE_LABEL)),
+        )
+        .await
+        .context(ListResourcesAcrossNamespaces {
+            label: IO_ENGINE_LABEL.to_string(),
+        })?;
+    namespaces.extend(
+        daemonsets
+            .iter()
+            .filter_map(|daemonset| daemonset.namespace()),
+    );
+
+    let deployments = api_stats
+        .timed(
+            LIST_DEPLOYMENTS,
+            Api::<Deployment>::all(client).list(&list_params(AGENT_CORE_LABEL)),
+        )
+        .await
+        .context(ListResourcesAcrossNamespaces {
+            label: AGENT_CORE_LABEL.to_string(),
+        })?;
+    namespaces.extend(
+        deployments
+            .iter()
+            .filter_map(|deployment| deployment.namespace()),
+    );
+
+    let mut namespaces = namespaces.into_iter();
+    match (namespaces.next(), namespaces.next()) {
+        (Some(namespace), None) => {
+            info!(%namespace, "Discovered the namespace of the install");
+            Ok(namespace)
+        }
+        (None, _) => NamespaceNotDiscovered.fail(),
+        (Some(first), Some(second)) => MultipleNamespacesDiscovered {
+            namespaces: [first, second]
+                .into_iter()
+                .chain(namespaces)
+                .collect::<Vec<String>>(),
+        }
+        .fail(),
+    }
+}
@@ -0,0 +1,259 @@
+use crate::common::{
+    api_stats::{ApiStats, APPLY_CONFIG_MAP},
+    error::{BindProgressSocket, Result},
+    kube_client::KubeClientSet,
+};
+use k8s_openapi::api::core::v1::ConfigMap;
+use kube::{
+    api::{Api, ObjectMeta, Patch, PatchParams},
+    ResourceExt,
+};
+use serde::Serialize;
+use snafu::ResultExt;
+use std::{
+    collections::BTreeMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+use tokio::{io::AsyncWriteExt, net::UnixListener, task::JoinHandle};
+use tracing::{debug, info, warn};
+
+/// This is the in-flight state of the upgrade.
+#[derive(Serialize, Clone, Debug, Default)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub(crate) struct Progress {
+    /// The ID of the upgrade run.
+    run_id: String,
+    /// The phase of the upgrade which is in progress.
+    pub(crate) phase: Option<String>,
+    /// The Node being upgraded, for the phases which are specific to a Node.
+    pub(crate) node_name: Option<String>,
+    /// The RFC 3339 time at which the phase started.
+    phase_started_at: Option<String>,
+    /// The number of Nodes with io-engine Pods when the data-plane upgrade started.
+    pub(crate) nodes_total: usize,
+    /// The number of Nodes whose data-plane has been upgraded so far.
+    pub(crate) nodes_upgraded: usize,
+    /// The total time spent waiting for the volume rebuilds before the Nodes were drained.
+    pub(crate) rebuild_wait_secs: f64,
+    /// The total time spent waiting for the Nodes to drain.
+    pub(crate) drain_wait_secs: f64,
+    /// This is set while the upgrade is paused.
+    pub(crate) paused: bool,
+}
+
+/// This is the current state of the upgrade, which is shared by the observability features,
+/// e.g. the progress socket, the metrics endpoint and the progress ConfigMap. The clones share
+/// the same state.
+#[derive(Clone, Debug)]
+pub(crate) struct UpgradeProgress {
+    progress: Arc<Mutex<Progress>>,
+}
+
+impl UpgradeProgress {
+    /// Creates the state of the upgrade run with the ID 'run_id', before the upgrade has started.
+    pub(crate) fn new(run_id: String) -> Self {
+        Self {
+            progress: Arc::new(Mutex::new(Progress {
+                run_id,
+                ..Default::default()
+            })),
+        }
+    }
+
+    /// Runs 'f' on the current state of the upgrade.
+    fn update<F: FnOnce(&mut Progress)>(&self, f: F) {
+        f(&mut self.progress.lock().unwrap_or_else(PoisonError::into_inner));
+    }
+
+    /// Records the start of a phase of the upgrade.
+    pub(crate) fn set_phase(&self, phase: &str, node_name: Option<&str>) {
+        self.update(|progress| {
+            progress.phase = Some(phase.to_string());
+            progress.node_name = node_name.map(ToString::to_string);
+            progress.phase_started_at =
+                Some(humantime::format_rfc3339_millis(SystemTime::now()).to_string());
+        });
+    }
+
+    /// Records that another Node's data-plane has been upgraded.
+    pub(crate) fn node_upgraded(&self) {
+        self.update(|progress| progress.nodes_upgraded += 1);
+    }
+
+    /// Records the number of Nodes with io-engine Pods, at the start of the data-plane upgrade.
+    pub(crate) fn set_nodes_total(&self, nodes_total: usize) {
+        self.update(|progress| progress.nodes_total = nodes_total);
+    }
+
+    /// Records the time a Node's upgrade spent waiting for the volume rebuilds and for the
+    /// drain.
+    pub(crate) fn record_node_waits(&self, rebuild_wait: Duration, drain_wait: Duration) {
+        self.update(|progress| {
+            progress.rebuild_wait_secs += rebuild_wait.as_secs_f64();
+            progress.drain_wait_secs += drain_wait.as_secs_f64();
+        });
+    }
+
+    /// Records whether the upgrade is paused.
+    pub(crate) fn set_paused(&self, paused: bool) {
+        self.update(|progress| progress.paused = paused);
+    }
+
+    /// Returns a copy of the current state of the upgrade.
+    pub(crate) fn current(&self) -> Progress {
+        self.progress
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
+    }
+}
+
+/// This is a Unix domain socket which responds to every connection with the current state of
+/// the upgrade as JSON, and closes the connection. The socket file is removed when this is
+/// dropped.
+pub(crate) struct ProgressSocket {
+    path: PathBuf,
+    server: JoinHandle<()>,
+}
+
+impl ProgressSocket {
+    /// Binds the socket at 'path', replacing any socket file left over from a previous run, and
+    /// starts serving the current state of the upgrade, from 'progress', on it.
+    pub(crate) fn serve(path: &Path, progress: UpgradeProgress) -> Result<Self> {
+        // A socket file left behind by a previous run would make the bind fail.
+        let _ = std::fs::remove_file(path);
+        let listener = UnixListener::bind(path).context(BindProgressSocket {
+            path: path.to_path_buf(),
+        })?;
+        info!(path = %path.display(), "Serving the upgrade progress on Unix socket");
+
+        let server = tokio::spawn(async move {
+            loop {
+                match listener.accept().await {
+                    Ok((mut stream, _)) => {
+                        let state = serde_json::to_string(&progress.current()).unwrap_or_default();
+                        if let Err(error) = stream.write_all(format!("{state}\n").as_bytes()).await
+                        {
+                            warn!(%error, "Failed to write upgrade progress to Unix socket");
+                        }
+                        let _ = stream.shutdown().await;
+                    }
+                    Err(error) => warn!(%error, "Failed to accept Unix socket connection"),
+                }
+            }
+        });
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            server,
+        })
+    }
+}
+
+/// The field manager of the server-side apply of the progress ConfigMap.
+const PROGRESS_CONFIG_MAP_FIELD_MANAGER: &str = "upgrade-job";
+
+/// This is a ConfigMap in the upgrade-job's namespace which carries the progress of the
+/// data-plane upgrade, so that the upgrade may be monitored without access to its logs. Failures
+/// to write the ConfigMap are logged, and don't fail the upgrade.
+pub(crate) struct ProgressConfigMap {
+    api: Api<ConfigMap>,
+    api_stats: Arc<ApiStats>,
+    name: String,
+    progress: UpgradeProgress,
+}
+
+impl ProgressConfigMap {
+    /// Creates the ConfigMap with the name 'name', or overwrites the one left over from a
+    /// previous run, with the current progress of the upgrade, from 'progress'.
+    pub(crate) async fn create(
+        k8s_client: &KubeClientSet,
+        name: String,
+        progress: UpgradeProgress,
+    ) -> Self {
+        let config_map = Self {
+            api: k8s_client.configmaps_api().clone(),
+            api_stats: k8s_client.api_stats().clone(),
+            name,
+            progress,
+        };
+        config_map.write(false).await;
+        info!(
+            config_map.name = %config_map.name,
+            "Writing the data-plane upgrade progress to ConfigMap"
+        );
+        config_map
+    }
+
+    /// Writes the current progress of the upgrade to the ConfigMap.
+    pub(crate) async fn update(&self) {
+        self.write(false).await;
+    }
+
+    /// Marks the upgrade as finished in the ConfigMap.
+    pub(crate) async fn finish(&self) {
+        self.write(true).await;
+    }
+
+    /// Applies the current progress of the upgrade to the ConfigMap.
+    async fn write(&self, finished: bool) {
+        let progress = self.progress.current();
+        let data = BTreeMap::from([
+            ("runId".to_string(), progress.run_id),
+            ("nodesTotal".to_string(), progress.nodes_total.to_string()),
+            (
+                "nodesUpgraded".to_string(),
+                progress.nodes_upgraded.to_string(),
+            ),
+            (
+                "nodeName".to_string(),
+                progress.node_name.unwrap_or_default(),
+            ),
+            ("phase".to_string(), progress.phase.unwrap_or_default()),
+            ("finished".to_string(), finished.to_string()),
+            (
+                "updatedAt".to_string(),
+                humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            ),
+        ]);
+        let config_map = ConfigMap {
+            metadata: ObjectMeta {
+                name: Some(self.name.clone()),
+                ..Default::default()
+            },
+            data: Some(data),
+            ..Default::default()
+        };
+
+        let params = PatchParams::apply(PROGRESS_CONFIG_MAP_FIELD_MANAGER).force();
+        match self
+            .api_stats
+            .timed(
+                APPLY_CONFIG_MAP,
+                self.api
+                    .patch(self.name.as_str(), &params, &Patch::Apply(&config_map)),
+            )
+            .await
+        {
+            Ok(config_map) => {
+                debug!(config_map.name = %config_map.name_any(), "Updated the progress ConfigMap")
+            }
+            Err(error) => warn!(
+                %error,
+                config_map.name = %self.name,
+                "Failed to write the data-plane upgrade progress to ConfigMap"
+            ),
+        }
+    }
+}
+
+impl Drop for ProgressSocket {
+    fn drop(&mut self) {
+        self.server.abort();
+        if let Err(error) = std::fs::remove_file(self.path.as_path()) {
+            warn!(%error, path = %self.path.display(), "Failed to remove progress socket file");
+        }
+    }
+}
@@ -1,41 +1,331 @@
-use crate::common::error::{RestClientConfiguration, RestUrlParse, Result};
-use openapi::tower::client::{ApiClient, Configuration as RestConfig};
+use crate::common::{
+    api_stats::{ApiStats, GET_NODE, GET_NODES, PUT_NODE_DRAIN},
+    error::{
+        InvalidRestCaCertificate, ReadRestCaCertificate, ReadRestTokenFile,
+        RestClientConfiguration, RestEndpointUnreachable, RestUrlParse, Result,
+    },
+};
+use async_trait::async_trait;
+use openapi::{
+    models::Node,
+    tower::client::{ApiClient, Configuration as RestConfig},
+};
 use snafu::ResultExt;
-use std::time::Duration;
+use std::{
+    collections::hash_map::RandomState,
+    future::Future,
+    hash::{BuildHasher, Hasher},
+    path::{Path, PathBuf},
+    sync::{Arc, PoisonError, RwLock},
+    time::Duration,
+};
+use tracing::{info, warn};
 use url::Url;
 
+/// This is the error type of the storage REST API calls.
+pub(crate) type RestError = openapi::tower::client::Error<openapi::models::RestJsonError>;
+
+/// The maximum number of attempts of a storage REST API call which fails transiently.
+const MAX_CALL_ATTEMPTS: u32 = 5;
+
+/// The delay before the first retry of a storage REST API call. This doubles with each retry,
+/// up to MAX_RETRY_BACKOFF.
+const INITIAL_RETRY_BACKOFF: Duration = Duration::from_millis(500);
+
+/// The maximum delay between the retries of a storage REST API call.
+const MAX_RETRY_BACKOFF: Duration = Duration::from_secs(8);
+
 /// This is a wrapper for the openapi::tower::client::ApiClient.
 pub(crate) struct RestClientSet {
-    client: ApiClient,
+    rest_url: Url,
+    /// The file which the bearer token is read from, if the storage REST API requires one.
+    token_file: Option<PathBuf>,
+    /// The PEM-encoded CA certificate which the storage REST API's TLS certificate is verified
+    /// against, if it is served over TLS with a certificate which isn't publicly trusted.
+    ca_certificate: Option<Vec<u8>>,
+    /// The client is replaced when the bearer token is refreshed. The API calls which are in
+    /// flight hold on to the client which they were started with, until they complete.
+    client: RwLock<Arc<ApiClient>>,
+    /// The counts and the latencies of the API calls, which may be shared with other API clients.
+    api_stats: Arc<ApiStats>,
 }
 
 impl RestClientSet {
-    /// Build the RestConfig, and the eventually the ApiClient. Fails if configuration is invalid.
+    /// Build the RestConfig, and the eventually the ApiClient, without a bearer token or a CA
+    /// certificate, e.g. for the unauthenticated in-cluster storage REST API. Fails if
+    /// configuration is invalid.
     pub(crate) fn new_with_url(rest_endpoint: String) -> Result<Self> {
+        Self::new_with_url_and_auth(rest_endpoint, None, None)
+    }
+
+    /// Build the RestConfig, and the eventually the ApiClient. Fails if configuration is invalid.
+    /// If 'token_file' is set, the bearer token is read from it. If 'ca_certificate_file' is set,
+    /// the storage REST API's TLS certificate is verified against the PEM-encoded CA certificate
+    /// read from it. Fails if the CA certificate is malformed.
+    pub(crate) fn new_with_url_and_auth(
+        rest_endpoint: String,
+        token_file: Option<PathBuf>,
+        ca_certificate_file: Option<PathBuf>,
+    ) -> Result<Self> {
         let rest_url =
             Url::try_from(rest_endpoint.as_str()).context(RestUrlParse { rest_endpoint })?;
 
-        let config = RestConfig::builder()
-            .with_timeout(Duration::from_secs(30))
-            .with_tracing(true)
-            .build_url(rest_url.clone())
-            .map_err(|e| {
-                RestClientConfiguration {
-                    source: e,
-                    rest_endpoint: rest_url,
+        let token = token_file.as_deref().map(read_token).transpose()?;
+        let ca_certificate = ca_certificate_file
+            .as_deref()
+            .map(read_ca_certificate)
+            .transpose()?;
+        let client = build_client(&rest_url, token, ca_certificate.as_deref())?;
+
+        Ok(RestClientSet {
+            rest_url,
+            token_file,
+            ca_certificate,
+            client: RwLock::new(Arc::new(client)),
+            api_stats: Arc::default(),
+        })
+    }
+
+    /// Records the counts and the latencies of the API calls in 'api_stats', which may be shared
+    /// with other API clients, instead of in stats of this RestClientSet's own.
+    #[must_use]
+    pub(crate) fn with_api_stats(mut self, api_stats: Arc<ApiStats>) -> Self {
+        self.api_stats = api_stats;
+        self
+    }
+
+    /// Returns the stats which the API calls are recorded in.
+    pub(crate) fn api_stats(&self) -> &Arc<ApiStats> {
+        &self.api_stats
+    }
+
+    /// Lists the storage Nodes to check that the storage REST API is reachable, so that a wrong
+    /// endpoint fails the upgrade up front, rather than midway through a Node's upgrade.
+    pub(crate) async fn verify_reachable(&self) -> Result<()> {
+        self.call(GET_NODES, |client| async move {
+            client.nodes_api().get_nodes(None).await
+        })
+        .await
+        .context(RestEndpointUnreachable {
+            rest_endpoint: self.rest_url.clone(),
+        })?;
+        Ok(())
+    }
+
+    /// Runs a storage REST API call with the current client, and records its latency, with
+    /// ApiStats::timed_rest(). If the call is rejected as Unauthorized, e.g. because the bearer
+    /// token has expired, the token is read from the token file again and the call is retried
+    /// once with the new client.
+    pub(crate) async fn call<F, Fut, T>(&self, api_call: &'static str, call: F) -> Fut::Output
+    where
+        F: Fn(Arc<ApiClient>) -> Fut,
+        Fut: Future<Output = std::result::Result<T, RestError>>,
+    {
+        match self
+            .api_stats
+            .timed_rest(api_call, call(self.client()))
+            .await
+        {
+            Err(openapi::clients::tower::Error::Response(response))
+                if response.status().as_u16() == 401 && self.refresh_token() =>
+            {
+                self.api_stats
+                    .timed_rest(api_call, call(self.client()))
+                    .await
+            }
+            result => result,
+        }
+    }
+
+    /// Runs a storage REST API call like call(), and retries it with an exponential backoff and
+    /// jitter if it fails transiently, e.g. while the api-rest Pod is restarting. The call is
+    /// attempted up to MAX_CALL_ATTEMPTS times. Client errors are not retried.
+    pub(crate) async fn call_with_retry<F, Fut, T>(
+        &self,
+        api_call: &'static str,
+        call: F,
+    ) -> Fut::Output
+    where
+        F: Fn(Arc<ApiClient>) -> Fut,
+        Fut: Future<Output = std::result::Result<T, RestError>>,
+    {
+        let mut backoff = INITIAL_RETRY_BACKOFF;
+        let mut attempt = 1;
+        loop {
+            match self.call(api_call, &call).await {
+                Err(error) if attempt < MAX_CALL_ATTEMPTS && is_transient(&error) => {
+                    let delay = with_jitter(backoff);
+                    warn!(
+                        %error,
+                        api_call,
+                        attempt,
+                        ?delay,
+                        "Storage REST API call failed transiently, retrying"
+                    );
+                    tokio::time::sleep(delay).await;
+                    backoff = (backoff * 2).min(MAX_RETRY_BACKOFF);
+                    attempt += 1;
                 }
-                .build()
-            })?;
-        let client = ApiClient::new(config);
+                result => return result,
+            }
+        }
+    }
+
+    /// Re-reads the bearer token from the token file, and replaces the client with one which
+    /// uses the new token. Returns true if the client was replaced.
+    fn refresh_token(&self) -> bool {
+        let Some(token_file) = self.token_file.as_deref() else {
+            return false;
+        };
+
+        let client = match read_token(token_file).and_then(|token| {
+            build_client(&self.rest_url, Some(token), self.ca_certificate.as_deref())
+        }) {
+            Ok(client) => client,
+            Err(error) => {
+                warn!(%error, "Failed to refresh the storage REST API bearer token");
+                return false;
+            }
+        };
+
+        *self.client.write().unwrap_or_else(PoisonError::into_inner) = Arc::new(client);
+        info!(
+            token_file = %token_file.display(),
+            "Refreshed the storage REST API bearer token"
+        );
+        true
+    }
 
-        Ok(RestClientSet { client })
+    /// Returns the current client. The storage REST API calls are made through call(), which
+    /// hands the client to them.
+    fn client(&self) -> Arc<ApiClient> {
+        self.client
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clone()
     }
+}
+
+/// These are the storage Node REST API calls which the drain state machine makes, so that the
+/// state machine doesn't depend on the REST API client directly.
+#[async_trait]
+pub(crate) trait NodeCordonApi: Sync {
+    /// Gets the storage Node with the id 'node_id'.
+    async fn get_node(&self, node_id: &str) -> std::result::Result<Node, RestError>;
+
+    /// Drains the storage Node with the id 'node_id', with the drain label 'label'.
+    async fn put_node_drain(
+        &self,
+        node_id: &str,
+        label: &str,
+    ) -> std::result::Result<Node, RestError>;
+}
+
+#[async_trait]
+impl NodeCordonApi for RestClientSet {
+    async fn get_node(&self, node_id: &str) -> std::result::Result<Node, RestError> {
+        self.call_with_retry(GET_NODE, |client| async move {
+            client.nodes_api().get_node(node_id).await
+        })
+        .await
+        .map(|node| node.into_body())
+    }
+
+    async fn put_node_drain(
+        &self,
+        node_id: &str,
+        label: &str,
+    ) -> std::result::Result<Node, RestError> {
+        self.call_with_retry(PUT_NODE_DRAIN, |client| async move {
+            client.nodes_api().put_node_drain(node_id, label).await
+        })
+        .await
+        .map(|node| node.into_body())
+    }
+}
+
+/// Returns true if the storage REST API call may succeed if retried, i.e. if the server failed
+/// to handle it, or if there was no response at all, e.g. because the connection was refused.
+fn is_transient(error: &RestError) -> bool {
+    match error {
+        openapi::clients::tower::Error::Response(response) => response.status().is_server_error(),
+        _ => true,
+    }
+}
 
-    pub(crate) fn nodes_api(&self) -> &dyn openapi::apis::nodes_api::tower::client::Nodes {
-        self.client.nodes_api()
+/// Returns true if the storage REST API call failed as the resource doesn't exist, e.g. a Node
+/// which has been removed from the cluster.
+pub(crate) fn is_not_found(error: &RestError) -> bool {
+    matches!(
+        error,
+        openapi::clients::tower::Error::Response(response) if response.status().as_u16() == 404
+    )
+}
+
+/// Returns true if the storage REST API rejected the request as Forbidden.
+pub(crate) fn is_forbidden(error: &RestError) -> bool {
+    matches!(
+        error,
+        openapi::clients::tower::Error::Response(response) if response.status().as_u16() == 403
+    )
+}
+
+/// Returns a random delay between half of 'backoff' and 'backoff', so that the retries of the
+/// concurrent calls, or the delays in between the upgrades of Nodes, are spread out.
+pub(crate) fn with_jitter(backoff: Duration) -> Duration {
+    let random = RandomState::new().build_hasher().finish();
+    let half = backoff / 2;
+    half + Duration::from_millis(random % (half.as_millis() as u64 + 1))
+}
+
+/// Builds an ApiClient for the storage REST API, with an optional bearer token and an optional
+/// CA certificate for TLS.
+fn build_client(
+    rest_url: &Url,
+    token: Option<String>,
+    ca_certificate: Option<&[u8]>,
+) -> Result<ApiClient> {
+    let mut builder = RestConfig::builder()
+        .with_timeout(Duration::from_secs(30))
+        .with_tracing(true)
+        .with_bearer_token(token);
+    if let Some(ca_certificate) = ca_certificate {
+        builder = builder.with_certificate(ca_certificate);
     }
 
-    pub(crate) fn volumes_api(&self) -> &dyn openapi::apis::volumes_api::tower::client::Volumes {
-        self.client.volumes_api()
+    let config = builder.build_url(rest_url.clone()).map_err(|e| {
+        RestClientConfiguration {
+            source: e,
+            rest_endpoint: rest_url.clone(),
+        }
+        .build()
+    })?;
+
+    Ok(ApiClient::new(config))
+}
+
+/// Reads the bearer token from 'path'.
+fn read_token(path: &Path) -> Result<String> {
+    let token = std::fs::read_to_string(path).context(ReadRestTokenFile {
+        path: path.to_path_buf(),
+    })?;
+    Ok(token.trim().to_string())
+}
+
+/// Reads the PEM-encoded CA certificate from 'path', and checks that it holds a certificate.
+fn read_ca_certificate(path: &Path) -> Result<Vec<u8>> {
+    let certificate = std::fs::read(path).context(ReadRestCaCertificate {
+        path: path.to_path_buf(),
+    })?;
+
+    let pem = String::from_utf8_lossy(&certificate);
+    let begin = pem.find("-----BEGIN CERTIFICATE-----");
+    let end = pem.find("-----END CERTIFICATE-----");
+    match (begin, end) {
+        (Some(begin), Some(end)) if begin < end => Ok(certificate),
+        _ => InvalidRestCaCertificate {
+            path: path.to_path_buf(),
+        }
+        .fail(),
     }
 }
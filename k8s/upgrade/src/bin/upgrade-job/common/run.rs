@@ -0,0 +1,53 @@
+use crate::common::{
+    api_stats::ApiStats,
+    health::ForwardProgress,
+    progress::UpgradeProgress,
+    timeline::{Timeline, TimelineEvent},
+};
+use openapi::apis::Uuid;
+use std::sync::Arc;
+
+/// This is the state which is shared by the whole upgrade run, from the control-plane upgrade to
+/// the data-plane upgrade, along with the health probes which observe the run. The clones share
+/// the same state.
+#[derive(Clone, Debug)]
+pub(crate) struct UpgradeRun {
+    /// The ID of the run, which is attached to its logs, Kubernetes Events, timeline, in-flight
+    /// state and reports, so that everything related to one run may be correlated.
+    pub(crate) id: String,
+    /// The counts and the latencies of the run's API calls.
+    pub(crate) api_stats: Arc<ApiStats>,
+    /// The last time the run made forward progress.
+    pub(crate) forward_progress: ForwardProgress,
+    /// The in-flight state of the run, which its observability features report.
+    pub(crate) progress: UpgradeProgress,
+    /// The timeline of the run's events, which the API calls are recorded in too. This records
+    /// nothing until the upgrade enables it.
+    pub(crate) timeline: Timeline,
+}
+
+impl UpgradeRun {
+    /// Starts the run with the ID 'id', or with a random UUID if 'id' is not set.
+    pub(crate) fn new(id: Option<String>) -> Self {
+        let id = id.unwrap_or_else(|| Uuid::new_v4().to_string());
+        let timeline = Timeline::new(id.clone());
+        Self {
+            api_stats: Arc::new(ApiStats::new(timeline.clone())),
+            forward_progress: ForwardProgress::default(),
+            progress: UpgradeProgress::new(id.clone()),
+            timeline,
+            id,
+        }
+    }
+
+    /// Records the start of a phase of the upgrade, in the timeline and in the upgrade's progress.
+    /// The start of a phase is forward progress.
+    pub(crate) fn record_phase(&self, phase: &str, node_name: Option<&str>) {
+        self.forward_progress.record();
+        self.progress.set_phase(phase, node_name);
+        self.timeline.record(TimelineEvent::Phase {
+            phase: phase.to_string(),
+            node_name: node_name.map(ToString::to_string),
+        });
+    }
+}
@@ -0,0 +1,111 @@
+use crate::common::error::{CreateTimelineFile, Result};
+use serde::Serialize;
+use snafu::ResultExt;
+use std::{
+    fs::File,
+    io::Write,
+    path::Path,
+    sync::{Arc, Mutex, PoisonError},
+    time::SystemTime,
+};
+use tracing::{info, warn};
+
+/// This is one entry of the timeline. The timeline file has one JSON object per line, in the order
+/// in which the events occurred, e.g.
+/// {"timestamp":"2024-01-01T00:00:00.000Z","kind":"phase","phase":"drain","nodeName":"node-1"}
+#[derive(Serialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+struct TimelineEntry<'a> {
+    /// The RFC 3339 time at which the event was recorded, with millisecond precision.
+    timestamp: String,
+    /// The ID of the upgrade run.
+    run_id: &'a str,
+    #[serde(flatten)]
+    event: &'a TimelineEvent,
+}
+
+/// These are the events which are recorded in the timeline.
+#[derive(Serialize, Debug)]
+#[serde(tag = "kind", rename_all(serialize = "camelCase"))]
+pub(crate) enum TimelineEvent {
+    /// A phase of the upgrade has started. The Node is absent for the phases which aren't
+    /// specific to a Node, e.g. the control-plane upgrade.
+    #[serde(rename_all(serialize = "camelCase"))]
+    Phase {
+        phase: String,
+        node_name: Option<String>,
+    },
+    /// A state machine observed the state of a storage Node, and decided on an action.
+    #[serde(rename_all(serialize = "camelCase"))]
+    StateObservation {
+        state_machine: serde_json::Value,
+        node_id: String,
+        observed_state: serde_json::Value,
+        decision: serde_json::Value,
+    },
+    /// A Kubernetes or storage API call completed.
+    #[serde(rename_all(serialize = "camelCase"))]
+    ApiCall { api_call: String, latency_ms: f64 },
+    /// The upgrade failed.
+    #[serde(rename_all(serialize = "camelCase"))]
+    Error { message: String },
+}
+
+/// This is the writer of the upgrade's timeline. It records nothing until it is enabled. The
+/// clones share the same file, so that the events recorded by the API clients, the drain state
+/// machines and the phases of the upgrade are written to the one timeline, in order.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct Timeline {
+    /// The ID of the upgrade run, which every entry carries.
+    run_id: String,
+    /// The file which the timeline is written to. This is only set once the timeline is enabled.
+    file: Arc<Mutex<Option<File>>>,
+}
+
+impl Timeline {
+    /// Creates the timeline of the upgrade run with the ID 'run_id'. It isn't enabled yet.
+    pub(crate) fn new(run_id: String) -> Self {
+        Self {
+            run_id,
+            file: Arc::default(),
+        }
+    }
+
+    /// This enables the timeline. The file at 'path' is created (or truncated), and every event
+    /// after this is appended to it as it occurs, so that a partial timeline is left behind if
+    /// the upgrade-job crashes.
+    pub(crate) fn enable(&self, path: &Path) -> Result<()> {
+        let file = File::create(path).context(CreateTimelineFile {
+            path: path.to_path_buf(),
+        })?;
+        info!(path = %path.display(), "Writing the upgrade timeline to file");
+        *self.file.lock().unwrap_or_else(PoisonError::into_inner) = Some(file);
+        Ok(())
+    }
+
+    /// Appends an event to the timeline. This is a no-op unless the timeline is enabled.
+    /// Failures to write are logged and are not returned.
+    pub(crate) fn record(&self, event: TimelineEvent) {
+        let mut file = self.file.lock().unwrap_or_else(PoisonError::into_inner);
+        let Some(file) = file.as_mut() else {
+            return;
+        };
+
+        let entry = TimelineEntry {
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            run_id: self.run_id.as_str(),
+            event: &event,
+        };
+        let line = match serde_json::to_string(&entry) {
+            Ok(line) => line,
+            Err(error) => {
+                warn!(%error, ?event, "Failed to serialize timeline event");
+                return;
+            }
+        };
+
+        if let Err(error) = writeln!(file, "{line}").and_then(|_| file.flush()) {
+            warn!(%error, "Failed to write timeline event");
+        }
+    }
+}
@@ -17,6 +17,7 @@ use tracing::error;
 #[derive(Serialize, Debug)]
 #[serde(rename_all(serialize = "camelCase"))]
 pub(crate) struct EventNote {
+    run_id: String,
     from_version: String,
     to_version: String,
     message: String,
@@ -25,6 +26,7 @@ pub(crate) struct EventNote {
 impl From<&EventRecorder> for EventNote {
     fn from(er: &EventRecorder) -> EventNote {
         EventNote {
+            run_id: er.run_id.clone(),
             from_version: er.from_version.clone(),
             to_version: er.to_version.clone(),
             message: Default::default(),
@@ -46,6 +48,7 @@ pub(crate) struct EventRecorderBuilder {
     namespace: Option<String>,
     from_version: Option<String>,
     to_version: Option<String>,
+    run_id: Option<String>,
 }
 
 impl EventRecorderBuilder {
@@ -71,6 +74,17 @@ impl EventRecorderBuilder {
         self
     }
 
+    /// This is a builder option to add the ID of the upgrade run, which is set in the notes of
+    /// the events.
+    #[must_use]
+    pub(crate) fn with_run_id<T>(mut self, run_id: T) -> Self
+    where
+        T: ToString,
+    {
+        self.run_id = Some(run_id.to_string());
+        self
+    }
+
     // TODO: Make the builder option validations error out at compile-time, using std::compile_error
     // or something similar.
     /// This builds the EventRecorder. This fails if Kubernetes API requests fail.
@@ -90,6 +104,7 @@ impl EventRecorderBuilder {
             .clone()
             .unwrap_or(vers_placeholder.clone());
         let to_version = self.to_version.clone().unwrap_or(vers_placeholder);
+        let run_id = self.run_id.clone().unwrap_or_default();
 
         let k8s_client = KubeClientSet::builder()
             .with_namespace(namespace.as_str())
@@ -175,6 +190,7 @@ impl EventRecorderBuilder {
             event_loop_handle,
             from_version,
             to_version,
+            run_id,
         })
     }
 }
@@ -185,6 +201,7 @@ pub(crate) struct EventRecorder {
     event_loop_handle: tokio::task::JoinHandle<()>,
     from_version: String,
     to_version: String,
+    run_id: String,
 }
 
 impl EventRecorder {
@@ -1,10 +1,12 @@
 use crate::{
     common::{
-        constants::{CORE_CHART_NAME, TO_UMBRELLA_SEMVER, UMBRELLA_CHART_NAME},
+        constants::{
+            CHART_VERSION_LABEL_KEY, CORE_CHART_NAME, TO_UMBRELLA_SEMVER, UMBRELLA_CHART_NAME,
+        },
         error::{
             CoreChartUpgradeNoneChartDir, HelmUpgradeOptionsAbsent, InvalidHelmUpgrade,
             InvalidUpgradePath, NoInputHelmChartDir, NotAKnownHelmChart, RegexCompile, Result,
-            RollbackForbidden, UmbrellaChartNotUpgraded,
+            RollbackForbidden, SameFromAndToVersion, UmbrellaChartNotUpgraded,
         },
     },
     helm::{client::HelmReleaseClient, values::generate_values_yaml_file},
@@ -33,8 +35,10 @@ pub(crate) struct HelmUpgradeBuilder {
     namespace: Option<String>,
     core_chart_dir: Option<PathBuf>,
     skip_upgrade_path_validation: bool,
+    fail_on_same_version: bool,
     helm_args_set: Option<String>,
     helm_args_set_file: Option<String>,
+    chart_version_label_key: Option<String>,
 }
 
 impl HelmUpgradeBuilder {
@@ -75,6 +79,14 @@ impl HelmUpgradeBuilder {
         self
     }
 
+    /// This sets the flag to fail the upgrade if the installed version is the same as the version
+    /// being upgraded to.
+    #[must_use]
+    pub(crate) fn with_fail_on_same_version(mut self, fail_on_same_version: bool) -> Self {
+        self.fail_on_same_version = fail_on_same_version;
+        self
+    }
+
     /// This is a builder option to add set flags set during upgrade.
     #[must_use]
     pub(crate) fn with_helm_args_set<J>(mut self, helm_args_set: J) -> Self
@@ -95,6 +107,17 @@ impl HelmUpgradeBuilder {
         self
     }
 
+    /// This is a builder option to set the key of the chart version label on the Storage REST API
+    /// Deployment, if it isn't CHART_VERSION_LABEL_KEY.
+    #[must_use]
+    pub(crate) fn with_chart_version_label_key<J>(mut self, chart_version_label_key: J) -> Self
+    where
+        J: ToString,
+    {
+        self.chart_version_label_key = Some(chart_version_label_key.to_string());
+        self
+    }
+
     /// This builds the HelmUpgrade object.
     pub(crate) async fn build(self) -> Result<HelmUpgrade> {
         ensure!(
@@ -105,6 +128,10 @@ impl HelmUpgradeBuilder {
         let namespace = self.namespace.clone().unwrap();
         let helm_args_set = self.helm_args_set.clone().unwrap_or_default();
         let helm_args_set_file = self.helm_args_set_file.clone().unwrap_or_default();
+        let chart_version_label_key = self
+            .chart_version_label_key
+            .clone()
+            .unwrap_or_else(|| CHART_VERSION_LABEL_KEY.to_string());
 
         // Generate HelmReleaseClient.
         let client = HelmReleaseClient::builder()
@@ -116,8 +143,11 @@ impl HelmUpgradeBuilder {
 
         // The version of the Core helm chart (installed as a the parent chart or as a dependent
         // chart) which is installed in the cluster.
-        let from_version: Version =
-            upgrade::path::version_from_rest_deployment_label(namespace.as_str()).await?;
+        let from_version: Version = upgrade::path::version_from_rest_deployment_label(
+            namespace.as_str(),
+            chart_version_label_key.as_str(),
+        )
+        .await?;
 
         // The version of the Core chart which we are (maybe) going to.
         let chart_dir: PathBuf = self.core_chart_dir.ok_or(
@@ -176,6 +206,12 @@ impl HelmUpgradeBuilder {
         {
             chart_variant = HelmChart::Core;
 
+            // This is a no-op for the helm chart, but the data-plane upgrade may carry on from a
+            // previous attempt. It's also what a mistaken invocation would look like.
+            if already_upgraded {
+                verify_same_version_allowed(&to_version, self.fail_on_same_version)?;
+            }
+
             // Skip upgrade-path validation and allow all upgrades for the Core helm chart, if the
             // flag is set.
             if !self.skip_upgrade_path_validation {
@@ -344,3 +380,45 @@ impl HelmUpgrade {
 /// HelmUpgradeRunner is returned after an upgrade is validated and dry-run-ed. Running
 /// it carries out helm upgrade.
 pub(crate) type HelmUpgradeRunner = Pin<Box<dyn Future<Output = Result<()>>>>;
+
+/// Fails with SameFromAndToVersion if 'fail_on_same_version' is set, as the installed version is
+/// the same as the version being upgraded to, 'version'.
+fn verify_same_version_allowed(version: &Version, fail_on_same_version: bool) -> Result<()> {
+    ensure!(
+        !fail_on_same_version,
+        SameFromAndToVersion {
+            version: version.to_string()
+        }
+    );
+    info!(
+        %version,
+        "The installed {CORE_CHART_NAME} helm chart version is the same as the version being \
+        upgraded to, only the data-plane Pods which haven't been upgraded yet will be restarted"
+    );
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::common::error::Error;
+
+    #[test]
+    fn same_version_is_allowed_without_fail_on_same_version() {
+        let version = Version::new(2, 5, 0);
+        assert!(verify_same_version_allowed(&version, false).is_ok());
+    }
+
+    #[test]
+    fn same_version_fails_with_fail_on_same_version() {
+        let version = Version::new(2, 5, 0);
+        let result = verify_same_version_allowed(&version, true);
+        assert!(
+            matches!(
+                result,
+                Err(Error::SameFromAndToVersion { ref version }) if version == "2.5.0"
+            ),
+            "{result:?}"
+        );
+    }
+}
@@ -1,5 +1,12 @@
 use crate::{
-    common::{constants::PRODUCT, error::Result},
+    common::{
+        constants::PRODUCT,
+        error::{Result, UpgradeExitCode},
+        health::HealthServer,
+        logging::init_logging,
+        namespace::discover_namespace,
+        run::UpgradeRun,
+    },
     opts::validators::{
         validate_helm_chart_dir, validate_helm_release, validate_helmv3_in_path,
         validate_namespace, validate_rest_endpoint,
@@ -8,11 +15,9 @@ use crate::{
 };
 use clap::Parser;
 use opts::CliArgs;
-use tracing::{error, info};
-use utils::{
-    print_package_info, raw_version_str,
-    tracing_telemetry::{default_tracing_tags, flush_traces, init_tracing},
-};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, info_span, Instrument};
+use utils::{print_package_info, tracing_telemetry::flush_traces};
 
 mod common;
 mod events;
@@ -23,34 +28,71 @@ mod upgrade;
 #[tokio::main]
 async fn main() -> Result<()> {
     print_package_info!();
-    init_logging();
+    let mut opts = CliArgs::parse();
+    init_logging(opts.log_format());
+    // The ID, the API calls and the forward progress of the whole run are kept in one place, for
+    // the logs, the API call summary and the health probes.
+    let run = UpgradeRun::new(opts.run_id());
+
+    // The namespace may be left out for a single install.
+    if !opts.namespace_is_set() {
+        let namespace = discover_namespace(&run.api_stats).await.map_err(|error| {
+            error!(%error, "Failed to upgrade {PRODUCT}");
+            error
+        })?;
+        opts.set_namespace(namespace);
+    }
 
-    let opts = parse_cli_args().await.map_err(|error| {
+    validate_cli_args(&opts).await.map_err(|error| {
         error!(%error, "Failed to upgrade {PRODUCT}");
         error
     })?;
 
-    upgrade(&opts).await.map_err(|error| {
-        error!(%error, "Failed to upgrade {PRODUCT}");
-        flush_traces();
-        error
-    })
-}
+    info!(run.id = %run.id, "Starting {PRODUCT} upgrade run");
 
-/// Initialize logging components -- tracing.
-fn init_logging() {
-    let tags = default_tracing_tags(raw_version_str(), env!("CARGO_PKG_VERSION"));
+    // The health probes stop serving when this is dropped, on returning.
+    let _health_server = match opts.health_port() {
+        Some(port) => Some(
+            HealthServer::serve(
+                port,
+                opts.liveness_stall_threshold(),
+                run.forward_progress.clone(),
+            )
+            .await?,
+        ),
+        None => None,
+    };
 
-    init_tracing("upgrade-job", tags, None);
+    // This is cancelled by the first termination signal during the data-plane upgrade, which
+    // requests a graceful shutdown.
+    let cancellation = CancellationToken::new();
+    let result = upgrade(&opts, &cancellation, &run)
+        .instrument(info_span!("upgrade", run.id = %run.id))
+        .await;
+    let exit_code = UpgradeExitCode::from(&result);
+    if let Err(error) = result {
+        error!(
+            %error,
+            run.id = %run.id,
+            exit_code = exit_code.as_i32(),
+            "Failed to upgrade {PRODUCT}"
+        );
+        flush_traces();
+        // The kind of failure is told apart by the exit code.
+        std::process::exit(exit_code.as_i32());
+    }
+    Ok(())
 }
 
-/// This function handles the following tasks -- 1. Argument parsing, 2. Validating arguments whose
-/// validation depends on other arguments.
-pub(crate) async fn parse_cli_args() -> Result<CliArgs> {
-    let opts = CliArgs::parse();
-
+/// This validates the arguments whose validation depends on other arguments, or on the cluster.
+pub(crate) async fn validate_cli_args(opts: &CliArgs) -> Result<()> {
     validate_namespace(opts.namespace()).await?;
-    validate_rest_endpoint(opts.rest_endpoint()).await?;
+    validate_rest_endpoint(
+        opts.rest_endpoint(),
+        opts.rest_token_file(),
+        opts.rest_ca_cert_file(),
+    )
+    .await?;
 
     validate_helmv3_in_path()?;
     validate_helm_release(opts.release_name(), opts.namespace())?;
@@ -58,5 +100,5 @@ pub(crate) async fn parse_cli_args() -> Result<CliArgs> {
 
     info!("Validated all inputs");
 
-    Ok(opts)
+    Ok(())
 }
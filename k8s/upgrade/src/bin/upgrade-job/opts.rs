@@ -1,7 +1,23 @@
-use crate::common::constants::PRODUCT;
+use crate::{
+    common::{
+        constants::{
+            AGENT_CORE_LABEL, CHART_VERSION_LABEL_KEY, CSI_NODE_LABEL, DRAIN_FOR_UPGRADE, PRODUCT,
+        },
+        drain_label::DrainLabels,
+        logging::{LogFormat, WaitLogVerbosity},
+    },
+    upgrade::{
+        drain_mode::DrainMode,
+        external_cordon::ExternalCordonPolicy,
+        maintenance_window::MaintenanceWindow,
+        node_order::{PriorityVolumesOrder, SecondaryOrder},
+        pod_selection::IoEnginePodSelection,
+        readiness::{ExtraPodReadiness, ReadinessStrategy},
+    },
+};
 use clap::Parser;
-use std::path::PathBuf;
-use utils::{package_description, version_info_str};
+use std::{path::PathBuf, time::Duration};
+use utils::{package_description, version_info_str, API_REST_LABEL, ETCD_LABEL};
 
 /// Validate input whose validation depends on other inputs.
 pub(crate) mod validators;
@@ -15,9 +31,10 @@ pub(crate) struct CliArgs {
     #[arg(short = 'e', long)]
     rest_endpoint: String,
 
-    /// This is the Kubernetes Namespace for the Helm release.
+    /// This is the Kubernetes Namespace for the Helm release. If this is not set, the namespace
+    /// of the io-engine DaemonSet and the agent-core Deployment is used, if there is exactly one.
     #[arg(short, long)]
-    namespace: String,
+    namespace: Option<String>,
 
     /// This is the release name of the installed Helm chart.
     #[arg(long)]
@@ -31,6 +48,586 @@ pub(crate) struct CliArgs {
     #[arg(long, default_value_t = false)]
     skip_data_plane_restart: bool,
 
+    /// The name of a ConfigMap in the release Namespace with per-phase timeouts for the
+    /// data-plane upgrade (keys: drainTimeout, rebuildTimeout, readinessTimeout,
+    /// controlPlaneTimeout). This is re-read before each Node is upgraded.
+    #[arg(long, value_name = "NAME")]
+    timeouts_config_map: Option<String>,
+
+    /// The UUIDs of volumes whose Nodes (target and replicas) are upgraded first or last
+    /// (can specify multiple or separate values with commas: uuid1,uuid2).
+    #[arg(long, value_delimiter = ',', value_name = "UUID")]
+    priority_volumes: Vec<String>,
+
+    /// The names of the Nodes to upgrade, for a targeted data-plane upgrade. If set, the io-engine
+    /// Pods on the rest of the Nodes are left as they are (can specify multiple or separate
+    /// values with commas: node1,node2).
+    #[arg(long, value_delimiter = ',', value_name = "NODE")]
+    include_nodes: Vec<String>,
+
+    /// The names of the Nodes which are not upgraded, even if they're included (can specify
+    /// multiple or separate values with commas: node1,node2).
+    #[arg(long, value_delimiter = ',', value_name = "NODE")]
+    exclude_nodes: Vec<String>,
+
+    /// This decides if the Nodes hosting the priority volumes are upgraded first or last.
+    #[arg(long, value_enum, default_value_t = PriorityVolumesOrder::First)]
+    priority_volumes_order: PriorityVolumesOrder,
+
+    /// This decides the order of the io-engine Pods within the Nodes hosting the priority volumes,
+    /// and within the rest of the Nodes. The priority volumes' ordering always comes first.
+    #[arg(long, value_enum, default_value_t = SecondaryOrder::None)]
+    secondary_order: SecondaryOrder,
+
+    /// If set, the consistency checks during the data-plane upgrade fail the upgrade instead of
+    /// logging a warning.
+    #[arg(long, default_value_t = false)]
+    strict: bool,
+
+    /// Testing aid, never use this for a real upgrade. If set, each of the data-plane upgrade's
+    /// wait phases lasts at least as long as the simulated phase duration, as if the cluster was
+    /// slow. This is used to validate the timeouts.
+    #[arg(long, hide = true, default_value_t = false)]
+    simulate_slow_cluster: bool,
+
+    /// The minimum duration of each data-plane upgrade wait phase with --simulate-slow-cluster.
+    #[arg(long, hide = true, default_value = "2m", value_parser = humantime::parse_duration)]
+    simulated_phase_duration: Duration,
+
+    /// If set, the list of storage Nodes is fetched once and reused for the rest of the upgrade,
+    /// instead of being fetched again each time it is required.
+    #[arg(long, default_value_t = false)]
+    cache_node_list: bool,
+
+    /// If set, after a Node's io-engine Pod is restarted, this waits for every volume which had a
+    /// replica on the Node to have as many Online replicas as its configured replica count. The
+    /// missing replicas are waited for to be re-created first, and the wait fails early if no
+    /// pool has room for them.
+    #[arg(long, default_value_t = false)]
+    verify_replica_count: bool,
+
+    /// If set, after a Node's io-engine Pod is restarted, this verifies that none of the volumes
+    /// which had their target or a replica on the Node is Faulted.
+    #[arg(long, default_value_t = false)]
+    verify_volumes_accessible: bool,
+
+    /// This decides which of a Node's yet-to-upgrade io-engine Pods are deleted with each drain
+    /// of the Node, if there is more than one.
+    #[arg(long, value_enum, default_value_t = IoEnginePodSelection::All)]
+    io_engine_pod_selection: IoEnginePodSelection,
+
+    /// If set, only the io-engine Pods owned by the DaemonSet with this name are upgraded.
+    #[arg(long, value_name = "NAME")]
+    io_engine_daemonset: Option<String>,
+
+    /// The keys of the Kubernetes Node labels which are verified to be unchanged after each
+    /// Node's upgrade (can specify multiple or separate values with commas: key1,key2).
+    #[arg(long, value_delimiter = ',', value_name = "KEY")]
+    monitored_node_labels: Vec<String>,
+
+    /// If set, every drain state of a storage Node observed while draining and uncordoning it
+    /// is logged as JSON, along with the action taken.
+    #[arg(long, default_value_t = false)]
+    drain_decision_log: bool,
+
+    /// A shell command which backs up the cluster state, e.g. by triggering an etcd backup. This
+    /// is run once, before anything is upgraded, and the upgrade is aborted if it fails.
+    #[arg(long, value_name = "COMMAND")]
+    pre_upgrade_backup: Option<String>,
+
+    /// If set, the upgrade fails if the installed version is the same as the version being
+    /// upgraded to, instead of only restarting the data-plane Pods which aren't upgraded yet.
+    #[arg(long, default_value_t = false)]
+    fail_on_same_version: bool,
+
+    /// If set, after a Node's io-engine Pod is restarted, this waits for the VolumeAttachments of
+    /// the PersistentVolumes backed by the volumes on the Node to be attached.
+    #[arg(long, default_value_t = false)]
+    wait_for_volume_attachments: bool,
+
+    /// If set, after a Node's io-engine Pod is restarted, this waits for the CSI node plugin Pods
+    /// on the Node to be Ready on the target version, so that the volumes on the Node may be
+    /// mounted and unmounted before the next Node is upgraded.
+    #[arg(long, default_value_t = false)]
+    verify_csi_node_plugin: bool,
+
+    /// The label selector of the CSI node plugin Pods.
+    #[arg(long, default_value = CSI_NODE_LABEL, value_name = "SELECTOR")]
+    csi_node_label: String,
+
+    /// If set, every significant event of the upgrade (phase transitions, storage Node state
+    /// observations, API calls and errors) is written to this file as it occurs, one JSON object
+    /// per line.
+    #[arg(long, value_name = "FILE")]
+    timeline: Option<PathBuf>,
+
+    /// If set, the control-plane's health is checked periodically while each Node is drained and
+    /// restarted, and the Node's upgrade is abandoned (and the Node uncordoned) if it becomes
+    /// unhealthy.
+    #[arg(long, default_value_t = false)]
+    monitor_control_plane: bool,
+
+    /// The label selectors of the control-plane components whose Pods have to be Ready on the
+    /// target version before and during each Node's upgrade (can specify multiple). Pass an empty
+    /// value to require none of them, e.g. for a deployment without these components.
+    #[arg(
+        long = "control-plane-component",
+        value_name = "LABEL_SELECTOR",
+        default_values = [AGENT_CORE_LABEL, API_REST_LABEL]
+    )]
+    control_plane_components: Vec<String>,
+
+    /// The label selectors of the control-plane components whose Pods have to be Ready on any
+    /// version before and during each Node's upgrade, as they aren't upgraded along with the chart
+    /// (can specify multiple). Pass an empty value to require none of them.
+    #[arg(
+        long = "unversioned-control-plane-component",
+        value_name = "LABEL_SELECTOR",
+        default_values = [ETCD_LABEL]
+    )]
+    unversioned_control_plane_components: Vec<String>,
+
+    /// If set, the agent-core and api-rest Deployments whose Pods aren't on the target version
+    /// are restarted before the wait for the control-plane ahead of each batch of Nodes, if their
+    /// Pod templates are on the target version. This way, the job doesn't rely on something else
+    /// to complete the control-plane's rollout.
+    #[arg(long, default_value_t = false)]
+    upgrade_control_plane: bool,
+
+    /// If set, a Node which fails to upgrade is uncordoned and skipped, and the upgrade carries
+    /// on with the rest of the Nodes. The upgrade still fails once all of the Nodes are done.
+    #[arg(long, default_value_t = false)]
+    continue_on_error: bool,
+
+    /// If set along with --continue-on-error, the upgrade is aborted after this many Nodes fail
+    /// to upgrade one after the other.
+    #[arg(long, requires = "continue_on_error", value_name = "COUNT")]
+    max_consecutive_failures: Option<u32>,
+
+    /// If set along with --continue-on-error, the upgrade is aborted once more than this many
+    /// Nodes have failed to upgrade in total.
+    #[arg(long, requires = "continue_on_error", value_name = "COUNT")]
+    max_node_failures: Option<u32>,
+
+    /// A shell command which tests the cluster after the upgrade, e.g. by creating, writing to,
+    /// reading from and deleting a test volume. The upgrade fails if the command exits with a
+    /// non-zero status. A failed smoke test leaves the cluster upgraded, but the upgrade is
+    /// reported as failed.
+    #[arg(long, value_name = "COMMAND")]
+    post_upgrade_smoke: Option<String>,
+
+    /// The maximum time that each of the hook commands, i.e. the pre-upgrade backup and the
+    /// post-upgrade smoke test, may run for. A hook command which runs for longer is killed, and
+    /// the hook fails.
+    #[arg(long, default_value = "30m", value_parser = humantime::parse_duration)]
+    hook_timeout: Duration,
+
+    /// If set, a Unix domain socket is served at this path, which responds to each connection
+    /// with the current phase, Node and progress of the upgrade as JSON. The socket file is
+    /// removed when the upgrade-job exits.
+    #[arg(long, value_name = "PATH")]
+    progress_socket: Option<PathBuf>,
+
+    /// If set, the ConfigMap with this name in the namespace is created at the start of the
+    /// data-plane upgrade, and is updated with the upgrade's progress after each Node, so that it
+    /// may be polled from outside of the cluster. It is marked as finished once the data-plane
+    /// upgrade completes.
+    #[arg(long, value_name = "NAME")]
+    progress_config_map: Option<String>,
+
+    /// If set, the progress of the data-plane upgrade is served as Prometheus metrics over HTTP
+    /// on this port, for as long as the data-plane upgrade runs.
+    #[arg(long, value_name = "PORT")]
+    metrics_port: Option<u16>,
+
+    /// If set, the '/healthz' and '/readyz' health probes are served over HTTP on this port, for
+    /// the upgrade-job's liveness and readiness probes.
+    #[arg(long, value_name = "PORT")]
+    health_port: Option<u16>,
+
+    /// The '/healthz' health probe fails once the upgrade has made no forward progress, i.e. no
+    /// phase has started and no Node has been upgraded, for this long. This must be longer than
+    /// the longest phase, e.g. the wait for the volume rebuilds.
+    #[arg(long, default_value = "15m", value_parser = humantime::parse_duration)]
+    liveness_stall_threshold: Duration,
+
+    /// If set, the data-plane upgrade is stopped once it has made no forward progress, i.e. no
+    /// phase has started and no Node has been upgraded, for this long. The Nodes which are
+    /// drained for the upgrade are uncordoned.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    idle_timeout: Option<Duration>,
+
+    /// If set, this is the deadman switch for the Nodes which are cordoned for the upgrade. Before
+    /// a Node is drained, its Kubernetes Node is annotated with the time at which its cordon
+    /// expires, this long from then. The annotation is renewed every third of this duration for
+    /// as long as the Node is cordoned, and it is removed once the Node is uncordoned. If the
+    /// upgrade-job dies without uncordoning the Node, e.g. if it is OOM-killed, the annotation
+    /// isn't renewed and the cordon expires. An upgrade-job which is run with this set uncordons
+    /// the Nodes whose cordon has expired before it drains any Node.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    cordon_ttl: Option<Duration>,
+
+    /// The time to wait for after a Node is upgraded, before the next Node is drained, so that
+    /// the replicas on the upgraded Node may re-sync first. Up to half of it is taken off at
+    /// random.
+    #[arg(long, default_value = "0s", value_parser = humantime::parse_duration)]
+    inter_node_delay: Duration,
+
+    /// This decides when a Node's restarted io-engine Pods are considered to be ready.
+    /// 'daemonset-status' also waits for the io-engine DaemonSets' status to show the rollout to
+    /// have progressed for the Node.
+    #[arg(long, value_enum, default_value_t = ReadinessStrategy::PodReady)]
+    readiness_strategy: ReadinessStrategy,
+
+    /// If set, the replacement io-engine Pods are only considered to be ready once this Pod
+    /// condition, e.g. a readiness gate, is True as well.
+    #[arg(long, value_name = "CONDITION_TYPE")]
+    io_engine_readiness_condition: Option<String>,
+
+    /// If set, the replacement io-engine Pods are only considered to be ready once this container
+    /// is ready as well, i.e. once its readiness probe passes.
+    #[arg(long, value_name = "CONTAINER_NAME")]
+    io_engine_ready_container: Option<String>,
+
+    /// The time for which the replacement io-engine Pods have to stay ready before they're
+    /// considered to be ready, so that a Pod which flaps between Ready and NotReady doesn't count
+    /// as upgraded.
+    #[arg(long, default_value = "0s", value_parser = humantime::parse_duration)]
+    io_engine_ready_stable_for: Duration,
+
+    /// If set, the drain and the rebuild timeouts for each Node are the total size of the
+    /// replicas on the Node divided by this expected rebuild throughput, in MiB/s, but no less
+    /// than the size-based timeout floor. The fixed timeouts are used for the Nodes whose replica
+    /// sizes can't be determined.
+    #[arg(long, value_name = "MIB_PER_SEC")]
+    rebuild_throughput: Option<u64>,
+
+    /// The least drain and rebuild timeout for each Node, when the timeouts are based on the size
+    /// of the Node's replicas, so that the Nodes with little data aren't timed out prematurely.
+    #[arg(long, default_value = "1m", value_parser = humantime::parse_duration)]
+    rebuild_timeout_floor: Duration,
+
+    /// If set then, before upgrading each Node, a small test volume is created and deleted, to
+    /// verify that the control plane is functional. This is intrusive, and so is disabled by
+    /// default.
+    #[arg(long, default_value_t = false)]
+    verify_volume_scheduling: bool,
+
+    /// If set, the data-plane upgrade only starts upgrading a Node within this daily maintenance
+    /// window, in UTC, e.g. '22:00-04:30'. A Node whose upgrade is in progress when the window
+    /// closes is upgraded regardless, and the upgrade resumes when the window opens again.
+    #[arg(long, value_name = "HH:MM-HH:MM")]
+    maintenance_window: Option<MaintenanceWindow>,
+
+    /// If set, the data-plane upgrade fails once this much time has passed since it started, e.g.
+    /// '4h'. No more Nodes are started on after the deadline, and the Nodes whose upgrades are
+    /// in progress are upgraded and uncordoned regardless.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    upgrade_deadline: Option<Duration>,
+
+    /// If set, the health of the etcd cluster is checked via the '/health' endpoint of this etcd
+    /// client URL, e.g. 'http://mayastor-etcd:2379', along with the control-plane checks before
+    /// each Node is upgraded. The upgrade fails if etcd reports an unhealthy cluster.
+    #[arg(long, value_name = "URL")]
+    etcd_health_endpoint: Option<String>,
+
+    /// The ID of this upgrade run, which is attached to its logs, Kubernetes Events, timeline and
+    /// in-flight state. A random UUID is used if this is not set.
+    #[arg(long, env = "RUN_ID")]
+    run_id: Option<String>,
+
+    /// The format of the upgrade-job's logs.
+    #[arg(long, value_enum, env = "LOG_FORMAT", default_value_t = LogFormat::Text)]
+    log_format: LogFormat,
+
+    /// How much the data-plane upgrade's waits log while they poll, e.g. the waits for the volume
+    /// rebuilds and for the io-engine Pods' readiness. 'quiet' logs each poll at trace, 'normal'
+    /// at debug, and 'verbose' at info, along with the state of the Pods or the volumes which are
+    /// waited on. The start and the end of each wait are logged at info regardless.
+    #[arg(
+        long,
+        value_enum,
+        env = "WAIT_LOG_VERBOSITY",
+        default_value_t = WaitLogVerbosity::Normal
+    )]
+    wait_log_verbosity: WaitLogVerbosity,
+
+    /// The label which the storage Nodes are drained and cordoned with for the upgrade, e.g. a
+    /// unique label per run so that concurrent or earlier upgrade runs' labels aren't confused
+    /// with this run's. Only the Nodes carrying this label are uncordoned by this run.
+    #[arg(long, default_value = DRAIN_FOR_UPGRADE)]
+    drain_label: String,
+
+    /// Additional labels which the storage Nodes are drained with alongside the drain label, e.g.
+    /// for external Node lifecycle controllers which watch for them. These are removed along with
+    /// the drain label once the Node is upgraded (can specify multiple or separate values with
+    /// commas: label1,label2).
+    #[arg(long, value_delimiter = ',', value_name = "LABEL")]
+    extra_drain_labels: Vec<String>,
+
+    /// The key of the label which carries the chart version on the Pods and the Deployments,
+    /// e.g. for clusters which were installed before the current labeling convention.
+    #[arg(long, default_value = CHART_VERSION_LABEL_KEY)]
+    chart_version_label_key: String,
+
+    /// If set then the storage Nodes which are left drained with the drain label by a previous
+    /// upgrade attempt are not uncordoned before the data-plane upgrade starts.
+    #[arg(long, default_value_t = false)]
+    skip_stale_drain_label_cleanup: bool,
+
+    /// If set then the distribution of the replicas across the storage pools is reported before
+    /// and after the data-plane upgrade, in the data-plane upgrade report, and an imbalance
+    /// introduced by the upgrade is flagged. This is purely informational, no replicas are moved.
+    #[arg(long, default_value_t = false)]
+    report_replica_distribution: bool,
+
+    /// The file which the bearer token for the storage REST API is read from, if the API requires
+    /// one. The token is read again if a request is rejected as Unauthorized, so that a token
+    /// which is rotated in the file doesn't fail a long-running upgrade when the old one expires.
+    #[arg(long, env = "REST_TOKEN_FILE")]
+    rest_token_file: Option<PathBuf>,
+
+    /// The file which the PEM-encoded CA certificate for the storage REST API's TLS certificate
+    /// is read from, if the API is served over TLS with a certificate which isn't publicly
+    /// trusted.
+    #[arg(long, env = "REST_CA_CERT_FILE")]
+    rest_ca_cert_file: Option<PathBuf>,
+
+    /// The kubeconfig file which the data-plane upgrade connects to the cluster with, e.g. when
+    /// running the upgrade from a workstation. The in-cluster config is used if neither this nor
+    /// the kubeconfig context is set.
+    #[arg(long)]
+    kubeconfig: Option<PathBuf>,
+
+    /// The kubeconfig context which the data-plane upgrade connects to the cluster with. The
+    /// kubeconfig's current context is used if this is not set.
+    #[arg(long)]
+    kube_context: Option<String>,
+
+    /// This decides how a Node is prepared for the restart of its io-engine Pods. 'full' drains
+    /// the Node, moving all of the volume targets off of it. 'soft' only cordons the Node and
+    /// reports the volumes whose targets are left on it, the I/O to those volumes is interrupted
+    /// while the io-engine restarts. 'cordon-only' cordons the Node like 'soft', but without
+    /// waiting for the volume rebuilds to complete first, which risks reduced redundancy while
+    /// the io-engine restarts.
+    #[arg(long, value_enum, default_value_t = DrainMode::Full)]
+    drain_mode: DrainMode,
+
+    /// This decides what is done with a storage Node which is cordoned or drained with a label
+    /// other than the upgrade's, e.g. by an operator for maintenance. Such a Node may be upgraded
+    /// anyway, skipped, or it may fail the upgrade.
+    #[arg(long, value_enum, default_value_t = ExternalCordonPolicy::Ignore)]
+    external_cordon_policy: ExternalCordonPolicy,
+
+    /// The maximum time to wait for each storage Node's drain to complete. The upgrade fails if
+    /// the drain takes longer. This may be overridden by the timeouts ConfigMap. If not set, the
+    /// upgrade waits for the drain indefinitely.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    drain_timeout: Option<Duration>,
+
+    /// The maximum time to wait for the replacement io-engine Pods on each Node to be Ready. On a
+    /// timeout, the Node is uncordoned and the upgrade fails. This may be overridden by the
+    /// timeouts ConfigMap.
+    #[arg(long, default_value = "15m", value_parser = humantime::parse_duration)]
+    readiness_timeout: Duration,
+
+    /// The Node's upgrade fails if a container of a replacement io-engine Pod restarts more than
+    /// this many times while waiting for the Pod to be Ready. Set to 0 to never fail on restarts.
+    #[arg(long, default_value_t = 5, value_name = "COUNT")]
+    max_io_engine_restarts: i32,
+
+    /// The number of the last lines of the io-engine containers' logs which are logged when the
+    /// replacement io-engine Pods on a Node don't become Ready. Set to 0 to not fetch the logs.
+    #[arg(long, default_value_t = 100, value_name = "LINES")]
+    io_engine_log_lines: i64,
+
+    /// The Node's upgrade fails if a deleted io-engine Pod is still terminating after this long,
+    /// as its replacement can't be scheduled until it is gone.
+    #[arg(long, default_value = "5m", value_parser = humantime::parse_duration)]
+    terminating_pod_timeout: Duration,
+
+    /// If set, a Node isn't drained while that would leave fewer than this many other Nodes
+    /// with a Ready io-engine Pod. The upgrade waits for other Nodes to recover, and fails if
+    /// they don't within the min-healthy-nodes-timeout.
+    #[arg(long, value_name = "COUNT")]
+    min_healthy_nodes: Option<usize>,
+
+    /// The maximum time to wait for enough Nodes with a Ready io-engine Pod, before a Node is
+    /// drained.
+    #[arg(long, default_value = "10m", value_parser = humantime::parse_duration)]
+    min_healthy_nodes_timeout: Duration,
+
+    /// If set, before a Node is drained, this waits for the snapshots of the volumes which have
+    /// their target or a replica on the Node to finish being created or deleted.
+    #[arg(long, default_value_t = false)]
+    wait_for_snapshots: bool,
+
+    /// The maximum time to wait for the snapshots of a Node's volumes to complete.
+    #[arg(long, default_value = "10m", value_parser = humantime::parse_duration)]
+    snapshot_timeout: Duration,
+
+    /// The termination grace period for the io-engine Pods which are deleted, in seconds. If not
+    /// set, the Pod's own termination grace period is used.
+    #[arg(long, value_name = "SECONDS")]
+    io_engine_grace_period: Option<u32>,
+
+    /// If set then the io-engine Pods are deleted with foreground propagation, and the upgrade
+    /// waits for each deleted Pod to be gone, for up to the readiness timeout, before waiting for
+    /// its replacement.
+    #[arg(long, default_value_t = false)]
+    foreground_pod_deletion: bool,
+
+    /// If set then a deleted io-engine Pod which isn't gone after the force delete wait is force
+    /// deleted, i.e. deleted with a grace period of zero. This is dangerous, as the io-engine
+    /// may still be running on the Node when its replacement starts.
+    #[arg(long, default_value_t = false)]
+    allow_force_delete: bool,
+
+    /// The time to wait for a deleted io-engine Pod to be gone, before force deleting it. This
+    /// only applies if force deletes are allowed.
+    #[arg(long, default_value = "5m", value_parser = humantime::parse_duration)]
+    force_delete_after: Duration,
+
+    /// If set then the io-engine Pods are evicted through the Eviction API instead of being
+    /// deleted, so that the PodDisruptionBudgets on them are respected. An eviction which a
+    /// PodDisruptionBudget blocks is retried for up to the PDB eviction timeout.
+    #[arg(long, default_value_t = false)]
+    respect_pdb: bool,
+
+    /// The maximum time to retry an io-engine Pod's eviction while a PodDisruptionBudget blocks
+    /// it. This only applies if the PodDisruptionBudgets are respected.
+    #[arg(long, default_value = "10m", value_parser = humantime::parse_duration)]
+    pdb_eviction_timeout: Duration,
+
+    /// The maximum time to wait for the control-plane Pods to be Ready on the target version,
+    /// before each Node's upgrade. This may be overridden by the timeouts ConfigMap.
+    #[arg(long, default_value = "10m", value_parser = humantime::parse_duration)]
+    control_plane_timeout: Duration,
+
+    /// The control-plane isn't checked again before a Node's upgrade if it was found to be Ready
+    /// on the target version less than this long ago, e.g. before the previous Node's upgrade.
+    #[arg(long, default_value = "0s", value_parser = humantime::parse_duration)]
+    control_plane_ready_ttl: Duration,
+
+    /// The maximum time to wait for the volume rebuilds to complete before draining each Node.
+    /// The upgrade fails if the rebuilds take longer. This may be overridden by the timeouts
+    /// ConfigMap. If not set, the upgrade waits for the rebuilds indefinitely.
+    #[arg(long, value_parser = humantime::parse_duration)]
+    rebuild_timeout: Option<Duration>,
+
+    /// The maximum time to wait for any volume rebuilds to kick in, before polling for the
+    /// rebuilds to complete. The wait ends early if none of the Node's volumes is waiting on a
+    /// rebuild to start.
+    #[arg(long, default_value = "60s", value_parser = humantime::parse_duration)]
+    rebuild_grace_period: Duration,
+
+    /// The interval at which the volume rebuilds are first polled for completion. The interval
+    /// doubles with each poll, up to --rebuild-poll-interval-max, and it is reset whenever another
+    /// volume starts rebuilding.
+    #[arg(long, default_value = "10s", value_parser = humantime::parse_duration)]
+    rebuild_poll_interval: Duration,
+
+    /// The maximum interval at which the volume rebuilds are polled for completion.
+    #[arg(long, default_value = "2m", value_parser = humantime::parse_duration)]
+    rebuild_poll_interval_max: Duration,
+
+    /// If set then the upgrade waits for the volume rebuilds across the whole cluster to complete
+    /// before draining each Node, rather than only for the rebuilds of the volumes which have
+    /// their target or a replica on the Node.
+    #[arg(long, default_value_t = false)]
+    wait_for_cluster_wide_rebuilds: bool,
+
+    /// If set then the data-plane upgrade only runs the checks before each Node's upgrade, and
+    /// logs the actions which it would take. No Node is drained, no io-engine Pod is deleted and
+    /// no Node is uncordoned. This doesn't affect the control-plane upgrade.
+    #[arg(long, default_value_t = false)]
+    data_plane_dry_run: bool,
+
+    /// If set then the upgrade-job prints a table of the io-engine Pods, with their Nodes, their
+    /// chart versions and whether the data-plane upgrade would restart them, and exits. Unlike
+    /// --data-plane-dry-run, nothing is upgraded, not even the control-plane.
+    #[arg(long, default_value_t = false)]
+    plan: bool,
+
+    /// If set then the upgrade-job only checks that the cluster is upgraded to the target
+    /// version, i.e. that the io-engine and the control-plane Pods are on the target version, that
+    /// the io-engine Pods are Ready and that no Node is left with the upgrade drain label. It
+    /// prints the discrepancies and fails if there are any. Nothing is upgraded.
+    #[arg(long, default_value_t = false, conflicts_with = "plan")]
+    verify_only: bool,
+
+    /// If set then the upgrade-job removes the upgrade drain labels from all of the storage Nodes
+    /// which carry them, e.g. after an upgrade which was aborted manually, prints the number of
+    /// Nodes it cleared, and exits. Nothing is upgraded.
+    #[arg(long, default_value_t = false, conflicts_with_all = ["plan", "verify_only"])]
+    uncordon_all: bool,
+
+    /// If set then the upgrade-job only upgrades the control-plane, i.e. it runs the helm
+    /// upgrade, rolls out the control-plane Deployments if --upgrade-control-plane is set, waits
+    /// for the control-plane to be Ready on the target version, and exits. The io-engine Pods are
+    /// left on the source version, for a later upgrade-job to upgrade, e.g. within a maintenance
+    /// window.
+    #[arg(
+        long,
+        default_value_t = false,
+        conflicts_with_all = ["plan", "verify_only", "uncordon_all", "skip_data_plane_restart"]
+    )]
+    control_plane_only: bool,
+
+    /// If set, up to this many Nodes are upgraded concurrently, at most one per availability
+    /// zone (the 'topology.kubernetes.io/zone' Node label). Nodes which share a volume are never
+    /// upgraded together, and Nodes without a zone label are upgraded on their own.
+    #[arg(long, value_name = "COUNT")]
+    max_parallel_zones: Option<usize>,
+
+    /// If set, the data-plane upgrade proceeds even if there are degraded or faulted volumes
+    /// before the first Node is drained.
+    #[arg(long, default_value_t = false)]
+    allow_unhealthy_volumes: bool,
+
+    /// If set, a Node is upgraded even if some of its storage pools are offline, at the risk of
+    /// leaving its volumes without a healthy replica while its io-engine restarts.
+    #[arg(long, default_value_t = false)]
+    allow_offline_pools: bool,
+
+    /// If set, the io-engine Pods, the storage Nodes' cordons and the volumes' replica topology
+    /// are written to this JSON file before the first Node is drained, as a record of the state
+    /// of the cluster before the data-plane upgrade.
+    #[arg(long, value_name = "FILE")]
+    state_snapshot: Option<PathBuf>,
+
+    /// If set, the data-plane upgrade fails if the state snapshot can't be written, rather than
+    /// only logging the failure.
+    #[arg(long, default_value_t = false, requires = "state_snapshot")]
+    require_state_snapshot: bool,
+
+    /// The name of the Lease in the upgrade-job's namespace which the data-plane upgrade holds,
+    /// so that no two upgrade-jobs upgrade the data-plane at once.
+    #[arg(long, default_value = "upgrade-job-lease", value_name = "NAME")]
+    upgrade_lease_name: String,
+
+    /// The duration of the upgrade Lease. The Lease is renewed every third of this, and a Lease
+    /// which isn't renewed for this long, e.g. that of a crashed upgrade-job, may be taken over.
+    #[arg(long, default_value = "60s", value_parser = humantime::parse_duration)]
+    upgrade_lease_duration: Duration,
+
+    /// If set, a JSON report of the data-plane upgrade, with each Node's outcome, is printed to
+    /// stdout at the end of the data-plane upgrade, even if it fails. With --control-plane-only,
+    /// the report notes the source version which the data-plane was left on.
+    #[arg(long, default_value_t = false)]
+    print_data_plane_report: bool,
+
+    /// If set, the data-plane is upgraded even if the target version is earlier than the
+    /// version which was installed, e.g. along with --skip-upgrade-path-validation.
+    #[arg(long, default_value_t = false)]
+    allow_downgrade: bool,
+
+    /// The earliest version which the data-plane may be upgraded from to the target version, e.g.
+    /// if the target version has a migration which can't be skipped. The data-plane upgrade fails
+    /// before any Node is drained if the installed version is earlier than this.
+    #[arg(long)]
+    min_source_version: Option<String>,
+
     /// If set then this skips the upgrade path validation.
     #[arg(long, default_value_t = false)]
     skip_upgrade_path_validation: bool,
@@ -58,7 +655,17 @@ impl CliArgs {
 
     /// This returns the Kubernetes Namespace for the Helm chart release.
     pub(crate) fn namespace(&self) -> String {
-        self.namespace.clone()
+        self.namespace.clone().unwrap_or_default()
+    }
+
+    /// This decides whether the namespace is set, or if it is yet to be discovered.
+    pub(crate) fn namespace_is_set(&self) -> bool {
+        self.namespace.is_some()
+    }
+
+    /// This sets the namespace, e.g. to the discovered namespace of the install.
+    pub(crate) fn set_namespace(&mut self, namespace: String) {
+        self.namespace = Some(namespace);
     }
 
     /// This returns the Helm release name for the installed Helm chart.
@@ -77,6 +684,511 @@ impl CliArgs {
         self.skip_data_plane_restart
     }
 
+    /// This returns the name of the ConfigMap with the data-plane upgrade timeouts, if any.
+    pub(crate) fn timeouts_config_map(&self) -> Option<String> {
+        self.timeouts_config_map.clone()
+    }
+
+    /// This returns the UUIDs of the volumes whose Nodes are upgraded first or last.
+    pub(crate) fn priority_volumes(&self) -> Vec<String> {
+        self.priority_volumes.clone()
+    }
+
+    /// This returns the names of the Nodes to upgrade, if only some of the Nodes are upgraded.
+    pub(crate) fn include_nodes(&self) -> Option<Vec<String>> {
+        (!self.include_nodes.is_empty()).then(|| self.include_nodes.clone())
+    }
+
+    /// This returns the names of the Nodes which are not upgraded.
+    pub(crate) fn exclude_nodes(&self) -> Vec<String> {
+        self.exclude_nodes.clone()
+    }
+
+    /// This returns whether the Nodes hosting the priority volumes are upgraded first or last.
+    pub(crate) fn priority_volumes_order(&self) -> PriorityVolumesOrder {
+        self.priority_volumes_order
+    }
+
+    /// This returns the order of the io-engine Pods within the primary ordering's groups.
+    pub(crate) fn secondary_order(&self) -> SecondaryOrder {
+        self.secondary_order
+    }
+
+    /// This decides if the data-plane consistency checks fail the upgrade, or if they only warn.
+    pub(crate) fn strict(&self) -> bool {
+        self.strict
+    }
+
+    /// This returns the minimum duration of each data-plane upgrade wait phase, if a slow cluster
+    /// is simulated to validate the timeouts.
+    pub(crate) fn simulated_phase_duration(&self) -> Option<Duration> {
+        self.simulate_slow_cluster
+            .then_some(self.simulated_phase_duration)
+    }
+
+    /// This decides if the list of storage Nodes is cached for the duration of the upgrade.
+    pub(crate) fn cache_node_list(&self) -> bool {
+        self.cache_node_list
+    }
+
+    /// This decides if the volumes on a Node are verified to be back at their configured replica
+    /// count after the Node is upgraded.
+    pub(crate) fn verify_replica_count(&self) -> bool {
+        self.verify_replica_count
+    }
+
+    /// This decides if the volumes on a Node are verified not to be Faulted after the Node is
+    /// upgraded.
+    pub(crate) fn verify_volumes_accessible(&self) -> bool {
+        self.verify_volumes_accessible
+    }
+
+    /// This returns which of a Node's yet-to-upgrade io-engine Pods are deleted with each drain.
+    pub(crate) fn io_engine_pod_selection(&self) -> IoEnginePodSelection {
+        self.io_engine_pod_selection
+    }
+
+    /// This returns the name of the io-engine DaemonSet whose Pods are upgraded, if any.
+    pub(crate) fn io_engine_daemonset(&self) -> Option<String> {
+        self.io_engine_daemonset.clone()
+    }
+
+    /// This returns the keys of the Kubernetes Node labels which are verified after each Node's
+    /// upgrade.
+    pub(crate) fn monitored_node_labels(&self) -> Vec<String> {
+        self.monitored_node_labels.clone()
+    }
+
+    /// This decides if the drain state machine's decisions are logged.
+    pub(crate) fn drain_decision_log(&self) -> bool {
+        self.drain_decision_log
+    }
+
+    /// This returns the shell command which backs up the cluster before the upgrade, if any.
+    pub(crate) fn pre_upgrade_backup(&self) -> Option<String> {
+        self.pre_upgrade_backup.clone()
+    }
+
+    /// This decides if the upgrade fails when the from and to versions are the same.
+    pub(crate) fn fail_on_same_version(&self) -> bool {
+        self.fail_on_same_version
+    }
+
+    /// This decides if the VolumeAttachments of the volumes on a Node are waited on, after the
+    /// Node is upgraded.
+    pub(crate) fn wait_for_volume_attachments(&self) -> bool {
+        self.wait_for_volume_attachments
+    }
+
+    /// This decides if the CSI node plugin Pods on a Node are waited for after its upgrade.
+    pub(crate) fn verify_csi_node_plugin(&self) -> bool {
+        self.verify_csi_node_plugin
+    }
+
+    /// This returns the label selector of the CSI node plugin Pods.
+    pub(crate) fn csi_node_label(&self) -> String {
+        self.csi_node_label.clone()
+    }
+
+    /// This returns the path to the timeline file, if any.
+    pub(crate) fn timeline(&self) -> Option<PathBuf> {
+        self.timeline.clone()
+    }
+
+    /// This decides if the control-plane's health is monitored while a Node is upgraded.
+    pub(crate) fn monitor_control_plane(&self) -> bool {
+        self.monitor_control_plane
+    }
+
+    /// This returns the label selectors of the control-plane components which have to be Ready
+    /// on the target version.
+    pub(crate) fn control_plane_components(&self) -> Vec<String> {
+        self.control_plane_components.clone()
+    }
+
+    /// This returns the label selectors of the control-plane components which have to be Ready
+    /// on any version.
+    pub(crate) fn unversioned_control_plane_components(&self) -> Vec<String> {
+        self.unversioned_control_plane_components.clone()
+    }
+
+    /// This decides if the control-plane Deployments are rolled out by the upgrade.
+    pub(crate) fn upgrade_control_plane(&self) -> bool {
+        self.upgrade_control_plane
+    }
+
+    /// This decides if the upgrade carries on when a Node fails to upgrade.
+    pub(crate) fn continue_on_error(&self) -> bool {
+        self.continue_on_error
+    }
+
+    /// This returns the number of consecutive Node failures which aborts the upgrade, if any.
+    pub(crate) fn max_consecutive_failures(&self) -> Option<u32> {
+        self.max_consecutive_failures
+    }
+
+    /// This returns the number of Node failures which may be tolerated in total, if any.
+    pub(crate) fn max_node_failures(&self) -> Option<u32> {
+        self.max_node_failures
+    }
+
+    /// This returns the shell command which smoke tests the cluster after the upgrade, if any.
+    pub(crate) fn post_upgrade_smoke(&self) -> Option<String> {
+        self.post_upgrade_smoke.clone()
+    }
+
+    /// This returns the maximum time that each of the hook commands may run for.
+    pub(crate) fn hook_timeout(&self) -> Duration {
+        self.hook_timeout
+    }
+
+    /// This returns the path to the progress Unix socket, if any.
+    pub(crate) fn progress_socket(&self) -> Option<PathBuf> {
+        self.progress_socket.clone()
+    }
+
+    /// This returns the name of the progress ConfigMap, if any.
+    pub(crate) fn progress_config_map(&self) -> Option<String> {
+        self.progress_config_map.clone()
+    }
+
+    /// This returns the port on which the Prometheus metrics are served, if any.
+    pub(crate) fn metrics_port(&self) -> Option<u16> {
+        self.metrics_port
+    }
+
+    /// This returns the port on which the health probes are served, if any.
+    pub(crate) fn health_port(&self) -> Option<u16> {
+        self.health_port
+    }
+
+    /// This returns the time without forward progress after which the liveness probe fails.
+    pub(crate) fn liveness_stall_threshold(&self) -> Duration {
+        self.liveness_stall_threshold
+    }
+
+    /// This returns the time after which the cordon of a Node expires, unless it is renewed, if
+    /// any.
+    pub(crate) fn cordon_ttl(&self) -> Option<Duration> {
+        self.cordon_ttl
+    }
+
+    /// This returns the time without forward progress after which the data-plane upgrade is
+    /// stopped, if any.
+    pub(crate) fn idle_timeout(&self) -> Option<Duration> {
+        self.idle_timeout
+    }
+
+    /// This returns the time to wait for in between the upgrades of Nodes.
+    pub(crate) fn inter_node_delay(&self) -> Duration {
+        self.inter_node_delay
+    }
+
+    /// This returns the readiness strategy for the restarted io-engine Pods.
+    pub(crate) fn readiness_strategy(&self) -> ReadinessStrategy {
+        self.readiness_strategy
+    }
+
+    /// This returns the readiness requirements for the replacement io-engine Pods, on top of
+    /// their Ready condition.
+    pub(crate) fn io_engine_extra_readiness(&self) -> ExtraPodReadiness {
+        ExtraPodReadiness {
+            condition: self.io_engine_readiness_condition.clone(),
+            container: self.io_engine_ready_container.clone(),
+        }
+    }
+
+    /// This returns the time for which the replacement io-engine Pods have to stay ready.
+    pub(crate) fn io_engine_ready_stable_for(&self) -> Duration {
+        self.io_engine_ready_stable_for
+    }
+
+    /// This returns the expected rebuild throughput in MiB/s, if set.
+    pub(crate) fn rebuild_throughput(&self) -> Option<u64> {
+        self.rebuild_throughput
+    }
+
+    /// This returns the least size-based drain and rebuild timeout for each Node.
+    pub(crate) fn rebuild_timeout_floor(&self) -> Duration {
+        self.rebuild_timeout_floor
+    }
+
+    /// This decides whether the control plane's ability to create and delete volumes is
+    /// verified before upgrading each Node.
+    pub(crate) fn verify_volume_scheduling(&self) -> bool {
+        self.verify_volume_scheduling
+    }
+
+    /// This returns the daily maintenance window for the data-plane upgrade, if set.
+    pub(crate) fn maintenance_window(&self) -> Option<MaintenanceWindow> {
+        self.maintenance_window
+    }
+
+    /// This returns the deadline for the whole data-plane upgrade, if set.
+    pub(crate) fn upgrade_deadline(&self) -> Option<Duration> {
+        self.upgrade_deadline
+    }
+
+    /// This returns the etcd client URL whose health is checked, if set.
+    pub(crate) fn etcd_health_endpoint(&self) -> Option<String> {
+        self.etcd_health_endpoint.clone()
+    }
+
+    /// This returns the ID of this upgrade run, if set.
+    pub(crate) fn run_id(&self) -> Option<String> {
+        self.run_id.clone()
+    }
+
+    /// This returns the format of the upgrade-job's logs.
+    pub(crate) fn log_format(&self) -> LogFormat {
+        self.log_format
+    }
+
+    /// This returns how much the data-plane upgrade's waits log while they poll.
+    pub(crate) fn wait_log_verbosity(&self) -> WaitLogVerbosity {
+        self.wait_log_verbosity
+    }
+
+    /// This returns the labels which the storage Nodes are drained with for the upgrade, i.e. the
+    /// drain label and the extra drain labels.
+    pub(crate) fn drain_labels(&self) -> DrainLabels {
+        DrainLabels::new(self.drain_label.clone(), self.extra_drain_labels.clone())
+    }
+
+    /// This returns the key of the chart version label.
+    pub(crate) fn chart_version_label_key(&self) -> String {
+        self.chart_version_label_key.clone()
+    }
+
+    /// This decides whether to leave the drain labels from previous upgrade attempts as they are.
+    pub(crate) fn skip_stale_drain_label_cleanup(&self) -> bool {
+        self.skip_stale_drain_label_cleanup
+    }
+
+    /// This decides whether the replica distribution across the storage pools is reported.
+    pub(crate) fn report_replica_distribution(&self) -> bool {
+        self.report_replica_distribution
+    }
+
+    /// This returns the file which the storage REST API bearer token is read from, if set.
+    pub(crate) fn rest_token_file(&self) -> Option<PathBuf> {
+        self.rest_token_file.clone()
+    }
+
+    /// This returns the file which the storage REST API CA certificate is read from, if set.
+    pub(crate) fn rest_ca_cert_file(&self) -> Option<PathBuf> {
+        self.rest_ca_cert_file.clone()
+    }
+
+    /// This returns the kubeconfig file which the data-plane upgrade connects with, if set.
+    pub(crate) fn kubeconfig(&self) -> Option<PathBuf> {
+        self.kubeconfig.clone()
+    }
+
+    /// This returns the kubeconfig context which the data-plane upgrade connects with, if set.
+    pub(crate) fn kube_context(&self) -> Option<String> {
+        self.kube_context.clone()
+    }
+
+    /// This returns the mode of preparing a Node for the restart of its io-engine Pods.
+    pub(crate) fn drain_mode(&self) -> DrainMode {
+        self.drain_mode
+    }
+
+    /// This returns the policy for the storage Nodes which are cordoned by something other than
+    /// the upgrade.
+    pub(crate) fn external_cordon_policy(&self) -> ExternalCordonPolicy {
+        self.external_cordon_policy
+    }
+
+    /// This returns the maximum time to wait for each storage Node's drain, if set.
+    pub(crate) fn drain_timeout(&self) -> Option<Duration> {
+        self.drain_timeout
+    }
+
+    /// This returns the maximum time to wait for the replacement io-engine Pods to be Ready.
+    pub(crate) fn readiness_timeout(&self) -> Duration {
+        self.readiness_timeout
+    }
+
+    /// This returns the number of restarts of a replacement io-engine container which fails the
+    /// Node's upgrade, if any.
+    pub(crate) fn max_io_engine_restarts(&self) -> Option<i32> {
+        (self.max_io_engine_restarts > 0).then_some(self.max_io_engine_restarts)
+    }
+
+    /// This returns the number of lines of the io-engine container's logs which are logged when
+    /// a Node's replacement io-engine Pods don't become Ready, if any.
+    pub(crate) fn io_engine_log_lines(&self) -> Option<i64> {
+        (self.io_engine_log_lines > 0).then_some(self.io_engine_log_lines)
+    }
+
+    /// This returns the maximum time a deleted io-engine Pod may be terminating for.
+    pub(crate) fn terminating_pod_timeout(&self) -> Duration {
+        self.terminating_pod_timeout
+    }
+
+    /// This returns the minimum number of Nodes with a Ready io-engine Pod, which are to be left
+    /// when a Node is drained, if any.
+    pub(crate) fn min_healthy_nodes(&self) -> Option<usize> {
+        self.min_healthy_nodes
+    }
+
+    /// This returns the maximum time to wait for enough Nodes with a Ready io-engine Pod.
+    pub(crate) fn min_healthy_nodes_timeout(&self) -> Duration {
+        self.min_healthy_nodes_timeout
+    }
+
+    /// This decides if the snapshots of a Node's volumes are waited for before it is drained.
+    pub(crate) fn wait_for_snapshots(&self) -> bool {
+        self.wait_for_snapshots
+    }
+
+    /// This returns the maximum time to wait for the snapshots of a Node's volumes to complete.
+    pub(crate) fn snapshot_timeout(&self) -> Duration {
+        self.snapshot_timeout
+    }
+
+    /// This returns the termination grace period for the deleted io-engine Pods, if set.
+    pub(crate) fn io_engine_grace_period(&self) -> Option<u32> {
+        self.io_engine_grace_period
+    }
+
+    /// This decides whether the io-engine Pods are deleted with foreground propagation.
+    pub(crate) fn foreground_pod_deletion(&self) -> bool {
+        self.foreground_pod_deletion
+    }
+
+    /// This returns the time to wait for a deleted io-engine Pod to be gone before force deleting
+    /// it, if force deletes are allowed.
+    pub(crate) fn force_delete_after(&self) -> Option<Duration> {
+        self.allow_force_delete.then_some(self.force_delete_after)
+    }
+
+    /// This decides whether the io-engine Pods are evicted, so that the PodDisruptionBudgets are
+    /// respected, instead of being deleted.
+    pub(crate) fn respect_pdb(&self) -> bool {
+        self.respect_pdb
+    }
+
+    /// This returns the maximum time to retry an eviction which a PodDisruptionBudget blocks.
+    pub(crate) fn pdb_eviction_timeout(&self) -> Duration {
+        self.pdb_eviction_timeout
+    }
+
+    /// This returns the maximum time to wait for the control-plane Pods to be Ready.
+    pub(crate) fn control_plane_timeout(&self) -> Duration {
+        self.control_plane_timeout
+    }
+
+    /// This returns how long the control-plane's readiness is considered to hold after a check.
+    pub(crate) fn control_plane_ready_ttl(&self) -> Duration {
+        self.control_plane_ready_ttl
+    }
+
+    /// This returns the maximum time to wait for the volume rebuilds on each Node, if set.
+    pub(crate) fn rebuild_timeout(&self) -> Option<Duration> {
+        self.rebuild_timeout
+    }
+
+    /// This returns the maximum time to wait for any volume rebuilds to kick in.
+    pub(crate) fn rebuild_grace_period(&self) -> Duration {
+        self.rebuild_grace_period
+    }
+
+    /// This returns the interval at which the volume rebuilds are first polled for completion.
+    pub(crate) fn rebuild_poll_interval(&self) -> Duration {
+        self.rebuild_poll_interval
+    }
+
+    /// This returns the maximum interval at which the volume rebuilds are polled for completion.
+    /// This is never less than the initial interval.
+    pub(crate) fn rebuild_poll_interval_max(&self) -> Duration {
+        self.rebuild_poll_interval_max
+            .max(self.rebuild_poll_interval)
+    }
+
+    /// This decides whether to wait for the volume rebuilds across the whole cluster.
+    pub(crate) fn wait_for_cluster_wide_rebuilds(&self) -> bool {
+        self.wait_for_cluster_wide_rebuilds
+    }
+
+    /// This decides whether the data-plane upgrade is a dry run.
+    pub(crate) fn data_plane_dry_run(&self) -> bool {
+        self.data_plane_dry_run
+    }
+
+    /// This decides whether only the data-plane upgrade plan is printed.
+    pub(crate) fn plan(&self) -> bool {
+        self.plan
+    }
+
+    /// This decides whether the upgrade is only verified.
+    pub(crate) fn verify_only(&self) -> bool {
+        self.verify_only
+    }
+
+    /// This decides if only the control-plane is upgraded, leaving the data-plane on the source
+    /// version.
+    pub(crate) fn control_plane_only(&self) -> bool {
+        self.control_plane_only
+    }
+
+    /// This decides whether the upgrade drain labels are only removed from the storage Nodes.
+    pub(crate) fn uncordon_all(&self) -> bool {
+        self.uncordon_all
+    }
+
+    /// This returns the number of Nodes which may be upgraded concurrently, across zones.
+    pub(crate) fn max_parallel_zones(&self) -> usize {
+        self.max_parallel_zones.unwrap_or(1)
+    }
+
+    /// This decides if the data-plane upgrade may start with unhealthy volumes.
+    pub(crate) fn allow_unhealthy_volumes(&self) -> bool {
+        self.allow_unhealthy_volumes
+    }
+
+    /// This decides if a Node with offline pools may be upgraded.
+    pub(crate) fn allow_offline_pools(&self) -> bool {
+        self.allow_offline_pools
+    }
+
+    /// This returns the path to the pre-upgrade state snapshot file, if any.
+    pub(crate) fn state_snapshot(&self) -> Option<PathBuf> {
+        self.state_snapshot.clone()
+    }
+
+    /// This decides if a failure to write the state snapshot fails the data-plane upgrade.
+    pub(crate) fn require_state_snapshot(&self) -> bool {
+        self.require_state_snapshot
+    }
+
+    /// This returns the name of the upgrade Lease.
+    pub(crate) fn upgrade_lease_name(&self) -> String {
+        self.upgrade_lease_name.clone()
+    }
+
+    /// This returns the duration of the upgrade Lease.
+    pub(crate) fn upgrade_lease_duration(&self) -> Duration {
+        self.upgrade_lease_duration
+    }
+
+    /// This decides if the data-plane upgrade report is printed.
+    pub(crate) fn print_data_plane_report(&self) -> bool {
+        self.print_data_plane_report
+    }
+
+    /// This decides if the data-plane may be upgraded to an earlier version.
+    pub(crate) fn allow_downgrade(&self) -> bool {
+        self.allow_downgrade
+    }
+
+    /// This returns the earliest version which the data-plane may be upgraded from, if any.
+    pub(crate) fn min_source_version(&self) -> Option<String> {
+        self.min_source_version.clone()
+    }
+
     /// This decides to skip upgrade path validation or not.
     pub(crate) fn skip_upgrade_path_validation(&self) -> bool {
         self.skip_upgrade_path_validation
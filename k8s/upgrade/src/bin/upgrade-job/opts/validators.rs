@@ -3,9 +3,8 @@ use crate::{
         constants::CORE_CHART_NAME,
         error::{
             FindingHelmChart, GetNamespace, HelmCommand, HelmListCommand, HelmRelease, HelmVersion,
-            HelmVersionCommand, ListStorageNodes, NotADirectory, NotAFile, ReadingFile,
-            RegexCompile, Result, U8VectorToString, ValidateDirPath, ValidateFilePath,
-            YamlParseFromFile,
+            HelmVersionCommand, NotADirectory, NotAFile, ReadingFile, RegexCompile, Result,
+            U8VectorToString, ValidateDirPath, ValidateFilePath, YamlParseFromFile,
         },
         kube_client::KubeClientSet,
         rest_client::RestClientSet,
@@ -229,14 +228,16 @@ pub(crate) async fn validate_namespace(ns: String) -> Result<()> {
 }
 
 /// This checks if the storage API is reachable and usable.
-pub(crate) async fn validate_rest_endpoint(rest_endpoint: String) -> Result<()> {
-    let rest_client = RestClientSet::new_with_url(rest_endpoint)?;
-
-    rest_client
-        .nodes_api()
-        .get_nodes(None)
-        .await
-        .context(ListStorageNodes)?;
-
-    Ok(())
+pub(crate) async fn validate_rest_endpoint(
+    rest_endpoint: String,
+    rest_token_file: Option<PathBuf>,
+    rest_ca_cert_file: Option<PathBuf>,
+) -> Result<()> {
+    let rest_client = match (rest_token_file, rest_ca_cert_file) {
+        (None, None) => RestClientSet::new_with_url(rest_endpoint)?,
+        (token_file, ca_cert_file) => {
+            RestClientSet::new_with_url_and_auth(rest_endpoint, token_file, ca_cert_file)?
+        }
+    };
+    rest_client.verify_reachable().await
 }
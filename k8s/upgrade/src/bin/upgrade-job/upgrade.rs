@@ -1,29 +1,202 @@
 use crate::{
-    common::{constants::PRODUCT, error::Result},
+    common::{
+        api_stats::ApiStats, constants::PRODUCT, error::Result, kube_client::KubeClientSet,
+        progress::ProgressSocket, rest_client::RestClientSet, run::UpgradeRun,
+        timeline::TimelineEvent,
+    },
     events::event_recorder::{EventAction, EventRecorder},
     helm::upgrade::{HelmUpgrade, HelmUpgradeRunner},
     opts::CliArgs,
 };
-use data_plane::upgrade_data_plane;
+use context::UpgradeContext;
+use data_plane::upgrade_control_plane_only;
+use deadline::UpgradeDeadline;
+use deadman::uncordon_all;
+use events::upgrade_data_plane_logging_events;
+use hooks::run_hook;
+use lease::UpgradeLease;
+use plan::{data_plane_plan, print_plan};
+use std::sync::Arc;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, warn};
+use verify::verify_upgrade;
 
 /// Contains the data-plane upgrade logic.
 pub(crate) mod data_plane;
 
+/// Contains the state which is shared by the steps of a data-plane upgrade.
+pub(crate) mod context;
+
 /// Contains upgrade utilities.
 pub(crate) mod utils;
 
 /// Tools to validate upgrade path.
 pub(crate) mod path;
 
-/// This function starts and sees upgrade through to the end.
-pub(crate) async fn upgrade(opts: &CliArgs) -> Result<()> {
+/// Contains the deadman switch which uncordons the Nodes if the upgrade is abandoned.
+pub(crate) mod deadman;
+
+/// Contains the wall-clock deadline for the whole data-plane upgrade.
+pub(crate) mod deadline;
+
+/// Contains the modes of preparing a Node for the restart of its io-engine Pods.
+pub(crate) mod drain_mode;
+
+/// Contains the handling of the Nodes which are cordoned by something other than the upgrade.
+pub(crate) mod external_cordon;
+
+/// Contains the decision log of the drain state machine.
+pub(crate) mod drain_decisions;
+
+/// Contains the user-provided hook commands.
+pub(crate) mod hooks;
+
+/// Contains the cache for the list of storage Nodes.
+pub(crate) mod node_cache;
+
+/// Contains the estimated and actual data-plane upgrade durations of the Nodes.
+pub(crate) mod node_durations;
+
+/// Contains the tracking of the Nodes which failed to upgrade.
+pub(crate) mod node_failures;
+
+/// Contains the verification of the monitored Kubernetes Node labels.
+pub(crate) mod node_labels;
+
+/// Contains the Kubernetes Events posted at each phase of a Node's data-plane upgrade.
+pub(crate) mod node_events;
+
+/// Contains the ordering of the Nodes for the data-plane upgrade.
+pub(crate) mod node_order;
+
+/// Contains the pausing and resuming of the data-plane upgrade.
+pub(crate) mod pause;
+
+/// Contains the selection of the io-engine Pods which are deleted on each Node.
+pub(crate) mod pod_selection;
+
+/// Contains the wait for the VolumeAttachments of the volumes on a Node.
+pub(crate) mod volume_attachments;
+
+/// Contains the verification of the etcd cluster's health.
+pub(crate) mod etcd_health;
+
+/// Contains the verification that the control plane can create and delete volumes.
+pub(crate) mod volume_scheduling;
+
+/// Contains the report of the replica distribution across the storage pools.
+pub(crate) mod replica_distribution;
+
+/// Contains the readiness strategies for the restarted io-engine Pods.
+pub(crate) mod readiness;
+
+/// Contains the tracking of the volume replica rebuilds' progress.
+pub(crate) mod rebuild_progress;
+
+/// Contains the slow cluster simulation, a testing aid for the data-plane upgrade timeouts.
+pub(crate) mod simulation;
+
+/// Contains the daily maintenance window, outside of which no Node is upgraded.
+pub(crate) mod maintenance_window;
+
+/// Contains the per-phase timeouts for the data-plane upgrade.
+pub(crate) mod timeouts;
+
+/// Contains the machine-readable report of the data-plane upgrade.
+pub(crate) mod report;
+
+/// Contains the include and exclude lists of the Nodes for a targeted data-plane upgrade.
+pub(crate) mod node_filter;
+
+/// Contains the grouping of the Nodes which are upgraded concurrently, by zone.
+pub(crate) mod zones;
+
+/// Contains the read-only plan of the data-plane upgrade.
+pub(crate) mod plan;
+
+/// Contains the wait for the snapshots of a Node's volumes to complete.
+pub(crate) mod snapshots;
+
+/// Contains the stream of the events of each Node's data-plane upgrade.
+pub(crate) mod events;
+
+/// Contains the read-only verification of the cluster's state after an upgrade.
+pub(crate) mod verify;
+
+/// Contains the verification of the Kubernetes API permissions for the data-plane upgrade.
+pub(crate) mod permissions;
+
+/// Contains the rollout of the control-plane Deployments which aren't on the target version.
+pub(crate) mod control_plane_rollout;
+
+/// Contains the check for offline storage pools on a Node before its upgrade.
+pub(crate) mod pools;
+
+/// Contains the warnings which don't stop the data-plane upgrade, for the upgrade report.
+pub(crate) mod warnings;
+
+/// Contains the watch on the Pods whose readiness the upgrade waits for.
+pub(crate) mod pod_watch;
+
+/// Contains the snapshot of the cluster's state before the data-plane upgrade.
+pub(crate) mod state_snapshot;
+
+/// Contains the mapping of the Kubernetes Node names to the storage Node ids.
+pub(crate) mod node_id;
+
+/// Contains the Lease which keeps two upgrade-jobs from upgrading the data-plane at once.
+pub(crate) mod lease;
+
+/// Contains the wait for the replicas which are missing after a Node's upgrade to be re-created.
+pub(crate) mod replica_relocation;
+
+/// Contains a fake storage REST API server for the tests of the drain and uncordon loops.
+#[cfg(test)]
+pub(crate) mod fake_rest_server;
+
+/// The name of the hook which backs up the cluster before the upgrade.
+const PRE_UPGRADE_BACKUP_HOOK: &str = "pre-upgrade-backup";
+
+/// The name of the hook which smoke tests the cluster after the upgrade.
+const POST_UPGRADE_SMOKE_HOOK: &str = "post-upgrade-smoke";
+
+/// This function starts and sees upgrade through to the end. No more Nodes are upgraded once
+/// 'cancellation' is cancelled, the data-plane upgrade cancels it on the first termination signal.
+/// The upgrade's API calls, progress and timeline are recorded in 'run', whose timeline is
+/// enabled here if the upgrade writes one.
+pub(crate) async fn upgrade(
+    opts: &CliArgs,
+    cancellation: &CancellationToken,
+    run: &UpgradeRun,
+) -> Result<()> {
+    if let Some(timeline_path) = opts.timeline() {
+        run.timeline.enable(timeline_path.as_path())?;
+    }
+
+    // The socket file is removed when this is dropped, on returning.
+    let _progress_socket = match opts.progress_socket() {
+        Some(socket_path) => Some(ProgressSocket::serve(
+            socket_path.as_path(),
+            run.progress.clone(),
+        )?),
+        None => None,
+    };
+
     let mut event = EventRecorder::builder()
         .with_pod_name(&opts.pod_name())
         .with_namespace(&opts.namespace())
+        .with_run_id(&run.id)
         .build()
         .await?;
 
-    let result = upgrade_product(opts, &mut event).await;
+    let result = upgrade_product(opts, &mut event, cancellation, run).await;
+    if let Err(error) = result.as_ref() {
+        run.timeline.record(TimelineEvent::Error {
+            message: error.to_string(),
+        });
+    }
+
+    log_api_call_summary(&run.api_stats);
 
     // This makes sure that the event worker attempts to publish
     // all of its events. It waits for the event worker to exit.
@@ -32,16 +205,79 @@ pub(crate) async fn upgrade(opts: &CliArgs) -> Result<()> {
     result
 }
 
+/// Logs how many calls were made to each of the Kubernetes and storage APIs, and how long they
+/// took, as a JSON document, along with the total number of calls made to each API.
+fn log_api_call_summary(api_stats: &ApiStats) {
+    let counts = api_stats.call_counts();
+    match serde_json::to_string(&api_stats.summary()) {
+        Ok(summary) => info!(
+            api_calls = %summary,
+            api_calls.kubernetes = counts.kubernetes,
+            api_calls.rest = counts.rest,
+            "API call summary"
+        ),
+        Err(error) => warn!(%error, "Failed to serialize API call summary"),
+    }
+}
+
+/// Removes the upgrade drain labels from all of the storage Nodes, e.g. after an upgrade which was
+/// aborted manually, and prints the number of Nodes which were cleared.
+async fn uncordon_drained_nodes(opts: &CliArgs, api_stats: &Arc<ApiStats>) -> Result<()> {
+    let rest_client = RestClientSet::new_with_url_and_auth(
+        opts.rest_endpoint(),
+        opts.rest_token_file(),
+        opts.rest_ca_cert_file(),
+    )?
+    .with_api_stats(api_stats.clone());
+
+    let cleared_nodes = uncordon_all(&opts.drain_labels(), &rest_client).await?;
+    println!("Removed the upgrade drain labels from {cleared_nodes} {PRODUCT} Nodes");
+    info!(nodes = cleared_nodes, "Removed the upgrade drain labels");
+    Ok(())
+}
+
+/// Acquires the upgrade Lease for the data-plane upgrade. Two upgrade-jobs would fight over the
+/// drain labels, and could drain more than one Node at once.
+async fn acquire_upgrade_lease(opts: &CliArgs, api_stats: &Arc<ApiStats>) -> Result<UpgradeLease> {
+    let k8s_client = KubeClientSet::builder()
+        .with_namespace(opts.namespace())
+        .with_kubeconfig(opts.kubeconfig(), opts.kube_context())
+        .with_api_stats(api_stats.clone())
+        .build()
+        .await?;
+
+    UpgradeLease::acquire(
+        &k8s_client,
+        opts.namespace().as_str(),
+        opts.upgrade_lease_name(),
+        opts.pod_name(),
+        opts.upgrade_lease_duration(),
+    )
+    .await
+}
+
 /// This carries out the helm upgrade validation, actual helm upgrade, and the io-engine Pod
 /// restarts.
-async fn upgrade_product(opts: &CliArgs, event: &mut EventRecorder) -> Result<()> {
+async fn upgrade_product(
+    opts: &CliArgs,
+    event: &mut EventRecorder,
+    cancellation: &CancellationToken,
+    run: &UpgradeRun,
+) -> Result<()> {
+    // The recovery only removes the upgrade drain labels, it doesn't need the chart versions.
+    if opts.uncordon_all() {
+        return uncordon_drained_nodes(opts, &run.api_stats).await;
+    }
+
     let helm_upgrade = HelmUpgrade::builder()
         .with_namespace(opts.namespace())
         .with_release_name(opts.release_name())
         .with_core_chart_dir(opts.core_chart_dir())
         .with_skip_upgrade_path_validation(opts.skip_upgrade_path_validation())
+        .with_fail_on_same_version(opts.fail_on_same_version())
         .with_helm_args_set(opts.helm_args_set())
         .with_helm_args_set_file(opts.helm_args_set_file())
+        .with_chart_version_label_key(opts.chart_version_label_key())
         .build()
         .await?;
 
@@ -54,6 +290,18 @@ async fn upgrade_product(opts: &CliArgs, event: &mut EventRecorder) -> Result<()
     event.set_from_version(from_version.clone());
     event.set_to_version(to_version.clone());
 
+    // The plan only reads from the cluster, nothing is upgraded.
+    if opts.plan() {
+        let entries = data_plane_plan(opts, to_version.as_str(), &run.api_stats).await?;
+        print_plan(from_version.as_str(), to_version.as_str(), &entries);
+        return Ok(());
+    }
+
+    // The verification only reads from the cluster, nothing is upgraded.
+    if opts.verify_only() {
+        return verify_upgrade(opts, to_version.as_str(), &run.api_stats).await;
+    }
+
     // Dry-run helm upgrade.
     let dry_run_result: Result<HelmUpgradeRunner> = helm_upgrade.dry_run().await;
     let run_helm_upgrade = match dry_run_result {
@@ -64,6 +312,22 @@ async fn upgrade_product(opts: &CliArgs, event: &mut EventRecorder) -> Result<()
         Ok(run_helm_upgrade) => Ok(run_helm_upgrade),
     }?;
 
+    // The backup is taken before anything is changed, so that it is a recovery point for the
+    // whole cluster.
+    if let Some(backup_command) = opts.pre_upgrade_backup() {
+        run.record_phase("pre-upgrade-backup", None);
+        if let Err(error) = run_hook(
+            PRE_UPGRADE_BACKUP_HOOK,
+            backup_command.as_str(),
+            opts.hook_timeout(),
+        )
+        .await
+        {
+            event.publish_unrecoverable(&error, false).await;
+            return Err(error);
+        }
+    }
+
     event
         .publish_normal(
             format!("Starting {PRODUCT} upgrade..."),
@@ -79,6 +343,7 @@ async fn upgrade_product(opts: &CliArgs, event: &mut EventRecorder) -> Result<()
         .await?;
 
     // Control plane containers are updated in this step.
+    run.record_phase("control-plane-upgrade", None);
     if let Err(error) = run_helm_upgrade.await {
         event.publish_unrecoverable(&error, false).await;
         return Err(error);
@@ -91,8 +356,20 @@ async fn upgrade_product(opts: &CliArgs, event: &mut EventRecorder) -> Result<()
         )
         .await?;
 
-    // Data plane containers are updated in this step.
-    if !opts.skip_data_plane_restart() {
+    if opts.control_plane_only() {
+        // The data-plane upgrade is left for a later upgrade-job, the control-plane is only
+        // verified.
+        run.record_phase("control-plane-verification", None);
+        let report = upgrade_control_plane_only(opts, from_version, to_version, run).await;
+        if opts.print_data_plane_report() {
+            report.print();
+        }
+        if let Err(error) = report.into_result() {
+            event.publish_unrecoverable(&error, false).await;
+            return Err(error);
+        }
+    } else if !opts.skip_data_plane_restart() {
+        // Data plane containers are updated in this step.
         event
             .publish_normal(
                 format!("Upgrading {PRODUCT} data-plane"),
@@ -100,9 +377,47 @@ async fn upgrade_product(opts: &CliArgs, event: &mut EventRecorder) -> Result<()
             )
             .await?;
 
-        if let Err(error) =
-            upgrade_data_plane(opts.namespace(), opts.rest_endpoint(), to_version).await
+        run.record_phase("data-plane-upgrade", None);
+        // A dry run changes nothing, it needn't hold the Lease.
+        let lease = if opts.data_plane_dry_run() {
+            None
+        } else {
+            match acquire_upgrade_lease(opts, &run.api_stats).await {
+                Ok(lease) => Some(lease),
+                Err(error) => {
+                    event.publish_unrecoverable(&error, false).await;
+                    return Err(error);
+                }
+            }
+        };
+        let deadline = UpgradeDeadline::start(opts.upgrade_deadline());
+        let ctx = match UpgradeContext::new(
+            opts,
+            from_version,
+            to_version,
+            deadline,
+            lease.as_ref(),
+            cancellation,
+            run,
+        )
+        .await
         {
+            Ok(ctx) => ctx,
+            Err(error) => {
+                event.publish_unrecoverable(&error, false).await;
+                return Err(error);
+            }
+        };
+        let report = upgrade_data_plane_logging_events(ctx).await;
+        // The Lease is released whether the data-plane upgrade succeeded or failed, so that the
+        // next upgrade-job needn't wait for it to expire.
+        if let Some(lease) = lease {
+            lease.release().await;
+        }
+        if opts.print_data_plane_report() {
+            report.print();
+        }
+        if let Err(error) = report.into_result() {
             event.publish_unrecoverable(&error, false).await;
             return Err(error);
         }
@@ -115,6 +430,23 @@ async fn upgrade_product(opts: &CliArgs, event: &mut EventRecorder) -> Result<()
             .await?;
     }
 
+    // A failed smoke test doesn't undo the upgrade, the cluster is left upgraded, but the upgrade
+    // is reported as failed.
+    if let Some(smoke_test_command) = opts.post_upgrade_smoke() {
+        run.record_phase("post-upgrade-smoke-test", None);
+        if let Err(error) = run_hook(
+            POST_UPGRADE_SMOKE_HOOK,
+            smoke_test_command.as_str(),
+            opts.hook_timeout(),
+        )
+        .await
+        {
+            event.publish_unrecoverable(&error, false).await;
+            return Err(error);
+        }
+    }
+
+    run.record_phase("upgrade-complete", None);
     event
         .publish_normal(
             format!("Successfully upgraded {PRODUCT}"),
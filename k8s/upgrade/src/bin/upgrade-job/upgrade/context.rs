@@ -0,0 +1,110 @@
+use crate::{
+    common::{
+        error::Result, kube_client::KubeClientSet, rest_client::RestClientSet, run::UpgradeRun,
+    },
+    opts::CliArgs,
+    upgrade::{
+        deadline::UpgradeDeadline,
+        deadman::{stop_if_shutdown_requested, CordonTtl},
+        drain_decisions::DrainDecisionLog,
+        events::UpgradeEventSender,
+        lease::{stop_if_lease_lost, UpgradeLease},
+        pause::UpgradePause,
+        report::DataPlaneUpgradeReport,
+    },
+};
+use tokio_util::sync::CancellationToken;
+
+/// This is the state of one data-plane upgrade, which is shared by the steps of the upgrade of
+/// each Node. It carries the API clients, the deadline, the upgrade Lease and the cancellation of
+/// the upgrade, along with the report which each Node's outcome is recorded in.
+pub(crate) struct UpgradeContext<'a> {
+    pub(crate) opts: &'a CliArgs,
+    pub(crate) upgrade_from_version: String,
+    pub(crate) upgrade_to_version: String,
+    /// If set, the Nodes are only checked, they are not drained, their io-engine Pods are not
+    /// deleted and they are not uncordoned.
+    pub(crate) dry_run: bool,
+    pub(crate) k8s_client: KubeClientSet,
+    pub(crate) rest_client: RestClientSet,
+    /// The state which is shared by the whole upgrade run, e.g. its API call stats.
+    pub(crate) run: UpgradeRun,
+    /// The decision log of the drain and the uncordon state machines.
+    pub(crate) drain_decision_log: DrainDecisionLog,
+    pub(crate) deadline: UpgradeDeadline,
+    /// The upgrade Lease which this upgrade-job holds. There is none for a dry run.
+    pub(crate) lease: Option<&'a UpgradeLease>,
+    /// The expiry of the cordons of the Nodes which are drained, if the upgrade is run with a
+    /// cordon TTL. There is none for a dry run.
+    pub(crate) cordon_ttl: Option<CordonTtl>,
+    /// This is cancelled by the first termination signal. No more Nodes are upgraded once it is
+    /// cancelled, the upgrade of the Nodes in progress runs up to their uncordon.
+    pub(crate) cancellation: &'a CancellationToken,
+    /// This pauses the upgrade in between Nodes, once its signals are handled.
+    pub(crate) pause: UpgradePause,
+    /// The events of each Node's upgrade are sent with this, if they are streamed.
+    pub(crate) events: UpgradeEventSender,
+    pub(crate) report: DataPlaneUpgradeReport,
+}
+
+impl<'a> UpgradeContext<'a> {
+    /// Builds the API clients for the upgrade from 'upgrade_from_version' to
+    /// 'upgrade_to_version', which record their API calls in the stats of 'run'. The storage REST
+    /// API isn't called yet.
+    pub(crate) async fn new(
+        opts: &'a CliArgs,
+        upgrade_from_version: String,
+        upgrade_to_version: String,
+        deadline: UpgradeDeadline,
+        lease: Option<&'a UpgradeLease>,
+        cancellation: &'a CancellationToken,
+        run: &UpgradeRun,
+    ) -> Result<Self> {
+        let k8s_client = KubeClientSet::builder()
+            .with_namespace(opts.namespace())
+            .with_kubeconfig(opts.kubeconfig(), opts.kube_context())
+            .with_api_stats(run.api_stats.clone())
+            .build()
+            .await?;
+        let rest_client = RestClientSet::new_with_url_and_auth(
+            opts.rest_endpoint(),
+            opts.rest_token_file(),
+            opts.rest_ca_cert_file(),
+        )?
+        .with_api_stats(run.api_stats.clone());
+        let cordon_ttl = opts
+            .cordon_ttl()
+            .filter(|_| !opts.data_plane_dry_run())
+            .map(|ttl| CordonTtl::new(ttl, &k8s_client));
+
+        Ok(Self {
+            opts,
+            upgrade_from_version,
+            upgrade_to_version,
+            dry_run: opts.data_plane_dry_run(),
+            k8s_client,
+            rest_client,
+            run: run.clone(),
+            drain_decision_log: DrainDecisionLog::new(
+                opts.drain_decision_log(),
+                run.timeline.clone(),
+            ),
+            deadline,
+            lease,
+            cordon_ttl,
+            cancellation,
+            pause: UpgradePause::default(),
+            events: UpgradeEventSender::default(),
+            report: DataPlaneUpgradeReport::new(run.id.clone()),
+        })
+    }
+
+    /// Fails if no more Nodes may be upgraded, i.e. once a shutdown is requested, once the
+    /// deadline has passed, or once the upgrade Lease is lost. This is checked before starting on
+    /// the next Nodes.
+    pub(crate) fn stop_if_interrupted(&self) -> Result<()> {
+        stop_if_shutdown_requested(self.cancellation)?;
+        self.deadline.stop_if_exceeded()?;
+        stop_if_lease_lost(self.lease)
+    }
+}
@@ -0,0 +1,152 @@
+use crate::{
+    common::{
+        api_stats::{LIST_DEPLOYMENTS, PATCH_DEPLOYMENT},
+        constants::AGENT_CORE_LABEL,
+        error::{ListDeploymentsWithLabel, ListPodsWithLabel, PatchDeployment, Result},
+        kube_client::KubeClientSet,
+    },
+    upgrade::utils::list_all_pods,
+};
+use k8s_openapi::api::apps::v1::Deployment;
+use kube::{
+    api::{ListParams, Patch, PatchParams},
+    ResourceExt,
+};
+use serde_json::json;
+use snafu::ResultExt;
+use std::time::SystemTime;
+use tracing::{info, warn};
+use utils::API_REST_LABEL;
+
+/// The Pod template annotation which is set to restart a Deployment's Pods, as with
+/// 'kubectl rollout restart'.
+const RESTARTED_AT_ANNOTATION: &str = "kubectl.kubernetes.io/restartedAt";
+
+/// Rolls out the agent-core and the api-rest Deployments whose Pods are still on a version other
+/// than 'upgrade_to_version', by restarting them, so that the control-plane wait doesn't rely on
+/// something else to complete their rollout. Only a Deployment whose Pod template is on the target
+/// version already is restarted, as a restart can't change the version of its Pods. A Deployment
+/// whose rollout is in progress is left to complete it. Returns true if any Deployment was
+/// restarted.
+pub(crate) async fn roll_out_control_plane(
+    namespace: String,
+    k8s_client: &KubeClientSet,
+    upgrade_to_version: &str,
+    version_label_key: &str,
+) -> Result<bool> {
+    let mut restarted = false;
+    for label in [AGENT_CORE_LABEL, API_REST_LABEL] {
+        let deployments = k8s_client
+            .api_stats()
+            .timed(
+                LIST_DEPLOYMENTS,
+                k8s_client
+                    .deployments_api()
+                    .list(&ListParams::default().labels(label)),
+            )
+            .await
+            .context(ListDeploymentsWithLabel {
+                namespace: namespace.clone(),
+                label_selector: label.to_string(),
+            })?;
+        if deployments.items.is_empty() {
+            continue;
+        }
+
+        let pods = list_all_pods(k8s_client, &ListParams::default().labels(label))
+            .await
+            .context(ListPodsWithLabel {
+                label: label.to_string(),
+                namespace: namespace.clone(),
+            })?;
+        let stale_pods: Vec<String> = pods
+            .iter()
+            .filter(|pod| {
+                pod.labels()
+                    .get(version_label_key)
+                    .map_or(true, |version| version.ne(upgrade_to_version))
+            })
+            .map(|pod| pod.name_any())
+            .collect();
+        if stale_pods.is_empty() {
+            continue;
+        }
+
+        for deployment in deployments.items.iter() {
+            let name = deployment.name_any();
+            let template_version = deployment
+                .spec
+                .as_ref()
+                .and_then(|spec| spec.template.metadata.as_ref())
+                .and_then(|metadata| metadata.labels.as_ref())
+                .and_then(|labels| labels.get(version_label_key));
+            if template_version.map(String::as_str) != Some(upgrade_to_version) {
+                warn!(
+                    deployment.name = %name,
+                    template_version = ?template_version,
+                    to_version = %upgrade_to_version,
+                    "Control-plane Deployment isn't on the target version, it can't be rolled out \
+                    by a restart"
+                );
+                continue;
+            }
+            if rollout_in_progress(deployment) {
+                info!(
+                    deployment.name = %name,
+                    "Control-plane Deployment is rolling out, waiting for its rollout"
+                );
+                continue;
+            }
+
+            info!(
+                deployment.name = %name,
+                pods = ?stale_pods,
+                "Restarting control-plane Deployment, its Pods aren't on the target version"
+            );
+            let restarted_at = humantime::format_rfc3339_seconds(SystemTime::now()).to_string();
+            let patch = json!({
+                "spec": {
+                    "template": {
+                        "metadata": {
+                            "annotations": { RESTARTED_AT_ANNOTATION: restarted_at }
+                        }
+                    }
+                }
+            });
+            k8s_client
+                .api_stats()
+                .timed(
+                    PATCH_DEPLOYMENT,
+                    k8s_client.deployments_api().patch(
+                        &name,
+                        &PatchParams::default(),
+                        &Patch::Merge(&patch),
+                    ),
+                )
+                .await
+                .context(PatchDeployment {
+                    name: name.clone(),
+                    namespace: namespace.clone(),
+                })?;
+            restarted = true;
+        }
+    }
+
+    Ok(restarted)
+}
+
+/// Returns true if the Deployment's controller hasn't yet caught up with its latest spec, or if
+/// some of its replicas aren't updated to the latest spec yet.
+fn rollout_in_progress(deployment: &Deployment) -> bool {
+    let generation = deployment.metadata.generation.unwrap_or_default();
+    let replicas = deployment
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.replicas)
+        .unwrap_or(1);
+    deployment.status.as_ref().map_or(true, |status| {
+        status.observed_generation.unwrap_or_default() < generation
+            || status.updated_replicas.unwrap_or_default() < replicas
+            || status.replicas.unwrap_or_default() > replicas
+    })
+}
@@ -1,156 +1,500 @@
 use crate::{
     common::{
+        api_stats::{
+            ApiStats, DELETE_NODE_CORDON, DELETE_POD, EVICT_POD, GET_NODE, GET_NODES, GET_POD,
+            LIST_DAEMONSETS,
+        },
         constants::{
-            AGENT_CORE_LABEL, CHART_VERSION_LABEL_KEY, DRAIN_FOR_UPGRADE, IO_ENGINE_LABEL, PRODUCT,
+            CONTROLLER_REVISION_HASH_LABEL_KEY, IO_ENGINE_CONTAINER_NAME, IO_ENGINE_LABEL, PRODUCT,
+            UPGRADE_SKIP_ANNOTATION_KEY,
         },
+        drain_label::DrainLabels,
         error::{
-            DrainStorageNode, EmptyPodNodeName, EmptyPodSpec, EmptyStorageNodeSpec, GetStorageNode,
-            ListPodsWithLabel, ListPodsWithLabelAndField, ListStorageNodes, PodDelete, Result,
-            StorageNodeUncordon, TooManyIoEnginePods,
+            ControlPlaneAheadOfTarget, ControlPlaneNotReady, ControlPlaneUnhealthy,
+            DaemonSetNotUpdated, DataPlanePodCrashLooping, DataPlanePodNotReady,
+            DrainLabelNotApplied, DrainStorageNode, DrainTimeout, EmptyStorageNodeSpec, Error,
+            GetIoEnginePod, GetStorageNode, InsufficientPermissions, ListDaemonSetsWithLabel,
+            ListPodsWithLabel, ListPodsWithLabelAndField, ListStorageNodes,
+            MinimumHealthyNodesViolated, MultipleTargetRevisions, NodeIdMappingFailed,
+            NodesLeftCordoned, NodesNotUpgraded, NotAnUpgrade, PodDelete, PodDeletionTimeout,
+            PodEviction, PodEvictionBlocked, PodForceDelete, PodNotOwnedByDaemonSet,
+            PodStuckTerminating, RebuildTimeout, ReplicaCountNotRestored, Result, SemverParse,
+            StorageNodeUncordon, StorageNodeVanished, TooManyIoEnginePods, UncordonDrainInProgress,
+            UnhealthyVolumesPresent, UnsupportedUpgradePath, UpgradeAbandoned, UpgradeStalled,
+            VolumeFaultedAfterUpgrade,
         },
         kube_client::KubeClientSet,
-        rest_client::RestClientSet,
+        logging::WaitLogVerbosity,
+        metrics::MetricsServer,
+        progress::ProgressConfigMap,
+        rest_client::{
+            is_forbidden, is_not_found, with_jitter, NodeCordonApi, RestClientSet, RestError,
+        },
+        run::UpgradeRun,
+    },
+    opts::CliArgs,
+    poll_status,
+    upgrade::{
+        context::UpgradeContext,
+        control_plane_rollout::roll_out_control_plane,
+        deadman::{
+            forced_termination_signal, uncordon_expired_cordons, uncordon_upgrade_drained_nodes,
+        },
+        drain_decisions::{DrainDecision, DrainDecisionLog, StateMachine},
+        drain_mode::{soft_drain_storage_node, DrainMode},
+        etcd_health::verify_etcd_health,
+        events::UpgradeEvent,
+        external_cordon::may_upgrade_cordoned_node,
+        node_cache::StorageNodeCache,
+        node_durations::{NodePhaseDurations, RebuildWait},
+        node_events::{emit_pod_events, NodeEventReason},
+        node_failures::NodeFailures,
+        node_filter::NodeFilter,
+        node_id::storage_node_id,
+        node_labels::{
+            has_upgrade_skip_annotation, monitored_node_labels, verify_node_labels_unchanged,
+        },
+        node_order::NodeOrder,
+        permissions::verify_permissions,
+        pod_selection::{
+            is_owned_by_daemonset, owner_daemonset_uid_and_name, pod_names_and_phases,
+            select_pods_per_node, validate_pods, NodePods,
+        },
+        pod_watch::PodChanges,
+        pools::verify_no_offline_pools,
+        readiness::{
+            wait_for_csi_node_plugin, wait_for_storage_node_online, CsiNodePluginPods,
+            ExtraPodReadiness, ReadinessStrategy,
+        },
+        rebuild_progress::RebuildProgressTracker,
+        replica_distribution::ReplicaDistribution,
+        replica_relocation::wait_for_replica_relocation,
+        report::{DataPlaneUpgradeReport, NodeUpgradeStatus},
+        simulation::{phase_may_complete, warn_slow_cluster_simulation},
+        snapshots::wait_for_snapshots_to_complete,
+        state_snapshot::write_state_snapshot,
+        timeouts::{
+            size_based_rebuild_timeout, timeout_exceeded, PhaseTimeouts, PhaseTimeoutsSource,
+        },
+        utils::{
+            all_pods_are_ready, data_plane_is_upgraded, faulted_volumes, list_all_pods,
+            list_unhealthy_volumes, list_volumes, log_volume_states, nodes_hosting_volume,
+            pod_is_ready, pod_is_terminated, rebuild_may_start, rebuild_result, volume_may_rebuild,
+            volumes_below_replica_count, volumes_hosted_on_node, volumes_with_replica_on_node,
+            PodInspector, RebuildResult,
+        },
+        volume_attachments::wait_for_volume_attachments,
+        volume_scheduling::verify_volume_scheduling,
+        warnings::UpgradeWarningKind,
+        zones::ZoneWaves,
     },
-    upgrade::utils::{all_pods_are_ready, data_plane_is_upgraded, rebuild_result, RebuildResult},
 };
+use futures::future::{join_all, try_join_all};
 use k8s_openapi::api::core::v1::Pod;
 use kube::{
-    api::{DeleteParams, ListParams, ObjectList},
+    api::{DeleteParams, EvictParams, ListParams, LogParams, Preconditions, PropagationPolicy},
     ResourceExt,
 };
-use openapi::models::CordonDrainState;
-use snafu::ResultExt;
-use std::time::Duration;
-use tracing::info;
-use utils::{API_REST_LABEL, ETCD_LABEL};
-
-/// Upgrade data plane by controlled restart of io-engine pods
-pub(crate) async fn upgrade_data_plane(
-    namespace: String,
-    rest_endpoint: String,
+use openapi::models::{CordonDrainState, Node};
+use semver::Version;
+use snafu::{ensure, IntoError, ResultExt};
+use std::{
+    cmp::Ordering,
+    collections::{BTreeMap, BTreeSet, HashMap, HashSet},
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+use tokio::select;
+use tracing::{debug, error, info, warn};
+
+/// The number of consecutive failed control-plane health checks after which a node's upgrade is
+/// abandoned, when the control-plane is monitored.
+const CONTROL_PLANE_UNHEALTHY_CHECKS: u32 = 3;
+
+/// The maximum time to wait for a storage Node which is still draining with the upgrade drain
+/// label to be drained, before it is uncordoned.
+const UNCORDON_DRAIN_WAIT: Duration = Duration::from_secs(300);
+
+/// Upgrade data plane by controlled restart of io-engine pods. For a dry run, the checks before
+/// each node's upgrade are run, but the nodes are not drained, their io-engine pods are not
+/// deleted and the nodes are not uncordoned. The actions which would be taken are logged instead.
+/// The returned report carries the result of the upgrade, along with each node's outcome. No more
+/// nodes are upgraded once the context's deadline has passed, or once its upgrade Lease is lost.
+/// The first termination signal cancels its cancellation token, after which no more nodes are
+/// upgraded either, and the nodes drained for the upgrade are uncordoned.
+pub(crate) async fn upgrade_data_plane(mut ctx: UpgradeContext<'_>) -> DataPlaneUpgradeReport {
+    let start = Instant::now();
+    let opts = ctx.opts;
+    let upgrade_path = verify_is_upgrade(
+        ctx.upgrade_from_version.as_str(),
+        ctx.upgrade_to_version.as_str(),
+        opts.allow_downgrade(),
+        !opts.fail_on_same_version(),
+    )
+    .and_then(|()| {
+        verify_min_source_version(
+            ctx.upgrade_from_version.as_str(),
+            ctx.upgrade_to_version.as_str(),
+            opts.min_source_version().as_deref(),
+        )
+    });
+    let result = match upgrade_path {
+        Ok(()) => run_data_plane_upgrade(&mut ctx).await,
+        Err(error) => Err(error),
+    };
+    let mut report = ctx.report;
+    info!(
+        nodes.upgraded = report.node_count(NodeUpgradeStatus::Upgraded),
+        nodes.skipped = report.node_count(NodeUpgradeStatus::Skipped),
+        nodes.dry_run = report.node_count(NodeUpgradeStatus::DryRun),
+        nodes.failed = report.node_count(NodeUpgradeStatus::Failed),
+        failed_nodes = ?report.nodes_with_status(NodeUpgradeStatus::Failed),
+        "Data-plane upgrade summary"
+    );
+    report.record_api_calls(ctx.k8s_client.api_stats());
+    report.finish(start.elapsed(), result);
+    report.log_warnings_summary();
+    report
+}
+
+/// Completes a control-plane only upgrade, for an upgrade which defers the data-plane's upgrade,
+/// e.g. to a maintenance window. The control-plane Deployments are rolled out if the upgrade
+/// rolls them out, and the control-plane is waited on to be Ready on the target version. The
+/// io-engine Pods are left alone, and the returned report notes that the data-plane was left on
+/// the source version.
+pub(crate) async fn upgrade_control_plane_only(
+    opts: &CliArgs,
+    upgrade_from_version: String,
     upgrade_to_version: String,
+    run: &UpgradeRun,
+) -> DataPlaneUpgradeReport {
+    let start = Instant::now();
+    let mut report = DataPlaneUpgradeReport::new(run.id.clone());
+    let result = verify_upgraded_control_plane(opts, &upgrade_to_version, &run.api_stats).await;
+    if result.is_ok() {
+        info!(
+            from_version = %upgrade_from_version,
+            to_version = %upgrade_to_version,
+            "Upgraded the control-plane only, the data-plane is left on the source version"
+        );
+    }
+    report.record_data_plane_left_on(upgrade_from_version);
+    report.record_api_calls(&run.api_stats);
+    report.finish(start.elapsed(), result);
+    report
+}
+
+/// Rolls out the control-plane Deployments if 'opts' says so, and waits for the control-plane
+/// to be Ready on the target version.
+async fn verify_upgraded_control_plane(
+    opts: &CliArgs,
+    upgrade_to_version: &String,
+    api_stats: &Arc<ApiStats>,
 ) -> Result<()> {
-    // Generate k8s clients.
+    let namespace = opts.namespace();
     let k8s_client = KubeClientSet::builder()
         .with_namespace(namespace.clone())
+        .with_kubeconfig(opts.kubeconfig(), opts.kube_context())
+        .with_api_stats(api_stats.clone())
         .build()
         .await?;
 
+    if opts.upgrade_control_plane() {
+        roll_out_control_plane(
+            namespace.clone(),
+            &k8s_client,
+            upgrade_to_version,
+            opts.chart_version_label_key().as_str(),
+        )
+        .await?;
+    }
+
+    // There's only the one check, there's nothing to cache it for.
+    let control_plane_cache = ControlPlaneReadyCache::new(Duration::ZERO);
+    // A slow cluster is only simulated for the data-plane upgrade.
+    let wait = ControlPlaneWait {
+        timeout: Some(opts.control_plane_timeout()),
+        strict: opts.strict(),
+        simulated_phase_duration: None,
+    };
+    verify_control_plane_is_running(
+        namespace,
+        &k8s_client,
+        upgrade_to_version,
+        &wait,
+        &control_plane_cache,
+        &ControlPlaneComponents::from_opts(opts),
+    )
+    .await
+}
+
+/// Validates that the target version is newer than the source version, so that swapped
+/// versions don't drain the whole cluster for a downgrade. An earlier target version is only
+/// allowed with 'allow_downgrade', and the same version only with 'allow_same', e.g. to carry on
+/// from an interrupted upgrade.
+fn verify_is_upgrade(
+    from_version: &str,
+    to_version: &str,
+    allow_downgrade: bool,
+    allow_same: bool,
+) -> Result<()> {
+    let parse = |version: &str| {
+        Version::parse(version).context(SemverParse {
+            version_string: version.to_string(),
+        })
+    };
+    let (from, to) = (parse(from_version)?, parse(to_version)?);
+
+    let allowed = match to.cmp(&from) {
+        Ordering::Greater => true,
+        Ordering::Equal => allow_same,
+        Ordering::Less => allow_downgrade,
+    };
+    ensure!(
+        allowed,
+        NotAnUpgrade {
+            from_version: from.to_string(),
+            to_version: to.to_string(),
+        }
+    );
+    Ok(())
+}
+
+/// Validates that the source version isn't earlier than 'min_source_version', if it is set, as
+/// the target version may only be upgraded to from that version or later.
+fn verify_min_source_version(
+    from_version: &str,
+    to_version: &str,
+    min_source_version: Option<&str>,
+) -> Result<()> {
+    let Some(min_source_version) = min_source_version else {
+        return Ok(());
+    };
+    let parse = |version: &str| {
+        Version::parse(version).context(SemverParse {
+            version_string: version.to_string(),
+        })
+    };
+    let (from, min) = (parse(from_version)?, parse(min_source_version)?);
+
+    ensure!(
+        from >= min,
+        UnsupportedUpgradePath {
+            from_version: from.to_string(),
+            to_version,
+            min_source_version: min.to_string(),
+        }
+    );
+    Ok(())
+}
+
+/// Runs the data-plane upgrade, recording each node's outcome in the context's report.
+async fn run_data_plane_upgrade(ctx: &mut UpgradeContext<'_>) -> Result<()> {
+    let opts = ctx.opts;
+    let dry_run = ctx.dry_run;
+    let namespace = opts.namespace();
+    let version_label_key = opts.chart_version_label_key();
+
+    if let Some(phase_duration) = opts.simulated_phase_duration() {
+        warn_slow_cluster_simulation(phase_duration);
+    }
+
+    if opts.drain_decision_log() {
+        info!("Logging every drain state machine decision");
+    }
+
+    // A missing permission would otherwise only show up once a Node is drained.
+    verify_permissions(
+        namespace.as_str(),
+        &ctx.k8s_client,
+        dry_run,
+        opts.respect_pdb(),
+        opts.io_engine_log_lines().is_some(),
+        ctx.cordon_ttl.is_some(),
+    )
+    .await?;
+
+    // A component which isn't deployed would keep the upgrade waiting for the control-plane.
+    let control_plane_components = ControlPlaneComponents::from_opts(opts);
+    info!(
+        versioned = ?control_plane_components.versioned,
+        unversioned = ?control_plane_components.unversioned,
+        "Requiring these control-plane components to be Ready before each Node's upgrade"
+    );
+
+    let progress_config_map = match opts.progress_config_map() {
+        Some(name) => {
+            Some(ProgressConfigMap::create(&ctx.k8s_client, name, ctx.run.progress.clone()).await)
+        }
+        None => None,
+    };
+
     // This makes data-plane upgrade idempotent.
-    let io_engine_label = format!("{IO_ENGINE_LABEL},{CHART_VERSION_LABEL_KEY}");
+    let io_engine_label = format!("{IO_ENGINE_LABEL},{version_label_key}");
     let io_engine_listparams = ListParams::default().labels(io_engine_label.as_str());
-    let io_engine_pod_list = k8s_client
-        .pods_api()
-        .list(&io_engine_listparams)
+    let io_engine_pod_list = list_all_pods(&ctx.k8s_client, &io_engine_listparams)
         .await
         .context(ListPodsWithLabel {
             label: io_engine_label,
             namespace: namespace.clone(),
         })?;
-    if data_plane_is_upgraded(&upgrade_to_version, &io_engine_pod_list).await? {
+    if io_engine_pod_list.is_empty() {
+        warn_no_versioned_io_engine_pods(namespace.clone(), &version_label_key, &ctx.k8s_client)
+            .await?;
+        if let Some(progress_config_map) = progress_config_map.as_ref() {
+            progress_config_map.finish().await;
+        }
+        return Ok(());
+    }
+    if data_plane_is_upgraded(
+        &ctx.upgrade_to_version,
+        &version_label_key,
+        &io_engine_pod_list,
+    )
+    .await?
+    {
         info!("Skipping data-plane upgrade: All data-plane Pods are already upgraded");
+        if let Some(progress_config_map) = progress_config_map.as_ref() {
+            progress_config_map.finish().await;
+        }
         return Ok(());
     }
 
     // If here, then there is a need to proceed to data-plane upgrade.
 
-    let yet_to_upgrade_io_engine_label_selector =
-        format!("{IO_ENGINE_LABEL},{CHART_VERSION_LABEL_KEY}!={upgrade_to_version}");
-    let io_engine_listparams =
-        ListParams::default().labels(yet_to_upgrade_io_engine_label_selector.as_str());
-    let namespace = namespace.clone();
-
-    // Generate storage REST API client.
-    let rest_client = RestClientSet::new_with_url(rest_endpoint)?;
+    ctx.rest_client.verify_reachable().await?;
+    let drain_labels = opts.drain_labels();
 
     info!("Starting data-plane upgrade...");
 
-    info!(
-        "Trying to remove upgrade {PRODUCT} Node Drain label from {PRODUCT} Nodes, \
-        if any left over from previous upgrade attempts..."
-    );
+    ctx.pause.handle_signals(&ctx.run.progress);
 
-    let storage_nodes_resp = rest_client
-        .nodes_api()
-        .get_nodes(None)
-        .await
-        .context(ListStorageNodes)?;
-    let storage_nodes = storage_nodes_resp.body();
-    for storage_node in storage_nodes {
-        uncordon_node(storage_node.id.as_str(), &rest_client).await?;
+    let mut node_cache = StorageNodeCache::new(opts.cache_node_list());
+
+    // The Nodes left cordoned by an upgrade-job which died are uncordoned once their cordon
+    // expires, before the stale drain labels are looked for.
+    if ctx.cordon_ttl.is_some() {
+        uncordon_expired_cordons(
+            &drain_labels,
+            &ctx.k8s_client,
+            &ctx.rest_client,
+            &mut node_cache,
+        )
+        .await?;
     }
 
-    loop {
-        let initial_io_engine_pod_list: ObjectList<Pod> = k8s_client
-            .pods_api()
-            .list(&io_engine_listparams)
-            .await
-            .context(ListPodsWithLabel {
-                label: yet_to_upgrade_io_engine_label_selector.clone(),
-                namespace: namespace.clone(),
-            })?;
+    if opts.skip_stale_drain_label_cleanup() {
+        info!("Skipping the removal of the upgrade drain labels left over from previous upgrades");
+    } else {
+        cleanup_stale_drain_labels(
+            &drain_labels,
+            &ctx.rest_client,
+            &mut node_cache,
+            dry_run,
+            &ctx.drain_decision_log,
+        )
+        .await?;
+    }
 
-        // Infinite loop exit.
-        if initial_io_engine_pod_list.items.is_empty() {
-            break;
-        }
+    // The metrics endpoint stops serving when this is dropped, on returning.
+    let _metrics_server = match opts.metrics_port() {
+        Some(port) => Some(MetricsServer::serve(port, ctx.run.progress.clone()).await?),
+        None => None,
+    };
+    let nodes_total: HashSet<&str> = io_engine_pod_list
+        .iter()
+        .filter_map(|pod| pod.spec.as_ref())
+        .filter_map(|spec| spec.node_name.as_deref())
+        .collect();
+    ctx.run.progress.set_nodes_total(nodes_total.len());
 
-        for pod in initial_io_engine_pod_list.iter() {
-            // Validate the control plane pod is up and running before we start.
-            verify_control_plane_is_running(namespace.clone(), &k8s_client, &upgrade_to_version)
-                .await?;
+    // The plan is logged before any node is drained, so that the number of nodes left may be
+    // told from the logs.
+    let nodes_to_upgrade: BTreeSet<&str> = io_engine_pod_list
+        .iter()
+        .filter(|pod| {
+            pod.labels()
+                .get(version_label_key.as_str())
+                .map_or(true, |version| version.ne(&ctx.upgrade_to_version))
+        })
+        .filter_map(|pod| pod.spec.as_ref())
+        .filter_map(|spec| spec.node_name.as_deref())
+        .collect();
+    info!(
+        from_version = %ctx.upgrade_from_version,
+        to_version = %ctx.upgrade_to_version,
+        nodes.count = nodes_to_upgrade.len(),
+        nodes = ?nodes_to_upgrade,
+        "Upgrading the data-plane of {} of {} nodes",
+        nodes_to_upgrade.len(),
+        nodes_total.len()
+    );
 
-            // Fetch the node name on which the io-engine pod is running
-            let node_name = pod
-                .spec
-                .as_ref()
-                .ok_or(
-                    EmptyPodSpec {
-                        name: pod.name_any(),
-                        namespace: namespace.clone(),
-                    }
-                    .build(),
-                )?
-                .node_name
-                .as_ref()
-                .ok_or(
-                    EmptyPodNodeName {
-                        name: pod.name_any(),
-                        namespace: namespace.clone(),
-                    }
-                    .build(),
-                )?
-                .as_str();
+    // A deleted Pod would be re-created on the old version if its DaemonSet isn't updated.
+    verify_daemonsets_updated(
+        namespace.clone(),
+        opts.io_engine_daemonset().as_deref(),
+        &ctx.upgrade_to_version,
+        &version_label_key,
+        &ctx.k8s_client,
+    )
+    .await?;
 
-            info!(
-                pod.name = %pod.name_any(),
-                node.name = %node_name,
-                "Starting upgrade for the data-plane pod"
-            );
+    // A Node isn't drained while a volume is already down a replica.
+    verify_volumes_are_healthy(&ctx.rest_client, opts.allow_unhealthy_volumes()).await?;
 
-            // Wait for any rebuild to complete
-            wait_for_rebuild(node_name, &rest_client).await?;
+    if let Some(path) = opts.state_snapshot() {
+        write_state_snapshot(ctx, path.as_path(), &version_label_key, namespace.clone()).await?;
+    }
 
-            // Issue node drain command
-            drain_storage_node(node_name, &rest_client).await?;
+    if opts.report_replica_distribution() {
+        let replica_distribution = ReplicaDistribution::collect(&ctx.rest_client).await;
+        ctx.report
+            .record_replica_distribution_before(replica_distribution);
+    }
 
-            // restart the data plane pod
-            delete_data_plane_pod(node_name, pod, &k8s_client).await?;
+    // If the Job is terminated mid-upgrade, e.g. it is deleted or it exceeds its deadline, the
+    // upgrade stops after the Nodes in progress, and the Nodes are not left drained for upgrade.
+    let cancellation = ctx.cancellation;
+    let forward_progress = ctx.run.forward_progress.clone();
+    select! {
+        result = upgrade_nodes(ctx, &mut node_cache, progress_config_map.as_ref()) => {
+            if result.is_err() && cancellation.is_cancelled() {
+                uncordon_upgrade_drained_nodes(&drain_labels, &ctx.rest_client).await;
+            }
+            result?
+        }
+        signal = forced_termination_signal(cancellation) => {
+            warn!(%signal, "Received termination signal, uncordoning Nodes drained for upgrade");
+            uncordon_upgrade_drained_nodes(&drain_labels, &ctx.rest_client).await;
+            return UpgradeAbandoned { signal }.fail();
+        }
+        idle_timeout = forward_progress.stalled(opts.idle_timeout()) => {
+            error!(
+                ?idle_timeout,
+                "The data-plane upgrade has made no forward progress, uncordoning Nodes drained \
+                for upgrade"
+            );
+            uncordon_upgrade_drained_nodes(&drain_labels, &ctx.rest_client).await;
+            return UpgradeStalled { idle_timeout }.fail();
+        }
+    }
 
-            // validate the new pod is up and running
-            verify_data_plane_pod_is_running(
-                node_name,
-                namespace.clone(),
-                &upgrade_to_version,
-                &k8s_client,
-            )
-            .await?;
+    report_cordoned_nodes(&drain_labels, &ctx.rest_client, opts.strict()).await?;
 
-            // Uncordon the drained node
-            uncordon_node(node_name, &rest_client).await?;
-        }
+    if opts.report_replica_distribution() {
+        let replica_distribution = ReplicaDistribution::collect(&ctx.rest_client).await;
+        ctx.report
+            .record_replica_distribution_after(replica_distribution);
+    }
 
-        info!("Checking to see if new {PRODUCT} Nodes have been added to the cluster, which require upgrade");
+    if let Some(progress_config_map) = progress_config_map.as_ref() {
+        progress_config_map.finish().await;
+    }
+
+    if dry_run {
+        info!("Data-plane upgrade dry run complete, no changes were made");
+        return Ok(());
     }
 
     info!("Successfully upgraded data-plane!");
@@ -158,255 +502,3849 @@ pub(crate) async fn upgrade_data_plane(
     Ok(())
 }
 
-/// Uncordon storage Node.
-async fn uncordon_node(node_id: &str, rest_client: &RestClientSet) -> Result<()> {
-    let drain_label_for_upgrade: String = DRAIN_FOR_UPGRADE.to_string();
-    let sleep_duration = Duration::from_secs(1_u64);
-    loop {
-        let storage_node =
-            rest_client
-                .nodes_api()
-                .get_node(node_id)
-                .await
-                .context(GetStorageNode {
-                    node_id: node_id.to_string(),
-                })?;
-
-        match storage_node
-            .into_body()
-            .spec
-            .ok_or(
-                EmptyStorageNodeSpec {
-                    node_id: node_id.to_string(),
-                }
-                .build(),
-            )?
-            .cordondrainstate
-        {
-            Some(CordonDrainState::drainedstate(drain_state))
-                if drain_state.drainlabels.contains(&drain_label_for_upgrade) =>
-            {
-                rest_client
-                    .nodes_api()
-                    .delete_node_cordon(node_id, DRAIN_FOR_UPGRADE)
-                    .await
-                    .context(StorageNodeUncordon {
-                        node_id: node_id.to_string(),
-                    })?;
-
-                info!(node.id = %node_id,
-                    label = %DRAIN_FOR_UPGRADE,
-                    "Removed drain label from {PRODUCT} Node"
-                );
-            }
-            _ => return Ok(()),
-        }
-        tokio::time::sleep(sleep_duration).await;
-    }
-}
-
-/// Issue delete command on dataplane pods.
-async fn delete_data_plane_pod(
-    node_name: &str,
-    pod: &Pod,
+/// Logs why there are no io-engine Pods with the chart version label, i.e. whether there are no
+/// io-engine Pods at all, or whether the io-engine Pods don't carry the chart version label. Either
+/// way, the label selectors may be misconfigured, so there's nothing to upgrade.
+async fn warn_no_versioned_io_engine_pods(
+    namespace: String,
+    version_label_key: &str,
     k8s_client: &KubeClientSet,
 ) -> Result<()> {
-    // Deleting the io-engine pod
-    let pod_name = pod.name_any();
-    info!(
-        pod.name = pod_name.clone(),
-        node.name = node_name,
-        "Deleting the pod"
-    );
-    k8s_client
-        .pods_api()
-        .delete(pod_name.as_str(), &DeleteParams::default())
+    let io_engine_pods = list_all_pods(k8s_client, &ListParams::default().labels(IO_ENGINE_LABEL))
         .await
-        .context(PodDelete {
-            name: pod_name,
-            node: node_name.to_string(),
+        .context(ListPodsWithLabel {
+            label: IO_ENGINE_LABEL.to_string(),
+            namespace: namespace.clone(),
         })?;
-    info!(node.name = %node_name, "Pod delete command issued");
+
+    if io_engine_pods.is_empty() {
+        warn!(
+            label = %IO_ENGINE_LABEL,
+            %namespace,
+            "No io-engine Pods found, the io-engine label selector may be misconfigured, \
+            nothing to upgrade"
+        );
+    } else {
+        warn!(
+            pods = io_engine_pods.len(),
+            label = %version_label_key,
+            "None of the io-engine Pods carry the chart version label, the label key may be \
+            misconfigured, nothing to upgrade"
+        );
+    }
     Ok(())
 }
 
-/// Wait for all the node drain process to complete.
-async fn verify_data_plane_pod_is_running(
-    node_name: &str,
-    namespace: String,
-    upgrade_to_version: &String,
-    k8s_client: &KubeClientSet,
+/// Uncordon the storage Nodes which are left drained for upgrade by a previous upgrade attempt,
+/// e.g. by an upgrade Job which crashed before uncordoning the Node it was upgrading. No Node is
+/// being upgraded by this run yet, so every Node carrying the upgrade drain label is stale.
+async fn cleanup_stale_drain_labels(
+    drain_labels: &DrainLabels,
+    rest_client: &RestClientSet,
+    node_cache: &mut StorageNodeCache,
+    dry_run: bool,
+    decision_log: &DrainDecisionLog,
 ) -> Result<()> {
-    let duration = Duration::from_secs(5_u64);
-    // Validate the new pod is up and running
-    info!(node.name = %node_name, "Waiting for data-plane Pod to come to Ready state");
-    while !data_plane_pod_is_running(node_name, namespace.clone(), upgrade_to_version, k8s_client)
-        .await?
+    info!(
+        "Trying to remove upgrade {PRODUCT} Node Drain label from {PRODUCT} Nodes, \
+        if any left over from previous upgrade attempts..."
+    );
+
+    let storage_nodes = node_cache.nodes(rest_client).await?.to_vec();
+    for storage_node in storage_nodes
+        .iter()
+        .filter(|node| is_drained_for_upgrade(node, drain_labels))
     {
-        tokio::time::sleep(duration).await;
+        if dry_run {
+            info!(
+                node.id = %storage_node.id,
+                "Dry run: would remove the upgrade drain label from {PRODUCT} Node"
+            );
+            continue;
+        }
+        info!(
+            node.id = %storage_node.id,
+            label = %drain_labels.drain_label(),
+            "Removing stale upgrade drain label from {PRODUCT} Node"
+        );
+        uncordon_node(
+            storage_node.id.as_str(),
+            drain_labels,
+            rest_client,
+            decision_log,
+        )
+        .await?;
     }
     Ok(())
 }
 
-/// Wait for the rebuild to complete if any.
-async fn wait_for_rebuild(node_name: &str, rest_client: &RestClientSet) -> Result<()> {
-    // Wait for 60 seconds for any rebuilds to kick in.
-    tokio::time::sleep(Duration::from_secs(60_u64)).await;
+/// Upgrade the io-engine Pods one Node at a time, until there are no more io-engine Pods left
+/// to upgrade. For a dry run, each Node is only checked, and the number of Nodes which would be
+/// upgraded is logged at the end.
+async fn upgrade_nodes(
+    ctx: &mut UpgradeContext<'_>,
+    node_cache: &mut StorageNodeCache,
+    progress_config_map: Option<&ProgressConfigMap>,
+) -> Result<()> {
+    let opts = ctx.opts;
+    let dry_run = ctx.dry_run;
+    let k8s_client = &ctx.k8s_client;
+    let rest_client = &ctx.rest_client;
+    let upgrade_from_version = ctx.upgrade_from_version.as_str();
+    let upgrade_to_version = &ctx.upgrade_to_version;
+    let namespace = opts.namespace();
+    let drain_labels = opts.drain_labels();
+    let version_label_key = opts.chart_version_label_key();
 
-    let mut result = RebuildResult::default();
-    loop {
-        let rebuild = rebuild_result(rest_client, &mut result.discarded_volumes, node_name).await?;
+    let yet_to_upgrade_io_engine_label_selector =
+        format!("{IO_ENGINE_LABEL},{version_label_key}!={upgrade_to_version}");
+    let io_engine_listparams =
+        ListParams::default().labels(yet_to_upgrade_io_engine_label_selector.as_str());
 
-        if rebuild.rebuilding {
-            info!(node.name = %node_name, "Waiting for volume rebuilds to complete");
-            tokio::time::sleep(Duration::from_secs(10_u64)).await;
-        } else {
-            break;
-        }
-    }
-    info!(node.name = %node_name, "No volume rebuilds in progress");
-    Ok(())
-}
+    // The timeouts are re-read before each Node's upgrade, so that they may be tuned while
+    // the upgrade is in progress.
+    let default_timeouts = PhaseTimeouts {
+        drain: opts.drain_timeout(),
+        readiness: Some(opts.readiness_timeout()),
+        control_plane: Some(opts.control_plane_timeout()),
+        rebuild: opts.rebuild_timeout(),
+        ..Default::default()
+    };
+    let mut timeouts_source =
+        PhaseTimeoutsSource::new(default_timeouts, opts.timeouts_config_map());
+
+    let node_order = NodeOrder::new(
+        rest_client,
+        &opts.priority_volumes(),
+        opts.priority_volumes_order(),
+        opts.secondary_order(),
+    )
+    .await?;
+
+    let mut node_failures =
+        NodeFailures::new(opts.max_consecutive_failures(), opts.max_node_failures());
+    // The number of nodes whose upgrade waited for the maintenance window to open.
+    let mut deferred_nodes = 0_usize;
+    // The nodes which are found to be upgraded already, e.g. by a previous run of the upgrade
+    // which was interrupted, while they still have Pods without the target version label.
+    let mut already_upgraded_nodes = HashSet::new();
+    // The nodes which would be upgraded, in a dry run. As their Pods are left as they are, they
+    // aren't checked again.
+    let mut dry_run_nodes = HashSet::new();
+    // The nodes which are skipped as they are cordoned by something other than the upgrade.
+    let mut externally_cordoned_nodes = HashSet::new();
+    // The nodes which are skipped as they're pinned to their version by the skip annotation.
+    let mut pinned_nodes = HashSet::new();
+    // The nodes which are skipped as their storage Nodes were removed during their upgrade.
+    let mut vanished_nodes = HashSet::new();
+    let mut node_filter = NodeFilter::new(opts.include_nodes(), opts.exclude_nodes());
+    // Nodes in distinct zones may be upgraded concurrently, if enabled.
+    let mut zone_waves = ZoneWaves::new(opts.max_parallel_zones());
+    // The control-plane's readiness is shared by the nodes which are upgraded in quick succession.
+    let control_plane_cache = ControlPlaneReadyCache::new(opts.control_plane_ready_ttl());
+    // This tells a data-plane with nothing to upgrade apart from one which has been upgraded.
+    let mut first_listing = true;
+    // The inter-node delay is only waited for after a node has been upgraded.
+    let mut node_upgraded = false;
 
-/// Issue the node drain command on the node.
-async fn drain_storage_node(node_id: &str, rest_client: &RestClientSet) -> Result<()> {
-    let drain_label_for_upgrade: String = DRAIN_FOR_UPGRADE.to_string();
-    let sleep_duration = Duration::from_secs(5_u64);
     loop {
-        let storage_node =
-            rest_client
-                .nodes_api()
-                .get_node(node_id)
+        let mut initial_io_engine_pod_list: Vec<Pod> =
+            list_all_pods(k8s_client, &io_engine_listparams)
                 .await
-                .context(GetStorageNode {
-                    node_id: node_id.to_string(),
+                .context(ListPodsWithLabel {
+                    label: yet_to_upgrade_io_engine_label_selector.clone(),
+                    namespace: namespace.clone(),
                 })?;
 
-        match storage_node
-            .into_body()
-            .spec
-            .ok_or(
-                EmptyStorageNodeSpec {
-                    node_id: node_id.to_string(),
-                }
-                .build(),
-            )?
-            .cordondrainstate
-        {
-            Some(CordonDrainState::drainingstate(drain_state))
-                if drain_state.drainlabels.contains(&drain_label_for_upgrade) =>
-            {
-                info!(node.id = %node_id, "Waiting for {PRODUCT} Node drain to complete");
-                // Wait for node drain to complete.
-                tokio::time::sleep(sleep_duration).await;
-            }
-            Some(CordonDrainState::drainedstate(drain_state))
-                if drain_state.drainlabels.contains(&drain_label_for_upgrade) =>
-            {
-                info!(node.id = %node_id, "Drain completed for {PRODUCT} Node");
-                return Ok(());
-            }
-            _ => {
-                rest_client
-                    .nodes_api()
-                    .put_node_drain(node_id, DRAIN_FOR_UPGRADE)
-                    .await
-                    .context(DrainStorageNode {
-                        node_id: node_id.to_string(),
-                    })?;
+        // Report all of the Pods which can't be upgraded before any node is drained.
+        validate_pods(&initial_io_engine_pod_list, namespace.as_str())?;
 
-                info!(node.id = %node_id, "Drain started for {PRODUCT} Node");
+        // The Pods on the nodes which are filtered out are left as they are.
+        node_filter.retain(&mut initial_io_engine_pod_list);
+
+        // Infinite loop exit.
+        if initial_io_engine_pod_list.is_empty() {
+            if first_listing {
+                info!("No io-engine Pods found on the source version, nothing to upgrade");
             }
+            break;
         }
-    }
-}
+        first_listing = false;
 
-/// Validate if io-engine DaemonSet Pod is running.
-async fn data_plane_pod_is_running(
-    node: &str,
-    namespace: String,
-    upgrade_to_version: &String,
-    k8s_client: &KubeClientSet,
-) -> Result<bool> {
-    let node_name_pod_field = format!("spec.nodeName={node}");
-    let pod_label = format!("{IO_ENGINE_LABEL},{CHART_VERSION_LABEL_KEY}={upgrade_to_version}");
-    let io_engine_listparam = ListParams::default()
-        .labels(pod_label.as_str())
-        .fields(node_name_pod_field.as_str());
+        node_order.sort(&mut initial_io_engine_pod_list);
 
-    let pod_list: ObjectList<Pod> = k8s_client
-        .pods_api()
-        .list(&io_engine_listparam)
-        .await
-        .context(ListPodsWithLabelAndField {
-            label: pod_label,
-            field: node_name_pod_field,
-            namespace: namespace.clone(),
-        })?;
+        let mut selected_pods = select_pods_per_node(
+            &initial_io_engine_pod_list,
+            opts.io_engine_pod_selection(),
+            opts.io_engine_daemonset().as_deref(),
+            namespace.as_str(),
+        )?;
 
-    if pod_list.items.is_empty() {
-        return Ok(false);
-    }
+        // The nodes which failed to upgrade aren't retried, and the nodes which are upgraded
+        // already aren't checked again.
+        selected_pods.retain(|node_pods| {
+            !node_failures.has_failed(node_pods.node_name.as_str())
+                && !already_upgraded_nodes.contains(&node_pods.node_name)
+                && !dry_run_nodes.contains(&node_pods.node_name)
+                && !externally_cordoned_nodes.contains(&node_pods.node_name)
+                && !pinned_nodes.contains(&node_pods.node_name)
+                && !vanished_nodes.contains(&node_pods.node_name)
+        });
 
-    if pod_list.items.len() != 1 {
-        return TooManyIoEnginePods { node_name: node }.fail();
-    }
+        // If an io-engine DaemonSet is selected, the Pods left over aren't owned by it, and they
+        // are left as they are. The Pods on the nodes which failed to upgrade, or which are
+        // upgraded already, are left as well.
+        if selected_pods.is_empty() {
+            break;
+        }
 
-    Ok(all_pods_are_ready(pod_list))
-}
+        let mut remaining = selected_pods;
+        while !remaining.is_empty() {
+            // No more nodes are upgraded once a shutdown is requested, once the deadline has
+            // passed, or once the upgrade Lease is lost.
+            ctx.stop_if_interrupted()?;
 
-async fn verify_control_plane_is_running(
-    namespace: String,
-    k8s_client: &KubeClientSet,
-    upgrade_to_version: &String,
-) -> Result<()> {
-    let duration = Duration::from_secs(3_u64);
-    while !control_plane_is_running(namespace.clone(), k8s_client, upgrade_to_version).await? {
-        tokio::time::sleep(duration).await;
-    }
+            let mut wave = Vec::new();
+            for node_pods in zone_waves
+                .next_wave(&mut remaining, k8s_client, rest_client)
+                .await?
+            {
+                // An interrupted upgrade may have upgraded the node already, e.g. if the old Pod
+                // is still terminating. The node isn't drained again.
+                let node_name = &node_pods.node_name;
+                if node_already_upgraded(
+                    node_name,
+                    upgrade_to_version,
+                    &version_label_key,
+                    k8s_client,
+                    &namespace,
+                )
+                .await?
+                {
+                    info!(
+                        node.name = %node_name,
+                        "Skipping node, its data-plane Pod is upgraded already"
+                    );
+                    ctx.report.record_warning(
+                        UpgradeWarningKind::NodeSkipped,
+                        Some(node_name),
+                        "The node's data-plane Pod is upgraded already".to_string(),
+                    );
+                    ctx.report.record(
+                        node_name,
+                        &node_pods.pods,
+                        Duration::ZERO,
+                        NodeUpgradeStatus::Skipped,
+                        None,
+                        None,
+                    );
+                    already_upgraded_nodes.insert(node_name.clone());
+                    continue;
+                }
 
-    Ok(())
-}
+                // A node which is cordoned for maintenance may be left as it is.
+                if !may_upgrade_cordoned_node(
+                    node_name,
+                    opts.external_cordon_policy(),
+                    &drain_labels,
+                    rest_client,
+                )
+                .await?
+                {
+                    ctx.report.record_warning(
+                        UpgradeWarningKind::NodeSkipped,
+                        Some(node_name),
+                        "The node is cordoned by something other than the upgrade".to_string(),
+                    );
+                    ctx.report.record(
+                        node_name,
+                        &node_pods.pods,
+                        Duration::ZERO,
+                        NodeUpgradeStatus::Skipped,
+                        None,
+                        None,
+                    );
+                    externally_cordoned_nodes.insert(node_name.clone());
+                    continue;
+                }
 
-/// Validate if control-plane pods are running -- etcd, agent-core, api-rest.
-async fn control_plane_is_running(
-    namespace: String,
-    k8s_client: &KubeClientSet,
-    upgrade_to_version: &String,
+                // A node which is pinned to its version by an operator is left as it is.
+                if has_upgrade_skip_annotation(node_name, k8s_client).await? {
+                    info!(
+                        node.name = %node_name,
+                        annotation = UPGRADE_SKIP_ANNOTATION_KEY,
+                        "Skipping node, it is annotated to be skipped by upgrades"
+                    );
+                    ctx.report.record_warning(
+                        UpgradeWarningKind::NodeSkipped,
+                        Some(node_name),
+                        format!("The node is annotated with {UPGRADE_SKIP_ANNOTATION_KEY}"),
+                    );
+                    ctx.report.record(
+                        node_name,
+                        &node_pods.pods,
+                        Duration::ZERO,
+                        NodeUpgradeStatus::Skipped,
+                        None,
+                        None,
+                    );
+                    pinned_nodes.insert(node_name.clone());
+                    continue;
+                }
+                wave.push(node_pods);
+            }
+            if wave.is_empty() {
+                continue;
+            }
+
+            // The replicas on the nodes which were just upgraded are given time to re-sync.
+            if node_upgraded && !opts.inter_node_delay().is_zero() {
+                let delay = with_jitter(opts.inter_node_delay());
+                info!(?delay, "Pausing before upgrading the next nodes");
+                select! {
+                    _ = tokio::time::sleep(delay) => {}
+                    _ = ctx.cancellation.cancelled() => {}
+                }
+                ctx.stop_if_interrupted()?;
+            }
+
+            // The upgrade may only be paused in between nodes.
+            ctx.pause
+                .wait_while_paused(ctx.cancellation, &ctx.run.forward_progress)
+                .await;
+            ctx.stop_if_interrupted()?;
+
+            // A node is only upgraded within the maintenance window.
+            if let Some(window) = opts.maintenance_window().filter(|window| !window.is_open()) {
+                for node_pods in wave.iter() {
+                    ctx.run.record_phase(
+                        "maintenance-window-wait",
+                        Some(node_pods.node_name.as_str()),
+                    );
+                }
+                select! {
+                    _ = window.wait_until_open(&ctx.run.forward_progress) => {}
+                    _ = ctx.cancellation.cancelled() => {}
+                }
+                ctx.stop_if_interrupted()?;
+                deferred_nodes += wave.len();
+            }
+
+            let timeouts = timeouts_source.refresh(k8s_client).await?;
+
+            // The control-plane is rolled out before the nodes wait for it to be Ready. A
+            // restarted control-plane isn't Ready, whatever the cache says.
+            if opts.upgrade_control_plane()
+                && !dry_run
+                && roll_out_control_plane(
+                    namespace.clone(),
+                    k8s_client,
+                    upgrade_to_version,
+                    &version_label_key,
+                )
+                .await?
+            {
+                control_plane_cache.invalidate();
+            }
+
+            // The nodes are looked up before their upgrades start, as the node cache can't be
+            // shared by the concurrent upgrades. The storage Node's id may differ from the
+            // node's name.
+            let mut node_ids = Vec::with_capacity(wave.len());
+            for node_pods in wave.iter() {
+                let node_name = node_pods.node_name.as_str();
+                node_ids
+                    .push(storage_node_id(node_name, node_cache, k8s_client, rest_client).await?);
+                ctx.run.record_phase("node-upgrade", Some(node_name));
+                ctx.events.send(UpgradeEvent::NodeStarted {
+                    node_name: node_name.to_string(),
+                });
+            }
+
+            let shared_ctx: &UpgradeContext<'_> = ctx;
+            let results = join_all(wave.iter().zip(&node_ids).map(|(node_pods, node_id)| {
+                upgrade_single_node(
+                    shared_ctx,
+                    node_pods,
+                    node_id.as_deref(),
+                    &timeouts,
+                    &control_plane_cache,
+                )
+            }))
+            .await;
+
+            for ((node_pods, node_id), (node_upgrade_duration, result)) in
+                wave.iter().zip(&node_ids).zip(results)
+            {
+                let node_name = node_pods.node_name.as_str();
+                let status = match (&result, dry_run) {
+                    (Ok(_), true) => NodeUpgradeStatus::DryRun,
+                    (Ok(_), false) => NodeUpgradeStatus::Upgraded,
+                    (Err(Error::StorageNodeVanished { .. }), _) => NodeUpgradeStatus::Skipped,
+                    (Err(_), _) => NodeUpgradeStatus::Failed,
+                };
+                ctx.report.record(
+                    node_name,
+                    &node_pods.pods,
+                    node_upgrade_duration,
+                    status,
+                    result.as_ref().ok().map(NodePhaseDurations::timings),
+                    result.as_ref().err().map(ToString::to_string),
+                );
+                ctx.events.send(match &result {
+                    Err(error) if status == NodeUpgradeStatus::Failed => UpgradeEvent::NodeFailed {
+                        node_name: node_name.to_string(),
+                        error: error.to_string(),
+                    },
+                    _ => UpgradeEvent::NodeComplete {
+                        node_name: node_name.to_string(),
+                        status,
+                    },
+                });
+                match result {
+                    Ok(_) if dry_run => {
+                        dry_run_nodes.insert(node_name.to_string());
+                    }
+                    Ok(phases) => {
+                        ctx.run
+                            .record_phase("node-upgrade-complete", Some(node_name));
+                        ctx.run.progress.record_node_waits(
+                            phases.rebuild.grace_period + phases.rebuild.polling,
+                            phases.drain,
+                        );
+                        ctx.report
+                            .record_node_duration(node_name, node_upgrade_duration, phases);
+                        node_failures.record_success();
+                        ctx.run.progress.node_upgraded();
+                    }
+                    // A node which is decommissioned mid-upgrade has nothing left to upgrade.
+                    Err(Error::StorageNodeVanished { .. }) => {
+                        warn!(
+                            node.name = %node_name,
+                            "Node was removed from the cluster during its upgrade, skipping it"
+                        );
+                        ctx.report.record_warning(
+                            UpgradeWarningKind::NodeSkipped,
+                            Some(node_name),
+                            "The node was removed from the cluster during its upgrade".to_string(),
+                        );
+                        vanished_nodes.insert(node_name.to_string());
+                    }
+                    Err(error) if opts.continue_on_error() => {
+                        error!(
+                            %error,
+                            node.name = %node_name,
+                            "Failed to upgrade node, continuing with the rest of the nodes"
+                        );
+                        ctx.report.record_warning(
+                            UpgradeWarningKind::NodeFailed,
+                            Some(node_name),
+                            error.to_string(),
+                        );
+                        // The failed node is not left drained for upgrade. A node without a storage
+                        // Node id wasn't drained.
+                        if let Some(node_id) = node_id.as_deref() {
+                            if let Err(uncordon_error) = uncordon_node(
+                                node_id,
+                                &drain_labels,
+                                rest_client,
+                                &ctx.drain_decision_log,
+                            )
+                            .await
+                            {
+                                error!(
+                                    error = %uncordon_error,
+                                    node.name = %node_name,
+                                    node.id = %node_id,
+                                    "Failed to uncordon node"
+                                );
+                                ctx.report.record_warning(
+                                    UpgradeWarningKind::UncordonFailed,
+                                    Some(node_name),
+                                    uncordon_error.to_string(),
+                                );
+                            }
+                        }
+                        node_failures.record_failure(node_name, &error)?;
+                    }
+                    Err(error) => return Err(error),
+                }
+            }
+
+            if !remaining.is_empty() {
+                if let Some(eta) = ctx.report.estimated_time_remaining(remaining.len()) {
+                    info!(
+                        nodes.remaining = remaining.len(),
+                        "Estimated time remaining: {}",
+                        humantime::format_duration(Duration::from_secs(eta.as_secs()))
+                    );
+                }
+            }
+
+            if let Some(progress_config_map) = progress_config_map {
+                progress_config_map.update().await;
+            }
+            node_upgraded = !dry_run;
+        }
+
+        info!("Checking to see if new {PRODUCT} Nodes have been added to the cluster, which require upgrade");
+    }
+
+    if dry_run {
+        info!(
+            nodes = dry_run_nodes.len(),
+            "Dry run: Nodes would be upgraded"
+        );
+    } else {
+        ctx.report.node_durations().log_summary();
+    }
+
+    if deferred_nodes > 0 {
+        info!(
+            nodes = deferred_nodes,
+            "Nodes were deferred to a later maintenance window"
+        );
+    }
+
+    // The Pods which are left as they are on purpose aren't expected to be upgraded.
+    if !dry_run && upgrade_from_version.ne(upgrade_to_version.as_str()) {
+        let io_engine_daemonset = opts.io_engine_daemonset();
+        verify_all_upgraded(
+            namespace,
+            upgrade_from_version,
+            &version_label_key,
+            k8s_client,
+            |pod| {
+                let node_name = pod
+                    .spec
+                    .as_ref()
+                    .and_then(|spec| spec.node_name.as_deref())
+                    .unwrap_or_default();
+                node_filter.allows(pod)
+                    && !node_failures.has_failed(node_name)
+                    && !externally_cordoned_nodes.contains(node_name)
+                    && !pinned_nodes.contains(node_name)
+                    && !vanished_nodes.contains(node_name)
+                    && io_engine_daemonset
+                        .as_deref()
+                        .map_or(true, |daemonset| is_owned_by_daemonset(pod, daemonset))
+            },
+        )
+        .await?;
+    }
+
+    node_failures.finish()
+}
+
+/// Validate that no io-engine Pods are left on the source version, e.g. a Pod which was created
+/// after the Pods were listed. Only the Pods for which 'expected' returns true are considered.
+async fn verify_all_upgraded<F>(
+    namespace: String,
+    upgrade_from_version: &str,
+    version_label_key: &str,
+    k8s_client: &KubeClientSet,
+    expected: F,
+) -> Result<()>
+where
+    F: Fn(&Pod) -> bool,
+{
+    let pod_label = format!("{IO_ENGINE_LABEL},{version_label_key}={upgrade_from_version}");
+    let pods: Vec<Pod> = list_all_pods(
+        k8s_client,
+        &ListParams::default().labels(pod_label.as_str()),
+    )
+    .await
+    .context(ListPodsWithLabel {
+        label: pod_label,
+        namespace,
+    })?;
+
+    let pods: Vec<String> = pods
+        .iter()
+        .filter(|pod| expected(pod))
+        .map(|pod| {
+            let node_name = pod
+                .spec
+                .as_ref()
+                .and_then(|spec| spec.node_name.as_deref())
+                .unwrap_or_default();
+            format!("{} ({node_name})", pod.name_any())
+        })
+        .collect();
+
+    ensure!(
+        pods.is_empty(),
+        NodesNotUpgraded {
+            version: upgrade_from_version,
+            pods,
+        }
+    );
+    Ok(())
+}
+
+/// Upgrade the data-plane pods on one node, and time it. This returns how long the node's upgrade
+/// took, along with its result. A node which isn't mapped to a storage Node, i.e. without a
+/// 'node_id', fails its upgrade.
+async fn upgrade_single_node(
+    ctx: &UpgradeContext<'_>,
+    node_pods: &NodePods,
+    node_id: Option<&str>,
+    timeouts: &PhaseTimeouts,
+    control_plane_cache: &ControlPlaneReadyCache,
+) -> (Duration, Result<NodePhaseDurations>) {
+    let opts = ctx.opts;
+    let NodePods { node_name, pods } = node_pods;
+    let start = Instant::now();
+
+    // The drain and the rebuild timeouts may be proportional to the size of the node's replicas.
+    let mut timeouts = timeouts.clone();
+    if let (Some(throughput), Some(node_id)) = (opts.rebuild_throughput(), node_id) {
+        if let Some(budget) =
+            size_based_rebuild_timeout(node_id, &ctx.rest_client, throughput).await
+        {
+            let budget = budget.max(opts.rebuild_timeout_floor());
+            info!(
+                node.name = %node_name,
+                timeout = ?budget,
+                "Using the size-based drain and rebuild timeout for the node"
+            );
+            timeouts.drain = Some(budget);
+            timeouts.rebuild = Some(budget);
+        }
+    }
+
+    let result = match node_id {
+        Some(node_id) => {
+            upgrade_node(
+                ctx,
+                node_name,
+                node_id,
+                pods,
+                &timeouts,
+                control_plane_cache,
+            )
+            .await
+        }
+        None => NodeIdMappingFailed {
+            node_name: node_name.to_string(),
+        }
+        .fail(),
+    };
+    let elapsed = start.elapsed();
+
+    if result.is_ok() && !ctx.dry_run {
+        info!(node.name = %node_name, ?elapsed, "Node {node_name} upgraded in {elapsed:?}");
+    }
+    (elapsed, result)
+}
+
+/// Upgrade the data-plane pods on one node. This drains the node, restarts its io-engine pods and
+/// uncordons it, along with the checks before and after. For a dry run, only the checks before
+/// the drain are run. The node is known to the storage API by 'node_id', which is usually its
+/// name.
+async fn upgrade_node(
+    ctx: &UpgradeContext<'_>,
+    node_name: &str,
+    node_id: &str,
+    pods: &[Pod],
+    timeouts: &PhaseTimeouts,
+    control_plane_cache: &ControlPlaneReadyCache,
+) -> Result<NodePhaseDurations> {
+    let opts = ctx.opts;
+    let dry_run = ctx.dry_run;
+    let k8s_client = &ctx.k8s_client;
+    let rest_client = &ctx.rest_client;
+    let upgrade_to_version = &ctx.upgrade_to_version;
+    let namespace = opts.namespace();
+    let drain_labels = opts.drain_labels();
+    let version_label_key = opts.chart_version_label_key();
+    let mut phases = NodePhaseDurations::default();
+    let control_plane_components = ControlPlaneComponents::from_opts(opts);
+
+    // Validate the control plane pod is up and running before we start.
+    let control_plane_wait_start = Instant::now();
+    let control_plane_wait = ControlPlaneWait {
+        timeout: timeouts.control_plane,
+        strict: opts.strict(),
+        simulated_phase_duration: opts.simulated_phase_duration(),
+    };
+    verify_control_plane_is_running(
+        namespace.clone(),
+        k8s_client,
+        upgrade_to_version,
+        &control_plane_wait,
+        control_plane_cache,
+        &control_plane_components,
+    )
+    .await?;
+
+    if let Some(endpoint) = opts.etcd_health_endpoint() {
+        verify_etcd_health(endpoint.as_str()).await?;
+    }
+
+    if opts.verify_volume_scheduling() {
+        verify_volume_scheduling(rest_client).await?;
+    }
+    phases.control_plane_wait = control_plane_wait_start.elapsed();
+
+    // The node's volumes may have no healthy replica elsewhere if one of its pools is offline.
+    if opts.allow_offline_pools() {
+        warn!(
+            node.name = %node_name,
+            "Not checking for offline pools, the node's volumes may become unavailable while its \
+            io-engine restarts"
+        );
+    } else {
+        verify_no_offline_pools(node_id, rest_client).await?;
+    }
+
+    let pod_names: Vec<String> = pods.iter().map(|pod| pod.name_any()).collect();
+    info!(
+        pod.names = ?pod_names,
+        node.name = %node_name,
+        "Starting upgrade for the data-plane pods"
+    );
+
+    // There may be more than one io-engine DaemonSet covering the node, so the replacement pods
+    // are told apart by the DaemonSets which own them.
+    let daemonsets = owner_daemonsets(node_name, pods)?;
+
+    // Wait for any rebuild to complete
+    if opts.drain_mode().waits_for_rebuilds() {
+        ctx.run.record_phase("rebuild-wait", Some(node_name));
+        phases.rebuild = wait_for_rebuild(ctx, node_id, timeouts.rebuild).await?;
+        emit_pod_events(
+            NodeEventReason::RebuildComplete,
+            "No volume rebuilds in progress, the node may be drained",
+            pods,
+            k8s_client,
+            &ctx.report,
+        )
+        .await;
+        ctx.events.send(UpgradeEvent::RebuildComplete {
+            node_name: node_name.to_string(),
+        });
+    } else {
+        warn!(
+            node.name = %node_name,
+            "Not waiting for volume rebuilds to complete, the node's volumes may have reduced \
+            redundancy while its io-engine restarts"
+        );
+    }
+
+    if dry_run {
+        info!(
+            node.name = %node_name,
+            pod.names = ?pod_names,
+            drain_mode = ?opts.drain_mode(),
+            "Dry run: would drain the node, delete its data-plane pods, wait for their \
+            replacements to be ready and uncordon the node"
+        );
+        return Ok(phases);
+    }
+
+    // Record the volumes with replicas on this node, to verify their replica count
+    // after the upgrade.
+    let node_volumes = if opts.verify_replica_count() {
+        volumes_with_replica_on_node(rest_client, node_id).await?
+    } else {
+        Vec::new()
+    };
+
+    // Record the volumes on this node, to tell if it needs to be drained, and to verify their
+    // attachments and their accessibility after the upgrade.
+    let hosted_volumes = volumes_hosted_on_node(rest_client, node_id).await?;
+
+    // Record the monitored node labels, to verify them after the upgrade.
+    let node_labels =
+        monitored_node_labels(node_name, &opts.monitored_node_labels(), k8s_client).await?;
+
+    // The node is drained, its data-plane pods are restarted, and it is uncordoned.
+    let restart_node = async {
+        if let Some(min_healthy_nodes) = opts.min_healthy_nodes() {
+            wait_for_min_healthy_nodes(
+                node_name,
+                namespace.clone(),
+                min_healthy_nodes,
+                k8s_client,
+                opts.min_healthy_nodes_timeout(),
+            )
+            .await?;
+        }
+
+        // This uncordons the node if any of the steps after its drain fails.
+        let mut cordon_guard = None;
+
+        // A node which hosts no volume replicas or targets has nothing to drain, its
+        // data-plane pods may be restarted right away.
+        if hosted_volumes.is_empty() {
+            info!(
+                node.name = %node_name,
+                "Skipping the drain, as the node has no volume replicas or targets"
+            );
+        } else {
+            // A snapshot whose source volume's replica goes away mid-way may fail.
+            if opts.wait_for_snapshots() {
+                wait_for_snapshots_to_complete(
+                    node_id,
+                    rest_client,
+                    Some(opts.snapshot_timeout()),
+                    opts.wait_log_verbosity(),
+                )
+                .await?;
+            }
+
+            // The node's cordon expires if this upgrade-job dies before uncordoning it.
+            if let Some(cordon_ttl) = ctx.cordon_ttl.as_ref() {
+                cordon_ttl.arm(node_name).await?;
+            }
+
+            // Issue node drain command
+            ctx.run.record_phase("drain", Some(node_name));
+            emit_pod_events(
+                NodeEventReason::DrainStarted,
+                "Draining the node for the data-plane upgrade",
+                pods,
+                k8s_client,
+                &ctx.report,
+            )
+            .await;
+            let drain_start = Instant::now();
+            let drained = match opts.drain_mode() {
+                DrainMode::Full => {
+                    drain_storage_node(
+                        node_id,
+                        &drain_labels,
+                        rest_client,
+                        timeouts.drain,
+                        &ctx.drain_decision_log,
+                        opts.simulated_phase_duration(),
+                        opts.wait_log_verbosity(),
+                    )
+                    .await
+                }
+                DrainMode::Soft | DrainMode::CordonOnly => {
+                    soft_drain_storage_node(node_id, &drain_labels, rest_client).await
+                }
+            };
+            if let Err(error) = drained {
+                if let Some(cordon_ttl) = ctx.cordon_ttl.as_ref() {
+                    cordon_ttl.release(node_name, false).await;
+                }
+                return Err(error);
+            }
+            phases.drain = drain_start.elapsed();
+            cordon_guard = Some(CordonGuard::new(
+                node_id,
+                &drain_labels,
+                rest_client,
+                &ctx.report,
+                &ctx.drain_decision_log,
+            ));
+            ctx.events.send(UpgradeEvent::DrainComplete {
+                node_name: node_name.to_string(),
+            });
+        }
+
+        // The node is uncordoned by the cordon guard if any of the steps after its drain fails.
+        let restart_pods = async {
+            // The DaemonSets' status after the replacement pods are ready, if it is awaited on.
+            let expected_daemonset_status = if opts.readiness_strategy()
+                == ReadinessStrategy::DaemonsetStatus
+            {
+                let status = daemonset_status(namespace.clone(), k8s_client).await?;
+                Some(DaemonSetStatusCounts {
+                    updated_number_scheduled: status.updated_number_scheduled + pods.len() as i32,
+                    number_ready: status.number_ready,
+                })
+            } else {
+                None
+            };
+
+            // A lingering terminating pod alongside its replacement could have the wrong pod
+            // deleted.
+            verify_single_io_engine_pod_per_daemonset(node_name, namespace.clone(), k8s_client)
+                .await?;
+
+            // The replacement pods are told apart from the deleted ones by their UIDs.
+            let deleted_pod_uids: HashSet<String> =
+                pods.iter().filter_map(|pod| pod.uid()).collect();
+
+            // restart the data plane pods
+            ctx.run.record_phase("pod-delete", Some(node_name));
+            let pod_restart_start = Instant::now();
+            for pod in pods {
+                delete_data_plane_pod(ctx, node_name, pod, timeouts.readiness).await?;
+            }
+            emit_pod_events(
+                NodeEventReason::PodDeleted,
+                "Deleted the io-engine Pod to restart it",
+                pods,
+                k8s_client,
+                &ctx.report,
+            )
+            .await;
+            ctx.events.send(UpgradeEvent::PodDeleted {
+                node_name: node_name.to_string(),
+                pod_names: pod_names.clone(),
+            });
+
+            // wait for the DaemonSet controller to schedule the replacement pods
+            if let Some(expected) = expected_daemonset_status {
+                wait_for_daemonset_status(
+                    node_name,
+                    namespace.clone(),
+                    DaemonSetStatusCounts {
+                        number_ready: 0,
+                        ..expected
+                    },
+                    k8s_client,
+                    timeouts.readiness,
+                    opts.io_engine_log_lines(),
+                )
+                .await?;
+            }
+            phases.pod_restart = pod_restart_start.elapsed();
+
+            // validate the new pod is up and running
+            ctx.run.record_phase("pod-readiness", Some(node_name));
+            let readiness_start = Instant::now();
+            let replacement_pods = ReplacementPods {
+                node_name,
+                namespace: namespace.clone(),
+                upgrade_to_version,
+                version_label_key: &version_label_key,
+                daemonsets: &daemonsets,
+                deleted_pod_uids: &deleted_pod_uids,
+            };
+            if let Err(error) = verify_data_plane_pod_is_running(
+                &replacement_pods,
+                k8s_client,
+                timeouts.readiness,
+                &DataPlanePodReadiness::from_opts(opts),
+            )
+            .await
+            {
+                // The node isn't left cordoned, the cordon guard uncordons it, so that a re-run
+                // of the upgrade may retry it.
+                ctx.run
+                    .record_phase("pod-readiness-failed", Some(node_name));
+                return Err(error);
+            }
+
+            match opts.readiness_strategy() {
+                ReadinessStrategy::PodReady => {}
+                ReadinessStrategy::NodeOnline => {
+                    wait_for_storage_node_online(
+                        node_id,
+                        rest_client,
+                        timeouts.readiness,
+                        opts.wait_log_verbosity(),
+                    )
+                    .await?
+                }
+                ReadinessStrategy::DaemonsetStatus => {
+                    if let Some(expected) = expected_daemonset_status {
+                        wait_for_daemonset_status(
+                            node_name,
+                            namespace.clone(),
+                            expected,
+                            k8s_client,
+                            timeouts.readiness,
+                            opts.io_engine_log_lines(),
+                        )
+                        .await?
+                    }
+                }
+            }
+            phases.readiness = readiness_start.elapsed();
+            emit_pod_events(
+                NodeEventReason::PodReady,
+                "The replacement io-engine Pod is ready",
+                pods,
+                k8s_client,
+                &ctx.report,
+            )
+            .await;
+            ctx.events.send(UpgradeEvent::PodReady {
+                node_name: node_name.to_string(),
+            });
+            Ok::<(), Error>(())
+        };
+        let result = match restart_pods.await {
+            Ok(()) => {
+                // Uncordon the drained node
+                ctx.run.record_phase("uncordon", Some(node_name));
+                uncordon_node(node_id, &drain_labels, rest_client, &ctx.drain_decision_log).await
+            }
+            Err(error) => Err(error),
+        };
+        let cordoned = cordon_guard.is_some();
+        if let Some(cordon_guard) = cordon_guard {
+            match result {
+                Ok(()) => cordon_guard.disarm(),
+                Err(_) => cordon_guard.release().await,
+            }
+        }
+        if let Some(cordon_ttl) = ctx.cordon_ttl.as_ref().filter(|_| cordoned) {
+            cordon_ttl.release(node_name, result.is_ok()).await;
+        }
+        result?;
+        emit_pod_events(
+            NodeEventReason::NodeUncordoned,
+            "Uncordoned the node after the data-plane upgrade",
+            pods,
+            k8s_client,
+            &ctx.report,
+        )
+        .await;
+        Ok::<(), Error>(())
+    };
+
+    if opts.monitor_control_plane() {
+        select! {
+            result = restart_node => result?,
+            error = monitor_control_plane(
+                node_name,
+                namespace.clone(),
+                k8s_client,
+                upgrade_to_version,
+                &control_plane_components,
+            ) => {
+                warn!(
+                    node.name = %node_name,
+                    "Control-plane became unhealthy, abandoning the node's upgrade"
+                );
+                if let Err(uncordon_error) = uncordon_node(
+                    node_id,
+                    &drain_labels,
+                    rest_client,
+                    &ctx.drain_decision_log,
+                )
+                .await
+                {
+                    error!(
+                        error = %uncordon_error,
+                        node.name = %node_name,
+                        "Failed to uncordon node"
+                    );
+                    ctx.report.record_warning(
+                        UpgradeWarningKind::UncordonFailed,
+                        Some(node_name),
+                        uncordon_error.to_string(),
+                    );
+                    if let Some(cordon_ttl) = ctx.cordon_ttl.as_ref() {
+                        cordon_ttl.release(node_name, false).await;
+                    }
+                } else if let Some(cordon_ttl) = ctx.cordon_ttl.as_ref() {
+                    cordon_ttl.release(node_name, true).await;
+                }
+                return Err(error);
+            }
+        }
+    } else {
+        restart_node.await?;
+    }
+
+    // Validate that the volumes on the node may be mounted and unmounted again.
+    if opts.verify_csi_node_plugin() {
+        let csi_node_label = opts.csi_node_label();
+        let csi_pods = CsiNodePluginPods {
+            label_selector: csi_node_label.as_str(),
+            version_label_key: &version_label_key,
+            upgrade_to_version,
+        };
+        wait_for_csi_node_plugin(
+            node_name,
+            namespace.clone(),
+            &csi_pods,
+            k8s_client,
+            timeouts.readiness,
+            opts.wait_log_verbosity(),
+        )
+        .await?;
+    }
+
+    // Validate that the node's volumes haven't faulted over its io-engine restart.
+    if opts.verify_volumes_accessible() {
+        verify_volumes_accessible(node_name, &hosted_volumes, rest_client).await?;
+    }
+
+    // Wait for the node's volumes to get back to their configured replica count. Their missing
+    // replicas are re-created first, and then rebuilt.
+    if opts.verify_replica_count() {
+        wait_for_replica_relocation(
+            node_name,
+            &node_volumes,
+            rest_client,
+            timeouts.rebuild,
+            opts.wait_log_verbosity(),
+        )
+        .await?;
+        wait_for_replica_count(
+            node_name,
+            &node_volumes,
+            rest_client,
+            timeouts.rebuild,
+            opts.wait_log_verbosity(),
+        )
+        .await?;
+    }
+
+    // Wait for the workloads' volumes on this node to be attached again.
+    if opts.wait_for_volume_attachments() {
+        wait_for_volume_attachments(node_name, &hosted_volumes, k8s_client, timeouts.readiness)
+            .await?;
+    }
+
+    // Validate that the target version label identifies a single Pod template.
+    verify_unique_target_revision(
+        namespace.clone(),
+        upgrade_to_version,
+        &version_label_key,
+        k8s_client,
+        opts.strict(),
+    )
+    .await?;
+
+    // Validate that the monitored node labels haven't changed.
+    verify_node_labels_unchanged(node_name, &node_labels, k8s_client, opts.strict()).await?;
+
+    Ok(phases)
+}
+
+/// Uncordon storage Node. This removes the drain label, along with the extra drain labels which
+/// the Node carries. Each of the Node's observed drain states is recorded in 'decision_log'.
+async fn uncordon_node(
+    node_id: &str,
+    drain_labels: &DrainLabels,
+    rest_client: &RestClientSet,
+    decision_log: &DrainDecisionLog,
+) -> Result<()> {
+    let sleep_duration = Duration::from_secs(1_u64);
+    let start = Instant::now();
+    loop {
+        let storage_node = match rest_client
+            .call_with_retry(GET_NODE, |client| async move {
+                client.nodes_api().get_node(node_id).await
+            })
+            .await
+        {
+            Ok(storage_node) => storage_node,
+            // There's no drain label to remove from a Node which no longer exists.
+            Err(error) if is_not_found(&error) => {
+                decision_log.log(
+                    StateMachine::Uncordon,
+                    node_id,
+                    None,
+                    DrainDecision::NodeVanished,
+                );
+                warn!(node.id = %node_id, "{PRODUCT} Node not found, skipping its uncordon");
+                return Ok(());
+            }
+            Err(error) => {
+                return Err(error).context(GetStorageNode {
+                    node_id: node_id.to_string(),
+                })
+            }
+        };
+
+        let cordon_drain_state = storage_node
+            .into_body()
+            .spec
+            .ok_or(
+                EmptyStorageNodeSpec {
+                    node_id: node_id.to_string(),
+                }
+                .build(),
+            )?
+            .cordondrainstate;
+        let log_decision = |decision| {
+            decision_log.log(
+                StateMachine::Uncordon,
+                node_id,
+                cordon_drain_state.as_ref(),
+                decision,
+            )
+        };
+
+        // The node may still be draining, e.g. if the upgrade is aborted mid-drain, and the
+        // control-plane may reject the uncordon until the drain completes.
+        if let Some(CordonDrainState::drainingstate(drain_state)) = cordon_drain_state.as_ref() {
+            if drain_state
+                .drainlabels
+                .iter()
+                .any(|label| drain_labels.is_upgrade_drain_label(label))
+            {
+                if let Some(timeout) = timeout_exceeded(start, Some(UNCORDON_DRAIN_WAIT)) {
+                    log_decision(DrainDecision::TimedOut);
+                    return UncordonDrainInProgress { node_id, timeout }.fail();
+                }
+                log_decision(DrainDecision::WaitForDrain);
+                warn!(
+                    node.id = %node_id,
+                    "{PRODUCT} Node is still draining, waiting for the drain to complete before \
+                    uncordoning it"
+                );
+                tokio::time::sleep(Duration::from_secs(5_u64)).await;
+                continue;
+            }
+        }
+
+        // The node may be drained, or only cordoned with the upgrade label by a soft drain.
+        let upgrade_labels: Vec<&String> = match cordon_drain_state.as_ref() {
+            Some(CordonDrainState::drainedstate(drain_state)) => drain_state
+                .drainlabels
+                .iter()
+                .filter(|label| drain_labels.is_upgrade_drain_label(label))
+                .collect(),
+            Some(CordonDrainState::cordonedstate(cordon_state)) => cordon_state
+                .cordonlabels
+                .iter()
+                .filter(|label| drain_labels.is_upgrade_drain_label(label))
+                .collect(),
+            _ => Vec::new(),
+        };
+
+        if upgrade_labels.is_empty() {
+            log_decision(DrainDecision::Done);
+            return Ok(());
+        }
+
+        log_decision(DrainDecision::RemoveDrainLabel);
+        for label in upgrade_labels {
+            rest_client
+                .call_with_retry(DELETE_NODE_CORDON, |client| async move {
+                    client.nodes_api().delete_node_cordon(node_id, label).await
+                })
+                .await
+                .context(StorageNodeUncordon {
+                    node_id: node_id.to_string(),
+                })?;
+
+            info!(node.id = %node_id,
+                %label,
+                "Removed drain label from {PRODUCT} Node"
+            );
+        }
+        tokio::time::sleep(sleep_duration).await;
+    }
+}
+
+/// This is held for a storage Node which was drained for upgrade, until the Node is uncordoned.
+/// It is released, which uncordons the Node, if any step after the Node's drain fails, or it is
+/// disarmed once the Node is uncordoned. A guard which is dropped while it is still armed, e.g.
+/// if the Node's upgrade is abandoned, only logs the Node which may be left drained, as the
+/// uncordon can't be awaited in a drop.
+struct CordonGuard<'a> {
+    node_id: String,
+    drain_labels: &'a DrainLabels,
+    rest_client: &'a RestClientSet,
+    /// The report which a failed uncordon is recorded in, as a warning.
+    report: &'a DataPlaneUpgradeReport,
+    /// The decision log which the uncordon's observed drain states are recorded in.
+    decision_log: &'a DrainDecisionLog,
+    armed: bool,
+}
+
+impl<'a> CordonGuard<'a> {
+    /// This is created once the Node is drained.
+    fn new(
+        node_id: &str,
+        drain_labels: &'a DrainLabels,
+        rest_client: &'a RestClientSet,
+        report: &'a DataPlaneUpgradeReport,
+        decision_log: &'a DrainDecisionLog,
+    ) -> Self {
+        Self {
+            node_id: node_id.to_string(),
+            drain_labels,
+            rest_client,
+            report,
+            decision_log,
+            armed: true,
+        }
+    }
+
+    /// The Node is uncordoned already, so the guard is dropped without uncordoning it again.
+    fn disarm(mut self) {
+        self.armed = false;
+    }
+
+    /// This uncordons the Node, as its upgrade stopped after it was drained. An uncordon which
+    /// fails is logged, and recorded as a warning.
+    async fn release(mut self) {
+        self.armed = false;
+        warn!(
+            node.id = %self.node_id,
+            "Uncordoning node, as its upgrade stopped after it was drained"
+        );
+        if let Err(error) = uncordon_node(
+            self.node_id.as_str(),
+            self.drain_labels,
+            self.rest_client,
+            self.decision_log,
+        )
+        .await
+        {
+            error!(%error, node.id = %self.node_id, "Failed to uncordon node");
+            self.report.record_warning(
+                UpgradeWarningKind::UncordonFailed,
+                Some(self.node_id.as_str()),
+                error.to_string(),
+            );
+        }
+    }
+}
+
+impl Drop for CordonGuard<'_> {
+    fn drop(&mut self) {
+        if self.armed {
+            warn!(
+                node.id = %self.node_id,
+                "The node's upgrade was abandoned after it was drained, it may be left drained \
+                for upgrade"
+            );
+        }
+    }
+}
+
+/// Lists the storage Nodes which are left cordoned, draining or drained with any label at the end
+/// of the upgrade, as those are out of service even though they're upgraded. This fails the
+/// upgrade if any of the Nodes still carry the upgrade drain label, which the upgrade should have
+/// removed, and for the other labels only if 'strict' is set.
+async fn report_cordoned_nodes(
+    drain_labels: &DrainLabels,
+    rest_client: &RestClientSet,
+    strict: bool,
+) -> Result<()> {
+    let nodes = rest_client
+        .call(GET_NODES, |client| async move {
+            client.nodes_api().get_nodes(None).await
+        })
+        .await
+        .context(ListStorageNodes)?
+        .into_body();
+
+    let cordoned_nodes: Vec<String> = nodes
+        .iter()
+        .filter_map(|node| {
+            let state = node.spec.as_ref()?.cordondrainstate.as_ref()?;
+            warn!(
+                node.id = %node.id,
+                cordon_drain_state = ?state,
+                "{PRODUCT} Node is left cordoned after the upgrade, it may need to be uncordoned"
+            );
+            Some(node.id.clone())
+        })
+        .collect();
+
+    let upgrade_labelled_nodes: Vec<String> = nodes
+        .iter()
+        .filter(|node| {
+            is_drained_for_upgrade(node, drain_labels)
+                || is_draining_for_upgrade(node, drain_labels)
+        })
+        .map(|node| node.id.clone())
+        .collect();
+    if !upgrade_labelled_nodes.is_empty() {
+        error!(
+            nodes = ?upgrade_labelled_nodes,
+            label = %drain_labels.drain_label(),
+            "{PRODUCT} Nodes still carry the upgrade drain label after the upgrade"
+        );
+        return NodesLeftCordoned {
+            nodes: upgrade_labelled_nodes,
+        }
+        .fail();
+    }
+
+    if !cordoned_nodes.is_empty() && strict {
+        return NodesLeftCordoned {
+            nodes: cordoned_nodes,
+        }
+        .fail();
+    }
+
+    Ok(())
+}
+
+/// Returns true if the storage Node is draining with the upgrade drain label.
+fn is_draining_for_upgrade(node: &Node, drain_labels: &DrainLabels) -> bool {
+    matches!(
+        node.spec.as_ref().and_then(|spec| spec.cordondrainstate.as_ref()),
+        Some(CordonDrainState::drainingstate(drain_state))
+            if drain_state
+                .drainlabels
+                .iter()
+                .any(|label| label.eq(drain_labels.drain_label()))
+    )
+}
+
+/// Returns true if the storage Node is drained with the upgrade drain label, or is cordoned with
+/// it by a soft drain.
+fn is_drained_for_upgrade(node: &Node, drain_labels: &DrainLabels) -> bool {
+    match node
+        .spec
+        .as_ref()
+        .and_then(|spec| spec.cordondrainstate.as_ref())
+    {
+        Some(CordonDrainState::drainedstate(drain_state)) => drain_state
+            .drainlabels
+            .iter()
+            .any(|label| label.eq(drain_labels.drain_label())),
+        Some(CordonDrainState::cordonedstate(cordon_state)) => cordon_state
+            .cordonlabels
+            .iter()
+            .any(|label| label.eq(drain_labels.drain_label())),
+        _ => false,
+    }
+}
+
+/// Validate that there is no more than one non-terminating io-engine Pod on the Node for each
+/// io-engine DaemonSet, i.e. that a Pod's replacement hasn't been created while it's terminating.
+async fn verify_single_io_engine_pod_per_daemonset(
+    node_name: &str,
+    namespace: String,
+    k8s_client: &KubeClientSet,
+) -> Result<()> {
+    let node_name_pod_field = format!("spec.nodeName={node_name}");
+    let list_params = ListParams::default()
+        .labels(IO_ENGINE_LABEL)
+        .fields(node_name_pod_field.as_str());
+    let pods: Vec<Pod> =
+        list_all_pods(k8s_client, &list_params)
+            .await
+            .context(ListPodsWithLabelAndField {
+                label: IO_ENGINE_LABEL.to_string(),
+                field: node_name_pod_field,
+                namespace,
+            })?;
+
+    // The Pods are grouped by the UIDs of the DaemonSets which own them.
+    let mut pods_per_daemonset: BTreeMap<Option<&str>, (&str, Vec<Pod>)> = BTreeMap::new();
+    for pod in pods
+        .iter()
+        .filter(|pod| pod.metadata.deletion_timestamp.is_none())
+    {
+        let owner = owner_daemonset_uid_and_name(pod);
+        pods_per_daemonset
+            .entry(owner.map(|(uid, _)| uid))
+            .or_insert_with(|| (owner.map_or("<none>", |(_, name)| name), Vec::new()))
+            .1
+            .push(pod.clone());
+    }
+
+    if let Some((daemonset, pods)) = pods_per_daemonset
+        .into_values()
+        .find(|(_, pods)| pods.len() > 1)
+    {
+        return TooManyIoEnginePods {
+            node_name,
+            daemonset,
+            pods: pod_names_and_phases(&pods),
+        }
+        .fail();
+    }
+    Ok(())
+}
+
+/// Wait for there to be at least 'min_healthy_nodes' Nodes other than 'node_name' with all of
+/// their io-engine Pods Ready, so that draining 'node_name' doesn't leave fewer healthy Nodes
+/// than that.
+async fn wait_for_min_healthy_nodes(
+    node_name: &str,
+    namespace: String,
+    min_healthy_nodes: usize,
+    k8s_client: &KubeClientSet,
+    timeout: Duration,
+) -> Result<()> {
+    let start = Instant::now();
+    loop {
+        let pods: Vec<Pod> =
+            list_all_pods(k8s_client, &ListParams::default().labels(IO_ENGINE_LABEL))
+                .await
+                .context(ListPodsWithLabel {
+                    label: IO_ENGINE_LABEL.to_string(),
+                    namespace: namespace.clone(),
+                })?;
+
+        // A Node is healthy only if all of its io-engine Pods are Ready.
+        let mut nodes: HashMap<&str, bool> = HashMap::new();
+        for pod in pods.iter() {
+            let Some(pod_node) = pod.spec.as_ref().and_then(|spec| spec.node_name.as_deref())
+            else {
+                continue;
+            };
+            let ready = pod.metadata.deletion_timestamp.is_none() && pod_is_ready(pod);
+            *nodes.entry(pod_node).or_insert(true) &= ready;
+        }
+        let healthy_nodes = nodes
+            .into_iter()
+            .filter(|(pod_node, healthy)| *healthy && *pod_node != node_name)
+            .count();
+
+        if healthy_nodes >= min_healthy_nodes {
+            return Ok(());
+        }
+        if timeout_exceeded(start, Some(timeout)).is_some() {
+            return MinimumHealthyNodesViolated {
+                node_name,
+                healthy_nodes,
+                min_healthy_nodes,
+                timeout,
+            }
+            .fail();
+        }
+        warn!(
+            node.name = %node_name,
+            healthy_nodes,
+            min_healthy_nodes,
+            "Waiting for more Nodes with a Ready io-engine Pod before draining the node"
+        );
+        tokio::time::sleep(Duration::from_secs(10_u64)).await;
+    }
+}
+
+/// Returns the names of the DaemonSets which own the io-engine Pods, keyed by the UIDs of the
+/// DaemonSets. This fails if any of the Pods isn't owned by a DaemonSet.
+fn owner_daemonsets(node_name: &str, pods: &[Pod]) -> Result<BTreeMap<String, String>> {
+    pods.iter()
+        .map(|pod| {
+            owner_daemonset_uid_and_name(pod)
+                .map(|(uid, name)| (uid.to_string(), name.to_string()))
+                .ok_or(
+                    PodNotOwnedByDaemonSet {
+                        pod_name: pod.name_any(),
+                        node_name,
+                    }
+                    .build(),
+                )
+        })
+        .collect()
+}
+
+/// The maximum number of attempts to delete an io-engine Pod, if the deletes fail transiently.
+const MAX_POD_DELETE_ATTEMPTS: u32 = 5;
+
+/// Returns true if the Kubernetes API request may succeed if retried, i.e. if the API server
+/// failed to handle it, throttled it, or couldn't be reached.
+fn is_transient_k8s_error(error: &kube::Error) -> bool {
+    match error {
+        kube::Error::Api(response) => response.code >= 500 || response.code == 429,
+        kube::Error::HyperError(_) | kube::Error::Service(_) => true,
+        _ => false,
+    }
+}
+
+/// Issue delete command on dataplane pods. If PodDisruptionBudgets are respected, the pod is
+/// evicted instead. If a force delete delay is set, the pod is force deleted if it isn't gone
+/// after that long. A foreground delete is waited on for up to 'timeout'.
+async fn delete_data_plane_pod(
+    ctx: &UpgradeContext<'_>,
+    node_name: &str,
+    pod: &Pod,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    let opts = ctx.opts;
+    let k8s_client = &ctx.k8s_client;
+    let grace_period_seconds = opts.io_engine_grace_period();
+    let foreground = opts.foreground_pod_deletion();
+    let force_delete_after = opts.force_delete_after();
+    let eviction_timeout = opts.respect_pdb().then_some(opts.pdb_eviction_timeout());
+
+    // Deleting the io-engine pod
+    let pod_name = pod.name_any();
+    info!(
+        pod.name = pod_name.clone(),
+        node.name = node_name,
+        ?grace_period_seconds,
+        "Deleting the pod"
+    );
+    let delete_params = DeleteParams {
+        grace_period_seconds,
+        propagation_policy: foreground.then_some(PropagationPolicy::Foreground),
+        ..Default::default()
+    };
+    // PodDisruptionBudgets are only respected by the Eviction API.
+    if let Some(eviction_timeout) = eviction_timeout {
+        evict_data_plane_pod(
+            node_name,
+            pod_name.as_str(),
+            &delete_params,
+            eviction_timeout,
+            k8s_client,
+        )
+        .await?;
+    } else {
+        // The delete is retried on transient errors, as it is idempotent.
+        let mut backoff = Duration::from_secs(1_u64);
+        let mut attempt = 1;
+        loop {
+            match k8s_client
+                .api_stats()
+                .timed(
+                    DELETE_POD,
+                    k8s_client
+                        .pods_api()
+                        .delete(pod_name.as_str(), &delete_params),
+                )
+                .await
+            {
+                Ok(_) => {
+                    info!(node.name = %node_name, "Pod delete command issued");
+                    break;
+                }
+                Err(kube::Error::Api(response)) if response.code == 404 => {
+                    info!(pod.name = %pod_name, node.name = %node_name, "Pod is deleted already");
+                    break;
+                }
+                Err(kube::Error::Api(response)) if response.code == 403 => {
+                    return InsufficientPermissions {
+                        operation: "delete",
+                        resource: format!("Pod {pod_name}"),
+                        reason: response.message,
+                    }
+                    .fail();
+                }
+                Err(error)
+                    if attempt < MAX_POD_DELETE_ATTEMPTS && is_transient_k8s_error(&error) =>
+                {
+                    warn!(
+                        %error,
+                        pod.name = %pod_name,
+                        node.name = %node_name,
+                        attempt,
+                        ?backoff,
+                        "Failed to delete the pod, retrying"
+                    );
+                    tokio::time::sleep(backoff).await;
+                    backoff *= 2;
+                    attempt += 1;
+                }
+                Err(error) => {
+                    return Err(error).context(PodDelete {
+                        name: pod_name.clone(),
+                        node: node_name.to_string(),
+                    })
+                }
+            }
+        }
+    }
+
+    if let Some(force_delete_after) = force_delete_after {
+        match wait_for_pod_deletion(node_name, pod, Some(force_delete_after), k8s_client).await {
+            Err(Error::PodDeletionTimeout { .. }) => {
+                force_delete_data_plane_pod(node_name, pod, force_delete_after, k8s_client).await?
+            }
+            result => result?,
+        }
+    }
+
+    if foreground {
+        wait_for_pod_deletion(node_name, pod, timeout, k8s_client).await?;
+    }
+    Ok(())
+}
+
+/// Evict the pod through the Eviction API, so that its PodDisruptionBudgets are respected. The
+/// eviction is retried while a PodDisruptionBudget blocks it, and this fails with
+/// PodEvictionBlocked if it's still blocked after 'timeout'.
+async fn evict_data_plane_pod(
+    node_name: &str,
+    pod_name: &str,
+    delete_params: &DeleteParams,
+    timeout: Duration,
+    k8s_client: &KubeClientSet,
+) -> Result<()> {
+    let evict_params = EvictParams {
+        delete_options: Some(delete_params.clone()),
+        ..Default::default()
+    };
+    let start = Instant::now();
+    loop {
+        match k8s_client
+            .api_stats()
+            .timed(
+                EVICT_POD,
+                k8s_client.pods_api().evict(pod_name, &evict_params),
+            )
+            .await
+        {
+            Ok(_) => {
+                info!(pod.name = %pod_name, node.name = %node_name, "Pod eviction issued");
+                return Ok(());
+            }
+            Err(kube::Error::Api(response)) if response.code == 404 => {
+                info!(pod.name = %pod_name, node.name = %node_name, "Pod is deleted already");
+                return Ok(());
+            }
+            Err(kube::Error::Api(response)) if response.code == 403 => {
+                return InsufficientPermissions {
+                    operation: "evict",
+                    resource: format!("Pod {pod_name}"),
+                    reason: response.message,
+                }
+                .fail();
+            }
+            // A PodDisruptionBudget would be violated by the eviction.
+            Err(kube::Error::Api(response)) if response.code == 429 => {
+                if let Some(timeout) = timeout_exceeded(start, Some(timeout)) {
+                    return PodEvictionBlocked {
+                        name: pod_name,
+                        node: node_name,
+                        timeout,
+                        reason: response.message,
+                    }
+                    .fail();
+                }
+                info!(
+                    pod.name = %pod_name,
+                    node.name = %node_name,
+                    reason = %response.message,
+                    "Pod eviction is blocked by a PodDisruptionBudget, retrying"
+                );
+                tokio::time::sleep(Duration::from_secs(5_u64)).await;
+            }
+            Err(error) => {
+                return Err(error).context(PodEviction {
+                    name: pod_name,
+                    node: node_name,
+                })
+            }
+        }
+    }
+}
+
+/// Force delete the pod, i.e. delete it with a grace period of zero, so that it is removed
+/// without waiting for its containers to stop. The delete is only applied to the same pod, and
+/// not to a replacement pod with the same name.
+async fn force_delete_data_plane_pod(
+    node_name: &str,
+    pod: &Pod,
+    waited: Duration,
+    k8s_client: &KubeClientSet,
+) -> Result<()> {
+    let pod_name = pod.name_any();
+    error!(
+        pod.name = %pod_name,
+        node.name = %node_name,
+        ?waited,
+        "FORCE DELETING io-engine Pod, as it did not terminate, the io-engine may still be \
+        running on the node"
+    );
+    let delete_params = DeleteParams {
+        grace_period_seconds: Some(0),
+        preconditions: Some(Preconditions {
+            uid: pod.uid(),
+            resource_version: None,
+        }),
+        ..Default::default()
+    };
+    match k8s_client
+        .api_stats()
+        .timed(
+            DELETE_POD,
+            k8s_client
+                .pods_api()
+                .delete(pod_name.as_str(), &delete_params),
+        )
+        .await
+    {
+        Ok(_) => {}
+        // The pod is gone already, or the pod by its name is a replacement pod.
+        Err(kube::Error::Api(response)) if matches!(response.code, 404 | 409) => {}
+        Err(kube::Error::Api(response)) if response.code == 403 => {
+            return InsufficientPermissions {
+                operation: "force delete",
+                resource: format!("Pod {pod_name}"),
+                reason: response.message,
+            }
+            .fail();
+        }
+        Err(error) => {
+            return Err(error).context(PodForceDelete {
+                name: pod_name,
+                node: node_name.to_string(),
+            })
+        }
+    }
+    warn!(pod.name = %pod_name, node.name = %node_name, "Force deleted io-engine Pod");
+    Ok(())
+}
+
+/// Wait for the deleted Pod to be gone, i.e. for there to be no Pod by its name, or for the Pod
+/// by its name to be a different Pod, e.g. a replacement Pod with the same name.
+async fn wait_for_pod_deletion(
+    node_name: &str,
+    pod: &Pod,
+    timeout: Option<Duration>,
+    k8s_client: &KubeClientSet,
+) -> Result<()> {
+    let pod_name = pod.name_any();
+    let start = Instant::now();
+    loop {
+        let existing = k8s_client
+            .api_stats()
+            .timed(GET_POD, k8s_client.pods_api().get_opt(pod_name.as_str()))
+            .await
+            .context(GetIoEnginePod {
+                name: pod_name.clone(),
+                node: node_name.to_string(),
+            })?;
+        match existing {
+            Some(existing) if existing.uid().eq(&pod.uid()) => {}
+            _ => break,
+        }
+
+        if let Some(timeout) = timeout_exceeded(start, timeout) {
+            return PodDeletionTimeout {
+                name: pod_name,
+                node: node_name.to_string(),
+                timeout,
+            }
+            .fail();
+        }
+        info!(pod.name = %pod_name, node.name = %node_name, "Waiting for the Pod to be gone");
+        tokio::time::sleep(Duration::from_secs(2_u64)).await;
+    }
+    info!(pod.name = %pod_name, node.name = %node_name, "Pod is gone");
+    Ok(())
+}
+
+/// Wait for the upgraded io-engine Pods on the node to be Ready. While they're not, the io-engine
+/// Pods on the node which are still terminating are waited for, and the wait fails if any of them
+/// is terminating for longer than the readiness' terminating timeout. The deleted Pods are never
+/// taken for their replacements, e.g. a deleted Pod which lingers with the target version label
+/// when the version is unchanged. The Pods have to meet the readiness' extra requirements too,
+/// and to stay ready for long enough before they're taken to be ready. The io-engine Pods on the
+/// node are watched, so that their changes are checked right away.
+async fn verify_data_plane_pod_is_running<P: PodInspector + ?Sized>(
+    replacement_pods: &ReplacementPods<'_>,
+    k8s_client: &P,
+    timeout: Option<Duration>,
+    readiness: &DataPlanePodReadiness,
+) -> Result<()> {
+    let node_name = replacement_pods.node_name;
+    let namespace = replacement_pods.namespace.clone();
+    let stable_for = readiness.stable_for;
+    let terminating_timeout = readiness.terminating_timeout;
+    let duration = Duration::from_secs(5_u64);
+    let start = Instant::now();
+    // When each of the terminating Pods was first seen terminating.
+    let mut terminating_since: HashMap<String, Instant> = HashMap::new();
+    // When the Pods were last found to have become ready, so that Pods which flap between Ready
+    // and NotReady aren't taken to be ready.
+    let mut ready_since: Option<Instant> = None;
+    let mut pod_changes = PodChanges::watch(
+        k8s_client,
+        &[IO_ENGINE_LABEL],
+        Some(format!("spec.nodeName={node_name}").as_str()),
+    );
+    // Validate the new pods are up and running
+    info!(node.name = %node_name, "Waiting for data-plane Pods to come to Ready state");
+    loop {
+        if data_plane_pod_is_running(
+            replacement_pods,
+            k8s_client,
+            readiness.max_restarts,
+            &readiness.extra,
+        )
+        .await?
+        {
+            let since = *ready_since.get_or_insert_with(Instant::now);
+            if since.elapsed() >= stable_for
+                && phase_may_complete(start, readiness.simulated_phase_duration)
+            {
+                break;
+            }
+        } else if ready_since.take().is_some() {
+            warn!(
+                node.name = %node_name,
+                "Data-plane Pods are no longer ready, waiting for them to be ready again"
+            );
+        }
+
+        if let Some(timeout) = timeout_exceeded(start, timeout) {
+            return Err(data_plane_pod_not_ready(
+                node_name,
+                namespace,
+                timeout,
+                readiness.log_lines,
+                k8s_client,
+            )
+            .await);
+        }
+
+        let terminating =
+            terminating_io_engine_pods(node_name, namespace.clone(), k8s_client).await?;
+        terminating_since.retain(|pod_name, _| terminating.contains(pod_name));
+        for pod_name in terminating.iter() {
+            let since = terminating_since
+                .entry(pod_name.clone())
+                .or_insert_with(Instant::now);
+            ensure!(
+                since.elapsed() < terminating_timeout,
+                PodStuckTerminating {
+                    pod_name,
+                    node_name,
+                    timeout: terminating_timeout,
+                }
+            );
+        }
+        if let Some(since) = ready_since {
+            poll_status!(
+                readiness.verbosity,
+                node.name = %node_name,
+                ready_for = ?since.elapsed(),
+                ?stable_for,
+                "Waiting for the new data-plane Pods to stay ready"
+            );
+        } else if terminating.is_empty() {
+            poll_status!(
+                readiness.verbosity,
+                node.name = %node_name,
+                "Waiting for the new data-plane Pods to be scheduled and become Ready"
+            );
+        } else {
+            poll_status!(
+                readiness.verbosity,
+                node.name = %node_name,
+                pods = ?terminating,
+                "Waiting for the old data-plane Pods to terminate"
+            );
+        }
+        if readiness.verbosity.logs_wait_state() {
+            log_io_engine_pod_states(node_name, namespace.clone(), k8s_client).await;
+        }
+        pod_changes.wait(duration).await;
+    }
+
+    let new_pod_uids: Vec<String> = replacement_pods
+        .list(k8s_client)
+        .await?
+        .iter()
+        .filter_map(|pod| pod.uid())
+        .collect();
+    info!(
+        node.name = %node_name,
+        old_pod.uids = ?replacement_pods.deleted_pod_uids,
+        new_pod.uids = ?new_pod_uids,
+        "Data-plane Pods have been replaced"
+    );
+    Ok(())
+}
+
+/// Returns the names of the io-engine Pods on the node which are terminating.
+async fn terminating_io_engine_pods<P: PodInspector + ?Sized>(
+    node_name: &str,
+    namespace: String,
+    k8s_client: &P,
+) -> Result<Vec<String>> {
+    let node_name_pod_field = format!("spec.nodeName={node_name}");
+    let list_params = ListParams::default()
+        .labels(IO_ENGINE_LABEL)
+        .fields(node_name_pod_field.as_str());
+    let pods: Vec<Pod> =
+        k8s_client
+            .list_pods(&list_params)
+            .await
+            .context(ListPodsWithLabelAndField {
+                label: IO_ENGINE_LABEL.to_string(),
+                field: node_name_pod_field,
+                namespace,
+            })?;
+
+    Ok(pods
+        .iter()
+        .filter(|pod| pod.metadata.deletion_timestamp.is_some())
+        .map(|pod| pod.name_any())
+        .collect())
+}
+
+/// Returns the error for the io-engine Pods on the Node not becoming Ready within the timeout,
+/// along with the Pods' last observed phases and container statuses. If 'log_lines' is set, the
+/// last lines of the Pods' io-engine container logs are logged too.
+async fn data_plane_pod_not_ready<P: PodInspector + ?Sized>(
+    node_name: &str,
+    namespace: String,
+    timeout: Duration,
+    log_lines: Option<i64>,
+    k8s_client: &P,
+) -> Error {
+    let pods = match io_engine_pod_statuses(node_name, namespace.clone(), k8s_client).await {
+        Ok(pods) => pods,
+        Err(error) => return error,
+    };
+    error!(
+        node.name = %node_name,
+        ?timeout,
+        ?pods,
+        "Timed out waiting for the data-plane pods to become ready"
+    );
+    if let Some(log_lines) = log_lines {
+        log_io_engine_container_logs(node_name, namespace.as_str(), log_lines, k8s_client).await;
+    }
+    DataPlanePodNotReady {
+        node_name: node_name.to_string(),
+        timeout,
+        pods,
+    }
+    .build()
+}
+
+/// Logs the last 'lines' lines of the io-engine container logs of each of the io-engine Pods on
+/// the Node. If the container has restarted, the logs of its previous run are logged too, as
+/// they're likely to tell why it failed. This is best-effort, errors are logged and ignored.
+async fn log_io_engine_container_logs<P: PodInspector + ?Sized>(
+    node_name: &str,
+    namespace: &str,
+    lines: i64,
+    k8s_client: &P,
+) {
+    let node_name_pod_field = format!("spec.nodeName={node_name}");
+    let list_params = ListParams::default()
+        .labels(IO_ENGINE_LABEL)
+        .fields(node_name_pod_field.as_str());
+    let pods = match k8s_client.list_pods(&list_params).await {
+        Ok(pods) => pods,
+        Err(error) => {
+            warn!(
+                %error,
+                node.name = %node_name,
+                namespace,
+                "Failed to list the io-engine Pods to fetch their logs"
+            );
+            return;
+        }
+    };
+
+    for pod in pods.iter() {
+        let restarted = pod
+            .status
+            .as_ref()
+            .and_then(|status| status.container_statuses.as_ref())
+            .into_iter()
+            .flatten()
+            .any(|container| {
+                container.name.eq(IO_ENGINE_CONTAINER_NAME) && container.restart_count > 0
+            });
+
+        for previous in [true, false] {
+            if previous && !restarted {
+                continue;
+            }
+            let log_params = LogParams {
+                container: Some(IO_ENGINE_CONTAINER_NAME.to_string()),
+                tail_lines: Some(lines),
+                previous,
+                ..Default::default()
+            };
+            match k8s_client
+                .pod_logs(pod.name_any().as_str(), &log_params)
+                .await
+            {
+                Ok(logs) => error!(
+                    node.name = %node_name,
+                    pod.name = %pod.name_any(),
+                    container.name = IO_ENGINE_CONTAINER_NAME,
+                    previous,
+                    %logs,
+                    "Logs of the io-engine container which didn't become ready"
+                ),
+                Err(error) => warn!(
+                    %error,
+                    node.name = %node_name,
+                    pod.name = %pod.name_any(),
+                    previous,
+                    "Failed to fetch the io-engine container logs"
+                ),
+            }
+        }
+    }
+}
+
+/// Logs the phase and the container statuses of each of the io-engine Pods on the Node, for the
+/// verbose wait logs. A failure to list the Pods is only logged, as the wait doesn't depend on it.
+async fn log_io_engine_pod_states<P: PodInspector + ?Sized>(
+    node_name: &str,
+    namespace: String,
+    k8s_client: &P,
+) {
+    match io_engine_pod_statuses(node_name, namespace, k8s_client).await {
+        Ok(pods) => info!(node.name = %node_name, ?pods, "Current state of the io-engine Pods"),
+        Err(error) => {
+            warn!(%error, node.name = %node_name, "Failed to list the io-engine Pods' states");
+        }
+    }
+}
+
+/// Returns the phase and the container statuses of each of the io-engine Pods on the Node, e.g.
+/// "io-engine-abcde (Running, io-engine: waiting CrashLoopBackOff, restarts 3)".
+async fn io_engine_pod_statuses<P: PodInspector + ?Sized>(
+    node_name: &str,
+    namespace: String,
+    k8s_client: &P,
+) -> Result<Vec<String>> {
+    let node_name_pod_field = format!("spec.nodeName={node_name}");
+    let list_params = ListParams::default()
+        .labels(IO_ENGINE_LABEL)
+        .fields(node_name_pod_field.as_str());
+    let pods: Vec<Pod> =
+        k8s_client
+            .list_pods(&list_params)
+            .await
+            .context(ListPodsWithLabelAndField {
+                label: IO_ENGINE_LABEL.to_string(),
+                field: node_name_pod_field,
+                namespace,
+            })?;
+
+    Ok(pods
+        .iter()
+        .map(|pod| {
+            let status = pod.status.as_ref();
+            let phase = status
+                .and_then(|status| status.phase.as_deref())
+                .unwrap_or("Unknown");
+            let containers: Vec<String> = status
+                .and_then(|status| status.container_statuses.as_ref())
+                .into_iter()
+                .flatten()
+                .map(|container| {
+                    let state = container.state.as_ref();
+                    let state = match (
+                        state.and_then(|state| state.waiting.as_ref()),
+                        state.and_then(|state| state.terminated.as_ref()),
+                    ) {
+                        (Some(waiting), _) => {
+                            format!("waiting {}", waiting.reason.as_deref().unwrap_or_default())
+                        }
+                        (_, Some(terminated)) => format!(
+                            "terminated {}",
+                            terminated.reason.as_deref().unwrap_or_default()
+                        ),
+                        _ if container.ready => "ready".to_string(),
+                        _ => "not ready".to_string(),
+                    };
+                    format!(
+                        "{}: {}, restarts {}",
+                        container.name,
+                        state.trim_end(),
+                        container.restart_count
+                    )
+                })
+                .collect();
+            match containers.is_empty() {
+                true => format!("{} ({phase})", pod.name_any()),
+                false => format!("{} ({phase}, {})", pod.name_any(), containers.join(", ")),
+            }
+        })
+        .collect())
+}
+
+/// Validates that the Pod templates of the io-engine DaemonSets carry the target chart version
+/// label, so that the replacements of the deleted io-engine Pods are on the target version. Only
+/// the selected io-engine DaemonSet is validated, if there is one.
+async fn verify_daemonsets_updated(
+    namespace: String,
+    io_engine_daemonset: Option<&str>,
+    upgrade_to_version: &str,
+    version_label_key: &str,
+    k8s_client: &KubeClientSet,
+) -> Result<()> {
+    let daemonset_list = k8s_client
+        .api_stats()
+        .timed(
+            LIST_DAEMONSETS,
+            k8s_client
+                .daemonsets_api()
+                .list(&ListParams::default().labels(IO_ENGINE_LABEL)),
+        )
+        .await
+        .context(ListDaemonSetsWithLabel {
+            label: IO_ENGINE_LABEL.to_string(),
+            namespace,
+        })?;
+
+    let outdated_daemonsets: Vec<String> = daemonset_list
+        .iter()
+        .filter(|daemonset| io_engine_daemonset.map_or(true, |name| daemonset.name_any().eq(name)))
+        .filter_map(|daemonset| {
+            let version = daemonset
+                .spec
+                .as_ref()
+                .and_then(|spec| spec.template.metadata.as_ref())
+                .and_then(|metadata| metadata.labels.as_ref())
+                .and_then(|labels| labels.get(version_label_key));
+            match version {
+                Some(version) if version.eq(upgrade_to_version) => None,
+                Some(version) => Some(format!("{} ({version})", daemonset.name_any())),
+                None => Some(format!("{} (<none>)", daemonset.name_any())),
+            }
+        })
+        .collect();
+
+    if !outdated_daemonsets.is_empty() {
+        error!(
+            daemonsets = ?outdated_daemonsets,
+            to_version = %upgrade_to_version,
+            "The io-engine DaemonSets' Pod templates aren't on the target version"
+        );
+        return DaemonSetNotUpdated {
+            upgrade_to_version,
+            daemonsets: outdated_daemonsets,
+        }
+        .fail();
+    }
+
+    Ok(())
+}
+
+/// These are the counts of the io-engine DaemonSets' Pods which the DaemonSet status readiness
+/// strategy waits on, summed across the DaemonSets.
+#[derive(Clone, Copy, Debug)]
+struct DaemonSetStatusCounts {
+    /// The total .status.updatedNumberScheduled, i.e. the Pods scheduled from the updated Pod
+    /// template.
+    updated_number_scheduled: i32,
+    /// The total .status.numberReady, i.e. the Pods which are Ready.
+    number_ready: i32,
+}
+
+impl DaemonSetStatusCounts {
+    /// Returns true if both of the counts have reached those of 'expected'.
+    fn reached(&self, expected: &Self) -> bool {
+        self.updated_number_scheduled >= expected.updated_number_scheduled
+            && self.number_ready >= expected.number_ready
+    }
+}
+
+/// Returns the total .status.updatedNumberScheduled and .status.numberReady across the io-engine
+/// DaemonSets.
+async fn daemonset_status(
+    namespace: String,
+    k8s_client: &KubeClientSet,
+) -> Result<DaemonSetStatusCounts> {
+    let daemonset_list = k8s_client
+        .api_stats()
+        .timed(
+            LIST_DAEMONSETS,
+            k8s_client
+                .daemonsets_api()
+                .list(&ListParams::default().labels(IO_ENGINE_LABEL)),
+        )
+        .await
+        .context(ListDaemonSetsWithLabel {
+            label: IO_ENGINE_LABEL.to_string(),
+            namespace,
+        })?;
+
+    Ok(daemonset_list
+        .iter()
+        .filter_map(|daemonset| daemonset.status.as_ref())
+        .fold(
+            DaemonSetStatusCounts {
+                updated_number_scheduled: 0,
+                number_ready: 0,
+            },
+            |counts, status| DaemonSetStatusCounts {
+                updated_number_scheduled: counts.updated_number_scheduled
+                    + status.updated_number_scheduled.unwrap_or_default(),
+                number_ready: counts.number_ready + status.number_ready,
+            },
+        ))
+}
+
+/// Wait for the io-engine DaemonSets' status counts to reach 'expected', e.g. for
+/// .status.updatedNumberScheduled to go up from the value it had before the io-engine Pods were
+/// deleted, i.e. for the replacement Pods to be scheduled from the updated Pod template. The
+/// counts are summed across the Nodes, so the Pods of the Nodes which are upgraded concurrently
+/// are waited on too.
+async fn wait_for_daemonset_status(
+    node_name: &str,
+    namespace: String,
+    expected: DaemonSetStatusCounts,
+    k8s_client: &KubeClientSet,
+    timeout: Option<Duration>,
+    log_lines: Option<i64>,
+) -> Result<()> {
+    let duration = Duration::from_secs(5_u64);
+    let start = Instant::now();
+    info!(
+        node.name = %node_name,
+        ?expected,
+        "Waiting for the io-engine DaemonSet status to show the Node's rollout"
+    );
+    while !daemonset_status(namespace.clone(), k8s_client)
+        .await?
+        .reached(&expected)
+    {
+        if let Some(timeout) = timeout_exceeded(start, timeout) {
+            return Err(data_plane_pod_not_ready(
+                node_name, namespace, timeout, log_lines, k8s_client,
+            )
+            .await);
+        }
+        tokio::time::sleep(duration).await;
+    }
+    Ok(())
+}
+
+/// Wait for the rebuild to complete if any. This first waits for the rebuild grace period for any
+/// rebuilds to kick in, and then polls for the rebuilds to complete, first after the rebuild
+/// polling interval. The polling interval doubles up to its maximum while the same volume is
+/// rebuilding, and is reset when another volume starts rebuilding, so that long rebuilds aren't
+/// polled for needlessly often. This returns how long was spent in the initial grace period, and
+/// how long was spent polling for the rebuilds to complete. Only the rebuilds of the volumes on
+/// the node are waited for, unless the cluster-wide rebuilds are waited for. The wait is skipped
+/// if the node doesn't host any volume replicas or targets, as there is nothing on it to rebuild.
+/// The stalled rebuilds are recorded as warnings in the context's report.
+async fn wait_for_rebuild(
+    ctx: &UpgradeContext<'_>,
+    node_id: &str,
+    timeout: Option<Duration>,
+) -> Result<RebuildWait> {
+    let rest_client = &ctx.rest_client;
+    let grace_period = ctx.opts.rebuild_grace_period();
+    let poll_interval = ctx.opts.rebuild_poll_interval();
+    let max_poll_interval = ctx.opts.rebuild_poll_interval_max();
+    let cluster_wide = ctx.opts.wait_for_cluster_wide_rebuilds();
+    let verbosity = ctx.opts.wait_log_verbosity();
+    if !cluster_wide {
+        let volumes: Vec<_> = list_volumes(rest_client)
+            .await?
+            .into_iter()
+            .filter(|volume| nodes_hosting_volume(volume).contains(node_id))
+            .collect();
+        if volumes.is_empty() {
+            info!(
+                node.id = %node_id,
+                "Skipping wait for volume rebuilds, as the node has no volume replicas or targets"
+            );
+            return Ok(RebuildWait::default());
+        }
+
+        let single_replica_volumes: Vec<String> = volumes
+            .iter()
+            .filter(|volume| !volume_may_rebuild(volume))
+            .map(|volume| volume.spec.uuid.to_string())
+            .collect();
+        if !single_replica_volumes.is_empty() {
+            warn!(
+                node.id = %node_id,
+                volumes = ?single_replica_volumes,
+                "Volumes with a single replica will be unavailable while the node is upgraded"
+            );
+        }
+        if single_replica_volumes.len() == volumes.len() {
+            info!(
+                node.id = %node_id,
+                "Skipping wait for volume rebuilds, as none of the node's volumes may be rebuilt"
+            );
+            return Ok(RebuildWait::default());
+        }
+    }
+
+    let start = Instant::now();
+    // Wait for any rebuilds to kick in, for up to the grace period. The wait ends as soon as
+    // none of the node's volumes is waiting on a rebuild to start.
+    while start.elapsed() < grace_period
+        && rebuild_may_start(rest_client, node_id, verbosity).await?
+    {
+        tokio::time::sleep(poll_interval.min(grace_period.saturating_sub(start.elapsed()))).await;
+    }
+    let grace_period = start.elapsed();
+
+    let mut result = RebuildResult::default();
+    let mut rebuild_progress = RebuildProgressTracker::new(verbosity);
+    // The volumes whose rebuilds were waited on, for the node's upgrade.
+    let mut rebuilt_volumes: BTreeSet<String> = BTreeSet::new();
+    // The volume which was rebuilding when the polling interval was last reset.
+    let mut rebuilding_volume: Option<String> = None;
+    let mut interval = poll_interval;
+    loop {
+        let rebuild = rebuild_result(
+            rest_client,
+            &mut result.discarded_volumes,
+            (!cluster_wide).then_some(node_id),
+            verbosity,
+        )
+        .await?;
+        if rebuild.rebuilding_volume.is_some() && rebuild.rebuilding_volume.ne(&rebuilding_volume) {
+            rebuilding_volume = rebuild.rebuilding_volume.clone();
+            interval = poll_interval;
+        }
+        rebuilt_volumes.extend(rebuild.rebuilding_volume);
+
+        if rebuild.rebuilding || !phase_may_complete(start, ctx.opts.simulated_phase_duration()) {
+            if let Some(timeout) = timeout_exceeded(start, timeout) {
+                return RebuildTimeout {
+                    node_name: node_id.to_string(),
+                    timeout,
+                }
+                .fail();
+            }
+            poll_status!(
+                verbosity,
+                node.id = %node_id,
+                rebuild.poll_interval = ?interval,
+                "Waiting for volume rebuilds to complete"
+            );
+            if verbosity.logs_wait_state() {
+                log_volume_states(node_id, rest_client, |volume| {
+                    cluster_wide || nodes_hosting_volume(volume).contains(node_id)
+                })
+                .await;
+            }
+            rebuild_progress
+                .poll(rest_client, (!cluster_wide).then_some(node_id), &ctx.report)
+                .await?;
+            tokio::time::sleep(interval).await;
+            interval = (interval * 2).min(max_poll_interval);
+        } else {
+            break;
+        }
+    }
+    let rebuild_wait = RebuildWait {
+        grace_period,
+        polling: start.elapsed().saturating_sub(grace_period),
+    };
+    info!(
+        node.id = %node_id,
+        rebuild.grace_period = ?rebuild_wait.grace_period,
+        rebuild.polling = ?rebuild_wait.polling,
+        rebuild.volumes = ?rebuilt_volumes,
+        "No volume rebuilds in progress"
+    );
+    Ok(rebuild_wait)
+}
+
+/// Validate that none of the volumes which were on the node is Faulted. Volumes which are Online,
+/// or which are Degraded and rebuilding their replicas are accessible.
+async fn verify_volumes_accessible(
+    node_name: &str,
+    volume_uuids: &[String],
+    rest_client: &RestClientSet,
+) -> Result<()> {
+    if volume_uuids.is_empty() {
+        return Ok(());
+    }
+
+    let volumes = faulted_volumes(rest_client, volume_uuids).await?;
+    if !volumes.is_empty() {
+        error!(node.name = %node_name, ?volumes, "Volumes are Faulted after the Node's upgrade");
+        return VolumeFaultedAfterUpgrade {
+            node_name: node_name.to_string(),
+            volumes,
+        }
+        .fail();
+    }
+
+    info!(node.name = %node_name, "All volumes on the Node are accessible");
+    Ok(())
+}
+
+/// Wait for the volumes which had replicas on the node to be rebuilt to their configured replica
+/// count. This isn't the same as there being no rebuilds in progress, e.g. a replica which
+/// failed to rebuild would not be rebuilding either.
+async fn wait_for_replica_count(
+    node_name: &str,
+    volume_uuids: &[String],
+    rest_client: &RestClientSet,
+    timeout: Option<Duration>,
+    verbosity: WaitLogVerbosity,
+) -> Result<()> {
+    if volume_uuids.is_empty() {
+        return Ok(());
+    }
+
+    let start = Instant::now();
+    loop {
+        let volumes = volumes_below_replica_count(rest_client, volume_uuids).await?;
+        if volumes.is_empty() {
+            break;
+        }
+
+        if let Some(timeout) = timeout_exceeded(start, timeout) {
+            error!(
+                node.name = %node_name,
+                ?volumes,
+                "Volumes were not rebuilt to their configured replica count"
+            );
+            return ReplicaCountNotRestored {
+                volume: volumes[0].clone(),
+                node_name: node_name.to_string(),
+                timeout,
+            }
+            .fail();
+        }
+        poll_status!(
+            verbosity,
+            node.name = %node_name,
+            ?volumes,
+            "Waiting for volumes to be rebuilt to their configured replica count"
+        );
+        if verbosity.logs_wait_state() {
+            log_volume_states(node_name, rest_client, |volume| {
+                volumes.contains(&volume.spec.uuid.to_string())
+            })
+            .await;
+        }
+        tokio::time::sleep(Duration::from_secs(10_u64)).await;
+    }
+    info!(
+        node.name = %node_name,
+        "All volumes with replicas on the Node are at their configured replica count"
+    );
+    Ok(())
+}
+
+/// Logs the drain labels on a storage Node whose drain has timed out, and returns the error
+/// carrying the Node's last observed drain state.
+fn drain_timeout(
+    node_id: &str,
+    timeout: Duration,
+    state: Option<&CordonDrainState>,
+    drain_labels: &[String],
+) -> Error {
+    error!(
+        node.id = %node_id,
+        ?timeout,
+        ?drain_labels,
+        "Timed out waiting for {PRODUCT} Node drain to complete"
+    );
+    DrainTimeout {
+        node_id: node_id.to_string(),
+        timeout,
+        state: format!("{state:?}"),
+    }
+    .build()
+}
+
+/// Returns the error for a failed drain request, which is InsufficientPermissions if the storage
+/// REST API rejected it as Forbidden.
+fn drain_error(node_id: &str, error: RestError) -> Error {
+    if is_forbidden(&error) {
+        return InsufficientPermissions {
+            operation: "drain",
+            resource: format!("{PRODUCT} Node {node_id}"),
+            reason: error.to_string(),
+        }
+        .build();
+    }
+    DrainStorageNode { node_id }.into_error(error)
+}
+
+/// The maximum number of drain requests after which the drain labels are expected to be on the
+/// storage Node.
+const MAX_DRAIN_LABEL_ATTEMPTS: u32 = 5;
+
+/// The backoff before the first repeated drain request, which doubles with every request after.
+const DRAIN_LABEL_RETRY_BACKOFF: Duration = Duration::from_secs(1);
+
+/// Drain the storage Node with the upgrade drain label, and with the extra drain labels once it is
+/// draining, and wait for the drain to complete. This fails with DrainLabelNotApplied if the
+/// labels don't show up on the Node after MAX_DRAIN_LABEL_ATTEMPTS drain requests. The repeated
+/// drain requests are made after a jittered, exponential backoff. Each of the Node's observed
+/// drain states is recorded in 'decision_log'. A drained Node is only taken to be drained
+/// after 'simulated_phase_duration', if a slow cluster is simulated. The polls of the drain are
+/// logged at the level which 'verbosity' picks.
+async fn drain_storage_node<A>(
+    node_id: &str,
+    upgrade_drain_labels: &DrainLabels,
+    node_api: &A,
+    timeout: Option<Duration>,
+    decision_log: &DrainDecisionLog,
+    simulated_phase_duration: Option<Duration>,
+    verbosity: WaitLogVerbosity,
+) -> Result<()>
+where
+    A: NodeCordonApi + ?Sized,
+{
+    let drain_label_for_upgrade: String = upgrade_drain_labels.drain_label().to_string();
+    let sleep_duration = Duration::from_secs(5_u64);
+    let start = Instant::now();
+    // The drain requests with the drain label, and with the missing extra drain labels.
+    let mut drain_attempts = 0_u32;
+    let mut extra_label_attempts = 0_u32;
+    let mut drain_backoff = DRAIN_LABEL_RETRY_BACKOFF;
+    let mut extra_label_backoff = DRAIN_LABEL_RETRY_BACKOFF;
+    loop {
+        let storage_node = match node_api.get_node(node_id).await {
+            Ok(storage_node) => storage_node,
+            Err(error) if is_not_found(&error) => {
+                decision_log.log(
+                    StateMachine::Drain,
+                    node_id,
+                    None,
+                    DrainDecision::NodeVanished,
+                );
+                warn!(node.id = %node_id, "{PRODUCT} Node not found, skipping its drain");
+                return StorageNodeVanished { node_id }.fail();
+            }
+            Err(error) => {
+                return Err(error).context(GetStorageNode {
+                    node_id: node_id.to_string(),
+                })
+            }
+        };
+
+        let cordon_drain_state = storage_node
+            .spec
+            .ok_or(
+                EmptyStorageNodeSpec {
+                    node_id: node_id.to_string(),
+                }
+                .build(),
+            )?
+            .cordondrainstate;
+        let log_decision = |decision| {
+            decision_log.log(
+                StateMachine::Drain,
+                node_id,
+                cordon_drain_state.as_ref(),
+                decision,
+            )
+        };
+
+        let drain_labels: &[String] = match cordon_drain_state.as_ref() {
+            Some(CordonDrainState::drainingstate(drain_state)) => &drain_state.drainlabels,
+            Some(CordonDrainState::drainedstate(drain_state)) => &drain_state.drainlabels,
+            _ => &[],
+        };
+        let missing_extra_labels: Vec<&String> = upgrade_drain_labels
+            .extra_drain_labels()
+            .iter()
+            .filter(|label| !drain_labels.contains(label))
+            .collect();
+
+        match cordon_drain_state.as_ref() {
+            Some(_)
+                if drain_labels.contains(&drain_label_for_upgrade)
+                    && !missing_extra_labels.is_empty() =>
+            {
+                if extra_label_attempts >= MAX_DRAIN_LABEL_ATTEMPTS {
+                    return DrainLabelNotApplied {
+                        node_id,
+                        labels: missing_extra_labels
+                            .into_iter()
+                            .cloned()
+                            .collect::<Vec<String>>(),
+                        attempts: extra_label_attempts,
+                    }
+                    .fail();
+                }
+                if extra_label_attempts > 0 {
+                    let delay = with_jitter(extra_label_backoff);
+                    warn!(
+                        node.id = %node_id,
+                        attempts = extra_label_attempts,
+                        ?delay,
+                        "The extra drain labels aren't on the {PRODUCT} Node yet, retrying"
+                    );
+                    tokio::time::sleep(delay).await;
+                    extra_label_backoff *= 2;
+                }
+                extra_label_attempts += 1;
+                log_decision(DrainDecision::AddExtraDrainLabels);
+                for label in missing_extra_labels {
+                    node_api
+                        .put_node_drain(node_id, label)
+                        .await
+                        .map_err(|error| drain_error(node_id, error))?;
+                    info!(node.id = %node_id, %label, "Added extra drain label to {PRODUCT} Node");
+                }
+            }
+            Some(CordonDrainState::drainingstate(drain_state))
+                if drain_state.drainlabels.contains(&drain_label_for_upgrade) =>
+            {
+                if let Some(timeout) = timeout_exceeded(start, timeout) {
+                    log_decision(DrainDecision::TimedOut);
+                    return Err(drain_timeout(
+                        node_id,
+                        timeout,
+                        cordon_drain_state.as_ref(),
+                        &drain_state.drainlabels,
+                    ));
+                }
+                log_decision(DrainDecision::WaitForDrain);
+                poll_status!(
+                    verbosity,
+                    node.id = %node_id,
+                    "Waiting for {PRODUCT} Node drain to complete"
+                );
+                // Wait for node drain to complete.
+                tokio::time::sleep(sleep_duration).await;
+            }
+            Some(CordonDrainState::drainedstate(drain_state))
+                if drain_state.drainlabels.contains(&drain_label_for_upgrade) =>
+            {
+                if phase_may_complete(start, simulated_phase_duration) {
+                    log_decision(DrainDecision::DrainCompleted);
+                    info!(node.id = %node_id, "Drain completed for {PRODUCT} Node");
+                    return Ok(());
+                }
+                if let Some(timeout) = timeout_exceeded(start, timeout) {
+                    log_decision(DrainDecision::TimedOut);
+                    return Err(drain_timeout(
+                        node_id,
+                        timeout,
+                        cordon_drain_state.as_ref(),
+                        &drain_state.drainlabels,
+                    ));
+                }
+                log_decision(DrainDecision::WaitForSimulatedPhase);
+                tokio::time::sleep(sleep_duration).await;
+            }
+            _ => {
+                if drain_attempts >= MAX_DRAIN_LABEL_ATTEMPTS {
+                    error!(
+                        node.id = %node_id,
+                        attempts = drain_attempts,
+                        "The drain label didn't show up on the {PRODUCT} Node after the drain \
+                        requests"
+                    );
+                    return DrainLabelNotApplied {
+                        node_id,
+                        labels: vec![drain_label_for_upgrade],
+                        attempts: drain_attempts,
+                    }
+                    .fail();
+                }
+                if drain_attempts > 0 {
+                    let delay = with_jitter(drain_backoff);
+                    warn!(
+                        node.id = %node_id,
+                        attempts = drain_attempts,
+                        ?delay,
+                        "The drain label isn't on the {PRODUCT} Node yet, retrying the drain"
+                    );
+                    tokio::time::sleep(delay).await;
+                    drain_backoff *= 2;
+                }
+                drain_attempts += 1;
+                log_decision(DrainDecision::StartDrain);
+                node_api
+                    .put_node_drain(node_id, upgrade_drain_labels.drain_label())
+                    .await
+                    .map_err(|error| drain_error(node_id, error))?;
+
+                info!(node.id = %node_id, "Drain started for {PRODUCT} Node");
+            }
+        }
+    }
+}
+
+/// These are the upgraded io-engine Pods on a Node which replace its deleted io-engine Pods.
+struct ReplacementPods<'a> {
+    node_name: &'a str,
+    namespace: String,
+    upgrade_to_version: &'a String,
+    version_label_key: &'a str,
+    /// The DaemonSets which own the deleted Pods, which maps the DaemonSets' UIDs to their names.
+    daemonsets: &'a BTreeMap<String, String>,
+    /// The UIDs of the deleted Pods, which are never taken for their replacements.
+    deleted_pod_uids: &'a HashSet<String>,
+}
+
+impl ReplacementPods<'_> {
+    /// Lists the upgraded io-engine Pods on the Node which are owned by the DaemonSets, leaving
+    /// out the deleted Pods.
+    async fn list<P: PodInspector + ?Sized>(&self, k8s_client: &P) -> Result<Vec<Pod>> {
+        Ok(upgraded_pods_on_node(
+            self.node_name,
+            self.namespace.clone(),
+            self.upgrade_to_version,
+            self.version_label_key,
+            k8s_client,
+        )
+        .await?
+        .into_iter()
+        .filter(|pod| {
+            owner_daemonset_uid_and_name(pod)
+                .is_some_and(|(uid, _)| self.daemonsets.contains_key(uid))
+        })
+        .filter(|pod| {
+            pod.uid()
+                .map_or(true, |uid| !self.deleted_pod_uids.contains(&uid))
+        })
+        .collect())
+    }
+}
+
+/// These are the requirements for the replacement io-engine Pods to be taken to be ready, on top
+/// of their Ready condition.
+struct DataPlanePodReadiness {
+    /// The number of times the Pods' containers may restart, if limited.
+    max_restarts: Option<i32>,
+    /// The time that the old Pods may be terminating for.
+    terminating_timeout: Duration,
+    /// The number of lines of the Pods' logs which are logged, if they don't become ready.
+    log_lines: Option<i64>,
+    extra: ExtraPodReadiness,
+    /// The time that the Pods have to stay ready for.
+    stable_for: Duration,
+    /// The minimum duration of the wait, if a slow cluster is simulated.
+    simulated_phase_duration: Option<Duration>,
+    /// How much the wait logs while it polls.
+    verbosity: WaitLogVerbosity,
+}
+
+impl DataPlanePodReadiness {
+    /// This returns the readiness requirements which are set in 'opts'.
+    fn from_opts(opts: &CliArgs) -> Self {
+        Self {
+            max_restarts: opts.max_io_engine_restarts(),
+            terminating_timeout: opts.terminating_pod_timeout(),
+            log_lines: opts.io_engine_log_lines(),
+            extra: opts.io_engine_extra_readiness(),
+            stable_for: opts.io_engine_ready_stable_for(),
+            simulated_phase_duration: opts.simulated_phase_duration(),
+            verbosity: opts.wait_log_verbosity(),
+        }
+    }
+}
+
+/// Validate if there is one upgraded io-engine Pod running on the Node for each of the
+/// DaemonSets which owned its deleted Pods. The upgraded Pods of any other io-engine DaemonSets on
+/// the Node, and the deleted Pods, are ignored. The Pods have to meet 'extra_readiness' too.
+async fn data_plane_pod_is_running<P: PodInspector + ?Sized>(
+    replacement_pods: &ReplacementPods<'_>,
+    k8s_client: &P,
+    max_restarts: Option<i32>,
+    extra_readiness: &ExtraPodReadiness,
+) -> Result<bool> {
+    let node = replacement_pods.node_name;
+    let daemonsets = replacement_pods.daemonsets;
+    let pod_list = replacement_pods.list(k8s_client).await?;
+
+    // A crash-looping io-engine would never become Ready.
+    for pod in pod_list.iter() {
+        for container in pod
+            .status
+            .as_ref()
+            .and_then(|status| status.container_statuses.as_ref())
+            .into_iter()
+            .flatten()
+            .filter(|container| container.restart_count > 0)
+        {
+            let reason = container
+                .last_state
+                .as_ref()
+                .and_then(|state| state.terminated.as_ref())
+                .and_then(|terminated| terminated.reason.clone())
+                .unwrap_or_default();
+            warn!(
+                node.name = %node,
+                pod.name = %pod.name_any(),
+                container.name = %container.name,
+                restarts = container.restart_count,
+                last_termination_reason = %reason,
+                "Upgraded data-plane pod's container has restarted"
+            );
+            if max_restarts.is_some_and(|max| container.restart_count > max) {
+                return DataPlanePodCrashLooping {
+                    node_name: node,
+                    pod_name: pod.name_any(),
+                    container: container.name.clone(),
+                    restarts: container.restart_count,
+                    reason,
+                }
+                .fail();
+            }
+        }
+    }
+
+    for (uid, daemonset) in daemonsets {
+        let pods: Vec<Pod> = pod_list
+            .iter()
+            .filter(|pod| owner_daemonset_uid_and_name(pod).is_some_and(|(owner, _)| owner.eq(uid)))
+            .cloned()
+            .collect();
+        match pods.len() {
+            0 => return Ok(false),
+            1 => {}
+            _ => {
+                return TooManyIoEnginePods {
+                    node_name: node,
+                    daemonset,
+                    pods: pod_names_and_phases(&pods),
+                }
+                .fail()
+            }
+        }
+    }
+
+    // The io-engine may be Ready before it has re-imported its pools.
+    if let Some(pod) = pod_list
+        .iter()
+        .filter(|pod| !pod_is_terminated(pod))
+        .find(|pod| !extra_readiness.is_met(pod))
+    {
+        info!(
+            node.name = %node,
+            pod.name = %pod.name_any(),
+            ?extra_readiness,
+            "Data-plane Pod doesn't meet the extra readiness requirements yet"
+        );
+        return Ok(false);
+    }
+
+    Ok(all_pods_are_ready(pod_list))
+}
+
+/// Returns true if the Node has an io-engine Pod with the target version label, i.e. the Node
+/// has been upgraded already.
+async fn node_already_upgraded(
+    node_name: &str,
+    upgrade_to_version: &String,
+    version_label_key: &str,
+    k8s_client: &KubeClientSet,
+    namespace: &str,
 ) -> Result<bool> {
-    let agent_core_selector_label =
-        format!("{AGENT_CORE_LABEL},{CHART_VERSION_LABEL_KEY}={upgrade_to_version}");
-    let pod_list: ObjectList<Pod> = k8s_client
-        .pods_api()
-        .list(&ListParams::default().labels(agent_core_selector_label.as_str()))
+    let upgraded_pods = upgraded_pods_on_node(
+        node_name,
+        namespace.to_string(),
+        upgrade_to_version,
+        version_label_key,
+        k8s_client,
+    )
+    .await?;
+    Ok(!upgraded_pods.is_empty())
+}
+
+/// Lists the io-engine Pods with the target version label on the Node.
+async fn upgraded_pods_on_node<P: PodInspector + ?Sized>(
+    node: &str,
+    namespace: String,
+    upgrade_to_version: &String,
+    version_label_key: &str,
+    k8s_client: &P,
+) -> Result<Vec<Pod>> {
+    let node_name_pod_field = format!("spec.nodeName={node}");
+    let pod_label = format!("{IO_ENGINE_LABEL},{version_label_key}={upgrade_to_version}");
+    let io_engine_listparam = ListParams::default()
+        .labels(pod_label.as_str())
+        .fields(node_name_pod_field.as_str());
+
+    let pod_list: Vec<Pod> =
+        k8s_client
+            .list_pods(&io_engine_listparam)
+            .await
+            .context(ListPodsWithLabelAndField {
+                label: pod_label,
+                field: node_name_pod_field,
+                namespace,
+            })?;
+
+    Ok(pod_list)
+}
+
+/// Validate that none of the volumes are degraded or faulted. This fails the upgrade, unless
+/// 'allow_unhealthy' is set, in which case the volumes are only logged.
+async fn verify_volumes_are_healthy(
+    rest_client: &RestClientSet,
+    allow_unhealthy: bool,
+) -> Result<()> {
+    let volumes: Vec<String> = list_unhealthy_volumes(rest_client, &[])
+        .await?
+        .iter()
+        .map(|volume| volume.spec.uuid.to_string())
+        .collect();
+
+    if volumes.is_empty() {
+        return Ok(());
+    }
+    if !allow_unhealthy {
+        return UnhealthyVolumesPresent { volumes }.fail();
+    }
+    warn!(
+        ?volumes,
+        "Proceeding with the data-plane upgrade, even though there are unhealthy volumes"
+    );
+    Ok(())
+}
+
+/// Validate that all of the io-engine Pods carrying the target version label were created from
+/// the same DaemonSet revision. If they weren't, the version label doesn't uniquely identify the
+/// intended Pod template. This fails the upgrade only if 'strict' is set.
+async fn verify_unique_target_revision(
+    namespace: String,
+    upgrade_to_version: &String,
+    version_label_key: &str,
+    k8s_client: &KubeClientSet,
+    strict: bool,
+) -> Result<()> {
+    let pod_label = format!("{IO_ENGINE_LABEL},{version_label_key}={upgrade_to_version}");
+    let pod_list: Vec<Pod> = list_all_pods(
+        k8s_client,
+        &ListParams::default().labels(pod_label.as_str()),
+    )
+    .await
+    .context(ListPodsWithLabel {
+        label: pod_label,
+        namespace,
+    })?;
+
+    let revisions: BTreeSet<String> = pod_list
+        .iter()
+        .filter_map(|pod| {
+            pod.labels()
+                .get(CONTROLLER_REVISION_HASH_LABEL_KEY)
+                .cloned()
+        })
+        .collect();
+
+    if revisions.len() > 1 {
+        let revisions: Vec<String> = revisions.into_iter().collect();
+        if strict {
+            return MultipleTargetRevisions {
+                label_key: version_label_key.to_string(),
+                version: upgrade_to_version.clone(),
+                revisions,
+            }
+            .fail();
+        }
+        warn!(
+            ?revisions,
+            "io-engine Pods with the target version label have more than one DaemonSet revision"
+        );
+    }
+
+    Ok(())
+}
+
+/// Wait for the control-plane to be Ready on the target version, within the bounds of 'wait'. A
+/// control-plane on a later version than the target is reported first, it fails the upgrade only
+/// if the wait is strict. The check is skipped if 'cache' holds a recent enough Ready result. The
+/// control-plane Pods are watched, so that their changes are checked right away.
+async fn verify_control_plane_is_running(
+    namespace: String,
+    k8s_client: &KubeClientSet,
+    upgrade_to_version: &String,
+    wait: &ControlPlaneWait,
+    cache: &ControlPlaneReadyCache,
+    components: &ControlPlaneComponents,
+) -> Result<()> {
+    if cache.is_fresh() {
+        debug!("Control-plane was found to be Ready moments ago, skipping its readiness check");
+        return Ok(());
+    }
+
+    verify_control_plane_not_ahead(
+        namespace.clone(),
+        k8s_client,
+        upgrade_to_version,
+        wait.strict,
+        &components.versioned,
+        components.version_label_key.as_str(),
+    )
+    .await?;
+
+    let duration = Duration::from_secs(3_u64);
+    let start = Instant::now();
+    let label_selectors: Vec<&str> = components
+        .versioned
+        .iter()
+        .chain(components.unversioned.iter())
+        .map(String::as_str)
+        .collect();
+    let mut pod_changes = PodChanges::watch(k8s_client, &label_selectors, None);
+    loop {
+        let readiness = control_plane_is_running(
+            namespace.clone(),
+            k8s_client,
+            upgrade_to_version,
+            components,
+        )
+        .await?;
+        if readiness.is_ready() && phase_may_complete(start, wait.simulated_phase_duration) {
+            break;
+        }
+
+        if let Some(timeout) = timeout_exceeded(start, wait.timeout) {
+            let components = readiness.unready_components();
+            error!(
+                ?timeout,
+                ?components,
+                "Timed out waiting for the control-plane to become Ready"
+            );
+            return ControlPlaneNotReady {
+                timeout,
+                components: components.into_iter().map(ToString::to_string).collect(),
+            }
+            .fail();
+        }
+        pod_changes.wait(duration).await;
+    }
+    cache.record_ready();
+
+    Ok(())
+}
+
+/// This remembers when the control-plane was last found to be Ready, so that it isn't checked
+/// again for each of the nodes whose upgrades start within 'ttl' of that.
+struct ControlPlaneReadyCache {
+    ttl: Duration,
+    last_ready: Mutex<Option<Instant>>,
+}
+
+impl ControlPlaneReadyCache {
+    /// Creates a ControlPlaneReadyCache. A zero 'ttl' disables the cache.
+    fn new(ttl: Duration) -> Self {
+        Self {
+            ttl,
+            last_ready: Mutex::new(None),
+        }
+    }
+
+    /// Returns true if the control-plane was found to be Ready within the TTL.
+    fn is_fresh(&self) -> bool {
+        let last_ready = *self
+            .last_ready
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner);
+        last_ready.is_some_and(|last_ready| last_ready.elapsed() < self.ttl)
+    }
+
+    /// Forgets when the control-plane was last found to be Ready, e.g. once it is restarted.
+    fn invalidate(&self) {
+        *self
+            .last_ready
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = None;
+    }
+
+    /// Records that the control-plane has just been found to be Ready.
+    fn record_ready(&self) {
+        *self
+            .last_ready
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner) = Some(Instant::now());
+    }
+}
+
+/// Validate that the agent-core and api-rest Pods are not on a later chart version than
+/// 'upgrade_to_version', e.g. because of a mismatched step of a chained upgrade. Such a
+/// control-plane fails the upgrade only if 'strict' is set, otherwise it is logged. The Pods are
+/// selected by the label selectors of the versioned control-plane components.
+async fn verify_control_plane_not_ahead<P: PodInspector + ?Sized>(
+    namespace: String,
+    k8s_client: &P,
+    upgrade_to_version: &str,
+    strict: bool,
+    versioned_components: &[String],
+    version_label_key: &str,
+) -> Result<()> {
+    let target = Version::parse(upgrade_to_version).context(SemverParse {
+        version_string: upgrade_to_version.to_string(),
+    })?;
+
+    let mut later_pods: Vec<(Version, String)> = Vec::new();
+    for label in versioned_components {
+        let pods = k8s_client
+            .list_pods(&ListParams::default().labels(label))
+            .await
+            .context(ListPodsWithLabel {
+                label: label.to_string(),
+                namespace: namespace.clone(),
+            })?;
+        // The Pods without a valid version label are left to the readiness check.
+        later_pods.extend(pods.iter().filter_map(|pod| {
+            let version = Version::parse(pod.labels().get(version_label_key)?).ok()?;
+            let pod = format!("{} ({version})", pod.name_any());
+            version.gt(&target).then_some((version, pod))
+        }));
+    }
+
+    let Some(control_plane_version) = later_pods.iter().map(|(version, _)| version).max() else {
+        return Ok(());
+    };
+    let control_plane_version = control_plane_version.to_string();
+    let pods: Vec<String> = later_pods.into_iter().map(|(_, pod)| pod).collect();
+    warn!(
+        %control_plane_version,
+        %upgrade_to_version,
+        ?pods,
+        "The control-plane is on a later version than the data-plane target version"
+    );
+    if strict {
+        return ControlPlaneAheadOfTarget {
+            control_plane_version,
+            upgrade_to_version: upgrade_to_version.to_string(),
+            pods,
+        }
+        .fail();
+    }
+    Ok(())
+}
+
+/// Periodically checks that the control-plane is healthy while a node is being upgraded. This
+/// only returns when the control-plane has been unhealthy for a few consecutive checks, or when
+/// the check itself fails.
+async fn monitor_control_plane(
+    node_name: &str,
+    namespace: String,
+    k8s_client: &KubeClientSet,
+    upgrade_to_version: &String,
+    components: &ControlPlaneComponents,
+) -> Error {
+    let duration = Duration::from_secs(10_u64);
+    let mut unhealthy_checks = 0_u32;
+    loop {
+        tokio::time::sleep(duration).await;
+        match control_plane_is_running(
+            namespace.clone(),
+            k8s_client,
+            upgrade_to_version,
+            components,
+        )
         .await
-        .context(ListPodsWithLabel {
-            label: AGENT_CORE_LABEL.to_string(),
-            namespace: namespace.clone(),
-        })?;
-    let core_is_ready = all_pods_are_ready(pod_list);
+        {
+            Ok(readiness) if readiness.is_ready() => unhealthy_checks = 0,
+            Ok(readiness) => {
+                unhealthy_checks += 1;
+                warn!(
+                    node.name = %node_name,
+                    unhealthy_checks,
+                    components = ?readiness.unready_components(),
+                    "Control-plane is unhealthy during the node's upgrade"
+                );
+                if unhealthy_checks >= CONTROL_PLANE_UNHEALTHY_CHECKS {
+                    return ControlPlaneUnhealthy {
+                        node_name: node_name.to_string(),
+                    }
+                    .build();
+                }
+            }
+            Err(error) => return error,
+        }
+    }
+}
+
+/// These are the bounds of a wait for the control-plane to be Ready.
+struct ControlPlaneWait {
+    /// The time that the control-plane is waited on for, if limited.
+    timeout: Option<Duration>,
+    /// If set, a control-plane on a later version than the target fails the upgrade.
+    strict: bool,
+    /// The minimum duration of the wait, if a slow cluster is simulated.
+    simulated_phase_duration: Option<Duration>,
+}
+
+/// These are the label selectors of the control-plane components which have to be Ready before
+/// and during each node's upgrade.
+#[derive(Clone, Debug)]
+struct ControlPlaneComponents {
+    /// The components which have to be Ready on the target version, i.e. agent-core and
+    /// api-rest by default.
+    versioned: Vec<String>,
+    /// The components which have to be Ready on any version, i.e. etcd by default. etcd is
+    /// deployed by a dependency chart, and its Pods carry no chart version label.
+    unversioned: Vec<String>,
+    /// The key of the chart version label on the versioned components' Pods.
+    version_label_key: String,
+}
+
+impl ControlPlaneComponents {
+    /// Returns the control-plane components set in the CLI options. The empty selectors are left
+    /// out, so that a deployment without some of the components may leave them out.
+    fn from_opts(opts: &CliArgs) -> Self {
+        let selectors = |selectors: Vec<String>| {
+            selectors
+                .into_iter()
+                .filter(|selector| !selector.trim().is_empty())
+                .collect()
+        };
+        Self {
+            versioned: selectors(opts.control_plane_components()),
+            unversioned: selectors(opts.unversioned_control_plane_components()),
+            version_label_key: opts.chart_version_label_key(),
+        }
+    }
+}
+
+/// This is the readiness of each of the control-plane components, by label selector.
+struct ControlPlaneReadiness {
+    components: Vec<(String, bool)>,
+}
+
+impl ControlPlaneReadiness {
+    /// Returns true if all of the control-plane components are ready.
+    fn is_ready(&self) -> bool {
+        self.components.iter().all(|(_, ready)| *ready)
+    }
+
+    /// Returns the label selectors of the control-plane components which aren't ready.
+    fn unready_components(&self) -> Vec<&str> {
+        self.components
+            .iter()
+            .filter(|(_, ready)| !ready)
+            .map(|(component, _)| component.as_str())
+            .collect()
+    }
+}
+
+/// Validate if the control-plane 'components' are running. The versioned components' Pods must
+/// be Ready on the target version, whereas the unversioned components' Pods need only be Ready.
+async fn control_plane_is_running<P: PodInspector + ?Sized>(
+    namespace: String,
+    k8s_client: &P,
+    upgrade_to_version: &String,
+    components: &ControlPlaneComponents,
+) -> Result<ControlPlaneReadiness> {
+    let selectors = components
+        .versioned
+        .iter()
+        .map(|component| {
+            let selector = format!(
+                "{component},{}={upgrade_to_version}",
+                components.version_label_key
+            );
+            (component, selector)
+        })
+        .chain(
+            components
+                .unversioned
+                .iter()
+                .map(|component| (component, component.clone())),
+        );
+
+    // The components are listed concurrently, as this is polled between every Node's upgrade.
+    let components = try_join_all(selectors.map(|(component, selector)| {
+        let namespace = namespace.clone();
+        async move {
+            let pods = k8s_client
+                .list_pods(&ListParams::default().labels(selector.as_str()))
+                .await
+                .context(ListPodsWithLabel {
+                    label: component.clone(),
+                    namespace,
+                })?;
+            Ok::<_, Error>((component.clone(), all_pods_are_ready(pods)))
+        }
+    }))
+    .await?;
+
+    Ok(ControlPlaneReadiness { components })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::upgrade::{
+        fake_rest_server::{FakeRestServer, FAKE_NODE_ID},
+        utils::MockPodInspector,
+    };
+    use async_trait::async_trait;
+    use openapi::clients::tower::{ResponseContentUnexpected, ResponseError};
+    use serde_json::json;
+    use std::collections::VecDeque;
+
+    const NODE_ID: &str = "node-1";
+    const UPGRADE_DRAIN_LABEL: &str = "mayastor-upgrade";
+    const NAMESPACE: &str = "mayastor";
+    const NODE_NAME: &str = "worker-1";
+    const VERSION_LABEL_KEY: &str = "openebs.io/version";
+    const FROM_VERSION: &str = "2.4.0";
+    const TO_VERSION: &str = "2.5.0";
+    const DAEMONSET_UID: &str = "io-engine-uid";
+
+    /// This is a NodeCordonApi which returns the storage Nodes it was created with, one for each
+    /// get_node() call, and records the labels of the drain requests.
+    struct MockNodeApi {
+        nodes: Mutex<VecDeque<std::result::Result<Node, RestError>>>,
+        drain_requests: Mutex<Vec<String>>,
+    }
+
+    impl MockNodeApi {
+        fn new(nodes: Vec<std::result::Result<Node, RestError>>) -> Self {
+            Self {
+                nodes: Mutex::new(nodes.into()),
+                drain_requests: Mutex::new(Vec::new()),
+            }
+        }
+
+        fn drain_requests(&self) -> Vec<String> {
+            self.drain_requests.lock().unwrap().clone()
+        }
+    }
+
+    #[async_trait]
+    impl NodeCordonApi for MockNodeApi {
+        async fn get_node(&self, node_id: &str) -> std::result::Result<Node, RestError> {
+            assert_eq!(node_id, NODE_ID);
+            self.nodes
+                .lock()
+                .unwrap()
+                .pop_front()
+                .expect("no more storage Nodes to return")
+        }
+
+        async fn put_node_drain(
+            &self,
+            node_id: &str,
+            label: &str,
+        ) -> std::result::Result<Node, RestError> {
+            assert_eq!(node_id, NODE_ID);
+            self.drain_requests.lock().unwrap().push(label.to_string());
+            Ok(storage_node(json!({
+                "drainingstate": { "cordonlabels": [], "drainlabels": [label] }
+            })))
+        }
+    }
+
+    /// Returns a storage Node with the cordon/drain state 'cordon_drain_state'.
+    fn storage_node(cordon_drain_state: serde_json::Value) -> Node {
+        serde_json::from_value(json!({
+            "id": NODE_ID,
+            "spec": {
+                "id": NODE_ID,
+                "grpcEndpoint": "10.0.0.1:10124",
+                "cordondrainstate": cordon_drain_state,
+            },
+        }))
+        .unwrap()
+    }
+
+    /// Returns an error of a storage REST API call which failed with the status 'status'.
+    fn rest_error(status: http::StatusCode) -> RestError {
+        openapi::clients::tower::Error::Response(ResponseError::Unexpected(
+            ResponseContentUnexpected {
+                status,
+                text: String::new(),
+            },
+        ))
+    }
+
+    fn drain_labels() -> DrainLabels {
+        DrainLabels::new(UPGRADE_DRAIN_LABEL.to_string(), Vec::new())
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drain_completes_from_cordoned_through_draining_to_drained() {
+        let node_api = MockNodeApi::new(vec![
+            Ok(storage_node(json!({
+                "cordonedstate": { "cordonlabels": ["maintenance"] }
+            }))),
+            Ok(storage_node(json!({
+                "drainingstate": {
+                    "cordonlabels": ["maintenance"],
+                    "drainlabels": [UPGRADE_DRAIN_LABEL],
+                }
+            }))),
+            Ok(storage_node(json!({
+                "drainedstate": {
+                    "cordonlabels": ["maintenance"],
+                    "drainlabels": [UPGRADE_DRAIN_LABEL],
+                }
+            }))),
+        ]);
 
-    let api_rest_selector_label =
-        format!("{API_REST_LABEL},{CHART_VERSION_LABEL_KEY}={upgrade_to_version}");
-    let pod_list: ObjectList<Pod> = k8s_client
-        .pods_api()
-        .list(&ListParams::default().labels(api_rest_selector_label.as_str()))
+        drain_storage_node(
+            NODE_ID,
+            &drain_labels(),
+            &node_api,
+            None,
+            &DrainDecisionLog::default(),
+            None,
+            WaitLogVerbosity::default(),
+        )
         .await
-        .context(ListPodsWithLabel {
-            label: API_REST_LABEL.to_string(),
-            namespace: namespace.clone(),
-        })?;
-    let rest_is_ready = all_pods_are_ready(pod_list);
+        .unwrap();
+        assert_eq!(node_api.drain_requests(), vec![UPGRADE_DRAIN_LABEL]);
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drain_times_out_while_draining() {
+        let node_api = MockNodeApi::new(vec![Ok(storage_node(json!({
+            "drainingstate": { "cordonlabels": [], "drainlabels": [UPGRADE_DRAIN_LABEL] }
+        })))]);
+
+        let result = drain_storage_node(
+            NODE_ID,
+            &drain_labels(),
+            &node_api,
+            Some(Duration::ZERO),
+            &DrainDecisionLog::default(),
+            None,
+            WaitLogVerbosity::default(),
+        )
+        .await;
+        assert!(
+            matches!(result, Err(Error::DrainTimeout { ref node_id, .. }) if node_id == NODE_ID),
+            "{result:?}"
+        );
+        assert!(node_api.drain_requests().is_empty());
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drain_backs_off_in_between_the_drain_requests() {
+        // The drain label never shows up on the storage Node.
+        let node_api = MockNodeApi::new(
+            std::iter::repeat_with(|| Ok(storage_node(serde_json::Value::Null)))
+                .take(MAX_DRAIN_LABEL_ATTEMPTS as usize + 1)
+                .collect(),
+        );
+
+        let start = tokio::time::Instant::now();
+        let result = drain_storage_node(
+            NODE_ID,
+            &drain_labels(),
+            &node_api,
+            None,
+            &DrainDecisionLog::default(),
+            None,
+            WaitLogVerbosity::default(),
+        )
+        .await;
+        assert!(
+            matches!(
+                result,
+                Err(Error::DrainLabelNotApplied { attempts, .. })
+                    if attempts == MAX_DRAIN_LABEL_ATTEMPTS
+            ),
+            "{result:?}"
+        );
+        assert_eq!(
+            node_api.drain_requests().len(),
+            MAX_DRAIN_LABEL_ATTEMPTS as usize
+        );
+        // The backoffs of 1s, 2s, 4s and 8s are each cut by up to half by the jitter.
+        assert!(start.elapsed() >= Duration::from_millis(7_500));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn drain_fails_when_the_storage_node_cannot_be_fetched() {
+        let node_api = MockNodeApi::new(vec![Err(rest_error(
+            http::StatusCode::INTERNAL_SERVER_ERROR,
+        ))]);
+
+        let result = drain_storage_node(
+            NODE_ID,
+            &drain_labels(),
+            &node_api,
+            None,
+            &DrainDecisionLog::default(),
+            None,
+            WaitLogVerbosity::default(),
+        )
+        .await;
+        assert!(
+            matches!(result, Err(Error::GetStorageNode { ref node_id, .. }) if node_id == NODE_ID),
+            "{result:?}"
+        );
+        assert!(node_api.drain_requests().is_empty());
+    }
+
+    /// Returns a Running Pod on NODE_NAME with the labels 'labels', which is Ready if 'ready' is
+    /// set, and whose container has restarted 'restarts' times.
+    fn pod(name: &str, labels: serde_json::Value, ready: bool, restarts: i32) -> Pod {
+        serde_json::from_value(json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": {
+                "name": name,
+                "namespace": NAMESPACE,
+                "uid": format!("{name}-uid"),
+                "labels": labels,
+                "ownerReferences": [{
+                    "apiVersion": "apps/v1",
+                    "kind": "DaemonSet",
+                    "name": "io-engine",
+                    "uid": DAEMONSET_UID,
+                }],
+            },
+            "spec": {
+                "nodeName": NODE_NAME,
+                "containers": [{ "name": IO_ENGINE_CONTAINER_NAME }],
+            },
+            "status": {
+                "phase": "Running",
+                "conditions": [{ "type": "Ready", "status": if ready { "True" } else { "False" } }],
+                "containerStatuses": [{
+                    "name": IO_ENGINE_CONTAINER_NAME,
+                    "image": "io-engine",
+                    "imageID": "io-engine",
+                    "ready": ready,
+                    "restartCount": restarts,
+                }],
+            },
+        }))
+        .unwrap()
+    }
 
-    let pod_list: ObjectList<Pod> = k8s_client
-        .pods_api()
-        .list(&ListParams::default().labels(ETCD_LABEL))
+    /// Returns an io-engine Pod on the version 'version'.
+    fn io_engine_pod(name: &str, version: &str, ready: bool, restarts: i32) -> Pod {
+        pod(
+            name,
+            json!({ "app": "io-engine", VERSION_LABEL_KEY: version }),
+            ready,
+            restarts,
+        )
+    }
+
+    fn control_plane_components() -> ControlPlaneComponents {
+        ControlPlaneComponents {
+            versioned: vec!["app=api-rest".to_string()],
+            unversioned: vec!["app=etcd".to_string()],
+            version_label_key: VERSION_LABEL_KEY.to_string(),
+        }
+    }
+
+    async fn control_plane_readiness(pods: Vec<Pod>) -> ControlPlaneReadiness {
+        control_plane_is_running(
+            NAMESPACE.to_string(),
+            &MockPodInspector::new(pods),
+            &TO_VERSION.to_string(),
+            &control_plane_components(),
+        )
         .await
-        .context(ListPodsWithLabel {
-            label: ETCD_LABEL.to_string(),
-            namespace: namespace.clone(),
-        })?;
-    let etcd_is_ready = all_pods_are_ready(pod_list);
+        .unwrap()
+    }
+
+    async fn data_plane_pod_readiness(
+        pods: Vec<Pod>,
+        deleted_pod_uids: &[&str],
+        max_restarts: Option<i32>,
+    ) -> Result<bool> {
+        let replacement_pods = ReplacementPods {
+            node_name: NODE_NAME,
+            namespace: NAMESPACE.to_string(),
+            upgrade_to_version: &TO_VERSION.to_string(),
+            version_label_key: VERSION_LABEL_KEY,
+            daemonsets: &BTreeMap::from([(DAEMONSET_UID.to_string(), "io-engine".to_string())]),
+            deleted_pod_uids: &deleted_pod_uids.iter().map(|uid| uid.to_string()).collect(),
+        };
+        data_plane_pod_is_running(
+            &replacement_pods,
+            &MockPodInspector::new(pods),
+            max_restarts,
+            &ExtraPodReadiness::default(),
+        )
+        .await
+    }
+
+    #[tokio::test]
+    async fn control_plane_is_ready_once_the_upgraded_pods_are_ready() {
+        let readiness = control_plane_readiness(vec![
+            pod(
+                "api-rest-old",
+                json!({ "app": "api-rest", VERSION_LABEL_KEY: FROM_VERSION }),
+                false,
+                0,
+            ),
+            pod(
+                "api-rest-new",
+                json!({ "app": "api-rest", VERSION_LABEL_KEY: TO_VERSION }),
+                true,
+                0,
+            ),
+            pod("etcd-0", json!({ "app": "etcd" }), true, 0),
+        ])
+        .await;
+
+        assert!(readiness.is_ready());
+        assert!(readiness.unready_components().is_empty());
+    }
+
+    #[tokio::test]
+    async fn control_plane_isnt_ready_while_the_upgraded_pods_arent_ready() {
+        let readiness = control_plane_readiness(vec![
+            pod(
+                "api-rest-old",
+                json!({ "app": "api-rest", VERSION_LABEL_KEY: FROM_VERSION }),
+                true,
+                0,
+            ),
+            pod(
+                "api-rest-new",
+                json!({ "app": "api-rest", VERSION_LABEL_KEY: TO_VERSION }),
+                false,
+                0,
+            ),
+            pod("etcd-0", json!({ "app": "etcd" }), true, 0),
+        ])
+        .await;
 
-    Ok(core_is_ready && rest_is_ready && etcd_is_ready)
+        assert!(!readiness.is_ready());
+        assert_eq!(readiness.unready_components(), vec!["app=api-rest"]);
+    }
+
+    #[tokio::test]
+    async fn control_plane_isnt_ready_while_the_unversioned_pods_arent_ready() {
+        let readiness = control_plane_readiness(vec![
+            pod(
+                "api-rest-new",
+                json!({ "app": "api-rest", VERSION_LABEL_KEY: TO_VERSION }),
+                true,
+                0,
+            ),
+            pod("etcd-0", json!({ "app": "etcd" }), false, 0),
+        ])
+        .await;
+
+        assert!(!readiness.is_ready());
+        assert_eq!(readiness.unready_components(), vec!["app=etcd"]);
+    }
+
+    #[tokio::test]
+    async fn data_plane_pod_is_running_once_the_upgraded_pod_is_ready() {
+        let pods = vec![
+            io_engine_pod("io-engine-old", FROM_VERSION, false, 0),
+            io_engine_pod("io-engine-new", TO_VERSION, true, 0),
+        ];
+
+        assert!(data_plane_pod_readiness(pods, &[], None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn data_plane_pod_isnt_running_until_the_upgraded_pod_is_ready() {
+        let pods = vec![io_engine_pod("io-engine-new", TO_VERSION, false, 0)];
+        assert!(!data_plane_pod_readiness(pods, &[], None).await.unwrap());
+
+        let pods = vec![io_engine_pod("io-engine-old", FROM_VERSION, true, 0)];
+        assert!(!data_plane_pod_readiness(pods, &[], None).await.unwrap());
+    }
+
+    #[tokio::test]
+    async fn data_plane_pod_ignores_the_deleted_pods() {
+        let pods = vec![io_engine_pod("io-engine-new", TO_VERSION, true, 0)];
+
+        assert!(
+            !data_plane_pod_readiness(pods, &["io-engine-new-uid"], None)
+                .await
+                .unwrap()
+        );
+    }
+
+    #[tokio::test]
+    async fn data_plane_pod_fails_once_it_restarts_too_often() {
+        let pods = vec![io_engine_pod("io-engine-new", TO_VERSION, false, 3)];
+
+        let result = data_plane_pod_readiness(pods, &[], Some(2)).await;
+        assert!(
+            matches!(
+                result,
+                Err(Error::DataPlanePodCrashLooping { restarts: 3, .. })
+            ),
+            "{result:?}"
+        );
+    }
+
+    #[tokio::test]
+    async fn drain_completes_against_the_rest_server() {
+        let server = FakeRestServer::start(json!({
+            "cordonedstate": { "cordonlabels": ["maintenance"] }
+        }))
+        .with_drain_completing();
+
+        drain_storage_node(
+            FAKE_NODE_ID,
+            &drain_labels(),
+            &server.rest_client(),
+            None,
+            &DrainDecisionLog::default(),
+            None,
+            WaitLogVerbosity::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            server.calls(),
+            vec![
+                "GET".to_string(),
+                format!("PUT drain {UPGRADE_DRAIN_LABEL}"),
+                "GET".to_string(),
+            ]
+        );
+        assert_eq!(
+            server.cordon_drain_state(),
+            json!({
+                "drainedstate": {
+                    "cordonlabels": ["maintenance"],
+                    "drainlabels": [UPGRADE_DRAIN_LABEL],
+                }
+            })
+        );
+    }
+
+    #[tokio::test]
+    async fn drain_times_out_against_the_rest_server() {
+        let server = FakeRestServer::start(json!({
+            "drainingstate": { "cordonlabels": [], "drainlabels": [UPGRADE_DRAIN_LABEL] }
+        }));
+
+        let result = drain_storage_node(
+            FAKE_NODE_ID,
+            &drain_labels(),
+            &server.rest_client(),
+            Some(Duration::ZERO),
+            &DrainDecisionLog::default(),
+            None,
+            WaitLogVerbosity::default(),
+        )
+        .await;
+        assert!(
+            matches!(result, Err(Error::DrainTimeout { .. })),
+            "{result:?}"
+        );
+        assert_eq!(server.calls(), vec!["GET"]);
+    }
+
+    #[tokio::test]
+    async fn uncordon_removes_the_drain_label_against_the_rest_server() {
+        let server = FakeRestServer::start(json!({
+            "drainedstate": { "cordonlabels": [], "drainlabels": [UPGRADE_DRAIN_LABEL] }
+        }));
+
+        uncordon_node(
+            FAKE_NODE_ID,
+            &drain_labels(),
+            &server.rest_client(),
+            &DrainDecisionLog::default(),
+        )
+        .await
+        .unwrap();
+        assert_eq!(
+            server.calls(),
+            vec![
+                "GET",
+                &format!("DELETE cordon {UPGRADE_DRAIN_LABEL}"),
+                "GET"
+            ]
+        );
+        assert_eq!(server.cordon_drain_state(), serde_json::Value::Null);
+    }
+
+    #[tokio::test]
+    async fn uncordon_fails_against_the_rest_server() {
+        let server = FakeRestServer::start(json!({
+            "drainedstate": { "cordonlabels": [], "drainlabels": [UPGRADE_DRAIN_LABEL] }
+        }))
+        .with_uncordon_failing(http::StatusCode::BAD_REQUEST);
+
+        let result = uncordon_node(
+            FAKE_NODE_ID,
+            &drain_labels(),
+            &server.rest_client(),
+            &DrainDecisionLog::default(),
+        )
+        .await;
+        assert!(
+            matches!(
+                result,
+                Err(Error::StorageNodeUncordon { ref node_id, .. }) if node_id == FAKE_NODE_ID
+            ),
+            "{result:?}"
+        );
+        assert_eq!(
+            server.calls(),
+            vec![
+                "GET".to_string(),
+                format!("DELETE cordon {UPGRADE_DRAIN_LABEL}"),
+            ]
+        );
+    }
 }
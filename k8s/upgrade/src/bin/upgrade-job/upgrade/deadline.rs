@@ -0,0 +1,45 @@
+use crate::common::error::{GlobalUpgradeDeadlineExceeded, Result};
+use std::time::{Duration, Instant};
+use tracing::{info, warn};
+
+/// This is the wall-clock deadline for the data-plane upgrade, i.e. the time at which the
+/// data-plane upgrade started, and the time it may take to upgrade all of the Nodes. Once it has
+/// passed, no more Nodes are upgraded.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct UpgradeDeadline {
+    start: Instant,
+    deadline: Option<Duration>,
+}
+
+impl UpgradeDeadline {
+    /// This starts the deadline for the data-plane upgrade now. There is no deadline if
+    /// 'deadline' is None.
+    pub(crate) fn start(deadline: Option<Duration>) -> Self {
+        if let Some(deadline) = deadline {
+            info!(
+                ?deadline,
+                "The data-plane upgrade must complete within the deadline"
+            );
+        }
+        Self {
+            start: Instant::now(),
+            deadline,
+        }
+    }
+
+    /// Fails with GlobalUpgradeDeadlineExceeded if the data-plane upgrade's deadline has passed.
+    /// This is checked before starting on the next Nodes, so that the Nodes in progress are
+    /// upgraded and uncordoned.
+    pub(crate) fn stop_if_exceeded(&self) -> Result<()> {
+        match self.deadline {
+            Some(deadline) if self.start.elapsed().ge(&deadline) => {
+                warn!(
+                    ?deadline,
+                    "The data-plane upgrade deadline has passed, no more Nodes are upgraded"
+                );
+                GlobalUpgradeDeadlineExceeded { deadline }.fail()
+            }
+            _ => Ok(()),
+        }
+    }
+}
@@ -0,0 +1,391 @@
+use crate::{
+    common::{
+        api_stats::{ApiStats, DELETE_NODE_CORDON, GET_NODES, LIST_K8S_NODES, PATCH_K8S_NODE},
+        constants::{CORDON_EXPIRY_ANNOTATION_KEY, PRODUCT},
+        drain_label::DrainLabels,
+        error::{
+            ListK8sNodes, ListStorageNodes, NodesLeftCordoned, PatchK8sNode, Result,
+            UpgradeCancelled,
+        },
+        kube_client::KubeClientSet,
+        rest_client::RestClientSet,
+    },
+    upgrade::{node_cache::StorageNodeCache, node_id::storage_node_id},
+};
+use k8s_openapi::api::core::v1::Node as K8sNode;
+use kube::{
+    api::{Api, ListParams, Patch, PatchParams},
+    ResourceExt,
+};
+use openapi::models::CordonDrainState;
+use serde_json::json;
+use snafu::{ensure, ResultExt};
+use std::{
+    collections::BTreeSet,
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, SystemTime},
+};
+use tokio::{
+    select,
+    signal::unix::{signal, SignalKind},
+    task::JoinHandle,
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, warn};
+
+/// This resolves when the process receives a SIGTERM or a SIGINT, e.g. when the Job Pod is
+/// deleted or when the Job exceeds its deadline. The returned value is the name of the signal.
+/// If the signal handlers can't be registered, this never resolves.
+pub(crate) async fn termination_signal() -> String {
+    let (mut sigterm, mut sigint) = match (
+        signal(SignalKind::terminate()),
+        signal(SignalKind::interrupt()),
+    ) {
+        (Ok(sigterm), Ok(sigint)) => (sigterm, sigint),
+        (Err(error), _) | (_, Err(error)) => {
+            error!(%error, "Failed to register termination signal handlers");
+            return std::future::pending().await;
+        }
+    };
+
+    select! {
+        _ = sigterm.recv() => "SIGTERM".to_string(),
+        _ = sigint.recv() => "SIGINT".to_string(),
+    }
+}
+
+/// This resolves on the second termination signal. The first termination signal only requests a
+/// graceful shutdown, by cancelling 'cancellation', i.e. the upgrade of the Nodes which are in
+/// progress runs up to their uncordon, and no more Nodes are upgraded. The second one abandons the
+/// Nodes in progress too.
+pub(crate) async fn forced_termination_signal(cancellation: &CancellationToken) -> String {
+    let signal = termination_signal().await;
+    warn!(
+        %signal,
+        "Received termination signal, stopping the data-plane upgrade once the Nodes being \
+        upgraded are uncordoned, send it again to stop immediately"
+    );
+    cancellation.cancel();
+    termination_signal().await
+}
+
+/// Fails with UpgradeCancelled once 'cancellation' is cancelled, i.e. once a graceful shutdown
+/// has been requested. This is checked before starting on the next Nodes.
+pub(crate) fn stop_if_shutdown_requested(cancellation: &CancellationToken) -> Result<()> {
+    ensure!(!cancellation.is_cancelled(), UpgradeCancelled);
+    Ok(())
+}
+
+/// Removes the upgrade drain label from all of the storage Nodes which are draining or are
+/// drained for upgrade, or which are cordoned for upgrade by a soft drain. This is a best-effort
+/// attempt, errors are logged and are not returned.
+pub(crate) async fn uncordon_upgrade_drained_nodes(
+    drain_labels: &DrainLabels,
+    rest_client: &RestClientSet,
+) {
+    if let Err(error) = uncordon_all(drain_labels, rest_client).await {
+        error!(%error, "Failed to remove the upgrade drain label from the {PRODUCT} Nodes");
+    }
+}
+
+/// Removes the upgrade drain label, along with the extra drain labels, from all of the storage
+/// Nodes which carry it, and returns the number of Nodes which were cleared. This may be run at
+/// any time and is idempotent, the Nodes without the drain label are left as they are. A Node
+/// whose labels can't be removed doesn't stop the rest, it fails this with NodesLeftCordoned
+/// once all of the Nodes are cleared.
+pub(crate) async fn uncordon_all(
+    upgrade_drain_labels: &DrainLabels,
+    rest_client: &RestClientSet,
+) -> Result<usize> {
+    uncordon_nodes(upgrade_drain_labels, rest_client, |_| true).await
+}
+
+/// Removes the upgrade drain label, along with the extra drain labels, from the storage Nodes
+/// which carry it and whose id 'include' picks, as uncordon_all does.
+async fn uncordon_nodes(
+    upgrade_drain_labels: &DrainLabels,
+    rest_client: &RestClientSet,
+    include: impl Fn(&str) -> bool,
+) -> Result<usize> {
+    let nodes = rest_client
+        .call(GET_NODES, |client| async move {
+            client.nodes_api().get_nodes(None).await
+        })
+        .await
+        .context(ListStorageNodes)?
+        .into_body();
+
+    let mut cleared_nodes = 0_usize;
+    let mut failed_nodes: Vec<String> = Vec::new();
+    for node in nodes.into_iter().filter(|node| include(node.id.as_str())) {
+        let drain_labels = match node.spec.and_then(|spec| spec.cordondrainstate) {
+            Some(CordonDrainState::drainingstate(drain_state)) => drain_state.drainlabels,
+            Some(CordonDrainState::drainedstate(drain_state)) => drain_state.drainlabels,
+            // A soft drain only cordons the Node.
+            Some(CordonDrainState::cordonedstate(cordon_state)) => cordon_state.cordonlabels,
+            _ => continue,
+        };
+        if !drain_labels
+            .iter()
+            .any(|label| label.eq(upgrade_drain_labels.drain_label()))
+        {
+            continue;
+        }
+
+        // The extra drain labels are removed along with the drain label.
+        let node_id = node.id.as_str();
+        let mut cleared = true;
+        for label in drain_labels
+            .iter()
+            .filter(|label| upgrade_drain_labels.is_upgrade_drain_label(label))
+        {
+            match rest_client
+                .call(DELETE_NODE_CORDON, |client| async move {
+                    client.nodes_api().delete_node_cordon(node_id, label).await
+                })
+                .await
+            {
+                Ok(_) => info!(
+                    node.id = %node.id,
+                    %label,
+                    "Removed drain label from {PRODUCT} Node"
+                ),
+                Err(error) => {
+                    error!(
+                        %error,
+                        node.id = %node.id,
+                        %label,
+                        "Failed to remove drain label from {PRODUCT} Node"
+                    );
+                    cleared = false;
+                }
+            }
+        }
+        if cleared {
+            cleared_nodes += 1;
+        } else {
+            failed_nodes.push(node.id.clone());
+        }
+    }
+
+    ensure!(
+        failed_nodes.is_empty(),
+        NodesLeftCordoned {
+            nodes: failed_nodes
+        }
+    );
+    Ok(cleared_nodes)
+}
+
+/// This is the deadman switch for the Nodes which are cordoned for upgrade, if the upgrade is run
+/// with a cordon TTL. Before a Node is drained, its Kubernetes Node is annotated with the time at
+/// which its cordon expires, 'ttl' from then. The annotation is renewed every third of 'ttl' for
+/// as long as the Node is cordoned and this upgrade-job runs, and it is removed once the Node is
+/// uncordoned. If the upgrade-job dies without uncordoning the Node, e.g. if it crashes or it is
+/// OOM-killed or SIGKILL-ed, the annotation isn't renewed anymore, and the cordon expires 'ttl'
+/// after it was last renewed. The next upgrade-job which is run with a cordon TTL uncordons the
+/// Nodes whose cordon has expired, with uncordon_expired_cordons, before it drains any Node. The
+/// renewal stops when this is dropped.
+pub(crate) struct CordonTtl {
+    ttl: Duration,
+    nodes_api: Api<K8sNode>,
+    api_stats: Arc<ApiStats>,
+    /// The Kubernetes Nodes whose cordon expiry is renewed.
+    cordoned: Arc<Mutex<BTreeSet<String>>>,
+    renewal: JoinHandle<()>,
+}
+
+impl CordonTtl {
+    /// Starts renewing the cordon expiries of the Nodes which are cordoned for upgrade, with
+    /// 'k8s_client'. No Node's expiry is renewed until it is armed.
+    pub(crate) fn new(ttl: Duration, k8s_client: &KubeClientSet) -> Self {
+        let nodes_api = k8s_client.nodes_api().clone();
+        let api_stats = k8s_client.api_stats().clone();
+        let cordoned = Arc::new(Mutex::new(BTreeSet::new()));
+        let renewal = tokio::spawn(renew_cordon_expiries(
+            ttl,
+            nodes_api.clone(),
+            api_stats.clone(),
+            cordoned.clone(),
+        ));
+        Self {
+            ttl,
+            nodes_api,
+            api_stats,
+            cordoned,
+            renewal,
+        }
+    }
+
+    /// Annotates the Kubernetes Node 'node_name' with the expiry of its cordon, and renews it
+    /// until the Node is released. This is done before the Node is drained, so that no Node is
+    /// cordoned for upgrade without an expiry.
+    pub(crate) async fn arm(&self, node_name: &str) -> Result<()> {
+        annotate_cordon_expiry(&self.nodes_api, &self.api_stats, node_name, Some(self.ttl)).await?;
+        self.cordoned
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(node_name.to_string());
+        Ok(())
+    }
+
+    /// Stops renewing the cordon expiry of the Kubernetes Node 'node_name'. The annotation is
+    /// removed if the Node was 'uncordoned', otherwise it is left to expire, so that the next
+    /// upgrade-job retries the uncordon. A failure to remove the annotation is only logged, the
+    /// next upgrade-job removes the expired annotations of the Nodes which aren't cordoned.
+    pub(crate) async fn release(&self, node_name: &str, uncordoned: bool) {
+        self.cordoned
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .remove(node_name);
+        if !uncordoned {
+            warn!(
+                node.name = %node_name,
+                ttl = ?self.ttl,
+                "The node may be left cordoned, its cordon expires once it isn't renewed for the \
+                cordon TTL"
+            );
+            return;
+        }
+
+        if let Err(error) =
+            annotate_cordon_expiry(&self.nodes_api, &self.api_stats, node_name, None).await
+        {
+            warn!(%error, node.name = %node_name, "Failed to remove the cordon expiry of node");
+        }
+    }
+}
+
+impl Drop for CordonTtl {
+    fn drop(&mut self) {
+        self.renewal.abort();
+    }
+}
+
+/// Renews the cordon expiries of the Kubernetes Nodes in 'cordoned' every third of 'ttl'. A
+/// failed renewal is logged, and retried with the next renewal.
+async fn renew_cordon_expiries(
+    ttl: Duration,
+    nodes_api: Api<K8sNode>,
+    api_stats: Arc<ApiStats>,
+    cordoned: Arc<Mutex<BTreeSet<String>>>,
+) {
+    loop {
+        tokio::time::sleep(ttl / 3).await;
+
+        let node_names: Vec<String> = cordoned
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .iter()
+            .cloned()
+            .collect();
+        for node_name in node_names {
+            if let Err(error) =
+                annotate_cordon_expiry(&nodes_api, &api_stats, node_name.as_str(), Some(ttl)).await
+            {
+                warn!(%error, node.name = %node_name, "Failed to renew the cordon expiry of node");
+            }
+        }
+    }
+}
+
+/// Sets the cordon expiry annotation of the Kubernetes Node 'node_name' to 'ttl' from now, or
+/// removes it if 'ttl' is None.
+async fn annotate_cordon_expiry(
+    nodes_api: &Api<K8sNode>,
+    api_stats: &ApiStats,
+    node_name: &str,
+    ttl: Option<Duration>,
+) -> Result<()> {
+    let expiry =
+        ttl.map(|ttl| humantime::format_rfc3339_seconds(SystemTime::now() + ttl).to_string());
+    let patch = json!({
+        "metadata": {
+            "annotations": { CORDON_EXPIRY_ANNOTATION_KEY: expiry }
+        }
+    });
+    api_stats
+        .timed(
+            PATCH_K8S_NODE,
+            nodes_api.patch(node_name, &PatchParams::default(), &Patch::Merge(&patch)),
+        )
+        .await
+        .context(PatchK8sNode {
+            node_name: node_name.to_string(),
+        })?;
+    Ok(())
+}
+
+/// Uncordons the storage Nodes whose cordon for upgrade has expired, i.e. whose Kubernetes Node's
+/// cordon expiry annotation is in the past, as the upgrade-job which cordoned them died without
+/// uncordoning them, and removes their annotations. An annotation which isn't a valid timestamp
+/// is taken to have expired too. The cordons which haven't expired yet are left as they are.
+/// This is run before any Node is drained. Returns the number of Nodes which were uncordoned.
+pub(crate) async fn uncordon_expired_cordons(
+    upgrade_drain_labels: &DrainLabels,
+    k8s_client: &KubeClientSet,
+    rest_client: &RestClientSet,
+    node_cache: &mut StorageNodeCache,
+) -> Result<usize> {
+    let nodes = k8s_client
+        .api_stats()
+        .timed(
+            LIST_K8S_NODES,
+            k8s_client.nodes_api().list(&ListParams::default()),
+        )
+        .await
+        .context(ListK8sNodes)?;
+
+    let now = SystemTime::now();
+    let expired_nodes: Vec<String> = nodes
+        .iter()
+        .filter(|node| {
+            node.annotations()
+                .get(CORDON_EXPIRY_ANNOTATION_KEY)
+                .is_some_and(|expiry| {
+                    humantime::parse_rfc3339(expiry).map_or(true, |expiry| expiry <= now)
+                })
+        })
+        .map(|node| node.name_any())
+        .collect();
+    if expired_nodes.is_empty() {
+        return Ok(0);
+    }
+
+    let mut storage_node_ids: BTreeSet<String> = BTreeSet::new();
+    for node_name in expired_nodes.iter() {
+        warn!(
+            node.name = %node_name,
+            "The node's cordon for upgrade has expired, as the upgrade-job which cordoned it \
+            didn't uncordon it, uncordoning it"
+        );
+        match storage_node_id(node_name, node_cache, k8s_client, rest_client).await? {
+            Some(node_id) => {
+                storage_node_ids.insert(node_id);
+            }
+            None => warn!(
+                node.name = %node_name,
+                "No {PRODUCT} Node runs on the node, only its cordon expiry is removed"
+            ),
+        }
+    }
+
+    let uncordoned_nodes = uncordon_nodes(upgrade_drain_labels, rest_client, |node_id| {
+        storage_node_ids.contains(node_id)
+    })
+    .await?;
+    for node_name in expired_nodes.iter() {
+        annotate_cordon_expiry(
+            k8s_client.nodes_api(),
+            k8s_client.api_stats(),
+            node_name,
+            None,
+        )
+        .await?;
+    }
+    info!(
+        nodes = uncordoned_nodes,
+        "Uncordoned the {PRODUCT} Nodes whose cordon for upgrade had expired"
+    );
+    Ok(uncordoned_nodes)
+}
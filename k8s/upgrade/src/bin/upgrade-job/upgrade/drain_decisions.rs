@@ -0,0 +1,102 @@
+use crate::common::timeline::{Timeline, TimelineEvent};
+use openapi::models::CordonDrainState;
+use serde::Serialize;
+use std::time::SystemTime;
+use tracing::{info, warn};
+
+/// These are the state machines whose decisions are logged.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub(crate) enum StateMachine {
+    /// The drain of a storage Node before its io-engine Pod is restarted.
+    Drain,
+    /// The removal of the upgrade drain label from a storage Node.
+    Uncordon,
+}
+
+/// These are the actions which the state machines take on observing a CordonDrainState.
+#[derive(Clone, Copy, Debug, Serialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub(crate) enum DrainDecision {
+    /// The drain label is absent, so the drain is started.
+    StartDrain,
+    /// The Node is draining or drained with the drain label, but not with all of the extra drain
+    /// labels, so the missing ones are added.
+    AddExtraDrainLabels,
+    /// The Node is draining, so the drain is polled again.
+    WaitForDrain,
+    /// The Node is drained, but the slow cluster simulation holds the phase back.
+    WaitForSimulatedPhase,
+    /// The Node is drained, so the drain is complete.
+    DrainCompleted,
+    /// The drain didn't complete within the drain timeout.
+    TimedOut,
+    /// The Node is drained, so the drain label is removed.
+    RemoveDrainLabel,
+    /// The Node isn't drained with the drain label, so there's nothing left to do.
+    Done,
+    /// The Node no longer exists, e.g. it has been decommissioned, so there's nothing left to do.
+    NodeVanished,
+}
+
+/// This is one entry of the decision log, serialized as a single line of JSON.
+#[derive(Serialize)]
+#[serde(rename_all(serialize = "camelCase"))]
+struct DecisionLogEntry<'a> {
+    timestamp: String,
+    state_machine: StateMachine,
+    node_id: &'a str,
+    observed_state: Option<&'a CordonDrainState>,
+    decision: DrainDecision,
+}
+
+/// This is where the decisions of the drain and the uncordon state machines are recorded. Every
+/// decision is recorded in the timeline, and it is logged too if the decision log is enabled.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct DrainDecisionLog {
+    /// If set, every CordonDrainState observed by the state machines is logged with the action
+    /// taken, so that a drain may be replayed.
+    enabled: bool,
+    timeline: Timeline,
+}
+
+impl DrainDecisionLog {
+    /// Creates a decision log which records the decisions in 'timeline', and which logs them too
+    /// if 'enabled' is set.
+    pub(crate) fn new(enabled: bool, timeline: Timeline) -> Self {
+        Self { enabled, timeline }
+    }
+
+    /// Records the CordonDrainState which a state machine observed for a storage Node, and the
+    /// action it took, in the timeline. This is only logged if the decision log is enabled.
+    pub(crate) fn log(
+        &self,
+        state_machine: StateMachine,
+        node_id: &str,
+        observed_state: Option<&CordonDrainState>,
+        decision: DrainDecision,
+    ) {
+        self.timeline.record(TimelineEvent::StateObservation {
+            state_machine: serde_json::to_value(state_machine).unwrap_or_default(),
+            node_id: node_id.to_string(),
+            observed_state: serde_json::to_value(observed_state).unwrap_or_default(),
+            decision: serde_json::to_value(decision).unwrap_or_default(),
+        });
+
+        if !self.enabled {
+            return;
+        }
+
+        let entry = DecisionLogEntry {
+            timestamp: humantime::format_rfc3339_millis(SystemTime::now()).to_string(),
+            state_machine,
+            node_id,
+            observed_state,
+            decision,
+        };
+        match serde_json::to_string(&entry) {
+            Ok(entry) => info!(drain_decision = %entry, "Drain state machine decision"),
+            Err(error) => warn!(%error, "Failed to serialize drain state machine decision"),
+        }
+    }
+}
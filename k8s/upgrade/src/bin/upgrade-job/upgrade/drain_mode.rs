@@ -0,0 +1,93 @@
+use crate::{
+    common::{
+        api_stats::PUT_NODE_CORDON,
+        constants::PRODUCT,
+        drain_label::DrainLabels,
+        error::{Result, StorageNodeCordon},
+        rest_client::RestClientSet,
+    },
+    upgrade::utils::list_volumes,
+};
+use clap::ValueEnum;
+use snafu::ResultExt;
+use tracing::{info, warn};
+
+/// This decides how a Node is prepared for the restart of its io-engine Pods.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum DrainMode {
+    /// Drain the Node, which moves the targets of all of the volumes off of it, and wait for the
+    /// drain to complete.
+    #[default]
+    Full,
+    /// Only cordon the Node, so that no new targets or replicas are placed on it. The storage
+    /// REST API has no partial drain, so no targets are moved off of the Node, and the volumes
+    /// whose targets are on the Node are reported instead. The I/O to these volumes is
+    /// interrupted while the io-engine restarts, and it resumes only if the application's
+    /// host reconnects to the volume's target once it is back, or if the target is republished
+    /// elsewhere. A volume whose only healthy replica is on the Node is unavailable until the
+    /// io-engine is back.
+    Soft,
+    /// Only cordon the Node, like 'Soft', and don't wait for the volume rebuilds to complete
+    /// before cordoning it either. This is faster, but it risks reduced redundancy while the
+    /// io-engine restarts: a volume whose replicas on other Nodes are still rebuilding from an
+    /// earlier Node's upgrade may be left with no healthy replica until the io-engine is back.
+    CordonOnly,
+}
+
+impl DrainMode {
+    /// Returns true if the volume rebuilds are waited for before the Node is prepared for the
+    /// restart of its io-engine Pods.
+    pub(crate) fn waits_for_rebuilds(&self) -> bool {
+        !matches!(self, Self::CordonOnly)
+    }
+}
+
+/// Cordons the storage Node with the upgrade drain label, and reports the volumes whose targets
+/// are left on the Node.
+pub(crate) async fn soft_drain_storage_node(
+    node_id: &str,
+    drain_labels: &DrainLabels,
+    rest_client: &RestClientSet,
+) -> Result<()> {
+    let drain_label = drain_labels.drain_label();
+    rest_client
+        .call(PUT_NODE_CORDON, |client| async move {
+            client
+                .nodes_api()
+                .put_node_cordon(node_id, drain_label)
+                .await
+        })
+        .await
+        .context(StorageNodeCordon {
+            node_id: node_id.to_string(),
+        })?;
+    info!(
+        node.id = %node_id,
+        label = %drain_label,
+        "Cordoned {PRODUCT} Node"
+    );
+
+    let volumes_with_local_target: Vec<String> = list_volumes(rest_client)
+        .await?
+        .iter()
+        .filter(|volume| {
+            volume
+                .state
+                .target
+                .as_ref()
+                .map_or(false, |target| target.node.eq(node_id))
+        })
+        .map(|volume| volume.spec.uuid.to_string())
+        .collect();
+
+    if !volumes_with_local_target.is_empty() {
+        warn!(
+            node.id = %node_id,
+            volumes = ?volumes_with_local_target,
+            "The targets of these volumes were not relocated off of the {PRODUCT} Node, their I/O \
+            is interrupted while the io-engine restarts"
+        );
+    }
+
+    Ok(())
+}
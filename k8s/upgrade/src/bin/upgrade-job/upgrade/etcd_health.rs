@@ -0,0 +1,68 @@
+use crate::common::error::{
+    EtcdHealthRequest, EtcdHealthResponse, EtcdHealthTimeout, EtcdHealthUri, EtcdUnhealthy, Result,
+};
+use hyper::{body, Client, Uri};
+use serde::Deserialize;
+use snafu::{ensure, ResultExt};
+use std::time::Duration;
+use tracing::info;
+
+/// The time allowed for the etcd health endpoint to respond.
+const ETCD_HEALTH_TIMEOUT: Duration = Duration::from_secs(30);
+
+/// This is the response of etcd's '/health' endpoint.
+#[derive(Deserialize, Debug)]
+struct EtcdHealth {
+    /// This is "true" if the etcd cluster has a leader, and the member can serve requests.
+    health: String,
+    /// The reason for an unhealthy cluster, if any.
+    #[serde(default)]
+    reason: String,
+}
+
+/// Verifies that the etcd cluster is healthy, via the '/health' endpoint of etcd's client URL,
+/// e.g. 'http://mayastor-etcd:2379'. An etcd cluster which has lost its quorum, or which has
+/// been split, reports itself as unhealthy.
+pub(crate) async fn verify_etcd_health(endpoint: &str) -> Result<()> {
+    let uri = format!("{}/health", endpoint.trim_end_matches('/'));
+    let uri: Uri = uri.parse().context(EtcdHealthUri { uri: uri.clone() })?;
+
+    let response = tokio::time::timeout(ETCD_HEALTH_TIMEOUT, Client::new().get(uri.clone()))
+        .await
+        .map_err(|_| {
+            EtcdHealthTimeout {
+                uri: uri.to_string(),
+                timeout: ETCD_HEALTH_TIMEOUT,
+            }
+            .build()
+        })?
+        .context(EtcdHealthRequest {
+            uri: uri.to_string(),
+        })?;
+    let status = response.status();
+    let bytes = body::to_bytes(response.into_body())
+        .await
+        .context(EtcdHealthRequest {
+            uri: uri.to_string(),
+        })?;
+    let health: EtcdHealth = serde_json::from_slice(&bytes).context(EtcdHealthResponse {
+        uri: uri.to_string(),
+    })?;
+
+    info!(
+        etcd.health = %health.health,
+        etcd.reason = %health.reason,
+        http.status = %status,
+        "Checked etcd cluster health"
+    );
+
+    ensure!(
+        status.is_success() && health.health.eq("true"),
+        EtcdUnhealthy {
+            uri: uri.to_string(),
+            reason: health.reason,
+        }
+    );
+
+    Ok(())
+}
@@ -0,0 +1,153 @@
+use crate::upgrade::{
+    context::UpgradeContext,
+    data_plane::upgrade_data_plane,
+    report::{DataPlaneUpgradeReport, NodeUpgradeStatus},
+};
+use futures::{channel::mpsc, stream, Future, Stream, StreamExt};
+use tokio::select;
+use tracing::debug;
+
+/// These are the events of each Node's data-plane upgrade, in the order in which they occur.
+#[derive(Debug)]
+pub(crate) enum UpgradeEvent {
+    /// The Node's upgrade has started.
+    NodeStarted { node_name: String },
+    /// There are no volume rebuilds in progress, so the Node may be drained.
+    RebuildComplete { node_name: String },
+    /// The Node is drained, or only cordoned, as per the drain mode.
+    DrainComplete { node_name: String },
+    /// The Node's io-engine Pods were deleted to restart them.
+    PodDeleted {
+        node_name: String,
+        pod_names: Vec<String>,
+    },
+    /// The replacement io-engine Pods on the Node are ready.
+    PodReady { node_name: String },
+    /// The Node's upgrade is over, e.g. it was upgraded or skipped.
+    NodeComplete {
+        node_name: String,
+        status: NodeUpgradeStatus,
+    },
+    /// The Node's upgrade failed.
+    NodeFailed { node_name: String, error: String },
+    /// The data-plane upgrade is over. This is always the last event.
+    Finished(DataPlaneUpgradeReport),
+}
+
+/// This sends the events of the data-plane upgrade to the stream of upgrade_data_plane_events.
+/// The events are dropped unless they are streamed.
+#[derive(Default)]
+pub(crate) struct UpgradeEventSender {
+    sender: Option<mpsc::UnboundedSender<UpgradeEvent>>,
+}
+
+impl UpgradeEventSender {
+    /// Sends the event to the stream of the data-plane upgrade events, if there is one.
+    pub(crate) fn send(&self, event: UpgradeEvent) {
+        if let Some(sender) = self.sender.as_ref() {
+            // The consumer may have dropped the stream, the upgrade carries on regardless.
+            let _ = sender.unbounded_send(event);
+        }
+    }
+}
+
+/// Upgrades the data-plane like upgrade_data_plane, and streams the events of each Node's upgrade
+/// as they occur, e.g. for a live progress UI. The upgrade is only driven while the stream is
+/// polled. The stream ends with the Finished event, which carries the upgrade report.
+pub(crate) fn upgrade_data_plane_events(
+    mut ctx: UpgradeContext<'_>,
+) -> impl Stream<Item = UpgradeEvent> + '_ {
+    let (sender, receiver) = mpsc::unbounded();
+    ctx.events = UpgradeEventSender {
+        sender: Some(sender),
+    };
+    stream_upgrade_events(Box::pin(upgrade_data_plane(ctx)), receiver)
+}
+
+/// Upgrades the data-plane like upgrade_data_plane, and logs the events of each Node's upgrade as
+/// they occur. Returns the upgrade report which the Finished event carries.
+pub(crate) async fn upgrade_data_plane_logging_events(
+    ctx: UpgradeContext<'_>,
+) -> DataPlaneUpgradeReport {
+    let mut events = Box::pin(upgrade_data_plane_events(ctx));
+    while let Some(event) = events.next().await {
+        match event {
+            UpgradeEvent::Finished(report) => return report,
+            event => debug!(?event, "Data-plane upgrade event"),
+        }
+    }
+    unreachable!("the data-plane upgrade events end with the Finished event")
+}
+
+/// Streams the events which 'upgrade' sends to 'receiver' as they occur, and the Finished event
+/// with the report which 'upgrade' returns once it is over, after all of the other events. The
+/// 'upgrade' is only driven while the stream is polled.
+fn stream_upgrade_events<U>(
+    upgrade: U,
+    receiver: mpsc::UnboundedReceiver<UpgradeEvent>,
+) -> impl Stream<Item = UpgradeEvent>
+where
+    U: Future<Output = DataPlaneUpgradeReport> + Unpin,
+{
+    stream::unfold(Some((upgrade, receiver, None)), |state| async move {
+        let (mut upgrade, mut receiver, mut report) = state?;
+        loop {
+            // The events which are sent already are streamed before the upgrade is driven on.
+            select! {
+                biased;
+                event = receiver.next() => {
+                    let Some(event) = event else {
+                        // The sender is dropped along with the upgrade's context, once it is over.
+                        let report = match report {
+                            Some(report) => report,
+                            None => (&mut upgrade).await,
+                        };
+                        return Some((UpgradeEvent::Finished(report), None));
+                    };
+                    return Some((event, Some((upgrade, receiver, report))));
+                }
+                upgraded = &mut upgrade, if report.is_none() => report = Some(upgraded),
+            }
+        }
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn events_stream_ends_with_the_finished_event() {
+        let (sender, receiver) = mpsc::unbounded();
+        let events = UpgradeEventSender {
+            sender: Some(sender),
+        };
+        let upgrade = Box::pin(async move {
+            events.send(UpgradeEvent::NodeStarted {
+                node_name: "node-1".to_string(),
+            });
+            tokio::task::yield_now().await;
+            events.send(UpgradeEvent::NodeComplete {
+                node_name: "node-1".to_string(),
+                status: NodeUpgradeStatus::Upgraded,
+            });
+            DataPlaneUpgradeReport::new("run-1".to_string())
+        });
+
+        let events: Vec<UpgradeEvent> = stream_upgrade_events(upgrade, receiver).collect().await;
+        assert!(
+            matches!(
+                events.as_slice(),
+                [
+                    UpgradeEvent::NodeStarted { node_name: started },
+                    UpgradeEvent::NodeComplete {
+                        node_name: completed,
+                        status: NodeUpgradeStatus::Upgraded,
+                    },
+                    UpgradeEvent::Finished(_),
+                ] if started == "node-1" && completed == "node-1"
+            ),
+            "{events:?}"
+        );
+    }
+}
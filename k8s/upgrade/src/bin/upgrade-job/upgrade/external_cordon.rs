@@ -0,0 +1,109 @@
+use crate::common::{
+    api_stats::GET_NODE,
+    drain_label::DrainLabels,
+    error::{ExternallyCordonedNode, GetStorageNode, Result},
+    rest_client::RestClientSet,
+};
+use clap::ValueEnum;
+use openapi::models::CordonDrainState;
+use snafu::ResultExt;
+use tracing::{info, warn};
+
+/// This decides what is done with a storage Node which is cordoned or drained with a label other
+/// than the upgrade drain label, e.g. by an operator for maintenance.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum ExternalCordonPolicy {
+    /// Upgrade the Node anyway. The upgrade drain label is added alongside the other labels, and
+    /// only the upgrade drain label is removed once the Node is upgraded.
+    #[default]
+    Ignore,
+    /// Leave the Node as it is, and move on to the next Node.
+    Skip,
+    /// Fail the upgrade.
+    Fail,
+}
+
+/// Returns the cordon and drain labels on the storage Node, other than the upgrade drain labels.
+/// A Node which isn't found has no labels.
+async fn external_cordon_labels(
+    node_id: &str,
+    drain_labels: &DrainLabels,
+    rest_client: &RestClientSet,
+) -> Result<Vec<String>> {
+    let node = match rest_client
+        .call_with_retry(GET_NODE, |client| async move {
+            client.nodes_api().get_node(node_id).await
+        })
+        .await
+    {
+        Ok(node) => node.into_body(),
+        Err(openapi::clients::tower::Error::Response(response))
+            if response.status().as_u16() == 404 =>
+        {
+            return Ok(Vec::new());
+        }
+        Err(error) => {
+            return Err(error).context(GetStorageNode {
+                node_id: node_id.to_string(),
+            })
+        }
+    };
+
+    let labels = match node.spec.and_then(|spec| spec.cordondrainstate) {
+        Some(CordonDrainState::cordonedstate(state)) => state.cordonlabels,
+        Some(CordonDrainState::drainingstate(state)) => {
+            [state.cordonlabels, state.drainlabels].concat()
+        }
+        Some(CordonDrainState::drainedstate(state)) => {
+            [state.cordonlabels, state.drainlabels].concat()
+        }
+        None => Vec::new(),
+    };
+
+    Ok(labels
+        .into_iter()
+        .filter(|label| !drain_labels.is_upgrade_drain_label(label))
+        .collect())
+}
+
+/// Returns true if the storage Node is to be upgraded, as per the policy. A Node which is
+/// cordoned by something other than the upgrade is skipped or fails the upgrade, if the policy
+/// says so.
+pub(crate) async fn may_upgrade_cordoned_node(
+    node_id: &str,
+    policy: ExternalCordonPolicy,
+    drain_labels: &DrainLabels,
+    rest_client: &RestClientSet,
+) -> Result<bool> {
+    if policy == ExternalCordonPolicy::Ignore {
+        return Ok(true);
+    }
+
+    let labels = external_cordon_labels(node_id, drain_labels, rest_client).await?;
+    if labels.is_empty() {
+        return Ok(true);
+    }
+
+    match policy {
+        ExternalCordonPolicy::Skip => {
+            info!(
+                node.id = %node_id,
+                ?labels,
+                "Skipping node, it is cordoned by something other than the upgrade"
+            );
+            Ok(false)
+        }
+        _ => {
+            warn!(
+                node.id = %node_id,
+                ?labels,
+                "Node is cordoned by something other than the upgrade"
+            );
+            ExternallyCordonedNode {
+                node_id: node_id.to_string(),
+                labels,
+            }
+            .fail()
+        }
+    }
+}
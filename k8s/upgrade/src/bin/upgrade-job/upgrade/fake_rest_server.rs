@@ -0,0 +1,200 @@
+use crate::common::rest_client::RestClientSet;
+use hyper::{
+    service::{make_service_fn, service_fn},
+    Body, Request, Response, Server, StatusCode,
+};
+use serde_json::{json, Value};
+use std::{
+    convert::Infallible,
+    net::SocketAddr,
+    sync::{Arc, Mutex, MutexGuard, PoisonError},
+};
+
+/// The id of the storage Node which the FakeRestServer serves.
+pub(crate) const FAKE_NODE_ID: &str = "node-1";
+
+/// This is the storage Node which the FakeRestServer serves, along with the calls made to it.
+struct FakeNode {
+    /// The CordonDrainState of the Node, as it is serialized by the storage REST API.
+    cordon_drain_state: Value,
+    /// If set, a drain request drains the Node at once, otherwise it leaves the Node draining.
+    drain_completes: bool,
+    /// If set, the uncordon requests fail with this status.
+    uncordon_failure: Option<StatusCode>,
+    /// The calls made to the Node, e.g. "PUT drain mayastor-upgrade".
+    calls: Vec<String>,
+}
+
+impl FakeNode {
+    /// Returns the labels in the 'kind' list of the Node's cordon/drain state, e.g. the
+    /// "drainlabels".
+    fn labels(&self, kind: &str) -> Vec<String> {
+        self.cordon_drain_state
+            .as_object()
+            .and_then(|state| state.values().next())
+            .and_then(|state| state.get(kind))
+            .and_then(Value::as_array)
+            .map(|labels| {
+                labels
+                    .iter()
+                    .filter_map(|label| label.as_str().map(ToString::to_string))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Sets the Node's cordon/drain state from its cordon labels and drain labels.
+    fn set_labels(&mut self, cordon_labels: Vec<String>, drain_labels: Vec<String>) {
+        self.cordon_drain_state = match (cordon_labels.is_empty(), drain_labels.is_empty()) {
+            (true, true) => Value::Null,
+            (false, true) => json!({ "cordonedstate": { "cordonlabels": cordon_labels } }),
+            _ if self.drain_completes => json!({
+                "drainedstate": { "cordonlabels": cordon_labels, "drainlabels": drain_labels }
+            }),
+            _ => json!({
+                "drainingstate": { "cordonlabels": cordon_labels, "drainlabels": drain_labels }
+            }),
+        };
+    }
+
+    /// Returns the Node, as it is serialized by the storage REST API.
+    fn to_json(&self) -> Value {
+        json!({
+            "id": FAKE_NODE_ID,
+            "spec": {
+                "id": FAKE_NODE_ID,
+                "grpcEndpoint": "10.0.0.1:10124",
+                "cordondrainstate": self.cordon_drain_state,
+            },
+        })
+    }
+
+    /// Serves the get_node, put_node_drain and delete_node_cordon calls for the Node. Any other
+    /// call is Not Found.
+    fn handle(&mut self, method: &str, path: &str) -> (StatusCode, Value) {
+        let segments: Vec<&str> = path.split('/').filter(|s| !s.is_empty()).collect();
+        let node_call = match segments.iter().position(|segment| segment.eq(&"nodes")) {
+            Some(index) => &segments[index + 1..],
+            None => &[],
+        };
+
+        match (method, node_call) {
+            ("GET", [FAKE_NODE_ID]) => {
+                self.calls.push("GET".to_string());
+            }
+            ("PUT", [FAKE_NODE_ID, "drain", label]) => {
+                self.calls.push(format!("PUT drain {label}"));
+                let mut drain_labels = self.labels("drainlabels");
+                if !drain_labels.iter().any(|drain_label| drain_label.eq(label)) {
+                    drain_labels.push(label.to_string());
+                }
+                self.set_labels(self.labels("cordonlabels"), drain_labels);
+            }
+            ("DELETE", [FAKE_NODE_ID, "cordon", label]) => {
+                self.calls.push(format!("DELETE cordon {label}"));
+                if let Some(status) = self.uncordon_failure {
+                    return (
+                        status,
+                        json!({ "details": "", "message": "uncordon failed", "kind": "Internal" }),
+                    );
+                }
+                let without_label = |labels: Vec<String>| {
+                    labels
+                        .into_iter()
+                        .filter(|other| other.ne(label))
+                        .collect::<Vec<String>>()
+                };
+                self.set_labels(
+                    without_label(self.labels("cordonlabels")),
+                    without_label(self.labels("drainlabels")),
+                );
+            }
+            _ => {
+                return (
+                    StatusCode::NOT_FOUND,
+                    json!({ "details": "", "message": "not found", "kind": "NotFound" }),
+                )
+            }
+        }
+        (StatusCode::OK, self.to_json())
+    }
+}
+
+/// This is an HTTP server which serves the storage REST API calls of the drain and the uncordon
+/// of a single storage Node, FAKE_NODE_ID, for the tests of the drain and uncordon loops. The
+/// server runs until the test's runtime shuts down.
+pub(crate) struct FakeRestServer {
+    address: SocketAddr,
+    node: Arc<Mutex<FakeNode>>,
+}
+
+impl FakeRestServer {
+    /// Starts the server, with the Node in the cordon/drain state 'cordon_drain_state', which is
+    /// e.g. null for a Node which isn't cordoned.
+    pub(crate) fn start(cordon_drain_state: Value) -> Self {
+        let node = Arc::new(Mutex::new(FakeNode {
+            cordon_drain_state,
+            drain_completes: false,
+            uncordon_failure: None,
+            calls: Vec::new(),
+        }));
+
+        let service_node = node.clone();
+        let make_service = make_service_fn(move |_| {
+            let node = service_node.clone();
+            async move {
+                Ok::<_, Infallible>(service_fn(move |request: Request<Body>| {
+                    let (status, body) = node
+                        .lock()
+                        .unwrap_or_else(PoisonError::into_inner)
+                        .handle(request.method().as_str(), request.uri().path());
+                    async move {
+                        Ok::<_, Infallible>(
+                            Response::builder()
+                                .status(status)
+                                .header("content-type", "application/json")
+                                .body(Body::from(body.to_string()))
+                                .unwrap(),
+                        )
+                    }
+                }))
+            }
+        });
+
+        let server = Server::bind(&SocketAddr::from(([127, 0, 0, 1], 0))).serve(make_service);
+        let address = server.local_addr();
+        tokio::spawn(server);
+        Self { address, node }
+    }
+
+    /// Makes the drain requests drain the Node at once, rather than leave it draining.
+    pub(crate) fn with_drain_completing(self) -> Self {
+        self.node().drain_completes = true;
+        self
+    }
+
+    /// Makes the uncordon requests fail with 'status'.
+    pub(crate) fn with_uncordon_failing(self, status: StatusCode) -> Self {
+        self.node().uncordon_failure = Some(status);
+        self
+    }
+
+    /// Returns a RestClientSet for the server.
+    pub(crate) fn rest_client(&self) -> RestClientSet {
+        RestClientSet::new_with_url(format!("http://{}", self.address)).unwrap()
+    }
+
+    /// Returns the Node's cordon/drain state.
+    pub(crate) fn cordon_drain_state(&self) -> Value {
+        self.node().cordon_drain_state.clone()
+    }
+
+    /// Returns the calls made to the Node, in the order they were made in.
+    pub(crate) fn calls(&self) -> Vec<String> {
+        self.node().calls.clone()
+    }
+
+    fn node(&self) -> MutexGuard<'_, FakeNode> {
+        self.node.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+}
@@ -0,0 +1,54 @@
+use crate::common::error::{HookCommand, HookCommandExec, HookCommandTimeout, Result};
+use snafu::{ensure, ResultExt};
+use std::time::Duration;
+use tokio::process::Command;
+use tracing::info;
+
+/// The shell which runs the hook commands.
+const SHELL: &str = "sh";
+
+/// Runs a user-provided hook command with `sh -c`. The hook fails if the command exits with a
+/// non-zero status, or if it doesn't exit within 'timeout', in which case the shell is killed.
+pub(crate) async fn run_hook(hook: &str, command: &str, timeout: Duration) -> Result<()> {
+    info!(hook, command, ?timeout, "Running hook command");
+
+    let output = tokio::time::timeout(
+        timeout,
+        Command::new(SHELL)
+            .args(["-c", command])
+            .kill_on_drop(true)
+            .output(),
+    )
+    .await
+    .map_err(|_| {
+        HookCommandTimeout {
+            hook: hook.to_string(),
+            command: command.to_string(),
+            timeout,
+        }
+        .build()
+    })?
+    .context(HookCommandExec {
+        hook: hook.to_string(),
+        command: command.to_string(),
+    })?;
+
+    info!(
+        hook,
+        stdout = %String::from_utf8_lossy(output.stdout.as_slice()),
+        "Hook command standard output"
+    );
+
+    ensure!(
+        output.status.success(),
+        HookCommand {
+            hook: hook.to_string(),
+            command: command.to_string(),
+            status: output.status.to_string(),
+            std_err: String::from_utf8_lossy(output.stderr.as_slice()).to_string(),
+        }
+    );
+
+    info!(hook, "Hook command succeeded");
+    Ok(())
+}
@@ -0,0 +1,318 @@
+use crate::common::{
+    api_stats::{ApiStats, CREATE_LEASE, GET_LEASE, REPLACE_LEASE},
+    error::{
+        ConcurrentUpgradeInProgress, CreateLease, GetLease, ReplaceLease, Result, UpgradeLeaseLost,
+    },
+    kube_client::KubeClientSet,
+};
+use k8s_openapi::{
+    api::coordination::v1::{Lease, LeaseSpec},
+    apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta},
+    chrono::Utc,
+};
+use kube::api::{Api, PostParams};
+use snafu::ResultExt;
+use std::{
+    sync::{Arc, Mutex, PoisonError},
+    time::{Duration, Instant},
+};
+use tokio::task::JoinHandle;
+use tracing::{error, info, warn};
+
+/// This is a Lease in the upgrade-job's namespace which only one upgrade-job may hold at a time,
+/// so that two upgrade-jobs don't drain Nodes at once. It is renewed in the background while it
+/// is held, and it expires if it isn't renewed for its duration, e.g. if the upgrade-job crashes.
+/// It is released with release(), once the data-plane upgrade is over.
+pub(crate) struct UpgradeLease {
+    api: Api<Lease>,
+    api_stats: Arc<ApiStats>,
+    name: String,
+    holder: String,
+    renewal: JoinHandle<()>,
+    /// The identity of the holder which took the Lease over from this upgrade-job, if it was
+    /// lost. This is set by the renewal.
+    lost_to: Arc<Mutex<Option<String>>>,
+    released: bool,
+}
+
+impl UpgradeLease {
+    /// Acquires the Lease with the name 'name' for 'holder', creating it if it doesn't exist. This
+    /// fails with ConcurrentUpgradeInProgress if another holder holds the Lease, and it hasn't
+    /// expired.
+    pub(crate) async fn acquire(
+        k8s_client: &KubeClientSet,
+        namespace: &str,
+        name: String,
+        holder: String,
+        duration: Duration,
+    ) -> Result<Self> {
+        let api: Api<Lease> = Api::namespaced(k8s_client.client(), namespace);
+        let api_stats = k8s_client.api_stats().clone();
+        let duration_secs = duration.as_secs().max(1) as i32;
+
+        let lease = api_stats
+            .timed(GET_LEASE, api.get_opt(name.as_str()))
+            .await
+            .context(GetLease { name: name.clone() })?;
+        let now = MicroTime(Utc::now());
+        match lease {
+            None => {
+                let lease = Lease {
+                    metadata: ObjectMeta {
+                        name: Some(name.clone()),
+                        ..Default::default()
+                    },
+                    spec: Some(LeaseSpec {
+                        holder_identity: Some(holder.clone()),
+                        lease_duration_seconds: Some(duration_secs),
+                        acquire_time: Some(now.clone()),
+                        renew_time: Some(now),
+                        lease_transitions: Some(0),
+                    }),
+                };
+                match api_stats
+                    .timed(CREATE_LEASE, api.create(&PostParams::default(), &lease))
+                    .await
+                {
+                    Ok(_) => {}
+                    // Another upgrade-job created the Lease first.
+                    Err(kube::Error::Api(response)) if response.code == 409 => {
+                        return ConcurrentUpgradeInProgress {
+                            name,
+                            holder: "unknown".to_string(),
+                        }
+                        .fail();
+                    }
+                    Err(error) => return Err(error).context(CreateLease { name }),
+                }
+            }
+            Some(mut lease) => {
+                let spec = lease.spec.get_or_insert_with(Default::default);
+                if let Some(other_holder) = spec
+                    .holder_identity
+                    .as_ref()
+                    .filter(|other_holder| other_holder.ne(&&holder))
+                {
+                    if !has_expired(spec) {
+                        return ConcurrentUpgradeInProgress {
+                            name,
+                            holder: other_holder.clone(),
+                        }
+                        .fail();
+                    }
+                    warn!(
+                        lease.name = %name,
+                        lease.holder = %other_holder,
+                        "Taking over the upgrade Lease, it has expired"
+                    );
+                }
+                if spec.holder_identity.as_ref() != Some(&holder) {
+                    spec.lease_transitions = Some(spec.lease_transitions.unwrap_or_default() + 1);
+                    spec.acquire_time = Some(now.clone());
+                }
+                spec.holder_identity = Some(holder.clone());
+                spec.lease_duration_seconds = Some(duration_secs);
+                spec.renew_time = Some(now);
+                // The Lease's resourceVersion makes this fail if another upgrade-job updated the
+                // Lease since it was read.
+                match api_stats
+                    .timed(
+                        REPLACE_LEASE,
+                        api.replace(name.as_str(), &PostParams::default(), &lease),
+                    )
+                    .await
+                {
+                    Ok(_) => {}
+                    Err(kube::Error::Api(response)) if response.code == 409 => {
+                        return ConcurrentUpgradeInProgress {
+                            name,
+                            holder: "unknown".to_string(),
+                        }
+                        .fail();
+                    }
+                    Err(error) => return Err(error).context(ReplaceLease { name }),
+                }
+            }
+        }
+
+        info!(
+            lease.name = %name,
+            lease.holder = %holder,
+            ?duration,
+            "Acquired the upgrade Lease"
+        );
+        let lost_to = Arc::new(Mutex::new(None));
+        let renewal = tokio::spawn(renew(
+            api.clone(),
+            api_stats.clone(),
+            name.clone(),
+            holder.clone(),
+            duration,
+            lost_to.clone(),
+        ));
+        Ok(Self {
+            api,
+            api_stats,
+            name,
+            holder,
+            renewal,
+            lost_to,
+            released: false,
+        })
+    }
+
+    /// Fails with UpgradeLeaseLost if the Lease was lost while it was held. This is checked
+    /// before starting on the next Nodes.
+    pub(crate) fn stop_if_lost(&self) -> Result<()> {
+        match self
+            .lost_to
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .as_ref()
+        {
+            Some(holder) => UpgradeLeaseLost {
+                holder: holder.clone(),
+            }
+            .fail(),
+            None => Ok(()),
+        }
+    }
+
+    /// Stops the renewal, and releases the Lease, if this upgrade-job still holds it, so that the
+    /// next upgrade-job needn't wait for it to expire.
+    pub(crate) async fn release(mut self) {
+        self.renewal.abort();
+        self.released = true;
+
+        let mut lease = match self
+            .api_stats
+            .timed(GET_LEASE, self.api.get(self.name.as_str()))
+            .await
+        {
+            Ok(lease) => lease,
+            Err(error) => {
+                warn!(%error, lease.name = %self.name, "Failed to release the upgrade Lease");
+                return;
+            }
+        };
+        let Some(spec) = lease
+            .spec
+            .as_mut()
+            .filter(|spec| spec.holder_identity.as_ref() == Some(&self.holder))
+        else {
+            return;
+        };
+        spec.holder_identity = None;
+        spec.renew_time = None;
+        match self
+            .api_stats
+            .timed(
+                REPLACE_LEASE,
+                self.api
+                    .replace(self.name.as_str(), &PostParams::default(), &lease),
+            )
+            .await
+        {
+            Ok(_) => info!(lease.name = %self.name, "Released the upgrade Lease"),
+            Err(error) => {
+                warn!(%error, lease.name = %self.name, "Failed to release the upgrade Lease")
+            }
+        }
+    }
+}
+
+impl Drop for UpgradeLease {
+    fn drop(&mut self) {
+        self.renewal.abort();
+        if !self.released {
+            warn!(
+                lease.name = %self.name,
+                "The upgrade Lease wasn't released, it expires once it isn't renewed for its \
+                duration"
+            );
+        }
+    }
+}
+
+/// Renews the Lease every third of its duration for as long as 'holder' holds it. If another
+/// holder takes the Lease over, or if it can't be renewed for its duration, the Lease is taken to
+/// be lost, and the renewal stops. The holder which the Lease was lost to is set in 'lost_to'.
+async fn renew(
+    api: Api<Lease>,
+    api_stats: Arc<ApiStats>,
+    name: String,
+    holder: String,
+    duration: Duration,
+    lost_to: Arc<Mutex<Option<String>>>,
+) {
+    let mut renewed_at = Instant::now();
+    loop {
+        tokio::time::sleep(duration / 3).await;
+
+        match renew_once(&api, &api_stats, name.as_str(), holder.as_str()).await {
+            Ok(None) => renewed_at = Instant::now(),
+            Ok(Some(other_holder)) => {
+                error!(
+                    lease.name = %name,
+                    lease.holder = %other_holder,
+                    "The upgrade Lease was taken over by another holder, no more Nodes will be \
+                    upgraded"
+                );
+                *lost_to.lock().unwrap_or_else(PoisonError::into_inner) = Some(other_holder);
+                return;
+            }
+            Err(error) if renewed_at.elapsed() < duration => {
+                warn!(%error, lease.name = %name, "Failed to renew the upgrade Lease, retrying");
+            }
+            Err(error) => {
+                error!(
+                    %error,
+                    lease.name = %name,
+                    "The upgrade Lease has expired, as it couldn't be renewed, no more Nodes will \
+                    be upgraded"
+                );
+                *lost_to.lock().unwrap_or_else(PoisonError::into_inner) =
+                    Some("unknown".to_string());
+                return;
+            }
+        }
+    }
+}
+
+/// Renews the Lease for 'holder'. Returns the identity of the Lease's holder, if it isn't
+/// 'holder'.
+async fn renew_once(
+    api: &Api<Lease>,
+    api_stats: &ApiStats,
+    name: &str,
+    holder: &str,
+) -> kube::Result<Option<String>> {
+    let mut lease = api_stats.timed(GET_LEASE, api.get(name)).await?;
+    let spec = lease.spec.get_or_insert_with(Default::default);
+    if spec.holder_identity.as_deref() != Some(holder) {
+        return Ok(Some(spec.holder_identity.clone().unwrap_or_default()));
+    }
+    spec.renew_time = Some(MicroTime(Utc::now()));
+    api_stats
+        .timed(
+            REPLACE_LEASE,
+            api.replace(name, &PostParams::default(), &lease),
+        )
+        .await?;
+    Ok(None)
+}
+
+/// Returns true if the Lease hasn't been renewed for its duration.
+fn has_expired(spec: &LeaseSpec) -> bool {
+    let Some(renew_time) = spec.renew_time.as_ref() else {
+        return true;
+    };
+    let duration_secs = spec.lease_duration_seconds.unwrap_or_default();
+    (Utc::now() - renew_time.0).num_seconds() >= i64::from(duration_secs)
+}
+
+/// Fails with UpgradeLeaseLost if the upgrade Lease 'lease' was lost while it was held. There is
+/// no Lease to lose if 'lease' is None, e.g. for a dry run.
+pub(crate) fn stop_if_lease_lost(lease: Option<&UpgradeLease>) -> Result<()> {
+    lease.map_or(Ok(()), UpgradeLease::stop_if_lost)
+}
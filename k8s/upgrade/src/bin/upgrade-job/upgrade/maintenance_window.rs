@@ -0,0 +1,118 @@
+use crate::common::health::ForwardProgress;
+use std::{
+    fmt,
+    str::FromStr,
+    time::{Duration, SystemTime, UNIX_EPOCH},
+};
+use tracing::info;
+
+/// The number of seconds in a day.
+const SECONDS_PER_DAY: u64 = 24 * 60 * 60;
+
+/// This is a daily maintenance window, in UTC, e.g. '22:00-04:30'. A window whose end is before
+/// its start spans midnight.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) struct MaintenanceWindow {
+    /// The start of the window, in seconds since midnight.
+    start: u64,
+    /// The end of the window, in seconds since midnight.
+    end: u64,
+}
+
+impl MaintenanceWindow {
+    /// Returns true if the window is open at 'seconds' since midnight.
+    fn contains(&self, seconds: u64) -> bool {
+        if self.start <= self.end {
+            (self.start..self.end).contains(&seconds)
+        } else {
+            seconds >= self.start || seconds < self.end
+        }
+    }
+
+    /// Returns true if the window is open now.
+    pub(crate) fn is_open(&self) -> bool {
+        self.contains(seconds_since_midnight())
+    }
+
+    /// Returns the time left until the window opens, which is zero if the window is open.
+    fn time_until_open(&self) -> Duration {
+        let seconds = seconds_since_midnight();
+        if self.contains(seconds) {
+            return Duration::ZERO;
+        }
+        Duration::from_secs((self.start + SECONDS_PER_DAY - seconds) % SECONDS_PER_DAY)
+    }
+
+    /// Waits until the window is open. The wait is recorded as 'forward_progress'.
+    pub(crate) async fn wait_until_open(&self, forward_progress: &ForwardProgress) {
+        let time_until_open = self.time_until_open();
+        if time_until_open.is_zero() {
+            return;
+        }
+
+        info!(
+            maintenance_window = %self,
+            ?time_until_open,
+            "Outside the maintenance window, waiting for it to open"
+        );
+        // The window is re-checked after sleeping, in case the clock has changed.
+        while !self.time_until_open().is_zero() {
+            // Waiting for the window isn't being hung.
+            forward_progress.record();
+            tokio::time::sleep(self.time_until_open().min(Duration::from_secs(60_u64))).await;
+        }
+        info!(maintenance_window = %self, "Maintenance window is open");
+    }
+}
+
+/// Returns the number of seconds since midnight, in UTC.
+fn seconds_since_midnight() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+        % SECONDS_PER_DAY
+}
+
+/// Parses a time of the day in the format 'HH:MM' into seconds since midnight.
+fn parse_time_of_day(time: &str) -> Result<u64, String> {
+    let invalid = || format!("invalid time of the day '{time}', expected HH:MM");
+    let (hours, minutes) = time.trim().split_once(':').ok_or_else(invalid)?;
+    let hours: u64 = hours.parse().map_err(|_| invalid())?;
+    let minutes: u64 = minutes.parse().map_err(|_| invalid())?;
+    if hours > 23 || minutes > 59 {
+        return Err(invalid());
+    }
+    Ok(hours * 3600 + minutes * 60)
+}
+
+impl FromStr for MaintenanceWindow {
+    type Err = String;
+
+    fn from_str(window: &str) -> Result<Self, Self::Err> {
+        let (start, end) = window.split_once('-').ok_or_else(|| {
+            format!("invalid maintenance window '{window}', expected HH:MM-HH:MM")
+        })?;
+        let start = parse_time_of_day(start)?;
+        let end = parse_time_of_day(end)?;
+        if start == end {
+            return Err(format!(
+                "invalid maintenance window '{window}', the start and the end must differ"
+            ));
+        }
+        Ok(Self { start, end })
+    }
+}
+
+impl fmt::Display for MaintenanceWindow {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "{:02}:{:02}-{:02}:{:02}",
+            self.start / 3600,
+            self.start % 3600 / 60,
+            self.end / 3600,
+            self.end % 3600 / 60
+        )
+    }
+}
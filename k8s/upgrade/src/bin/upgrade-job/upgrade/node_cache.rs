@@ -0,0 +1,84 @@
+use crate::common::{
+    api_stats::{GET_NODE, GET_NODES},
+    error::{GetStorageNode, ListStorageNodes, Result},
+    rest_client::{is_not_found, RestClientSet},
+};
+use openapi::models::Node;
+use snafu::ResultExt;
+use tracing::debug;
+
+/// This is a list of the storage Nodes. If caching is enabled, the list is fetched once and is
+/// reused for the rest of the upgrade, and is only re-fetched when a Node isn't found in it
+/// (e.g. a Node which was added to the cluster during the upgrade). The cached list is only ever
+/// used for lookups which don't need an up-to-date Node state, e.g. the drain state machine
+/// always GETs the Node.
+pub(crate) struct StorageNodeCache {
+    enabled: bool,
+    nodes: Option<Vec<Node>>,
+}
+
+impl StorageNodeCache {
+    /// Creates a new StorageNodeCache. Nothing is fetched until the Nodes are needed.
+    pub(crate) fn new(enabled: bool) -> Self {
+        Self {
+            enabled,
+            nodes: None,
+        }
+    }
+
+    /// Returns the list of storage Nodes. This is only fetched from the storage REST API if
+    /// caching is disabled, or if the list hasn't been fetched yet.
+    pub(crate) async fn nodes(&mut self, rest_client: &RestClientSet) -> Result<&[Node]> {
+        if !self.enabled || self.nodes.is_none() {
+            self.refresh(rest_client).await?;
+        }
+
+        Ok(self.nodes.as_deref().unwrap_or_default())
+    }
+
+    /// Returns the storage Node with the id 'node_id', or None if there is no such Node.
+    pub(crate) async fn node(
+        &mut self,
+        node_id: &str,
+        rest_client: &RestClientSet,
+    ) -> Result<Option<Node>> {
+        if !self.enabled {
+            return match rest_client
+                .call(GET_NODE, |client| async move {
+                    client.nodes_api().get_node(node_id).await
+                })
+                .await
+            {
+                Ok(node) => Ok(Some(node.into_body())),
+                Err(error) if is_not_found(&error) => Ok(None),
+                Err(error) => Err(error).context(GetStorageNode {
+                    node_id: node_id.to_string(),
+                }),
+            };
+        }
+
+        let find = |nodes: &[Node]| nodes.iter().find(|node| node.id.eq(node_id)).cloned();
+
+        if let Some(node) = find(self.nodes(rest_client).await?) {
+            return Ok(Some(node));
+        }
+
+        // The Node may have been added after the list was cached.
+        debug!(node.id = %node_id, "Storage Node not found in cached list, re-fetching list");
+        self.refresh(rest_client).await?;
+        Ok(find(self.nodes.as_deref().unwrap_or_default()))
+    }
+
+    /// Fetches the list of storage Nodes from the storage REST API.
+    async fn refresh(&mut self, rest_client: &RestClientSet) -> Result<()> {
+        let nodes = rest_client
+            .call(GET_NODES, |client| async move {
+                client.nodes_api().get_nodes(None).await
+            })
+            .await
+            .context(ListStorageNodes)?
+            .into_body();
+        self.nodes = Some(nodes);
+        Ok(())
+    }
+}
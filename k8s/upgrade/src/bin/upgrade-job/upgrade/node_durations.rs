@@ -0,0 +1,209 @@
+use serde::Serialize;
+use std::time::Duration;
+use tracing::{info, warn};
+
+/// A Node whose upgrade takes longer than this many times its estimated duration is logged as
+/// an outlier.
+const OUTLIER_FACTOR: f64 = 2.0;
+
+/// This is the time spent waiting for the volume rebuilds before a Node is drained.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct RebuildWait {
+    /// The time spent in the fixed initial grace period, for any rebuilds to kick in.
+    pub(crate) grace_period: Duration,
+    /// The time spent polling for the rebuilds to complete, after the grace period.
+    pub(crate) polling: Duration,
+}
+
+/// This is the time spent in each of the phases of one Node's data-plane upgrade.
+#[derive(Clone, Copy, Debug, Default)]
+pub(crate) struct NodePhaseDurations {
+    /// The time spent waiting for the control plane to be Ready before the Node is drained.
+    pub(crate) control_plane_wait: Duration,
+    /// The time spent waiting for the volume rebuilds before the Node is drained.
+    pub(crate) rebuild: RebuildWait,
+    /// The time spent draining the Node.
+    pub(crate) drain: Duration,
+    /// The time spent deleting the io-engine Pods and waiting for their replacements to be
+    /// scheduled.
+    pub(crate) pod_restart: Duration,
+    /// The time spent waiting for the replacement io-engine Pods to be ready.
+    pub(crate) readiness: Duration,
+}
+
+impl NodePhaseDurations {
+    /// Returns the name of the phase which took the longest.
+    fn dominant_phase(&self) -> &'static str {
+        [
+            ("controlPlaneWait", self.control_plane_wait),
+            ("rebuildGracePeriod", self.rebuild.grace_period),
+            ("rebuildPolling", self.rebuild.polling),
+            ("drain", self.drain),
+            ("podRestart", self.pod_restart),
+            ("readiness", self.readiness),
+        ]
+        .into_iter()
+        .max_by_key(|(_, duration)| *duration)
+        .map(|(phase, _)| phase)
+        .unwrap_or_default()
+    }
+
+    /// Returns the time spent in each of the phases, in seconds, along with the phase which took
+    /// the longest.
+    pub(crate) fn timings(&self) -> NodePhaseTimings {
+        NodePhaseTimings {
+            dominant_phase: self.dominant_phase(),
+            control_plane_wait_secs: self.control_plane_wait.as_secs_f64(),
+            rebuild_grace_period_secs: self.rebuild.grace_period.as_secs_f64(),
+            rebuild_polling_secs: self.rebuild.polling.as_secs_f64(),
+            drain_secs: self.drain.as_secs_f64(),
+            pod_restart_secs: self.pod_restart.as_secs_f64(),
+            readiness_secs: self.readiness.as_secs_f64(),
+        }
+    }
+
+    /// Returns the total time spent in all of the phases.
+    fn total(&self) -> Duration {
+        self.control_plane_wait
+            + self.rebuild.grace_period
+            + self.rebuild.polling
+            + self.drain
+            + self.pod_restart
+            + self.readiness
+    }
+}
+
+/// This is the time spent in each of the phases of one Node's data-plane upgrade, in seconds.
+#[derive(Serialize, Clone, Copy, Debug)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub(crate) struct NodePhaseTimings {
+    /// The phase which took the longest.
+    dominant_phase: &'static str,
+    control_plane_wait_secs: f64,
+    rebuild_grace_period_secs: f64,
+    rebuild_polling_secs: f64,
+    drain_secs: f64,
+    pod_restart_secs: f64,
+    readiness_secs: f64,
+}
+
+/// This is the estimated and the actual duration of one Node's data-plane upgrade.
+#[derive(Serialize, Debug)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub(crate) struct NodeDuration {
+    node_name: String,
+    /// The mean duration of the Nodes which were upgraded before this one. This is absent for
+    /// the first Node.
+    estimated_secs: Option<f64>,
+    actual_secs: f64,
+    /// The actual duration minus the estimated duration.
+    deviation_secs: Option<f64>,
+    outlier: bool,
+    #[serde(flatten)]
+    phases: NodePhaseTimings,
+    /// The time which isn't attributed to any of the phases, e.g. the checks after the upgrade.
+    other_secs: f64,
+}
+
+/// This records how long each Node's data-plane upgrade took, against an estimate derived from
+/// the Nodes which were upgraded before it. This is serialized as the list of the Nodes'
+/// durations, for the data-plane upgrade report.
+#[derive(Serialize, Debug, Default)]
+#[serde(transparent)]
+pub(crate) struct NodeDurations {
+    durations: Vec<NodeDuration>,
+}
+
+impl NodeDurations {
+    /// The estimated duration of the next Node's upgrade, i.e. the mean duration of the Nodes
+    /// which have been upgraded so far.
+    pub(crate) fn estimate(&self) -> Option<Duration> {
+        if self.durations.is_empty() {
+            return None;
+        }
+        let total: f64 = self.durations.iter().map(|d| d.actual_secs).sum();
+        Some(Duration::from_secs_f64(total / self.durations.len() as f64))
+    }
+
+    /// Records the actual duration of a Node's upgrade along with the time spent in each of its
+    /// phases, and logs it if it took far longer than its estimate.
+    pub(crate) fn record(&mut self, node_name: &str, actual: Duration, phases: NodePhaseDurations) {
+        let estimated = self.estimate();
+        let outlier = estimated
+            .map(|estimated| actual.as_secs_f64() > estimated.as_secs_f64() * OUTLIER_FACTOR)
+            .unwrap_or(false);
+
+        let timings = phases.timings();
+        let dominant_phase = timings.dominant_phase;
+        if outlier {
+            warn!(
+                node.name = %node_name,
+                estimated = ?estimated.unwrap_or_default(),
+                ?actual,
+                dominant_phase,
+                ?phases,
+                "Data-plane upgrade of Node took far longer than estimated"
+            );
+        } else {
+            info!(
+                node.name = %node_name,
+                ?estimated,
+                ?actual,
+                dominant_phase,
+                ?phases,
+                "Data-plane upgrade of Node completed"
+            );
+        }
+
+        self.durations.push(NodeDuration {
+            node_name: node_name.to_string(),
+            estimated_secs: estimated.map(|estimated| estimated.as_secs_f64()),
+            actual_secs: actual.as_secs_f64(),
+            deviation_secs: estimated
+                .map(|estimated| actual.as_secs_f64() - estimated.as_secs_f64()),
+            outlier,
+            phases: timings,
+            other_secs: actual.saturating_sub(phases.total()).as_secs_f64(),
+        });
+    }
+
+    /// Logs the total time spent upgrading the Nodes, the estimated and actual durations of all of
+    /// the Nodes as a JSON document, and the total time spent in the rebuild grace period against
+    /// the total time spent polling for rebuilds. If the grace period is longer than the rebuilds
+    /// on most Nodes, it may be lowered.
+    pub(crate) fn log_summary(&self) {
+        let grace_period: f64 = self
+            .durations
+            .iter()
+            .map(|d| d.phases.rebuild_grace_period_secs)
+            .sum();
+        let polling: f64 = self
+            .durations
+            .iter()
+            .map(|d| d.phases.rebuild_polling_secs)
+            .sum();
+        let total: f64 = self.durations.iter().map(|d| d.actual_secs).sum();
+        info!(
+            total = ?Duration::from_secs_f64(total),
+            nodes = self.durations.len(),
+            "Total time spent upgrading Nodes"
+        );
+        let grace_period_exceeded_rebuild = self
+            .durations
+            .iter()
+            .filter(|d| d.phases.rebuild_grace_period_secs > d.phases.rebuild_polling_secs)
+            .count();
+        info!(
+            rebuild.grace_period = ?Duration::from_secs_f64(grace_period),
+            rebuild.polling = ?Duration::from_secs_f64(polling),
+            nodes = self.durations.len(),
+            nodes_with_grace_period_exceeding_rebuild = grace_period_exceeded_rebuild,
+            "Time spent waiting for volume rebuilds"
+        );
+
+        match serde_json::to_string(&self.durations) {
+            Ok(summary) => info!(node_durations = %summary, "Data-plane Node upgrade durations"),
+            Err(error) => warn!(%error, "Failed to serialize data-plane Node upgrade durations"),
+        }
+    }
+}
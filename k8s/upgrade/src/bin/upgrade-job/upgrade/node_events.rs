@@ -0,0 +1,108 @@
+use crate::{
+    common::{api_stats::CREATE_EVENT, kube_client::KubeClientSet},
+    upgrade::{report::DataPlaneUpgradeReport, warnings::UpgradeWarningKind},
+};
+use k8s_openapi::{
+    api::{
+        core::v1::{ObjectReference, Pod},
+        events::v1::Event,
+    },
+    apimachinery::pkg::apis::meta::v1::{MicroTime, ObjectMeta},
+    chrono::Utc,
+};
+use kube::{api::PostParams, Resource};
+use std::fmt;
+use tracing::warn;
+
+/// The name of the controller which reports the data-plane upgrade Events.
+const REPORTING_CONTROLLER: &str = "openebs.io/upgrade-job";
+
+/// These are the reasons of the Events which are posted at each of the phases of a Node's
+/// data-plane upgrade.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum NodeEventReason {
+    DrainStarted,
+    RebuildComplete,
+    PodDeleted,
+    PodReady,
+    NodeUncordoned,
+}
+
+impl fmt::Display for NodeEventReason {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let reason = match self {
+            Self::DrainStarted => "UpgradeDrainStarted",
+            Self::RebuildComplete => "UpgradeRebuildComplete",
+            Self::PodDeleted => "UpgradePodDeleted",
+            Self::PodReady => "UpgradePodReady",
+            Self::NodeUncordoned => "UpgradeNodeUncordoned",
+        };
+        write!(f, "{reason}")
+    }
+}
+
+/// Posts a Normal Event with the 'reason' and the 'message' against the 'involved_object'. This
+/// is informational, so a failure to post the Event is logged and is recorded as a warning in the
+/// 'report', it is not returned.
+pub(crate) async fn emit_event(
+    reason: NodeEventReason,
+    message: String,
+    involved_object: ObjectReference,
+    k8s_client: &KubeClientSet,
+    report: &DataPlaneUpgradeReport,
+) {
+    let event = Event {
+        metadata: ObjectMeta {
+            generate_name: Some("upgrade-".to_string()),
+            namespace: involved_object.namespace.clone(),
+            ..Default::default()
+        },
+        event_time: MicroTime(Utc::now()),
+        type_: Some("Normal".to_string()),
+        reason: Some(reason.to_string()),
+        action: Some(reason.to_string()),
+        note: Some(message),
+        regarding: Some(involved_object),
+        reporting_controller: Some(REPORTING_CONTROLLER.to_string()),
+        reporting_instance: Some(REPORTING_CONTROLLER.to_string()),
+        ..Default::default()
+    };
+
+    if let Err(error) = k8s_client
+        .api_stats()
+        .timed(
+            CREATE_EVENT,
+            k8s_client
+                .events_api()
+                .create(&PostParams::default(), &event),
+        )
+        .await
+    {
+        warn!(%error, %reason, "Failed to post data-plane upgrade Event");
+        report.record_warning(
+            UpgradeWarningKind::EventNotPosted,
+            None,
+            format!("Failed to post the {reason} Event: {error}"),
+        );
+    }
+}
+
+/// Posts the Event against each of the io-engine Pods.
+pub(crate) async fn emit_pod_events(
+    reason: NodeEventReason,
+    message: &str,
+    pods: &[Pod],
+    k8s_client: &KubeClientSet,
+    report: &DataPlaneUpgradeReport,
+) {
+    for pod in pods {
+        emit_event(
+            reason,
+            message.to_string(),
+            pod.object_ref(&()),
+            k8s_client,
+            report,
+        )
+        .await;
+    }
+}
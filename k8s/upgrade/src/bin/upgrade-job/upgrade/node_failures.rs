@@ -0,0 +1,103 @@
+use crate::common::error::{
+    DataPlaneNodesFailed, Error, Result, TooManyConsecutiveNodeFailures, TooManyNodeFailures,
+};
+use tracing::{error, info};
+
+/// This keeps track of the Nodes which failed to upgrade when the upgrade continues on errors.
+/// It trips if too many Nodes fail one after the other, as that is more likely to be caused by
+/// something systemic than by the Nodes themselves, or if too many Nodes fail in total.
+pub(crate) struct NodeFailures {
+    max_consecutive_failures: Option<u32>,
+    max_failures: Option<u32>,
+    consecutive_failures: u32,
+    failed_nodes: Vec<String>,
+}
+
+impl NodeFailures {
+    /// Creates a NodeFailures which trips after 'max_consecutive_failures' consecutive failures,
+    /// if set, or after more than 'max_failures' failures in total, if set.
+    pub(crate) fn new(max_consecutive_failures: Option<u32>, max_failures: Option<u32>) -> Self {
+        Self {
+            max_consecutive_failures,
+            max_failures,
+            consecutive_failures: 0,
+            failed_nodes: Vec::new(),
+        }
+    }
+
+    /// Returns true if the Node has failed to upgrade.
+    pub(crate) fn has_failed(&self, node_name: &str) -> bool {
+        self.failed_nodes.iter().any(|node| node.eq(node_name))
+    }
+
+    /// Records a successful Node upgrade. This resets the count of consecutive failures.
+    pub(crate) fn record_success(&mut self) {
+        self.consecutive_failures = 0;
+    }
+
+    /// Records a failed Node upgrade. This fails if the maximum number of consecutive failures
+    /// has been reached, or if the maximum number of failures has been exceeded.
+    pub(crate) fn record_failure(&mut self, node_name: &str, error: &Error) -> Result<()> {
+        self.failed_nodes.push(node_name.to_string());
+        self.consecutive_failures += 1;
+
+        if let Some(max_failures) = self.max_failures {
+            if self.failed_nodes.len() > max_failures as usize {
+                error!(
+                    failures = self.failed_nodes.len(),
+                    max_failures,
+                    nodes = ?self.failed_nodes,
+                    last_error = %error,
+                    "Aborting the data-plane upgrade, too many node failures"
+                );
+                return TooManyNodeFailures {
+                    failures: self.failed_nodes.len(),
+                    max_failures,
+                    nodes: self.failed_nodes.clone(),
+                }
+                .fail();
+            }
+        }
+
+        match self.max_consecutive_failures {
+            Some(max) if self.consecutive_failures >= max => {
+                let nodes: Vec<String> = self
+                    .failed_nodes
+                    .iter()
+                    .rev()
+                    .take(self.consecutive_failures as usize)
+                    .rev()
+                    .cloned()
+                    .collect();
+                error!(
+                    consecutive_failures = self.consecutive_failures,
+                    ?nodes,
+                    last_error = %error,
+                    "Aborting the data-plane upgrade, too many consecutive node failures"
+                );
+                TooManyConsecutiveNodeFailures {
+                    failures: self.consecutive_failures,
+                    nodes,
+                }
+                .fail()
+            }
+            _ => Ok(()),
+        }
+    }
+
+    /// Reports the Nodes which failed to upgrade, if any, and fails if there are any.
+    pub(crate) fn finish(&self) -> Result<()> {
+        if self.failed_nodes.is_empty() {
+            return Ok(());
+        }
+
+        info!(
+            failed_nodes = ?self.failed_nodes,
+            "Data-plane upgrade completed with failed nodes"
+        );
+        DataPlaneNodesFailed {
+            nodes: self.failed_nodes.clone(),
+        }
+        .fail()
+    }
+}
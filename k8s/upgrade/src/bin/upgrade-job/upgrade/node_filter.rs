@@ -0,0 +1,74 @@
+use k8s_openapi::api::core::v1::Pod;
+use std::collections::HashSet;
+use tracing::info;
+
+/// This selects the Nodes which take part in a targeted data-plane upgrade, e.g. for a staged
+/// rollout. If an include list is set, only the Nodes on it are upgraded. The Nodes on the
+/// exclude list are never upgraded, even if they're on the include list.
+pub(crate) struct NodeFilter {
+    include: Option<HashSet<String>>,
+    exclude: HashSet<String>,
+    /// The Nodes which have been reported as filtered out, so that they're only logged once.
+    logged: HashSet<String>,
+}
+
+impl NodeFilter {
+    /// Creates a NodeFilter from the include and the exclude lists.
+    pub(crate) fn new(include: Option<Vec<String>>, exclude: Vec<String>) -> Self {
+        let filter = Self {
+            include: include.map(|nodes| nodes.into_iter().collect()),
+            exclude: exclude.into_iter().collect(),
+            logged: HashSet::new(),
+        };
+        if let Some(include) = filter.include.as_ref() {
+            info!(nodes = ?include, "Only the included nodes are upgraded");
+        }
+        if !filter.exclude.is_empty() {
+            info!(nodes = ?filter.exclude, "The excluded nodes are not upgraded");
+        }
+        filter
+    }
+
+    /// Removes the io-engine Pods on the Nodes which are filtered out, and logs each such Node
+    /// along with the reason, the first time it is filtered out.
+    pub(crate) fn retain(&mut self, pods: &mut Vec<Pod>) {
+        if self.include.is_none() && self.exclude.is_empty() {
+            return;
+        }
+
+        pods.retain(|pod| {
+            let node_name = node_name(pod);
+            let Some(reason) = self.filtered_out_reason(node_name) else {
+                return true;
+            };
+            if self.logged.insert(node_name.to_string()) {
+                info!(node.name = %node_name, reason, "Skipping node, it is filtered out");
+            }
+            false
+        });
+    }
+
+    /// Returns true if the io-engine Pod is on a Node which is upgraded.
+    pub(crate) fn allows(&self, pod: &Pod) -> bool {
+        self.filtered_out_reason(node_name(pod)).is_none()
+    }
+
+    /// Returns the reason for the Node being filtered out, if it is.
+    fn filtered_out_reason(&self, node_name: &str) -> Option<&'static str> {
+        match self.include.as_ref() {
+            _ if self.exclude.contains(node_name) => Some("node is on the exclude list"),
+            Some(include) if !include.contains(node_name) => {
+                Some("node is not on the include list")
+            }
+            _ => None,
+        }
+    }
+}
+
+/// Returns the name of the Node the Pod is scheduled on, or an empty string if there is none.
+fn node_name(pod: &Pod) -> &str {
+    pod.spec
+        .as_ref()
+        .and_then(|spec| spec.node_name.as_deref())
+        .unwrap_or_default()
+}
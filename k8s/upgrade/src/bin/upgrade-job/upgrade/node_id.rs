@@ -0,0 +1,118 @@
+use crate::{
+    common::{
+        api_stats::GET_K8S_NODE,
+        error::{GetK8sNode, Result},
+        kube_client::KubeClientSet,
+        rest_client::RestClientSet,
+    },
+    upgrade::node_cache::StorageNodeCache,
+};
+use k8s_openapi::api::core::v1::Node as K8sNode;
+use openapi::models::Node;
+use snafu::ResultExt;
+use std::collections::BTreeSet;
+use tracing::{info, warn};
+
+/// The Kubernetes Node label which carries the Node's hostname.
+const HOSTNAME_LABEL_KEY: &str = "kubernetes.io/hostname";
+
+/// Returns the id of the storage Node which runs on the Kubernetes Node 'node_name'. This is the
+/// Kubernetes Node name itself, unless the control-plane knows the Node by another id, e.g. if
+/// its hostname is overridden. Then the storage Node is the one whose id is the Kubernetes Node's
+/// hostname label or one of its addresses, or whose gRPC endpoint is on one of its addresses.
+/// Returns None if no storage Node, or more than one, matches.
+pub(crate) async fn storage_node_id(
+    node_name: &str,
+    node_cache: &mut StorageNodeCache,
+    k8s_client: &KubeClientSet,
+    rest_client: &RestClientSet,
+) -> Result<Option<String>> {
+    if node_cache.node(node_name, rest_client).await?.is_some() {
+        return Ok(Some(node_name.to_string()));
+    }
+
+    let Some(k8s_node) = k8s_client
+        .api_stats()
+        .timed(GET_K8S_NODE, k8s_client.nodes_api().get_opt(node_name))
+        .await
+        .context(GetK8sNode {
+            node_name: node_name.to_string(),
+        })?
+    else {
+        warn!(
+            node.name = %node_name,
+            "Kubernetes Node not found, it can't be mapped to a storage Node"
+        );
+        return Ok(None);
+    };
+    let identities = node_identities(&k8s_node);
+
+    let matches: Vec<&str> = node_cache
+        .nodes(rest_client)
+        .await?
+        .iter()
+        .filter(|node| {
+            identities.contains(node.id.as_str())
+                || grpc_endpoint_hosts(node).any(|host| identities.contains(host))
+        })
+        .map(|node| node.id.as_str())
+        .collect();
+
+    match matches.as_slice() {
+        [node_id] => {
+            info!(
+                node.name = %node_name,
+                node.id = %node_id,
+                "The storage Node's id differs from its Kubernetes Node's name"
+            );
+            Ok(Some(node_id.to_string()))
+        }
+        [] => Ok(None),
+        _ => {
+            warn!(
+                node.name = %node_name,
+                node.ids = ?matches,
+                "More than one storage Node matches the Kubernetes Node"
+            );
+            Ok(None)
+        }
+    }
+}
+
+/// Returns the hostname label and the addresses of the Kubernetes Node, which the storage Node
+/// may be known by.
+fn node_identities(node: &K8sNode) -> BTreeSet<&str> {
+    let hostname = node
+        .metadata
+        .labels
+        .as_ref()
+        .and_then(|labels| labels.get(HOSTNAME_LABEL_KEY))
+        .map(String::as_str);
+    let addresses = node
+        .status
+        .as_ref()
+        .and_then(|status| status.addresses.as_ref())
+        .into_iter()
+        .flatten()
+        .map(|address| address.address.as_str());
+    hostname.into_iter().chain(addresses).collect()
+}
+
+/// Returns the hosts of the storage Node's gRPC endpoints, i.e. without their ports.
+fn grpc_endpoint_hosts(node: &Node) -> impl Iterator<Item = &str> {
+    let spec_endpoint = node.spec.as_ref().map(|spec| spec.grpc_endpoint.as_str());
+    let state_endpoint = node
+        .state
+        .as_ref()
+        .map(|state| state.grpc_endpoint.as_str());
+    spec_endpoint
+        .into_iter()
+        .chain(state_endpoint)
+        .map(|endpoint| {
+            endpoint
+                .rsplit_once(':')
+                .map_or(endpoint, |(host, _port)| host)
+                .trim_start_matches('[')
+                .trim_end_matches(']')
+        })
+}
@@ -0,0 +1,108 @@
+use crate::common::{
+    api_stats::GET_K8S_NODE,
+    constants::UPGRADE_SKIP_ANNOTATION_KEY,
+    error::{GetK8sNode, NodeLabelsChanged, Result},
+    kube_client::KubeClientSet,
+};
+use kube::ResourceExt;
+use snafu::ResultExt;
+use std::collections::BTreeMap;
+use tracing::{info, warn};
+
+/// These are the values of the monitored labels of a Kubernetes Node. A label which is absent
+/// from the Node has the value None.
+pub(crate) type NodeLabels = BTreeMap<String, Option<String>>;
+
+/// GETs the Kubernetes Node and collects the values of the labels with the keys
+/// 'monitored_labels'.
+pub(crate) async fn monitored_node_labels(
+    node_name: &str,
+    monitored_labels: &[String],
+    k8s_client: &KubeClientSet,
+) -> Result<NodeLabels> {
+    if monitored_labels.is_empty() {
+        return Ok(NodeLabels::new());
+    }
+
+    let node = k8s_client
+        .api_stats()
+        .timed(GET_K8S_NODE, k8s_client.nodes_api().get(node_name))
+        .await
+        .context(GetK8sNode {
+            node_name: node_name.to_string(),
+        })?;
+    let labels = node.metadata.labels.unwrap_or_default();
+
+    Ok(monitored_labels
+        .iter()
+        .map(|key| (key.clone(), labels.get(key).cloned()))
+        .collect())
+}
+
+/// Returns true if the Kubernetes Node has the upgrade skip annotation set to "true", i.e. if it is
+/// pinned to the version it is on. A Node which isn't found isn't pinned.
+pub(crate) async fn has_upgrade_skip_annotation(
+    node_name: &str,
+    k8s_client: &KubeClientSet,
+) -> Result<bool> {
+    let node = k8s_client
+        .api_stats()
+        .timed(GET_K8S_NODE, k8s_client.nodes_api().get_opt(node_name))
+        .await
+        .context(GetK8sNode {
+            node_name: node_name.to_string(),
+        })?;
+
+    Ok(node.is_some_and(|node| {
+        node.annotations()
+            .get(UPGRADE_SKIP_ANNOTATION_KEY)
+            .is_some_and(|value| value.eq("true"))
+    }))
+}
+
+/// Verifies that the monitored labels of the Kubernetes Node have the same values as they did
+/// in 'labels_before'. Changed labels fail the upgrade only if 'strict' is set.
+pub(crate) async fn verify_node_labels_unchanged(
+    node_name: &str,
+    labels_before: &NodeLabels,
+    k8s_client: &KubeClientSet,
+    strict: bool,
+) -> Result<()> {
+    if labels_before.is_empty() {
+        return Ok(());
+    }
+
+    let monitored_labels: Vec<String> = labels_before.keys().cloned().collect();
+    let labels_after = monitored_node_labels(node_name, &monitored_labels, k8s_client).await?;
+
+    let changed: Vec<String> = labels_before
+        .iter()
+        .filter(|(key, before)| labels_after.get(*key) != Some(*before))
+        .map(|(key, _)| key.clone())
+        .collect();
+
+    if changed.is_empty() {
+        info!(node.name = %node_name, "Monitored Node labels are unchanged");
+        return Ok(());
+    }
+
+    for key in changed.iter() {
+        warn!(
+            node.name = %node_name,
+            label = %key,
+            before = ?labels_before.get(key).cloned().flatten(),
+            after = ?labels_after.get(key).cloned().flatten(),
+            "Monitored Node label changed during the upgrade"
+        );
+    }
+
+    if strict {
+        return NodeLabelsChanged {
+            node_name: node_name.to_string(),
+            labels: changed,
+        }
+        .fail();
+    }
+
+    Ok(())
+}
@@ -0,0 +1,165 @@
+use crate::{
+    common::{error::Result, rest_client::RestClientSet},
+    upgrade::utils::{list_volumes, nodes_hosting_volume},
+};
+use clap::ValueEnum;
+use k8s_openapi::api::core::v1::Pod;
+use kube::ResourceExt;
+use std::collections::{HashMap, HashSet};
+use tracing::{info, warn};
+
+/// This decides whether the Nodes which host the priority volumes are upgraded before or after
+/// the rest of the Nodes.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum PriorityVolumesOrder {
+    /// Upgrade the Nodes hosting the priority volumes before the other Nodes.
+    #[default]
+    First,
+    /// Upgrade the Nodes hosting the priority volumes after the other Nodes.
+    Last,
+}
+
+/// This orders the io-engine Pods within each of the groups made by the primary ordering, i.e.
+/// the Nodes hosting the priority volumes and the rest of the Nodes.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum SecondaryOrder {
+    /// Keep the order in which the Pods are listed.
+    #[default]
+    None,
+    /// Upgrade the oldest Pods first.
+    Age,
+    /// Upgrade the Pods whose containers have restarted the most first.
+    RestartCount,
+    /// Upgrade the Pods in the order of the names of their Nodes.
+    NodeName,
+    /// Upgrade the Pods on the Nodes which host the fewest volume replicas first, so that most
+    /// of the Nodes are upgraded quickly, and the Nodes with the most rebuilds come last.
+    FewestReplicas,
+}
+
+/// This orders the io-engine Pods for the data-plane upgrade.
+#[derive(Default)]
+pub(crate) struct NodeOrder {
+    priority_nodes: HashSet<String>,
+    priority_volumes_order: PriorityVolumesOrder,
+    secondary_order: SecondaryOrder,
+    /// The number of volume replicas on each Node. This is only collected for the
+    /// FewestReplicas order.
+    replica_counts: HashMap<String, usize>,
+}
+
+impl NodeOrder {
+    /// Creates a NodeOrder which moves the Nodes hosting the target or the replicas of any of
+    /// the priority volumes to the front or the back of the upgrade order, and which orders the
+    /// Pods by 'secondary_order' within the priority Nodes and within the rest of the Nodes.
+    pub(crate) async fn new(
+        rest_client: &RestClientSet,
+        priority_volumes: &[String],
+        priority_volumes_order: PriorityVolumesOrder,
+        secondary_order: SecondaryOrder,
+    ) -> Result<Self> {
+        if priority_volumes.is_empty() && secondary_order != SecondaryOrder::FewestReplicas {
+            return Ok(Self {
+                secondary_order,
+                ..Default::default()
+            });
+        }
+
+        let volumes = list_volumes(rest_client).await?;
+
+        let mut replica_counts: HashMap<String, usize> = HashMap::new();
+        if secondary_order == SecondaryOrder::FewestReplicas {
+            for node in volumes.iter().flat_map(|volume| {
+                volume
+                    .state
+                    .replica_topology
+                    .values()
+                    .filter_map(|topology| topology.node.as_ref())
+            }) {
+                *replica_counts.entry(node.clone()).or_default() += 1;
+            }
+            info!(?replica_counts, "Counted volume replicas per Node");
+        }
+
+        let mut priority_nodes = HashSet::new();
+        for volume_uuid in priority_volumes {
+            match volumes
+                .iter()
+                .find(|volume| volume.spec.uuid.to_string().eq(volume_uuid))
+            {
+                Some(volume) => {
+                    let nodes = nodes_hosting_volume(volume);
+                    info!(
+                        volume.uuid = %volume_uuid,
+                        ?nodes,
+                        "Found Nodes hosting priority volume"
+                    );
+                    priority_nodes.extend(nodes.into_iter().map(ToString::to_string));
+                }
+                None => warn!(volume.uuid = %volume_uuid, "Priority volume not found"),
+            }
+        }
+
+        Ok(Self {
+            priority_nodes,
+            priority_volumes_order,
+            secondary_order,
+            replica_counts,
+        })
+    }
+
+    /// Sorts the io-engine Pods so that the ones on the priority Nodes are upgraded first or
+    /// last. The Pods are sorted by the secondary order first, and as the sorts are stable, the
+    /// secondary order holds within the priority Nodes and within the rest of the Nodes.
+    pub(crate) fn sort(&self, pods: &mut [Pod]) {
+        match self.secondary_order {
+            SecondaryOrder::None => {}
+            // Pods without a creation timestamp are considered to be the newest.
+            SecondaryOrder::Age => pods.sort_by_key(|pod| {
+                let created = pod.creation_timestamp();
+                (created.is_none(), created)
+            }),
+            SecondaryOrder::RestartCount => {
+                pods.sort_by_key(|pod| std::cmp::Reverse(restart_count(pod)))
+            }
+            SecondaryOrder::NodeName => pods.sort_by(|a, b| node_name(a).cmp(&node_name(b))),
+            SecondaryOrder::FewestReplicas => pods.sort_by_key(|pod| {
+                node_name(pod)
+                    .and_then(|node| self.replica_counts.get(node))
+                    .copied()
+                    .unwrap_or_default()
+            }),
+        }
+
+        if self.priority_nodes.is_empty() && self.secondary_order == SecondaryOrder::None {
+            return;
+        }
+
+        pods.sort_by_key(|pod| {
+            let is_priority_node = node_name(pod)
+                .map(|node| self.priority_nodes.contains(node))
+                .unwrap_or(false);
+            match self.priority_volumes_order {
+                PriorityVolumesOrder::First => !is_priority_node,
+                PriorityVolumesOrder::Last => is_priority_node,
+            }
+        });
+
+        let ordering: Vec<&str> = pods.iter().filter_map(node_name).collect();
+        info!(nodes = ?ordering, "Derived data-plane upgrade Node ordering");
+    }
+}
+
+/// Returns the total number of restarts of the Pod's containers.
+fn restart_count(pod: &Pod) -> i32 {
+    pod.status
+        .as_ref()
+        .and_then(|status| status.container_statuses.as_ref())
+        .map(|statuses| statuses.iter().map(|status| status.restart_count).sum())
+        .unwrap_or_default()
+}
+
+/// Returns the name of the Node the Pod is scheduled on, if any.
+fn node_name(pod: &Pod) -> Option<&str> {
+    pod.spec.as_ref().and_then(|spec| spec.node_name.as_deref())
+}
@@ -1,6 +1,5 @@
 use crate::{
     common::{
-        constants::CHART_VERSION_LABEL_KEY,
         error::{
             ListDeploymentsWithLabel, NoRestDeployment, NoVersionLabelInDeployment, ReadingFile,
             Result, SemverParse, YamlParseBufferForUnsupportedVersion, YamlParseFromFile,
@@ -37,10 +36,13 @@ pub(crate) fn version_from_chart_yaml_file(path: PathBuf) -> Result<Version> {
     Ok(to_chart.version().clone())
 }
 
-/// Generate a semver::Version from the CHART_VERSION_LABEL_KEY label on the Storage REST API
+/// Generate a semver::Version from the chart version label on the Storage REST API
 /// Deployment.
-pub(crate) async fn version_from_rest_deployment_label(ns: &str) -> Result<Version> {
-    let labels = format!("{API_REST_LABEL},{CHART_VERSION_LABEL_KEY}");
+pub(crate) async fn version_from_rest_deployment_label(
+    ns: &str,
+    version_label_key: &str,
+) -> Result<Version> {
+    let labels = format!("{API_REST_LABEL},{version_label_key}");
 
     let k8s_client = KubeClientSet::builder().with_namespace(ns).build().await?;
     let mut deploy_list = k8s_client
@@ -77,8 +79,9 @@ pub(crate) async fn version_from_rest_deployment_label(ns: &str) -> Result<Versi
     // the 'already_upgraded' case in         crate::helm::upgrade. The upgraded version will be
     // on the latest-created REST API deployment.
     let deploy = &deploy_list.items[0];
-    let deploy_version = deploy.labels().get(CHART_VERSION_LABEL_KEY).ok_or(
+    let deploy_version = deploy.labels().get(version_label_key).ok_or(
         NoVersionLabelInDeployment {
+            label_key: version_label_key.to_string(),
             deployment_name: deploy.name_any(),
             namespace: ns.to_string(),
         }
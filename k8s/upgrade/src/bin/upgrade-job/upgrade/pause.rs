@@ -0,0 +1,90 @@
+use crate::common::{health::ForwardProgress, progress::UpgradeProgress};
+use std::{
+    sync::{
+        atomic::{AtomicBool, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+use tokio::{
+    select,
+    signal::unix::{signal, SignalKind},
+};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info};
+
+/// This is the pause state of the data-plane upgrade. SIGUSR1 pauses the upgrade, and SIGUSR2
+/// resumes it, once the signals are handled. The upgrade of the Node which is in progress is not
+/// interrupted, the upgrade waits before starting on the next Node.
+#[derive(Default)]
+pub(crate) struct UpgradePause {
+    /// This is set while the data-plane upgrade is paused.
+    paused: Arc<AtomicBool>,
+}
+
+impl UpgradePause {
+    /// Starts listening for SIGUSR1 and SIGUSR2, which pause and resume the data-plane upgrade.
+    /// The pause state is recorded in 'progress' too.
+    pub(crate) fn handle_signals(&self, progress: &UpgradeProgress) {
+        let (mut sigusr1, mut sigusr2) = match (
+            signal(SignalKind::user_defined1()),
+            signal(SignalKind::user_defined2()),
+        ) {
+            (Ok(sigusr1), Ok(sigusr2)) => (sigusr1, sigusr2),
+            (Err(error), _) | (_, Err(error)) => {
+                error!(%error, "Failed to register pause and resume signal handlers");
+                return;
+            }
+        };
+
+        let state = self.paused.clone();
+        let progress = progress.clone();
+        tokio::spawn(async move {
+            loop {
+                let paused = select! {
+                    _ = sigusr1.recv() => true,
+                    _ = sigusr2.recv() => false,
+                };
+                if state.swap(paused, Ordering::SeqCst) != paused {
+                    progress.set_paused(paused);
+                    if paused {
+                        info!(
+                            "Received SIGUSR1, pausing the data-plane upgrade after the current \
+                            Node"
+                        );
+                    } else {
+                        info!("Received SIGUSR2, resuming the data-plane upgrade");
+                    }
+                }
+            }
+        });
+    }
+
+    /// Returns true while the data-plane upgrade is paused.
+    fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Waits for as long as the data-plane upgrade is paused, or until a shutdown is requested by
+    /// cancelling 'cancellation'. The wait is recorded as 'forward_progress'.
+    pub(crate) async fn wait_while_paused(
+        &self,
+        cancellation: &CancellationToken,
+        forward_progress: &ForwardProgress,
+    ) {
+        if !self.is_paused() {
+            return;
+        }
+
+        info!("Data-plane upgrade is paused, send SIGUSR2 to resume");
+        while self.is_paused() && !cancellation.is_cancelled() {
+            // A paused upgrade isn't hung.
+            forward_progress.record();
+            select! {
+                _ = tokio::time::sleep(Duration::from_secs(1_u64)) => {}
+                _ = cancellation.cancelled() => {}
+            }
+        }
+        info!("Data-plane upgrade resumed");
+    }
+}
@@ -0,0 +1,178 @@
+use crate::common::{
+    api_stats::CREATE_ACCESS_REVIEW,
+    error::{CreateSelfSubjectAccessReview, MissingPermissions, Result},
+    kube_client::KubeClientSet,
+};
+use k8s_openapi::api::authorization::v1::{
+    ResourceAttributes, SelfSubjectAccessReview, SelfSubjectAccessReviewSpec,
+};
+use kube::api::{Api, PostParams};
+use snafu::ResultExt;
+use tracing::{error, info};
+
+/// This is a Kubernetes API permission which the data-plane upgrade needs.
+struct Permission {
+    verb: &'static str,
+    group: &'static str,
+    resource: &'static str,
+    /// This is empty for the resource itself.
+    subresource: &'static str,
+    /// This is false for the cluster-scoped resources.
+    namespaced: bool,
+}
+
+impl Permission {
+    const fn new(
+        verb: &'static str,
+        group: &'static str,
+        resource: &'static str,
+        namespaced: bool,
+    ) -> Self {
+        Self {
+            verb,
+            group,
+            resource,
+            subresource: "",
+            namespaced,
+        }
+    }
+
+    /// This is the permission for the subresource 'subresource' of the resource.
+    const fn on_subresource(self, subresource: &'static str) -> Self {
+        Self {
+            subresource,
+            ..self
+        }
+    }
+}
+
+/// These are the permissions which the data-plane upgrade needs, other than the deletes.
+const READ_PERMISSIONS: [Permission; 5] = [
+    Permission::new("list", "", "pods", true),
+    Permission::new("get", "", "pods", true),
+    Permission::new("list", "apps", "daemonsets", true),
+    Permission::new("get", "", "nodes", false),
+    Permission::new("create", "events.k8s.io", "events", true),
+];
+
+/// These are the permissions which the data-plane upgrade needs to restart the io-engine Pods.
+const DELETE_PERMISSIONS: [Permission; 1] = [Permission::new("delete", "", "pods", true)];
+
+/// These are the permissions which the data-plane upgrade needs to evict the io-engine Pods,
+/// instead of deleting them.
+const EVICTION_PERMISSIONS: [Permission; 1] =
+    [Permission::new("create", "", "pods", true).on_subresource("eviction")];
+
+/// These are the permissions which the data-plane upgrade needs to fetch the io-engine container
+/// logs, when the io-engine Pods don't become Ready.
+const LOG_PERMISSIONS: [Permission; 1] =
+    [Permission::new("get", "", "pods", true).on_subresource("log")];
+
+/// These are the permissions which the data-plane upgrade needs to hold the upgrade Lease.
+const LEASE_PERMISSIONS: [Permission; 3] = [
+    Permission::new("get", "coordination.k8s.io", "leases", true),
+    Permission::new("create", "coordination.k8s.io", "leases", true),
+    Permission::new("update", "coordination.k8s.io", "leases", true),
+];
+
+/// These are the permissions which the data-plane upgrade needs to annotate the Nodes with the
+/// expiry of their cordon, and to find the Nodes whose cordon has expired.
+const CORDON_TTL_PERMISSIONS: [Permission; 2] = [
+    Permission::new("list", "", "nodes", false),
+    Permission::new("patch", "", "nodes", false),
+];
+
+/// Validates that the upgrade-job's ServiceAccount may make the Kubernetes API calls which the
+/// data-plane upgrade needs, using a SelfSubjectAccessReview for each of them, so that a missing
+/// permission fails the upgrade before any Node is drained. The missing permissions are reported
+/// together. The deletes and the Lease permissions aren't checked for a dry run, as a dry run
+/// doesn't hold the Lease. The evictions are checked too if 'evict' is set, and the container logs
+/// if 'fetch_logs' is set, and the Node patches if 'cordon_ttl' is set, other than for a dry run,
+/// which restarts no Pods and cordons no Nodes.
+pub(crate) async fn verify_permissions(
+    namespace: &str,
+    k8s_client: &KubeClientSet,
+    dry_run: bool,
+    evict: bool,
+    fetch_logs: bool,
+    cordon_ttl: bool,
+) -> Result<()> {
+    let reviews_api: Api<SelfSubjectAccessReview> = Api::all(k8s_client.client());
+
+    let mut missing_permissions: Vec<String> = Vec::new();
+    let (deletes, leases) = if dry_run {
+        (&[][..], &[][..])
+    } else {
+        (&DELETE_PERMISSIONS[..], &LEASE_PERMISSIONS[..])
+    };
+    // An evicted Pod may still be force-deleted, the deletes are needed either way.
+    let evictions = if evict && !dry_run {
+        &EVICTION_PERMISSIONS[..]
+    } else {
+        &[][..]
+    };
+    let logs = if fetch_logs && !dry_run {
+        &LOG_PERMISSIONS[..]
+    } else {
+        &[][..]
+    };
+    let cordon_expiries = if cordon_ttl && !dry_run {
+        &CORDON_TTL_PERMISSIONS[..]
+    } else {
+        &[][..]
+    };
+    for permission in READ_PERMISSIONS
+        .iter()
+        .chain(deletes)
+        .chain(evictions)
+        .chain(logs)
+        .chain(cordon_expiries)
+        .chain(leases)
+    {
+        let review = SelfSubjectAccessReview {
+            spec: SelfSubjectAccessReviewSpec {
+                resource_attributes: Some(ResourceAttributes {
+                    verb: Some(permission.verb.to_string()),
+                    group: Some(permission.group.to_string()),
+                    resource: Some(permission.resource.to_string()),
+                    subresource: (!permission.subresource.is_empty())
+                        .then(|| permission.subresource.to_string()),
+                    namespace: permission.namespaced.then(|| namespace.to_string()),
+                    ..Default::default()
+                }),
+                ..Default::default()
+            },
+            ..Default::default()
+        };
+        let review = k8s_client
+            .api_stats()
+            .timed(
+                CREATE_ACCESS_REVIEW,
+                reviews_api.create(&PostParams::default(), &review),
+            )
+            .await
+            .context(CreateSelfSubjectAccessReview)?;
+
+        if !review.status.is_some_and(|status| status.allowed) {
+            let resource = match permission.subresource {
+                "" => permission.resource.to_string(),
+                subresource => format!("{}/{subresource}", permission.resource),
+            };
+            missing_permissions.push(format!("{} {resource}", permission.verb));
+        }
+    }
+
+    if !missing_permissions.is_empty() {
+        error!(
+            permissions = ?missing_permissions,
+            "The upgrade-job's ServiceAccount lacks permissions which the data-plane upgrade needs"
+        );
+        return MissingPermissions {
+            permissions: missing_permissions,
+        }
+        .fail();
+    }
+
+    info!("Verified the permissions for the data-plane upgrade");
+    Ok(())
+}
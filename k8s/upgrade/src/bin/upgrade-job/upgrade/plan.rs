@@ -0,0 +1,159 @@
+use crate::{
+    common::{
+        api_stats::ApiStats,
+        constants::IO_ENGINE_LABEL,
+        error::{ListPodsWithLabel, Result},
+        kube_client::KubeClientSet,
+    },
+    opts::CliArgs,
+    upgrade::{
+        node_filter::NodeFilter, pod_selection::is_owned_by_daemonset, utils::list_all_pods,
+    },
+};
+use k8s_openapi::api::core::v1::Pod;
+use kube::{api::ListParams, ResourceExt};
+use snafu::ResultExt;
+use std::{fmt, sync::Arc};
+
+/// This is what the data-plane upgrade would do with an io-engine Pod.
+#[derive(Clone, Copy, Debug, Eq, PartialEq)]
+pub(crate) enum PlannedAction {
+    /// The Pod would be restarted on the target version.
+    Upgrade,
+    /// The Pod is on the target version already.
+    UpToDate,
+    /// The Pod's Node is filtered out by the include or the exclude list.
+    FilteredOut,
+    /// The Pod isn't owned by the selected io-engine DaemonSet.
+    OtherDaemonSet,
+}
+
+impl fmt::Display for PlannedAction {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let action = match self {
+            Self::Upgrade => "upgrade",
+            Self::UpToDate => "up-to-date",
+            Self::FilteredOut => "skip (filtered out)",
+            Self::OtherDaemonSet => "skip (other DaemonSet)",
+        };
+        write!(f, "{action}")
+    }
+}
+
+/// This is one io-engine Pod's entry in the data-plane upgrade plan.
+#[derive(Debug)]
+pub(crate) struct NodePlanEntry {
+    pub(crate) node_name: String,
+    pub(crate) pod_name: String,
+    /// The value of the Pod's chart version label, if it has one.
+    pub(crate) version: Option<String>,
+    pub(crate) action: PlannedAction,
+}
+
+/// Lists every io-engine Pod, along with its Node, its chart version and what the data-plane
+/// upgrade to 'upgrade_to_version' would do with it. This only reads from the cluster, and the API
+/// calls are recorded in 'api_stats'.
+pub(crate) async fn data_plane_plan(
+    opts: &CliArgs,
+    upgrade_to_version: &str,
+    api_stats: &Arc<ApiStats>,
+) -> Result<Vec<NodePlanEntry>> {
+    let namespace = opts.namespace();
+    let k8s_client = KubeClientSet::builder()
+        .with_namespace(namespace.clone())
+        .with_kubeconfig(opts.kubeconfig(), opts.kube_context())
+        .with_api_stats(api_stats.clone())
+        .build()
+        .await?;
+
+    let mut pods: Vec<Pod> =
+        list_all_pods(&k8s_client, &ListParams::default().labels(IO_ENGINE_LABEL))
+            .await
+            .context(ListPodsWithLabel {
+                label: IO_ENGINE_LABEL.to_string(),
+                namespace,
+            })?;
+    pods.sort_by_key(|pod| (node_name(pod).to_string(), pod.name_any()));
+
+    let node_filter = NodeFilter::new(opts.include_nodes(), opts.exclude_nodes());
+    let io_engine_daemonset = opts.io_engine_daemonset();
+    let version_label_key = opts.chart_version_label_key();
+
+    Ok(pods
+        .iter()
+        .map(|pod| {
+            let version = pod.labels().get(version_label_key.as_str()).cloned();
+            // A Pod without the version label is matched by the upgrade's label selector.
+            let action = match version.as_deref() {
+                Some(version) if version.eq(upgrade_to_version) => PlannedAction::UpToDate,
+                _ if !node_filter.allows(pod) => PlannedAction::FilteredOut,
+                _ if io_engine_daemonset
+                    .as_deref()
+                    .is_some_and(|daemonset| !is_owned_by_daemonset(pod, daemonset)) =>
+                {
+                    PlannedAction::OtherDaemonSet
+                }
+                _ => PlannedAction::Upgrade,
+            };
+            NodePlanEntry {
+                node_name: node_name(pod).to_string(),
+                pod_name: pod.name_any(),
+                version,
+                action,
+            }
+        })
+        .collect())
+}
+
+/// Prints the data-plane upgrade plan to stdout as a table.
+pub(crate) fn print_plan(from_version: &str, to_version: &str, entries: &[NodePlanEntry]) {
+    let rows: Vec<[String; 4]> = entries
+        .iter()
+        .map(|entry| {
+            [
+                entry.node_name.clone(),
+                entry.pod_name.clone(),
+                entry
+                    .version
+                    .clone()
+                    .unwrap_or_else(|| "<none>".to_string()),
+                entry.action.to_string(),
+            ]
+        })
+        .collect();
+    let header = ["NODE", "POD", "VERSION", "ACTION"].map(ToString::to_string);
+
+    let mut widths = header.clone().map(|column| column.len());
+    for row in rows.iter() {
+        for (width, column) in widths.iter_mut().zip(row.iter()) {
+            *width = (*width).max(column.len());
+        }
+    }
+
+    println!("Data-plane upgrade plan from version {from_version} to version {to_version}:");
+    for row in std::iter::once(&header).chain(rows.iter()) {
+        let line: Vec<String> = row
+            .iter()
+            .zip(widths.iter())
+            .map(|(column, width)| format!("{column:<width$}"))
+            .collect();
+        println!("{}", line.join("  ").trim_end());
+    }
+
+    let upgrades = entries
+        .iter()
+        .filter(|entry| entry.action.eq(&PlannedAction::Upgrade))
+        .count();
+    println!(
+        "{upgrades} of {} io-engine Pods would be upgraded",
+        entries.len()
+    );
+}
+
+/// Returns the name of the Node the Pod is scheduled on, or an empty name if it isn't.
+fn node_name(pod: &Pod) -> &str {
+    pod.spec
+        .as_ref()
+        .and_then(|spec| spec.node_name.as_deref())
+        .unwrap_or_default()
+}
@@ -0,0 +1,150 @@
+use crate::common::error::{EmptyPodNodeName, EmptyPodSpec, InvalidPodsInList, Result};
+use clap::ValueEnum;
+use k8s_openapi::api::core::v1::Pod;
+use kube::ResourceExt;
+use tracing::warn;
+
+/// This decides which of the yet-to-upgrade io-engine Pods on a Node are deleted each time the
+/// Node is drained, when there is more than one such Pod on the Node (e.g. when there is more
+/// than one io-engine DaemonSet).
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum IoEnginePodSelection {
+    /// Delete all of the Node's yet-to-upgrade io-engine Pods with a single drain.
+    #[default]
+    All,
+    /// Delete only the oldest of the Node's yet-to-upgrade io-engine Pods. The rest of the Pods
+    /// are upgraded with subsequent drains of the Node.
+    Oldest,
+}
+
+/// These are the io-engine Pods which are deleted together on one Node.
+pub(crate) struct NodePods {
+    pub(crate) node_name: String,
+    pub(crate) pods: Vec<Pod>,
+}
+
+/// Validates that all of the io-engine Pods have a .spec and a .spec.nodeName, so that all of the
+/// Pods which can't be upgraded are reported together, before any Node is drained.
+pub(crate) fn validate_pods(pods: &[Pod], namespace: &str) -> Result<()> {
+    let invalid_pods: Vec<String> = pods
+        .iter()
+        .filter_map(|pod| match pod.spec.as_ref() {
+            None => Some(format!("{} (no .spec)", pod.name_any())),
+            Some(spec) if spec.node_name.is_none() => {
+                Some(format!("{} (no .spec.nodeName)", pod.name_any()))
+            }
+            Some(_) => None,
+        })
+        .collect();
+
+    if !invalid_pods.is_empty() {
+        return InvalidPodsInList {
+            namespace: namespace.to_string(),
+            pods: invalid_pods,
+        }
+        .fail();
+    }
+    Ok(())
+}
+
+/// Groups the yet-to-upgrade io-engine Pods by the Node they're on, preserving the order in
+/// which the Nodes first appear in 'pods'. If 'daemonset' is set, only the Pods owned by the
+/// DaemonSet with that name are selected, the rest are left as they are.
+pub(crate) fn select_pods_per_node(
+    pods: &[Pod],
+    selection: IoEnginePodSelection,
+    daemonset: Option<&str>,
+    namespace: &str,
+) -> Result<Vec<NodePods>> {
+    let mut node_pods: Vec<NodePods> = Vec::new();
+
+    for pod in pods {
+        if let Some(daemonset) = daemonset {
+            if !is_owned_by_daemonset(pod, daemonset) {
+                warn!(
+                    pod.name = %pod.name_any(),
+                    daemonset.name = %daemonset,
+                    "Skipping io-engine Pod which isn't owned by the selected DaemonSet"
+                );
+                continue;
+            }
+        }
+
+        let node_name = pod
+            .spec
+            .as_ref()
+            .ok_or(
+                EmptyPodSpec {
+                    name: pod.name_any(),
+                    namespace: namespace.to_string(),
+                }
+                .build(),
+            )?
+            .node_name
+            .as_ref()
+            .ok_or(
+                EmptyPodNodeName {
+                    name: pod.name_any(),
+                    namespace: namespace.to_string(),
+                }
+                .build(),
+            )?;
+
+        match node_pods
+            .iter_mut()
+            .find(|node_pods| node_pods.node_name.eq(node_name))
+        {
+            Some(node_pods) => node_pods.pods.push(pod.clone()),
+            None => node_pods.push(NodePods {
+                node_name: node_name.clone(),
+                pods: vec![pod.clone()],
+            }),
+        }
+    }
+
+    if selection == IoEnginePodSelection::Oldest {
+        for node_pods in node_pods.iter_mut() {
+            // Pods without a creation timestamp are considered to be the newest.
+            node_pods.pods.sort_by_key(|pod| {
+                let created = pod.creation_timestamp();
+                (created.is_none(), created)
+            });
+            node_pods.pods.truncate(1);
+        }
+    }
+
+    Ok(node_pods)
+}
+
+/// Returns the UID and the name of the DaemonSet which owns the Pod, if any. A DaemonSet which
+/// is re-created with the same name has a new UID.
+pub(crate) fn owner_daemonset_uid_and_name(pod: &Pod) -> Option<(&str, &str)> {
+    pod.owner_references()
+        .iter()
+        .find(|owner| owner.kind.eq("DaemonSet"))
+        .map(|owner| (owner.uid.as_str(), owner.name.as_str()))
+}
+
+/// Returns the Pods' names along with their phases, e.g. "io-engine-abcde (Running)".
+pub(crate) fn pod_names_and_phases(pods: &[Pod]) -> Vec<String> {
+    pods.iter()
+        .map(|pod| {
+            let phase = pod
+                .status
+                .as_ref()
+                .and_then(|status| status.phase.as_deref())
+                .unwrap_or("Unknown");
+            match pod.metadata.deletion_timestamp {
+                Some(_) => format!("{} ({phase}, Terminating)", pod.name_any()),
+                None => format!("{} ({phase})", pod.name_any()),
+            }
+        })
+        .collect()
+}
+
+/// Returns true if the Pod is owned by the DaemonSet with the name 'daemonset'.
+pub(crate) fn is_owned_by_daemonset(pod: &Pod, daemonset: &str) -> bool {
+    pod.owner_references()
+        .iter()
+        .any(|owner| owner.kind.eq("DaemonSet") && owner.name.eq(daemonset))
+}
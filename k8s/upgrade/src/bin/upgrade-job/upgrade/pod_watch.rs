@@ -0,0 +1,96 @@
+use crate::upgrade::utils::PodInspector;
+use futures::{
+    stream::{select_all, BoxStream},
+    FutureExt, StreamExt,
+};
+use k8s_openapi::api::core::v1::Pod;
+use kube::runtime::watcher;
+use std::time::Duration;
+use tokio::{select, time::sleep};
+use tracing::warn;
+
+/// This is how long a wait goes without a Pod change before the Pods are checked regardless, in
+/// case the watch missed a change.
+const WATCHED_RECHECK_INTERVAL: Duration = Duration::from_secs(30_u64);
+
+/// This is the least time between two checks of the Pods, so that a burst of Pod changes isn't
+/// checked once for each of them.
+const MIN_RECHECK_INTERVAL: Duration = Duration::from_secs(1_u64);
+
+/// This is a stream of the changes to some Pods, i.e. to those which a readiness wait waits on.
+type PodWatch = BoxStream<'static, watcher::Result<watcher::Event<Pod>>>;
+
+/// This wakes a readiness wait up as soon as the Pods which it waits on change, using the
+/// Kubernetes watch API, rather than on a fixed poll interval. The wait falls back to polling if
+/// the watch isn't supported or its connection drops.
+pub(crate) struct PodChanges {
+    watch: Option<PodWatch>,
+}
+
+impl PodChanges {
+    /// Watches the Pods which match any of the 'label_selectors', and 'field_selector' if set.
+    pub(crate) fn watch<P: PodInspector + ?Sized>(
+        k8s_client: &P,
+        label_selectors: &[&str],
+        field_selector: Option<&str>,
+    ) -> Self {
+        let watches: Option<Vec<PodWatch>> = label_selectors
+            .iter()
+            .map(|label_selector| {
+                let mut config = watcher::Config::default().labels(label_selector);
+                if let Some(field_selector) = field_selector {
+                    config = config.fields(field_selector);
+                }
+                k8s_client.watch_pods(config)
+            })
+            .collect();
+        Self {
+            watch: watches
+                .filter(|watches| !watches.is_empty())
+                .map(|watches| select_all(watches).boxed()),
+        }
+    }
+
+    /// Waits for the watched Pods to change, or for up to 'poll_interval' if they aren't watched.
+    /// The wait is never shorter than a second, nor longer than 30 seconds, while they are.
+    pub(crate) async fn wait(&mut self, poll_interval: Duration) {
+        let Some(watch) = self.watch.as_mut() else {
+            sleep(poll_interval).await;
+            return;
+        };
+
+        let changed = select! {
+            event = watch.next() => event,
+            _ = sleep(WATCHED_RECHECK_INTERVAL) => return,
+        };
+        match changed {
+            Some(Ok(_)) => {
+                sleep(MIN_RECHECK_INTERVAL).await;
+                // The changes which arrived meanwhile are covered by the next check.
+                while let Some(Some(event)) = watch.next().now_or_never() {
+                    if let Err(error) = event {
+                        self.stop_watching(error.to_string());
+                        return;
+                    }
+                }
+            }
+            Some(Err(error)) => {
+                self.stop_watching(error.to_string());
+                sleep(poll_interval).await;
+            }
+            None => {
+                self.stop_watching("the watch stream ended".to_string());
+                sleep(poll_interval).await;
+            }
+        }
+    }
+
+    /// Falls back to polling for the rest of the wait.
+    fn stop_watching(&mut self, reason: String) {
+        warn!(
+            %reason,
+            "Lost the watch on the Pods, falling back to polling for their readiness"
+        );
+        self.watch = None;
+    }
+}
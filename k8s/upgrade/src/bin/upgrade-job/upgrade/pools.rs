@@ -0,0 +1,48 @@
+use crate::common::{
+    api_stats::GET_POOLS,
+    error::{ListPools, PoolOffline, Result},
+    rest_client::RestClientSet,
+};
+use openapi::models::PoolStatus;
+use snafu::ResultExt;
+use tracing::info;
+
+/// Validates that none of the storage pools on the node are offline, as restarting the node's
+/// io-engine could then leave its volumes without a healthy replica. A pool with a spec but no
+/// state, i.e. which the io-engine doesn't report, counts as offline. This fails with
+/// PoolOffline, listing the offline pools.
+pub(crate) async fn verify_no_offline_pools(
+    node_name: &str,
+    rest_client: &RestClientSet,
+) -> Result<()> {
+    let pools = rest_client
+        .call(GET_POOLS, |client| async move {
+            client.pools_api().get_pools().await
+        })
+        .await
+        .context(ListPools)?
+        .into_body();
+
+    let offline_pools: Vec<String> = pools
+        .into_iter()
+        .filter(|pool| match (pool.state.as_ref(), pool.spec.as_ref()) {
+            (Some(state), _) => {
+                state.node.eq(node_name) && !matches!(state.status, PoolStatus::Online)
+            }
+            (None, Some(spec)) => spec.node.eq(node_name),
+            (None, None) => false,
+        })
+        .map(|pool| pool.id)
+        .collect();
+
+    if !offline_pools.is_empty() {
+        return PoolOffline {
+            node_name,
+            pools: offline_pools,
+        }
+        .fail();
+    }
+
+    info!(node.name = %node_name, "None of the node's pools are offline");
+    Ok(())
+}
@@ -0,0 +1,217 @@
+use crate::{
+    common::{
+        api_stats::GET_NODE,
+        error::{
+            CsiNodePluginNotReady, GetStorageNode, ListPodsWithLabelAndField, Result,
+            StorageNodeOnlineTimeout,
+        },
+        logging::WaitLogVerbosity,
+        rest_client::RestClientSet,
+    },
+    poll_status,
+    upgrade::{
+        pod_selection::pod_names_and_phases,
+        pod_watch::PodChanges,
+        timeouts::timeout_exceeded,
+        utils::{pod_is_ready, pod_is_terminated, PodInspector},
+    },
+};
+use clap::ValueEnum;
+use k8s_openapi::api::core::v1::Pod;
+use kube::api::ListParams;
+use openapi::models::NodeStatus;
+use snafu::ResultExt;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// This decides when a Node's restarted io-engine is considered to be ready.
+#[derive(Clone, Copy, Debug, Default, Eq, PartialEq, ValueEnum)]
+pub(crate) enum ReadinessStrategy {
+    /// The io-engine Pods on the Node are Ready.
+    #[default]
+    PodReady,
+    /// The io-engine Pods on the Node are Ready, and the control-plane reports the storage Node
+    /// as Online, i.e. the io-engine's gRPC endpoint is serving.
+    NodeOnline,
+    /// The io-engine Pods on the Node are Ready, and the io-engine DaemonSets' status shows the
+    /// rollout to have progressed for the Node, i.e. .status.updatedNumberScheduled has gone up
+    /// by the number of the deleted Pods, and .status.numberReady is back to what it was before
+    /// the Pods were deleted. The replacement Pods are scheduled once updatedNumberScheduled has
+    /// gone up, before their readiness is waited on.
+    DaemonsetStatus,
+}
+
+/// These are the readiness requirements for the replacement io-engine Pods on top of their Ready
+/// condition, as the io-engine may report Ready before it has re-imported its pools.
+#[derive(Clone, Debug, Default)]
+pub(crate) struct ExtraPodReadiness {
+    /// A Pod condition which has to be True, e.g. a readiness gate.
+    pub(crate) condition: Option<String>,
+    /// A container which has to be ready, i.e. whose readiness probe has to pass.
+    pub(crate) container: Option<String>,
+}
+
+impl ExtraPodReadiness {
+    /// Returns true if the Pod meets the requirements, which it does if none are set.
+    pub(crate) fn is_met(&self, pod: &Pod) -> bool {
+        let status = pod.status.as_ref();
+        let condition_is_true = self.condition.as_ref().map_or(true, |condition_type| {
+            status
+                .and_then(|status| status.conditions.as_ref())
+                .is_some_and(|conditions| {
+                    conditions.iter().any(|condition| {
+                        condition.type_.eq(condition_type) && condition.status.eq("True")
+                    })
+                })
+        });
+        let container_is_ready = self.container.as_ref().map_or(true, |container_name| {
+            status
+                .and_then(|status| status.container_statuses.as_ref())
+                .is_some_and(|containers| {
+                    containers
+                        .iter()
+                        .any(|container| container.name.eq(container_name) && container.ready)
+                })
+        });
+        condition_is_true && container_is_ready
+    }
+}
+
+/// Wait for the control-plane to report the storage Node as Online. A Pod may be Ready while the
+/// io-engine's gRPC endpoint is still initializing.
+pub(crate) async fn wait_for_storage_node_online(
+    node_id: &str,
+    rest_client: &RestClientSet,
+    timeout: Option<Duration>,
+    verbosity: WaitLogVerbosity,
+) -> Result<()> {
+    let duration = Duration::from_secs(5_u64);
+    let start = Instant::now();
+    info!(node.id = %node_id, "Waiting for the storage Node to be Online");
+    loop {
+        let node = rest_client
+            .call(GET_NODE, |client| async move {
+                client.nodes_api().get_node(node_id).await
+            })
+            .await
+            .context(GetStorageNode {
+                node_id: node_id.to_string(),
+            })?
+            .into_body();
+
+        if matches!(node.state, Some(ref state) if state.status == NodeStatus::Online) {
+            break;
+        }
+
+        if let Some(timeout) = timeout_exceeded(start, timeout) {
+            return StorageNodeOnlineTimeout {
+                node_id: node_id.to_string(),
+                timeout,
+            }
+            .fail();
+        }
+        poll_status!(
+            verbosity,
+            node.id = %node_id,
+            node.status = ?node.state.as_ref().map(|state| &state.status),
+            "Waiting for the storage Node to be Online"
+        );
+        tokio::time::sleep(duration).await;
+    }
+
+    info!(node.id = %node_id, "Storage Node is Online");
+    Ok(())
+}
+
+/// These pick the CSI node plugin Pods, and the version which they have to be on.
+pub(crate) struct CsiNodePluginPods<'a> {
+    /// The label selector of the Pods.
+    pub(crate) label_selector: &'a str,
+    /// The key of the label which the Pods' version is set in.
+    pub(crate) version_label_key: &'a str,
+    /// The version which the Pods have to be on.
+    pub(crate) upgrade_to_version: &'a str,
+}
+
+/// Wait for the CSI node plugin Pods on the node to be Ready on the target version, as the
+/// io-engine's restart may have disrupted them. The volumes on the node can't be mounted or
+/// unmounted until they are. The Pods in a terminal phase are left out, but there has to be at
+/// least one Pod which isn't.
+pub(crate) async fn wait_for_csi_node_plugin<P: PodInspector + ?Sized>(
+    node_name: &str,
+    namespace: String,
+    csi_pods: &CsiNodePluginPods<'_>,
+    k8s_client: &P,
+    timeout: Option<Duration>,
+    verbosity: WaitLogVerbosity,
+) -> Result<()> {
+    let CsiNodePluginPods {
+        label_selector,
+        version_label_key,
+        upgrade_to_version,
+    } = *csi_pods;
+    let duration = Duration::from_secs(5_u64);
+    let start = Instant::now();
+    let node_name_pod_field = format!("spec.nodeName={node_name}");
+    let list_params = ListParams::default()
+        .labels(label_selector)
+        .fields(node_name_pod_field.as_str());
+    let mut pod_changes = PodChanges::watch(
+        k8s_client,
+        &[label_selector],
+        Some(node_name_pod_field.as_str()),
+    );
+    info!(node.name = %node_name, "Waiting for the CSI node plugin Pods to be Ready");
+    loop {
+        let pods: Vec<Pod> = k8s_client
+            .list_pods(&list_params)
+            .await
+            .context(ListPodsWithLabelAndField {
+                label: label_selector.to_string(),
+                field: node_name_pod_field.clone(),
+                namespace: namespace.clone(),
+            })?
+            .into_iter()
+            .filter(|pod| !pod_is_terminated(pod))
+            .collect();
+        let all_ready = !pods.is_empty()
+            && pods.iter().all(|pod| {
+                pod_is_ready(pod)
+                    && pod.metadata.deletion_timestamp.is_none()
+                    && pod
+                        .metadata
+                        .labels
+                        .as_ref()
+                        .and_then(|labels| labels.get(version_label_key))
+                        .is_some_and(|version| version.eq(upgrade_to_version))
+            });
+        if all_ready {
+            break;
+        }
+
+        if let Some(timeout) = timeout_exceeded(start, timeout) {
+            return CsiNodePluginNotReady {
+                node_name: node_name.to_string(),
+                timeout,
+                pods: pod_names_and_phases(&pods),
+            }
+            .fail();
+        }
+        poll_status!(
+            verbosity,
+            node.name = %node_name,
+            "Waiting for the CSI node plugin Pods to be Ready"
+        );
+        if verbosity.logs_wait_state() {
+            info!(
+                node.name = %node_name,
+                pods = ?pod_names_and_phases(&pods),
+                "Current state of the CSI node plugin Pods"
+            );
+        }
+        pod_changes.wait(duration).await;
+    }
+
+    info!(node.name = %node_name, "CSI node plugin Pods are Ready");
+    Ok(())
+}
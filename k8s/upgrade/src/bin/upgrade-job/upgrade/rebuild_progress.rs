@@ -0,0 +1,111 @@
+use crate::{
+    common::{error::Result, logging::WaitLogVerbosity, rest_client::RestClientSet},
+    poll_status,
+    upgrade::{
+        report::DataPlaneUpgradeReport,
+        utils::{list_volumes, nodes_hosting_volume},
+        warnings::UpgradeWarningKind,
+    },
+};
+use std::collections::HashMap;
+use tracing::warn;
+
+/// The number of consecutive polls without any progress, after which a rebuild is considered to
+/// be stalled.
+const STALLED_REBUILD_POLLS: u32 = 6;
+
+/// The last observed progress of a replica rebuild.
+struct ObservedProgress {
+    progress: usize,
+    /// The number of consecutive polls for which the progress hasn't advanced.
+    unchanged_polls: u32,
+}
+
+/// This tracks the progress of the replica rebuilds across the polls for their completion, and
+/// reports the rebuilds which have stalled.
+pub(crate) struct RebuildProgressTracker {
+    /// The last observed progress of each rebuild, by volume UUID and replica URI.
+    observed: HashMap<(String, String), ObservedProgress>,
+    /// How much the progress of the rebuilds is logged.
+    verbosity: WaitLogVerbosity,
+}
+
+impl RebuildProgressTracker {
+    /// Creates a tracker which has observed no rebuilds yet, and which logs their progress at the
+    /// level which 'verbosity' picks.
+    pub(crate) fn new(verbosity: WaitLogVerbosity) -> Self {
+        Self {
+            observed: HashMap::new(),
+            verbosity,
+        }
+    }
+
+    /// Logs the rebuilding volumes and the progress of each of their replica rebuilds, and warns
+    /// of the rebuilds whose progress hasn't advanced for STALLED_REBUILD_POLLS polls. If
+    /// 'node_name' is set, only the volumes which have their target or a replica on the node are
+    /// considered. The stalled rebuilds are recorded as warnings in the 'report'.
+    pub(crate) async fn poll(
+        &mut self,
+        rest_client: &RestClientSet,
+        node_name: Option<&str>,
+        report: &DataPlaneUpgradeReport,
+    ) -> Result<()> {
+        let mut observed = HashMap::new();
+        for volume in list_volumes(rest_client).await?.iter().filter(|volume| {
+            node_name.map_or(true, |node_name| {
+                nodes_hosting_volume(volume).contains(node_name)
+            })
+        }) {
+            let Some(target) = volume.state.target.as_ref() else {
+                continue;
+            };
+            let volume_uuid = volume.spec.uuid.to_string();
+            for child in target.children.iter() {
+                let Some(progress) = child.rebuild_progress else {
+                    continue;
+                };
+                poll_status!(
+                    self.verbosity,
+                    volume.uuid = %volume_uuid,
+                    replica.uri = %child.uri,
+                    rebuild.progress = progress,
+                    "Volume replica is rebuilding"
+                );
+
+                let key = (volume_uuid.clone(), child.uri.clone());
+                let unchanged_polls = match self.observed.remove(&key) {
+                    Some(previous) if previous.progress >= progress => previous.unchanged_polls + 1,
+                    _ => 0,
+                };
+                if unchanged_polls == STALLED_REBUILD_POLLS {
+                    warn!(
+                        volume.uuid = %volume_uuid,
+                        replica.uri = %child.uri,
+                        rebuild.progress = progress,
+                        polls = unchanged_polls,
+                        "Volume replica rebuild hasn't progressed, it may be stalled"
+                    );
+                    report.record_warning(
+                        UpgradeWarningKind::RebuildStalled,
+                        node_name,
+                        format!(
+                            "The rebuild of replica {} of volume {volume_uuid} hasn't progressed \
+                            past {progress}% for {unchanged_polls} polls",
+                            child.uri
+                        ),
+                    );
+                }
+                observed.insert(
+                    key,
+                    ObservedProgress {
+                        progress,
+                        unchanged_polls,
+                    },
+                );
+            }
+        }
+        // The rebuilds which are no longer in progress are forgotten.
+        self.observed = observed;
+        Ok(())
+    }
+}
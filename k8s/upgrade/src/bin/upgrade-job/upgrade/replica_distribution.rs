@@ -0,0 +1,151 @@
+use crate::common::{
+    api_stats::{GET_POOLS, GET_REPLICAS},
+    rest_client::RestClientSet,
+};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use tracing::warn;
+
+/// The distribution of the replicas is considered imbalanced if the pool with the most replicas
+/// has more than this many times the mean number of replicas per pool.
+const IMBALANCE_FACTOR: f64 = 1.5;
+
+/// This is the number of replicas on one storage pool, along with the pool's capacity usage.
+#[derive(Serialize, Debug)]
+#[serde(rename_all(serialize = "camelCase"))]
+struct PoolReplicas {
+    pool_id: String,
+    node_name: String,
+    replicas: usize,
+    used_bytes: Option<u64>,
+    capacity_bytes: Option<u64>,
+}
+
+/// This is the distribution of the replicas across the storage pools.
+#[derive(Serialize, Debug)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub(crate) struct ReplicaDistribution {
+    pools: Vec<PoolReplicas>,
+    /// The number of replicas on the pool with the most replicas, divided by the mean number of
+    /// replicas per pool. This is absent if there are no replicas.
+    imbalance: Option<f64>,
+}
+
+impl ReplicaDistribution {
+    /// Lists the storage pools and the replicas, and counts the replicas on each pool. This is
+    /// purely informational, so errors are logged and None is returned.
+    pub(crate) async fn collect(rest_client: &RestClientSet) -> Option<Self> {
+        let pools = match rest_client
+            .call(GET_POOLS, |client| async move {
+                client.pools_api().get_pools().await
+            })
+            .await
+        {
+            Ok(pools) => pools.into_body(),
+            Err(error) => {
+                warn!(%error, "Failed to list pools for the replica distribution report");
+                return None;
+            }
+        };
+        let replicas = match rest_client
+            .call(GET_REPLICAS, |client| async move {
+                client.replicas_api().get_replicas().await
+            })
+            .await
+        {
+            Ok(replicas) => replicas.into_body(),
+            Err(error) => {
+                warn!(%error, "Failed to list replicas for the replica distribution report");
+                return None;
+            }
+        };
+
+        let mut replica_counts: BTreeMap<&str, usize> = BTreeMap::new();
+        for replica in replicas.iter() {
+            *replica_counts.entry(replica.pool.as_str()).or_default() += 1;
+        }
+
+        let pools: Vec<PoolReplicas> = pools
+            .iter()
+            .map(|pool| PoolReplicas {
+                pool_id: pool.id.clone(),
+                node_name: pool
+                    .state
+                    .as_ref()
+                    .map(|state| state.node.clone())
+                    .or_else(|| pool.spec.as_ref().map(|spec| spec.node.clone()))
+                    .unwrap_or_default(),
+                replicas: replica_counts
+                    .get(pool.id.as_str())
+                    .copied()
+                    .unwrap_or_default(),
+                used_bytes: pool.state.as_ref().map(|state| state.used),
+                capacity_bytes: pool.state.as_ref().map(|state| state.capacity),
+            })
+            .collect();
+
+        let total: usize = pools.iter().map(|pool| pool.replicas).sum();
+        let max = pools
+            .iter()
+            .map(|pool| pool.replicas)
+            .max()
+            .unwrap_or_default();
+        let imbalance = (total > 0).then(|| max as f64 / (total as f64 / pools.len() as f64));
+
+        Some(Self { pools, imbalance })
+    }
+
+    /// Returns true if the distribution is imbalanced.
+    fn is_imbalanced(&self) -> bool {
+        self.imbalance
+            .map_or(false, |imbalance| imbalance > IMBALANCE_FACTOR)
+    }
+}
+
+/// This is the distribution of the replicas before and after the data-plane upgrade, for the
+/// data-plane upgrade report. This only reports an imbalance, it is up to the operator to decide
+/// whether to rebalance the replicas.
+#[derive(Serialize, Debug)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub(crate) struct ReplicaDistributionReport {
+    /// The distribution before the upgrade. This is absent if it couldn't be collected.
+    before: Option<ReplicaDistribution>,
+    /// The distribution after the upgrade. This is absent if it couldn't be collected, or if the
+    /// upgrade didn't complete.
+    after: Option<ReplicaDistribution>,
+    /// This is true if the upgrade has left the distribution imbalanced, and more so than it was
+    /// before the upgrade.
+    imbalance_introduced: bool,
+}
+
+impl ReplicaDistributionReport {
+    /// Creates the report with the distribution of the replicas before the data-plane upgrade.
+    pub(crate) fn new(before: Option<ReplicaDistribution>) -> Self {
+        Self {
+            before,
+            after: None,
+            imbalance_introduced: false,
+        }
+    }
+
+    /// Completes the report with the distribution of the replicas after the data-plane upgrade,
+    /// and warns if the upgrade has left the distribution imbalanced.
+    pub(crate) fn finish(&mut self, after: Option<ReplicaDistribution>) {
+        let before = self.before.as_ref();
+        self.imbalance_introduced = after.as_ref().map_or(false, |after| {
+            after.is_imbalanced()
+                && before.map_or(true, |before| {
+                    before.imbalance.unwrap_or_default() < after.imbalance.unwrap_or_default()
+                })
+        });
+        if self.imbalance_introduced {
+            warn!(
+                imbalance.before = ?before.and_then(|before| before.imbalance),
+                imbalance.after = ?after.as_ref().and_then(|after| after.imbalance),
+                "Replica distribution across pools is imbalanced after the upgrade, \
+                consider rebalancing the replicas"
+            );
+        }
+        self.after = after;
+    }
+}
@@ -0,0 +1,150 @@
+use crate::{
+    common::{
+        api_stats::GET_POOLS,
+        error::{ListPools, ReplicaCountNotRestored, ReplicaRelocationFailed, Result},
+        logging::WaitLogVerbosity,
+        rest_client::RestClientSet,
+    },
+    poll_status,
+    upgrade::{
+        timeouts::timeout_exceeded,
+        utils::{list_volumes, volume_state},
+    },
+};
+use openapi::models::{Pool, PoolStatus, ReplicaState, Volume};
+use snafu::ResultExt;
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+/// This is how long a volume may go without its missing replicas and without room for them on
+/// another node, before its replicas are taken to be impossible to re-create. The control-plane
+/// may still be catching up with the node's io-engine restart until then.
+const RELOCATION_GRACE_PERIOD: Duration = Duration::from_secs(60_u64);
+
+/// Wait for the volumes which had replicas on the node to have their configured number of
+/// replicas again, i.e. for the missing replicas to be re-created, whether or not they are
+/// rebuilt yet. If a volume is missing replicas and none of the Online pools on the nodes which
+/// don't host one of its replicas has room for another, for longer than a grace period, this
+/// fails fast with ReplicaRelocationFailed, rather than waiting for the rebuild timeout.
+pub(crate) async fn wait_for_replica_relocation(
+    node_name: &str,
+    volume_uuids: &[String],
+    rest_client: &RestClientSet,
+    timeout: Option<Duration>,
+    verbosity: WaitLogVerbosity,
+) -> Result<()> {
+    if volume_uuids.is_empty() {
+        return Ok(());
+    }
+
+    let start = Instant::now();
+    // When the volumes which have no room for their missing replicas were first seen so.
+    let mut stuck_since: Option<Instant> = None;
+    loop {
+        let volumes: Vec<Volume> = list_volumes(rest_client)
+            .await?
+            .into_iter()
+            .filter(|volume| volume_uuids.contains(&volume.spec.uuid.to_string()))
+            .filter(|volume| replica_count(volume) < volume.spec.num_replicas as usize)
+            .collect();
+        if volumes.is_empty() {
+            break;
+        }
+
+        let pools = rest_client
+            .call(GET_POOLS, |client| async move {
+                client.pools_api().get_pools().await
+            })
+            .await
+            .context(ListPools)?
+            .into_body();
+        let stuck_volumes: Vec<String> = volumes
+            .iter()
+            .filter(|volume| !has_room_for_replica(volume, &pools))
+            .map(|volume| volume.spec.uuid.to_string())
+            .collect();
+        if stuck_volumes.is_empty() {
+            stuck_since = None;
+        } else {
+            let since = *stuck_since.get_or_insert_with(Instant::now);
+            if since.elapsed() >= RELOCATION_GRACE_PERIOD {
+                warn!(
+                    node.name = %node_name,
+                    volumes = ?stuck_volumes,
+                    "No pool has room for the volumes' missing replicas"
+                );
+                return ReplicaRelocationFailed {
+                    node_name: node_name.to_string(),
+                    volumes: stuck_volumes,
+                }
+                .fail();
+            }
+        }
+
+        if verbosity.logs_wait_state() {
+            let volumes: Vec<String> = volumes.iter().map(volume_state).collect();
+            info!(node.name = %node_name, ?volumes, "Current state of the volumes");
+        }
+        let volumes: Vec<String> = volumes
+            .iter()
+            .map(|volume| volume.spec.uuid.to_string())
+            .collect();
+        if let Some(timeout) = timeout_exceeded(start, timeout) {
+            warn!(
+                node.name = %node_name,
+                ?volumes,
+                "The volumes' missing replicas were not re-created"
+            );
+            return ReplicaCountNotRestored {
+                volume: volumes[0].clone(),
+                node_name: node_name.to_string(),
+                timeout,
+            }
+            .fail();
+        }
+        poll_status!(
+            verbosity,
+            node.name = %node_name,
+            ?volumes,
+            "Waiting for the volumes' missing replicas to be re-created"
+        );
+        tokio::time::sleep(Duration::from_secs(10_u64)).await;
+    }
+
+    info!(
+        node.name = %node_name,
+        "All volumes with replicas on the Node have their configured number of replicas"
+    );
+    Ok(())
+}
+
+/// Returns the number of the volume's replicas which aren't Faulted, whether or not they are
+/// Online.
+fn replica_count(volume: &Volume) -> usize {
+    volume
+        .state
+        .replica_topology
+        .values()
+        .filter(|topology| !matches!(topology.state, ReplicaState::Faulted))
+        .count()
+}
+
+/// Returns true if an Online pool on a node which doesn't host one of the volume's replicas has
+/// the free capacity for another replica of the volume.
+fn has_room_for_replica(volume: &Volume, pools: &[Pool]) -> bool {
+    let replica_nodes: HashSet<&str> = volume
+        .state
+        .replica_topology
+        .values()
+        .filter_map(|topology| topology.node.as_deref())
+        .collect();
+    pools
+        .iter()
+        .filter_map(|pool| pool.state.as_ref())
+        .filter(|state| matches!(state.status, PoolStatus::Online))
+        .filter(|state| !replica_nodes.contains(state.node.as_str()))
+        .any(|state| state.capacity.saturating_sub(state.used) >= volume.spec.size)
+}
@@ -0,0 +1,262 @@
+use crate::{
+    common::{
+        api_stats::{ApiCallCounts, ApiCallSummary, ApiStats},
+        error::Result,
+    },
+    upgrade::{
+        node_durations::{NodeDurations, NodePhaseDurations, NodePhaseTimings},
+        replica_distribution::{ReplicaDistribution, ReplicaDistributionReport},
+        warnings::{UpgradeWarning, UpgradeWarningKind},
+    },
+};
+use k8s_openapi::api::core::v1::Pod;
+use kube::ResourceExt;
+use serde::Serialize;
+use std::{
+    collections::BTreeMap,
+    sync::{Mutex, PoisonError},
+    time::Duration,
+};
+use tracing::warn;
+
+/// This is the outcome of one Node's data-plane upgrade.
+#[derive(Serialize, Clone, Copy, Debug, Eq, PartialEq)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub(crate) enum NodeUpgradeStatus {
+    /// The Node's io-engine Pods were restarted on the target version.
+    Upgraded,
+    /// The Node was upgraded already, e.g. by an earlier upgrade which was interrupted.
+    Skipped,
+    /// The Node was only checked, as this is a dry run.
+    DryRun,
+    /// The Node's upgrade failed.
+    Failed,
+}
+
+/// This is one Node's entry in the data-plane upgrade report.
+#[derive(Serialize, Debug)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub(crate) struct NodeUpgradeOutcome {
+    node_name: String,
+    pod_names: Vec<String>,
+    duration_secs: f64,
+    status: NodeUpgradeStatus,
+    /// The time spent in each of the upgrade's phases. This is absent for a Node which wasn't
+    /// upgraded, e.g. if it was skipped or its upgrade failed.
+    phase_timings: Option<NodePhaseTimings>,
+    /// The error which failed the Node's upgrade, if any.
+    error: Option<String>,
+}
+
+/// This is the machine-readable summary of the data-plane upgrade. It is filled in as the Nodes
+/// are upgraded, so that an upgrade which is aborted early still reports how far it got.
+#[derive(Serialize, Debug)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub(crate) struct DataPlaneUpgradeReport {
+    /// The ID of the upgrade run.
+    run_id: String,
+    nodes: Vec<NodeUpgradeOutcome>,
+    /// The estimated and the actual duration of each of the upgraded Nodes, along with the
+    /// deviation from the estimate, and whether the Node is an outlier.
+    node_durations: NodeDurations,
+    elapsed_secs: f64,
+    /// The Nodes which failed to upgrade, and which were skipped by the rest of the upgrade.
+    failed_nodes: Vec<String>,
+    /// The notable events which didn't stop the data-plane upgrade, in the order in which they
+    /// occurred. These are recorded by the Nodes which are upgraded concurrently, too.
+    warnings: Mutex<Vec<UpgradeWarning>>,
+    /// The source version which the data-plane was left on intentionally, if only the
+    /// control-plane was upgraded.
+    data_plane_left_on_version: Option<String>,
+    /// The distribution of the replicas across the storage pools before and after the upgrade,
+    /// if it is reported.
+    replica_distribution: Option<ReplicaDistributionReport>,
+    /// The count and the latencies of each of the API calls made by the upgrade.
+    api_calls: Vec<ApiCallSummary>,
+    /// The number of calls made to each of the Kubernetes and storage APIs.
+    api_call_counts: ApiCallCounts,
+    /// The error which failed the data-plane upgrade, if any.
+    error: Option<String>,
+    /// The result of the data-plane upgrade.
+    #[serde(skip)]
+    result: Result<()>,
+}
+
+impl DataPlaneUpgradeReport {
+    /// Creates the empty report of the upgrade run with the ID 'run_id'.
+    pub(crate) fn new(run_id: String) -> Self {
+        Self {
+            run_id,
+            nodes: Vec::new(),
+            node_durations: NodeDurations::default(),
+            elapsed_secs: 0.0,
+            failed_nodes: Vec::new(),
+            warnings: Mutex::new(Vec::new()),
+            data_plane_left_on_version: None,
+            replica_distribution: None,
+            api_calls: Vec::new(),
+            api_call_counts: ApiCallCounts::default(),
+            error: None,
+            result: Ok(()),
+        }
+    }
+
+    /// Records the outcome of a Node's upgrade, along with the time spent in each of its phases
+    /// and the error which failed it, if any.
+    pub(crate) fn record(
+        &mut self,
+        node_name: &str,
+        pods: &[Pod],
+        duration: Duration,
+        status: NodeUpgradeStatus,
+        phase_timings: Option<NodePhaseTimings>,
+        error: Option<String>,
+    ) {
+        self.nodes.push(NodeUpgradeOutcome {
+            node_name: node_name.to_string(),
+            pod_names: pods.iter().map(|pod| pod.name_any()).collect(),
+            duration_secs: duration.as_secs_f64(),
+            status,
+            phase_timings,
+            error,
+        });
+    }
+
+    /// Records how long an upgraded Node took against its estimate, along with the time spent in
+    /// each of its phases. Only the Nodes which were upgraded are recorded, so that the skipped
+    /// and the failed Nodes don't skew the estimates.
+    pub(crate) fn record_node_duration(
+        &mut self,
+        node_name: &str,
+        duration: Duration,
+        phases: NodePhaseDurations,
+    ) {
+        self.node_durations.record(node_name, duration, phases);
+    }
+
+    /// Returns the durations of the upgraded Nodes.
+    pub(crate) fn node_durations(&self) -> &NodeDurations {
+        &self.node_durations
+    }
+
+    /// Records a warning of the data-plane upgrade. The warning isn't logged here, the caller logs
+    /// it along with its details.
+    pub(crate) fn record_warning(
+        &self,
+        kind: UpgradeWarningKind,
+        node_name: Option<&str>,
+        message: String,
+    ) {
+        self.warnings
+            .lock()
+            .unwrap_or_else(PoisonError::into_inner)
+            .push(UpgradeWarning::new(kind, node_name, message));
+    }
+
+    /// Records that the data-plane was left on 'version' intentionally, as only the control-plane
+    /// was upgraded.
+    pub(crate) fn record_data_plane_left_on(&mut self, version: String) {
+        self.data_plane_left_on_version = Some(version);
+    }
+
+    /// Records the distribution of the replicas before the data-plane upgrade, which is None if it
+    /// couldn't be collected.
+    pub(crate) fn record_replica_distribution_before(
+        &mut self,
+        before: Option<ReplicaDistribution>,
+    ) {
+        self.replica_distribution = Some(ReplicaDistributionReport::new(before));
+    }
+
+    /// Records the distribution of the replicas after the data-plane upgrade, which is None if it
+    /// couldn't be collected. This is left out if the distribution before the upgrade wasn't
+    /// recorded.
+    pub(crate) fn record_replica_distribution_after(&mut self, after: Option<ReplicaDistribution>) {
+        if let Some(replica_distribution) = self.replica_distribution.as_mut() {
+            replica_distribution.finish(after);
+        }
+    }
+
+    /// Records the summary of the API calls which have been made so far, from 'api_stats'.
+    pub(crate) fn record_api_calls(&mut self, api_stats: &ApiStats) {
+        self.api_calls = api_stats.summary();
+        self.api_call_counts = api_stats.call_counts();
+    }
+
+    /// Returns the number of Nodes whose upgrade had the outcome 'status'.
+    pub(crate) fn node_count(&self, status: NodeUpgradeStatus) -> usize {
+        self.nodes
+            .iter()
+            .filter(|node| node.status.eq(&status))
+            .count()
+    }
+
+    /// Returns the names of the Nodes whose upgrade had the outcome 'status'.
+    pub(crate) fn nodes_with_status(&self, status: NodeUpgradeStatus) -> Vec<&str> {
+        self.nodes
+            .iter()
+            .filter(|node| node.status.eq(&status))
+            .map(|node| node.node_name.as_str())
+            .collect()
+    }
+
+    /// Returns the estimated time to upgrade 'remaining_nodes' more Nodes one at a time, from the
+    /// mean duration of the Nodes upgraded so far. The skipped and the failed Nodes are left out
+    /// of the mean, as they don't take as long. There's no estimate until a Node is upgraded.
+    pub(crate) fn estimated_time_remaining(&self, remaining_nodes: usize) -> Option<Duration> {
+        let durations: Vec<f64> = self
+            .nodes
+            .iter()
+            .filter(|node| node.status.eq(&NodeUpgradeStatus::Upgraded))
+            .map(|node| node.duration_secs)
+            .collect();
+        if durations.is_empty() {
+            return None;
+        }
+        let mean = durations.iter().sum::<f64>() / durations.len() as f64;
+        Some(Duration::from_secs_f64(mean * remaining_nodes as f64))
+    }
+
+    /// Completes the report with the total time taken and the result of the data-plane upgrade.
+    pub(crate) fn finish(&mut self, elapsed: Duration, result: Result<()>) {
+        self.elapsed_secs = elapsed.as_secs_f64();
+        self.failed_nodes = self
+            .nodes_with_status(NodeUpgradeStatus::Failed)
+            .into_iter()
+            .map(ToString::to_string)
+            .collect();
+        self.error = result.as_ref().err().map(ToString::to_string);
+        self.result = result;
+    }
+
+    /// Logs the number of warnings of each kind, if there were any. The warnings themselves are
+    /// logged as they occur.
+    pub(crate) fn log_warnings_summary(&self) {
+        let warnings = self.warnings.lock().unwrap_or_else(PoisonError::into_inner);
+        if warnings.is_empty() {
+            return;
+        }
+        let mut counts: BTreeMap<UpgradeWarningKind, usize> = BTreeMap::new();
+        for warning in warnings.iter() {
+            *counts.entry(warning.kind).or_default() += 1;
+        }
+        warn!(
+            warnings = warnings.len(),
+            ?counts,
+            "The data-plane upgrade had warnings"
+        );
+    }
+
+    /// Writes the report to stdout as a single line of JSON.
+    pub(crate) fn print(&self) {
+        match serde_json::to_string(self) {
+            Ok(report) => println!("{report}"),
+            Err(error) => warn!(%error, "Failed to serialize the data-plane upgrade report"),
+        }
+    }
+
+    /// Returns the result of the data-plane upgrade, consuming the report.
+    pub(crate) fn into_result(self) -> Result<()> {
+        self.result
+    }
+}
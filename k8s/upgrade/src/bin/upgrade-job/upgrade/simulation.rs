@@ -0,0 +1,22 @@
+use std::time::{Duration, Instant};
+use tracing::warn;
+
+/// Logs that a slow cluster is simulated. Each of the wait phases of the data-plane upgrade
+/// (drain, rebuild, readiness, control-plane) is considered incomplete until at least
+/// 'phase_duration' has passed since it started, so that the timeouts may be validated against
+/// a cluster which isn't actually slow.
+pub(crate) fn warn_slow_cluster_simulation(phase_duration: Duration) {
+    warn!(
+        phase.duration = ?phase_duration,
+        "Simulating a slow cluster, the data-plane upgrade waits are extended artificially"
+    );
+}
+
+/// Returns true if a wait phase which started at 'start' is allowed to complete. This is always
+/// true, unless a slow cluster is simulated with the 'simulated_phase_duration'.
+pub(crate) fn phase_may_complete(
+    start: Instant,
+    simulated_phase_duration: Option<Duration>,
+) -> bool {
+    simulated_phase_duration.map_or(true, |phase_duration| start.elapsed().ge(&phase_duration))
+}
@@ -0,0 +1,104 @@
+use crate::{
+    common::{
+        api_stats::GET_SNAPSHOTS,
+        error::{ListVolumeSnapshots, Result, SnapshotInProgress},
+        logging::WaitLogVerbosity,
+        rest_client::RestClientSet,
+    },
+    poll_status,
+    upgrade::{timeouts::timeout_exceeded, utils::volumes_hosted_on_node},
+};
+use openapi::models::{SpecStatus, VolumeSnapshot};
+use snafu::ResultExt;
+use std::time::{Duration, Instant};
+use tracing::info;
+
+/// Returns all of the volume snapshots.
+async fn list_snapshots(rest_client: &RestClientSet) -> Result<Vec<VolumeSnapshot>> {
+    let mut all_snapshots: Vec<VolumeSnapshot> = Vec::new();
+    // The number of snapshots to get per request.
+    let max_entries = 200;
+    let mut starting_token = Some(0_isize);
+
+    // The last paginated request will set the `starting_token` to `None`.
+    while starting_token.is_some() {
+        let snapshots = rest_client
+            .call(GET_SNAPSHOTS, |client| async move {
+                client
+                    .snapshots_api()
+                    .get_volumes_snapshots(max_entries, None, None, starting_token)
+                    .await
+            })
+            .await
+            .context(ListVolumeSnapshots)?
+            .into_body();
+
+        starting_token = snapshots.next_token;
+        all_snapshots.extend(snapshots.entries);
+    }
+    Ok(all_snapshots)
+}
+
+/// Returns the UUIDs of the snapshots of the volumes 'volume_uuids' which are being created or
+/// deleted.
+async fn snapshots_in_progress(
+    rest_client: &RestClientSet,
+    volume_uuids: &[String],
+) -> Result<Vec<String>> {
+    Ok(list_snapshots(rest_client)
+        .await?
+        .into_iter()
+        .filter(|snapshot| {
+            matches!(
+                snapshot.definition.metadata.status,
+                SpecStatus::Creating | SpecStatus::Deleting
+            )
+        })
+        .filter(|snapshot| {
+            volume_uuids.contains(&snapshot.definition.spec.source_volume.to_string())
+        })
+        .map(|snapshot| snapshot.definition.spec.uuid.to_string())
+        .collect())
+}
+
+/// Waits for the snapshots of the volumes which have their target or a replica on the node to
+/// be created or deleted, so that draining the node doesn't fail them. This fails with
+/// SnapshotInProgress if there are still snapshots in progress after 'timeout'.
+pub(crate) async fn wait_for_snapshots_to_complete(
+    node_name: &str,
+    rest_client: &RestClientSet,
+    timeout: Option<Duration>,
+    verbosity: WaitLogVerbosity,
+) -> Result<()> {
+    let volume_uuids = volumes_hosted_on_node(rest_client, node_name).await?;
+    if volume_uuids.is_empty() {
+        return Ok(());
+    }
+
+    let start = Instant::now();
+    loop {
+        let snapshots = snapshots_in_progress(rest_client, &volume_uuids).await?;
+        if snapshots.is_empty() {
+            break;
+        }
+
+        if let Some(timeout) = timeout_exceeded(start, timeout) {
+            return SnapshotInProgress {
+                node_name: node_name.to_string(),
+                timeout,
+                snapshots,
+            }
+            .fail();
+        }
+        poll_status!(
+            verbosity,
+            node.name = %node_name,
+            ?snapshots,
+            "Waiting for the snapshots of the node's volumes to complete"
+        );
+        tokio::time::sleep(Duration::from_secs(10_u64)).await;
+    }
+
+    info!(node.name = %node_name, "No snapshots of the node's volumes are in progress");
+    Ok(())
+}
@@ -0,0 +1,205 @@
+use crate::{
+    common::{
+        api_stats::GET_NODES,
+        constants::IO_ENGINE_LABEL,
+        error::{
+            ListPodsWithLabel, ListStorageNodes, Result, SerializeStateSnapshot, WriteStateSnapshot,
+        },
+    },
+    upgrade::{
+        context::UpgradeContext,
+        utils::{list_all_pods, list_volumes, pod_is_ready},
+    },
+};
+use k8s_openapi::api::core::v1::Pod;
+use kube::{api::ListParams, ResourceExt};
+use openapi::models::{CordonDrainState, Node, NodeStatus, ReplicaState, Volume, VolumeStatus};
+use serde::Serialize;
+use snafu::ResultExt;
+use std::{path::Path, time::SystemTime};
+use tracing::{info, warn};
+
+/// This is an io-engine Pod in the pre-upgrade state snapshot.
+#[derive(Serialize, Debug)]
+#[serde(rename_all(serialize = "camelCase"))]
+struct IoEnginePodState {
+    pod_name: String,
+    node_name: Option<String>,
+    /// The value of the Pod's chart version label, if it has one.
+    version: Option<String>,
+    phase: Option<String>,
+    ready: bool,
+}
+
+impl IoEnginePodState {
+    /// Returns the state of the io-engine Pod, whose chart version label has the key
+    /// 'version_label_key'.
+    fn new(pod: &Pod, version_label_key: &str) -> Self {
+        Self {
+            pod_name: pod.name_any(),
+            node_name: pod.spec.as_ref().and_then(|spec| spec.node_name.clone()),
+            version: pod.labels().get(version_label_key).cloned(),
+            phase: pod.status.as_ref().and_then(|status| status.phase.clone()),
+            ready: pod_is_ready(pod),
+        }
+    }
+}
+
+/// This is a storage Node in the pre-upgrade state snapshot, along with its cordons and drains.
+#[derive(Serialize, Debug)]
+#[serde(rename_all(serialize = "camelCase"))]
+struct StorageNodeState {
+    node_id: String,
+    status: Option<NodeStatus>,
+    cordon_drain_state: Option<CordonDrainState>,
+}
+
+impl From<Node> for StorageNodeState {
+    fn from(node: Node) -> Self {
+        Self {
+            node_id: node.id,
+            status: node.state.map(|state| state.status),
+            cordon_drain_state: node.spec.and_then(|spec| spec.cordondrainstate),
+        }
+    }
+}
+
+/// This is a volume replica in the pre-upgrade state snapshot.
+#[derive(Serialize, Debug)]
+#[serde(rename_all(serialize = "camelCase"))]
+struct VolumeReplicaState {
+    replica_id: String,
+    node_name: Option<String>,
+    pool_id: Option<String>,
+    state: ReplicaState,
+}
+
+/// This is a volume in the pre-upgrade state snapshot, along with where its target and its
+/// replicas are.
+#[derive(Serialize, Debug)]
+#[serde(rename_all(serialize = "camelCase"))]
+struct VolumeState {
+    volume_id: String,
+    status: VolumeStatus,
+    num_replicas: u8,
+    target_node: Option<String>,
+    replicas: Vec<VolumeReplicaState>,
+}
+
+impl From<Volume> for VolumeState {
+    fn from(volume: Volume) -> Self {
+        let mut replicas: Vec<VolumeReplicaState> = volume
+            .state
+            .replica_topology
+            .into_iter()
+            .map(|(replica_id, topology)| VolumeReplicaState {
+                replica_id,
+                node_name: topology.node,
+                pool_id: topology.pool,
+                state: topology.state,
+            })
+            .collect();
+        replicas.sort_by(|a, b| a.replica_id.cmp(&b.replica_id));
+        Self {
+            volume_id: volume.spec.uuid.to_string(),
+            status: volume.state.status,
+            num_replicas: volume.spec.num_replicas,
+            target_node: volume.state.target.map(|target| target.node),
+            replicas,
+        }
+    }
+}
+
+/// This is the state of the cluster before the data-plane upgrade, i.e. the io-engine Pods, the
+/// storage Nodes' cordons and the volumes' topology, for the operators to compare with if the
+/// upgrade goes wrong.
+#[derive(Serialize, Debug)]
+#[serde(rename_all(serialize = "camelCase"))]
+struct StateSnapshot {
+    /// The ID of the upgrade run.
+    run_id: String,
+    /// The RFC 3339 time at which the snapshot was taken.
+    timestamp: String,
+    from_version: String,
+    to_version: String,
+    io_engine_pods: Vec<IoEnginePodState>,
+    storage_nodes: Vec<StorageNodeState>,
+    volumes: Vec<VolumeState>,
+}
+
+/// Writes the state of the cluster before the data-plane upgrade to the JSON file at 'path'. A
+/// failure to take or to write the snapshot fails the upgrade only if the snapshot is required,
+/// it is logged otherwise.
+pub(crate) async fn write_state_snapshot(
+    ctx: &UpgradeContext<'_>,
+    path: &Path,
+    version_label_key: &str,
+    namespace: String,
+) -> Result<()> {
+    let required = ctx.opts.require_state_snapshot();
+    let result = take_state_snapshot(ctx, version_label_key, namespace).await;
+    let result = result.and_then(|snapshot| {
+        let snapshot = serde_json::to_vec_pretty(&snapshot).context(SerializeStateSnapshot)?;
+        std::fs::write(path, snapshot).context(WriteStateSnapshot {
+            path: path.to_path_buf(),
+        })
+    });
+
+    match result {
+        Ok(()) => {
+            info!(path = %path.display(), "Wrote the pre-upgrade state snapshot");
+            Ok(())
+        }
+        Err(error) if !required => {
+            warn!(
+                %error,
+                path = %path.display(),
+                "Failed to write the pre-upgrade state snapshot, carrying on with the upgrade"
+            );
+            Ok(())
+        }
+        Err(error) => Err(error),
+    }
+}
+
+/// Lists the io-engine Pods, the storage Nodes and the volumes.
+async fn take_state_snapshot(
+    ctx: &UpgradeContext<'_>,
+    version_label_key: &str,
+    namespace: String,
+) -> Result<StateSnapshot> {
+    let k8s_client = &ctx.k8s_client;
+    let rest_client = &ctx.rest_client;
+    let mut pods = list_all_pods(k8s_client, &ListParams::default().labels(IO_ENGINE_LABEL))
+        .await
+        .context(ListPodsWithLabel {
+            label: IO_ENGINE_LABEL.to_string(),
+            namespace,
+        })?;
+    pods.sort_by_key(|pod| pod.name_any());
+
+    let mut nodes = rest_client
+        .call(GET_NODES, |client| async move {
+            client.nodes_api().get_nodes(None).await
+        })
+        .await
+        .context(ListStorageNodes)?
+        .into_body();
+    nodes.sort_by(|a, b| a.id.cmp(&b.id));
+
+    let mut volumes = list_volumes(rest_client).await?;
+    volumes.sort_by_key(|volume| volume.spec.uuid);
+
+    Ok(StateSnapshot {
+        run_id: ctx.run.id.clone(),
+        timestamp: humantime::format_rfc3339_seconds(SystemTime::now()).to_string(),
+        from_version: ctx.upgrade_from_version.clone(),
+        to_version: ctx.upgrade_to_version.clone(),
+        io_engine_pods: pods
+            .iter()
+            .map(|pod| IoEnginePodState::new(pod, version_label_key))
+            .collect(),
+        storage_nodes: nodes.into_iter().map(StorageNodeState::from).collect(),
+        volumes: volumes.into_iter().map(VolumeState::from).collect(),
+    })
+}
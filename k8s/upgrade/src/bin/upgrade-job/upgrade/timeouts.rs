@@ -0,0 +1,213 @@
+use crate::common::{
+    api_stats::{GET_CONFIG_MAP, GET_REPLICAS},
+    error::{GetConfigMap, ParseTimeoutValue, Result},
+    kube_client::KubeClientSet,
+    rest_client::RestClientSet,
+};
+use snafu::ResultExt;
+use std::{
+    collections::BTreeMap,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+/// ConfigMap key for the timeout for a storage Node drain to complete.
+const DRAIN_TIMEOUT_KEY: &str = "drainTimeout";
+
+/// ConfigMap key for the timeout for volume rebuilds to complete.
+const REBUILD_TIMEOUT_KEY: &str = "rebuildTimeout";
+
+/// ConfigMap key for the timeout for an io-engine Pod to become Ready.
+const READINESS_TIMEOUT_KEY: &str = "readinessTimeout";
+
+/// ConfigMap key for the timeout for the control-plane Pods to become Ready.
+const CONTROL_PLANE_TIMEOUT_KEY: &str = "controlPlaneTimeout";
+
+/// These are the timeouts for each of the phases of a data-plane Node's upgrade. A value of None
+/// means that the phase waits indefinitely.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub(crate) struct PhaseTimeouts {
+    pub(crate) drain: Option<Duration>,
+    pub(crate) rebuild: Option<Duration>,
+    pub(crate) readiness: Option<Duration>,
+    pub(crate) control_plane: Option<Duration>,
+}
+
+impl PhaseTimeouts {
+    /// Overrides the timeout values with the ones found in the ConfigMap data. Keys which are
+    /// absent leave the existing value in place.
+    fn with_overrides(
+        mut self,
+        data: &BTreeMap<String, String>,
+        config_map_name: &str,
+    ) -> Result<Self> {
+        let parse = |key: &str| -> Result<Option<Duration>> {
+            data.get(key)
+                .map(|value| {
+                    humantime::parse_duration(value.trim()).context(ParseTimeoutValue {
+                        key: key.to_string(),
+                        value: value.clone(),
+                        config_map_name: config_map_name.to_string(),
+                    })
+                })
+                .transpose()
+        };
+
+        if let Some(drain) = parse(DRAIN_TIMEOUT_KEY)? {
+            self.drain = Some(drain);
+        }
+        if let Some(rebuild) = parse(REBUILD_TIMEOUT_KEY)? {
+            self.rebuild = Some(rebuild);
+        }
+        if let Some(readiness) = parse(READINESS_TIMEOUT_KEY)? {
+            self.readiness = Some(readiness);
+        }
+        if let Some(control_plane) = parse(CONTROL_PLANE_TIMEOUT_KEY)? {
+            self.control_plane = Some(control_plane);
+        }
+
+        Ok(self)
+    }
+}
+
+/// This keeps track of the PhaseTimeouts for the data-plane upgrade. If a ConfigMap is set,
+/// the timeouts are re-read from it every time refresh() is called, so that they may be tuned
+/// while the upgrade is in progress.
+pub(crate) struct PhaseTimeoutsSource {
+    defaults: PhaseTimeouts,
+    config_map_name: Option<String>,
+    current: Option<PhaseTimeouts>,
+}
+
+impl PhaseTimeoutsSource {
+    /// Creates a new PhaseTimeoutsSource. Values from the ConfigMap, if any, take precedence
+    /// over the defaults.
+    pub(crate) fn new(defaults: PhaseTimeouts, config_map_name: Option<String>) -> Self {
+        Self {
+            defaults,
+            config_map_name,
+            current: None,
+        }
+    }
+
+    /// Re-reads the timeouts from the ConfigMap and returns the updated values. Changes from
+    /// the previous set of values are logged. Failing to read the ConfigMap is an error only
+    /// when it is read for the first time, otherwise the previous values are retained.
+    pub(crate) async fn refresh(&mut self, k8s_client: &KubeClientSet) -> Result<PhaseTimeouts> {
+        let config_map_name = match self.config_map_name.as_ref() {
+            Some(name) => name,
+            None => {
+                return Ok(self.current.get_or_insert(self.defaults.clone()).clone());
+            }
+        };
+
+        let latest = match Self::read(k8s_client, config_map_name, self.defaults.clone()).await {
+            Ok(timeouts) => timeouts,
+            Err(error) => match self.current.as_ref() {
+                Some(current) => {
+                    warn!(
+                        %error,
+                        "Failed to re-read data-plane upgrade timeouts, retaining previous values"
+                    );
+                    return Ok(current.clone());
+                }
+                None => return Err(error),
+            },
+        };
+
+        match self.current.as_ref() {
+            Some(current) if current.ne(&latest) => {
+                info!(
+                    previous = ?current,
+                    current = ?latest,
+                    config_map.name = %config_map_name,
+                    "Data-plane upgrade timeouts changed"
+                );
+            }
+            None => {
+                info!(
+                    timeouts = ?latest,
+                    config_map.name = %config_map_name,
+                    "Using data-plane upgrade timeouts"
+                );
+            }
+            _ => {}
+        }
+
+        self.current = Some(latest.clone());
+        Ok(latest)
+    }
+
+    /// Reads the ConfigMap and generates the PhaseTimeouts from its data.
+    async fn read(
+        k8s_client: &KubeClientSet,
+        config_map_name: &str,
+        defaults: PhaseTimeouts,
+    ) -> Result<PhaseTimeouts> {
+        let config_map = k8s_client
+            .api_stats()
+            .timed(
+                GET_CONFIG_MAP,
+                k8s_client.configmaps_api().get(config_map_name),
+            )
+            .await
+            .context(GetConfigMap {
+                name: config_map_name.to_string(),
+            })?;
+
+        defaults.with_overrides(&config_map.data.unwrap_or_default(), config_map_name)
+    }
+}
+
+/// Returns the timeout if it has elapsed since the start Instant. This is always None when
+/// there is no timeout.
+pub(crate) fn timeout_exceeded(start: Instant, timeout: Option<Duration>) -> Option<Duration> {
+    timeout.filter(|timeout| start.elapsed().ge(timeout))
+}
+
+/// Computes a rebuild timeout for the Node from the total size of the replicas on it, and the
+/// expected rebuild throughput in MiB/s. This returns None if the replica sizes can't be
+/// determined, so that the fixed rebuild timeout may be used instead.
+pub(crate) async fn size_based_rebuild_timeout(
+    node_name: &str,
+    rest_client: &RestClientSet,
+    throughput_mib_per_sec: u64,
+) -> Option<Duration> {
+    let replicas = match rest_client
+        .call(GET_REPLICAS, |client| async move {
+            client.replicas_api().get_replicas().await
+        })
+        .await
+    {
+        Ok(replicas) => replicas.into_body(),
+        Err(error) => {
+            warn!(
+                %error,
+                node.name = %node_name,
+                "Failed to list replicas, using the fixed rebuild timeout"
+            );
+            return None;
+        }
+    };
+
+    let replica_bytes: u64 = replicas
+        .iter()
+        .filter(|replica| replica.node.eq(node_name))
+        .map(|replica| replica.size)
+        .sum();
+    if replica_bytes == 0 || throughput_mib_per_sec == 0 {
+        return None;
+    }
+
+    let timeout = Duration::from_secs_f64(
+        replica_bytes as f64 / (throughput_mib_per_sec * 1024 * 1024) as f64,
+    );
+    info!(
+        node.name = %node_name,
+        replica_bytes,
+        throughput_mib_per_sec,
+        rebuild.timeout = ?timeout,
+        "Computed rebuild timeout from the size of the Node's replicas"
+    );
+    Some(timeout)
+}
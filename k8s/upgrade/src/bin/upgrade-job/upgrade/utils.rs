@@ -1,13 +1,25 @@
-use crate::common::{
-    constants::CHART_VERSION_LABEL_KEY,
-    error::{
-        HelmChartVersionLabelHasNoValue, ListStorageVolumes, NoNamespaceInPod, Result, SemverParse,
+use crate::{
+    common::{
+        api_stats::{GET_POD_LOGS, GET_VOLUMES, LIST_PODS},
+        error::{
+            HelmChartVersionLabelHasNoValue, ListStorageVolumes, NoNamespaceInPod, Result,
+            SemverParse,
+        },
+        kube_client::KubeClientSet,
+        logging::WaitLogVerbosity,
+        rest_client::RestClientSet,
     },
-    rest_client::RestClientSet,
+    poll_status,
 };
+use async_trait::async_trait;
+use futures::{stream::BoxStream, StreamExt};
 use k8s_openapi::api::core::v1::Pod;
-use kube::{api::ObjectList, ResourceExt};
-use openapi::models::{Volume, VolumeStatus};
+use kube::{
+    api::{ListParams, LogParams},
+    runtime::watcher,
+    ResourceExt,
+};
+use openapi::models::{ReplicaState, Volume, VolumeStatus};
 use semver::Version;
 use snafu::ResultExt;
 use std::{collections::HashSet, time::Duration};
@@ -17,14 +29,19 @@ use tracing::{info, warn};
 #[derive(Default)]
 pub(crate) struct RebuildResult {
     pub(crate) rebuilding: bool,
+    /// The UUID of the volume which is rebuilding, if any.
+    pub(crate) rebuilding_volume: Option<String>,
     pub(crate) discarded_volumes: Vec<Volume>,
 }
 
-/// Function to check for any volume rebuild in progress across the cluster.
+/// Function to check for any volume rebuild in progress. If 'node_name' is set, only the volumes
+/// which have their target or a replica on the node are considered, otherwise the volumes across
+/// the cluster are.
 pub(crate) async fn rebuild_result(
     rest_client: &RestClientSet,
     stale_volumes: &mut Vec<Volume>,
-    node_name: &str,
+    node_name: Option<&str>,
+    verbosity: WaitLogVerbosity,
 ) -> Result<RebuildResult> {
     loop {
         let unhealthy_volumes = list_unhealthy_volumes(rest_client, stale_volumes).await?;
@@ -33,31 +50,23 @@ pub(crate) async fn rebuild_result(
         }
 
         for volume in unhealthy_volumes.iter() {
-            let target = if let Some(target) = volume.state.target.as_ref() {
-                target
-            } else {
+            if volume.state.target.is_none() {
                 continue;
-            };
-
-            let mut volume_over_nodes = HashSet::new();
-            volume_over_nodes.insert(target.node.as_str());
-
-            for (_, topology) in volume.state.replica_topology.iter() {
-                if let Some(node) = topology.node.as_ref() {
-                    volume_over_nodes.insert(node);
-                }
             }
 
-            if volume_over_nodes.contains(node_name) {
-                match replica_rebuild_count(volume) {
+            if node_name.map_or(true, |node_name| {
+                nodes_hosting_volume(volume).contains(node_name)
+            }) {
+                match replica_rebuild_count(volume, verbosity) {
                     0 => {
                         for _i in 0 .. 11 {
                             // wait for a minute for any rebuild to start
                             tokio::time::sleep(Duration::from_secs(60_u64)).await;
-                            let count = replica_rebuild_count(volume);
+                            let count = replica_rebuild_count(volume, verbosity);
                             if count > 0 {
                                 return Ok(RebuildResult {
                                     rebuilding: true,
+                                    rebuilding_volume: Some(volume.spec.uuid.to_string()),
                                     discarded_volumes: stale_volumes.clone(),
                                 });
                             }
@@ -67,6 +76,7 @@ pub(crate) async fn rebuild_result(
                     _ => {
                         return Ok(RebuildResult {
                             rebuilding: true,
+                            rebuilding_volume: Some(volume.spec.uuid.to_string()),
                             discarded_volumes: stale_volumes.to_vec(),
                         })
                     }
@@ -76,16 +86,141 @@ pub(crate) async fn rebuild_result(
     }
     Ok(RebuildResult {
         rebuilding: false,
+        rebuilding_volume: None,
         discarded_volumes: stale_volumes.to_vec(),
     })
 }
 
+/// Returns true if any of the volumes hosted on the node is unhealthy, and has none of its
+/// replicas rebuilding yet, i.e. a rebuild may be yet to kick in. Volumes which can't be rebuilt
+/// are ignored.
+pub(crate) async fn rebuild_may_start(
+    rest_client: &RestClientSet,
+    node_name: &str,
+    verbosity: WaitLogVerbosity,
+) -> Result<bool> {
+    Ok(list_unhealthy_volumes(rest_client, &[])
+        .await?
+        .iter()
+        .filter(|volume| volume.state.target.is_some())
+        .filter(|volume| volume_may_rebuild(volume))
+        .filter(|volume| nodes_hosting_volume(volume).contains(node_name))
+        .any(|volume| replica_rebuild_count(volume, verbosity) == 0))
+}
+
+/// Returns true if the volume is configured with more than one replica. A volume with a single
+/// replica is never rebuilt, as there's no other replica to rebuild it from.
+pub(crate) fn volume_may_rebuild(volume: &Volume) -> bool {
+    volume.spec.num_replicas > 1
+}
+
+/// Returns the names of the nodes which host the volume's target and its replicas.
+pub(crate) fn nodes_hosting_volume(volume: &Volume) -> HashSet<&str> {
+    let mut volume_over_nodes = HashSet::new();
+    if let Some(target) = volume.state.target.as_ref() {
+        volume_over_nodes.insert(target.node.as_str());
+    }
+
+    for topology in volume.state.replica_topology.values() {
+        if let Some(node) = topology.node.as_ref() {
+            volume_over_nodes.insert(node.as_str());
+        }
+    }
+
+    volume_over_nodes
+}
+
+/// Returns the UUIDs of the volumes which have their target or a replica on the node.
+pub(crate) async fn volumes_hosted_on_node(
+    rest_client: &RestClientSet,
+    node_name: &str,
+) -> Result<Vec<String>> {
+    Ok(list_volumes(rest_client)
+        .await?
+        .iter()
+        .filter(|volume| nodes_hosting_volume(volume).contains(node_name))
+        .map(|volume| volume.spec.uuid.to_string())
+        .collect())
+}
+
+/// Returns the UUIDs of the volumes which have a replica on the node.
+pub(crate) async fn volumes_with_replica_on_node(
+    rest_client: &RestClientSet,
+    node_name: &str,
+) -> Result<Vec<String>> {
+    Ok(list_volumes(rest_client)
+        .await?
+        .into_iter()
+        .filter(|volume| {
+            volume
+                .state
+                .replica_topology
+                .values()
+                .any(|topology| topology.node.as_deref() == Some(node_name))
+        })
+        .map(|volume| volume.spec.uuid.to_string())
+        .collect())
+}
+
+/// Returns the UUIDs of the volumes amongst 'volume_uuids' which have fewer Online replicas than
+/// their configured replica count. Volumes which no longer exist are ignored.
+pub(crate) async fn volumes_below_replica_count(
+    rest_client: &RestClientSet,
+    volume_uuids: &[String],
+) -> Result<Vec<String>> {
+    Ok(list_volumes(rest_client)
+        .await?
+        .into_iter()
+        .filter(|volume| volume_uuids.contains(&volume.spec.uuid.to_string()))
+        .filter(|volume| {
+            let online_replicas = volume
+                .state
+                .replica_topology
+                .values()
+                .filter(|topology| matches!(topology.state, ReplicaState::Online))
+                .count();
+            online_replicas < volume.spec.num_replicas as usize
+        })
+        .map(|volume| volume.spec.uuid.to_string())
+        .collect())
+}
+
+/// Returns the UUIDs of the volumes out of 'volume_uuids' which are Faulted.
+pub(crate) async fn faulted_volumes(
+    rest_client: &RestClientSet,
+    volume_uuids: &[String],
+) -> Result<Vec<String>> {
+    Ok(list_volumes(rest_client)
+        .await?
+        .into_iter()
+        .filter(|volume| volume_uuids.contains(&volume.spec.uuid.to_string()))
+        .filter(|volume| matches!(volume.state.status, VolumeStatus::Faulted))
+        .map(|volume| volume.spec.uuid.to_string())
+        .collect())
+}
+
 /// Return the list of unhealthy volumes.
 pub(crate) async fn list_unhealthy_volumes(
     rest_client: &RestClientSet,
     discarded_volumes: &[Volume],
 ) -> Result<Vec<Volume>> {
-    let mut unhealthy_volumes: Vec<Volume> = Vec::new();
+    let mut unhealthy_volumes: Vec<Volume> = list_volumes(rest_client)
+        .await?
+        .into_iter()
+        .filter(|volume| {
+            matches!(
+                volume.state.status,
+                VolumeStatus::Faulted | VolumeStatus::Degraded
+            )
+        })
+        .collect();
+    unhealthy_volumes.retain(|v| !discarded_volumes.contains(v));
+    Ok(unhealthy_volumes)
+}
+
+/// Return the list of all of the volumes.
+pub(crate) async fn list_volumes(rest_client: &RestClientSet) -> Result<Vec<Volume>> {
+    let mut all_volumes: Vec<Volume> = Vec::new();
     // The number of volumes to get per request.
     let max_entries = 200;
     let mut starting_token = Some(0_isize);
@@ -93,28 +228,70 @@ pub(crate) async fn list_unhealthy_volumes(
     // The last paginated request will set the `starting_token` to `None`.
     while starting_token.is_some() {
         let vols = rest_client
-            .volumes_api()
-            .get_volumes(max_entries, None, starting_token)
+            .call(GET_VOLUMES, |client| async move {
+                client
+                    .volumes_api()
+                    .get_volumes(max_entries, None, starting_token)
+                    .await
+            })
             .await
             .context(ListStorageVolumes)?;
 
         let volumes = vols.into_body();
         starting_token = volumes.next_token;
-        for volume in volumes.entries {
-            match volume.state.status {
-                VolumeStatus::Faulted | VolumeStatus::Degraded => {
-                    unhealthy_volumes.push(volume);
-                }
-                _ => continue,
-            }
+        all_volumes.extend(volumes.entries);
+    }
+    Ok(all_volumes)
+}
+
+/// Returns the volume's status and the nodes and the states of its replicas, e.g.
+/// "<uuid> (Degraded, replicas: node-a Online, node-b Faulted)", for the wait logs.
+pub(crate) fn volume_state(volume: &Volume) -> String {
+    let replicas: Vec<String> = volume
+        .state
+        .replica_topology
+        .values()
+        .map(|topology| {
+            format!(
+                "{} {:?}",
+                topology.node.as_deref().unwrap_or("unknown"),
+                topology.state
+            )
+        })
+        .collect();
+    format!(
+        "{} ({:?}, replicas: {})",
+        volume.spec.uuid,
+        volume.state.status,
+        replicas.join(", ")
+    )
+}
+
+/// Logs the states of the volumes which 'include' picks, for the verbose wait logs. A failure to
+/// list the volumes is only logged, as the wait doesn't depend on it.
+pub(crate) async fn log_volume_states(
+    node_name: &str,
+    rest_client: &RestClientSet,
+    include: impl Fn(&Volume) -> bool,
+) {
+    match list_volumes(rest_client).await {
+        Ok(volumes) => {
+            let volumes: Vec<String> = volumes
+                .iter()
+                .filter(|volume| include(volume))
+                .map(volume_state)
+                .collect();
+            info!(node.name = %node_name, ?volumes, "Current state of the volumes");
+        }
+        Err(error) => {
+            warn!(%error, node.name = %node_name, "Failed to list the volumes' states");
         }
     }
-    unhealthy_volumes.retain(|v| !discarded_volumes.contains(v));
-    Ok(unhealthy_volumes)
 }
 
-/// Count of number of replica rebuilding.
-pub(crate) fn replica_rebuild_count(volume: &Volume) -> i32 {
+/// Count of number of replica rebuilding. The count is logged at the level which 'verbosity'
+/// picks, if any replica is rebuilding.
+pub(crate) fn replica_rebuild_count(volume: &Volume, verbosity: WaitLogVerbosity) -> i32 {
     let mut rebuild_count = 0;
     if let Some(target) = &volume.state.target {
         for child in target.children.iter() {
@@ -123,7 +300,8 @@ pub(crate) fn replica_rebuild_count(volume: &Volume) -> i32 {
             }
         }
         if rebuild_count > 0 {
-            info!(
+            poll_status!(
+                verbosity,
                 "Rebuilding {} of {} replicas for volume {}",
                 rebuild_count,
                 target.children.len(),
@@ -134,15 +312,119 @@ pub(crate) fn replica_rebuild_count(volume: &Volume) -> i32 {
     rebuild_count
 }
 
-/// This function returns 'true' only if all of the containers in the Pods contained in the
-/// ObjectList<Pod> have their Ready status.condition value set to true.
-pub(crate) fn all_pods_are_ready(pod_list: ObjectList<Pod>) -> bool {
+/// The number of Pods to list per request.
+const LIST_PODS_PAGE_SIZE: u32 = 500;
+
+/// Lists all of the Pods which match 'list_params', one page at a time. The API server may not
+/// return all of the Pods in one response, the rest are listed using the continue token.
+pub(crate) async fn list_all_pods(
+    k8s_client: &KubeClientSet,
+    list_params: &ListParams,
+) -> kube::Result<Vec<Pod>> {
+    let mut list_params = list_params.clone().limit(LIST_PODS_PAGE_SIZE);
+    let mut pods: Vec<Pod> = Vec::new();
+
+    // The last page of the list has no continue token.
+    loop {
+        let page = k8s_client
+            .api_stats()
+            .timed(LIST_PODS, k8s_client.pods_api().list(&list_params))
+            .await?;
+        pods.extend(page.items);
+        match page.metadata.continue_ {
+            Some(token) if !token.is_empty() => {
+                list_params = list_params.continue_token(token.as_str())
+            }
+            _ => break,
+        }
+    }
+    Ok(pods)
+}
+
+/// These are the Pod calls which the readiness checks make, so that the checks don't depend on
+/// the Kubernetes API client directly.
+#[async_trait]
+pub(crate) trait PodInspector: Sync {
+    /// Lists all of the Pods in the namespace which match 'list_params'.
+    async fn list_pods(&self, list_params: &ListParams) -> kube::Result<Vec<Pod>>;
+
+    /// Gets the logs of the Pod with the name 'pod_name'.
+    async fn pod_logs(&self, pod_name: &str, log_params: &LogParams) -> kube::Result<String>;
+
+    /// Watches the Pods in the namespace which match 'watch_config', for the readiness checks to
+    /// react to their changes. This is None if the Pods can't be watched, the checks poll then.
+    fn watch_pods(
+        &self,
+        _watch_config: watcher::Config,
+    ) -> Option<BoxStream<'static, watcher::Result<watcher::Event<Pod>>>> {
+        None
+    }
+}
+
+#[async_trait]
+impl PodInspector for KubeClientSet {
+    async fn list_pods(&self, list_params: &ListParams) -> kube::Result<Vec<Pod>> {
+        list_all_pods(self, list_params).await
+    }
+
+    async fn pod_logs(&self, pod_name: &str, log_params: &LogParams) -> kube::Result<String> {
+        self.api_stats()
+            .timed(GET_POD_LOGS, self.pods_api().logs(pod_name, log_params))
+            .await
+    }
+
+    fn watch_pods(
+        &self,
+        watch_config: watcher::Config,
+    ) -> Option<BoxStream<'static, watcher::Result<watcher::Event<Pod>>>> {
+        Some(watcher(self.pods_api().clone(), watch_config).boxed())
+    }
+}
+
+/// Returns true if the Pod's Ready condition is true.
+pub(crate) fn pod_is_ready(pod: &Pod) -> bool {
+    pod.status
+        .as_ref()
+        .and_then(|status| status.conditions.as_ref())
+        .is_some_and(|conditions| {
+            conditions
+                .iter()
+                .any(|condition| condition.type_.eq("Ready") && condition.status.eq("True"))
+        })
+}
+
+/// Returns true if the Pod is in the terminal Succeeded or Failed phase, e.g. if it was evicted.
+pub(crate) fn pod_is_terminated(pod: &Pod) -> bool {
+    pod.status
+        .as_ref()
+        .and_then(|status| status.phase.as_deref())
+        .is_some_and(|phase| matches!(phase, "Succeeded" | "Failed"))
+}
+
+/// This function returns 'true' only if all of the containers in the Pods have their Ready
+/// status.condition value set to true. The Pods in a terminal phase, e.g. the evicted ones, are
+/// left out, as they are never Ready again, but there has to be at least one Pod which isn't.
+pub(crate) fn all_pods_are_ready(pod_list: Vec<Pod>) -> bool {
     let not_ready_warning = |pod_name: &String, namespace: &String| {
         warn!(
             "Couldn't verify the ready condition of Pod '{}' in namespace '{}' to be true",
             pod_name, namespace
         );
     };
+    let pods_listed = !pod_list.is_empty();
+    let (terminated_pods, pod_list): (Vec<Pod>, Vec<Pod>) =
+        pod_list.into_iter().partition(pod_is_terminated);
+    for pod in terminated_pods.iter() {
+        info!(
+            pod.name = %pod.name_any(),
+            "Ignoring Pod in a terminal phase for the readiness check"
+        );
+    }
+    if pods_listed && pod_list.is_empty() {
+        warn!("All of the Pods are in a terminal phase, none of them can be Ready");
+        return false;
+    }
+
     for pod in pod_list.into_iter() {
         match &pod
             .status
@@ -177,15 +459,17 @@ pub(crate) fn all_pods_are_ready(pod_list: ObjectList<Pod>) -> bool {
 /// chart.
 pub(crate) async fn data_plane_is_upgraded(
     to_version: &str,
-    io_engine_pod_list: &ObjectList<Pod>,
+    version_label_key: &str,
+    io_engine_pod_list: &[Pod],
 ) -> Result<bool> {
     let to_version_requirement: Version = Version::parse(to_version).context(SemverParse {
         version_string: to_version.to_string(),
     })?;
 
     for pod in io_engine_pod_list {
-        let version_str = pod.labels().get(CHART_VERSION_LABEL_KEY).ok_or(
+        let version_str = pod.labels().get(version_label_key).ok_or(
             HelmChartVersionLabelHasNoValue {
+                label_key: version_label_key.to_string(),
                 pod_name: pod.name_any(),
                 namespace: pod.namespace().ok_or(
                     NoNamespaceInPod {
@@ -208,3 +492,123 @@ pub(crate) async fn data_plane_is_upgraded(
 
     Ok(true)
 }
+
+/// This is a PodInspector which lists the Pods it was created with, for the tests of the
+/// readiness checks. Only the equality-based label selectors and the spec.nodeName field
+/// selector, which the readiness checks use, are matched.
+#[cfg(test)]
+pub(crate) struct MockPodInspector {
+    pods: Vec<Pod>,
+}
+
+#[cfg(test)]
+impl MockPodInspector {
+    /// Creates a MockPodInspector which lists the Pods 'pods'.
+    pub(crate) fn new(pods: Vec<Pod>) -> Self {
+        Self { pods }
+    }
+
+    /// Returns true if the Pod matches the label and field selectors of 'list_params'.
+    fn matches(pod: &Pod, list_params: &ListParams) -> bool {
+        let labels_match = list_params
+            .label_selector
+            .as_deref()
+            .map_or(true, |selector| {
+                selector
+                    .split(',')
+                    .all(|requirement| match requirement.split_once('=') {
+                        Some((key, value)) => {
+                            pod.labels().get(key).is_some_and(|label| label.eq(value))
+                        }
+                        None => pod.labels().contains_key(requirement),
+                    })
+            });
+        let fields_match = list_params
+            .field_selector
+            .as_deref()
+            .map_or(true, |selector| {
+                selector
+                    .split(',')
+                    .all(|requirement| match requirement.split_once('=') {
+                        Some(("spec.nodeName", node)) => {
+                            pod.spec.as_ref().and_then(|spec| spec.node_name.as_deref())
+                                == Some(node)
+                        }
+                        _ => false,
+                    })
+            });
+        labels_match && fields_match
+    }
+}
+
+#[cfg(test)]
+#[async_trait]
+impl PodInspector for MockPodInspector {
+    async fn list_pods(&self, list_params: &ListParams) -> kube::Result<Vec<Pod>> {
+        Ok(self
+            .pods
+            .iter()
+            .filter(|pod| Self::matches(pod, list_params))
+            .cloned()
+            .collect())
+    }
+
+    async fn pod_logs(&self, _pod_name: &str, _log_params: &LogParams) -> kube::Result<String> {
+        Ok(String::new())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    /// Returns an io-engine Pod in the phase 'phase', which is Ready if 'ready' is set.
+    fn pod(name: &str, phase: &str, reason: Option<&str>, ready: bool) -> Pod {
+        serde_json::from_value(json!({
+            "apiVersion": "v1",
+            "kind": "Pod",
+            "metadata": { "name": name, "namespace": "mayastor" },
+            "spec": { "nodeName": "worker-1", "containers": [{ "name": "io-engine" }] },
+            "status": {
+                "phase": phase,
+                "reason": reason,
+                "conditions": [{ "type": "Ready", "status": if ready { "True" } else { "False" } }],
+            },
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn evicted_pod_is_ignored_alongside_a_ready_pod() {
+        let pods = vec![
+            pod("io-engine-evicted", "Failed", Some("Evicted"), false),
+            pod("io-engine-running", "Running", None, true),
+        ];
+        assert!(all_pods_are_ready(pods));
+    }
+
+    #[test]
+    fn completed_pod_is_ignored_alongside_a_ready_pod() {
+        let pods = vec![
+            pod("io-engine-completed", "Succeeded", None, false),
+            pod("io-engine-running", "Running", None, true),
+        ];
+        assert!(all_pods_are_ready(pods));
+    }
+
+    #[test]
+    fn evicted_pod_doesnt_hide_an_unready_pod() {
+        let pods = vec![
+            pod("io-engine-evicted", "Failed", Some("Evicted"), false),
+            pod("io-engine-running", "Running", None, false),
+        ];
+        assert!(!all_pods_are_ready(pods));
+    }
+
+    #[test]
+    fn only_evicted_pods_are_not_ready() {
+        let pods = vec![pod("io-engine-evicted", "Failed", Some("Evicted"), false)];
+        assert!(!all_pods_are_ready(pods));
+    }
+}
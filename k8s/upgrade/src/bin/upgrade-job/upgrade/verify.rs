@@ -0,0 +1,131 @@
+use crate::{
+    common::{
+        api_stats::{ApiStats, GET_NODES},
+        constants::{AGENT_CORE_LABEL, IO_ENGINE_LABEL, PRODUCT},
+        error::{ListPodsWithLabel, ListStorageNodes, Result, UpgradeVerificationFailed},
+        kube_client::KubeClientSet,
+        rest_client::RestClientSet,
+    },
+    opts::CliArgs,
+    upgrade::utils::{list_all_pods, pod_is_ready},
+};
+use kube::{api::ListParams, ResourceExt};
+use openapi::models::CordonDrainState;
+use snafu::ResultExt;
+use std::sync::Arc;
+use tracing::info;
+use utils::API_REST_LABEL;
+
+/// Checks that the cluster is in the expected state after an upgrade to 'upgrade_to_version',
+/// without changing anything: the io-engine and the control-plane Pods are on the target version,
+/// the io-engine Pods are Ready, and no storage Node is left with the upgrade drain labels. The
+/// discrepancies are printed to stdout, and fail the check with UpgradeVerificationFailed. The API
+/// calls are recorded in 'api_stats'.
+pub(crate) async fn verify_upgrade(
+    opts: &CliArgs,
+    upgrade_to_version: &str,
+    api_stats: &Arc<ApiStats>,
+) -> Result<()> {
+    let namespace = opts.namespace();
+    let drain_labels = opts.drain_labels();
+    let version_label_key = opts.chart_version_label_key();
+
+    let k8s_client = KubeClientSet::builder()
+        .with_namespace(namespace.clone())
+        .with_kubeconfig(opts.kubeconfig(), opts.kube_context())
+        .with_api_stats(api_stats.clone())
+        .build()
+        .await?;
+    let rest_client = RestClientSet::new_with_url_and_auth(
+        opts.rest_endpoint(),
+        opts.rest_token_file(),
+        opts.rest_ca_cert_file(),
+    )?
+    .with_api_stats(api_stats.clone());
+
+    let mut discrepancies: Vec<String> = Vec::new();
+
+    for (component, label) in [
+        ("io-engine", IO_ENGINE_LABEL),
+        ("agent-core", AGENT_CORE_LABEL),
+        ("api-rest", API_REST_LABEL),
+    ] {
+        let pods = list_all_pods(&k8s_client, &ListParams::default().labels(label))
+            .await
+            .context(ListPodsWithLabel {
+                label: label.to_string(),
+                namespace: namespace.clone(),
+            })?;
+        if pods.is_empty() {
+            discrepancies.push(format!("No {component} Pods found"));
+        }
+
+        for pod in pods.iter() {
+            match pod.labels().get(version_label_key.as_str()) {
+                Some(version) if version.eq(upgrade_to_version) => {}
+                Some(version) => discrepancies.push(format!(
+                    "{component} Pod {} is on version {version}",
+                    pod.name_any()
+                )),
+                None => discrepancies.push(format!(
+                    "{component} Pod {} has no version label",
+                    pod.name_any()
+                )),
+            }
+            // The control-plane Pods' readiness is left to the control-plane's own checks.
+            if label.eq(IO_ENGINE_LABEL) && !pod_is_ready(pod) {
+                discrepancies.push(format!("{component} Pod {} is not Ready", pod.name_any()));
+            }
+        }
+    }
+
+    let nodes = rest_client
+        .call(GET_NODES, |client| async move {
+            client.nodes_api().get_nodes(None).await
+        })
+        .await
+        .context(ListStorageNodes)?
+        .into_body();
+    for node in nodes.iter() {
+        let labels = match node
+            .spec
+            .as_ref()
+            .and_then(|spec| spec.cordondrainstate.as_ref())
+        {
+            Some(CordonDrainState::cordonedstate(state)) => state.cordonlabels.clone(),
+            Some(CordonDrainState::drainingstate(state)) => {
+                [state.cordonlabels.clone(), state.drainlabels.clone()].concat()
+            }
+            Some(CordonDrainState::drainedstate(state)) => {
+                [state.cordonlabels.clone(), state.drainlabels.clone()].concat()
+            }
+            None => Vec::new(),
+        };
+        let upgrade_labels: Vec<&String> = labels
+            .iter()
+            .filter(|label| drain_labels.is_upgrade_drain_label(label))
+            .collect();
+        if !upgrade_labels.is_empty() {
+            discrepancies.push(format!(
+                "{PRODUCT} Node {} still carries the upgrade drain labels {upgrade_labels:?}",
+                node.id
+            ));
+        }
+    }
+
+    if discrepancies.is_empty() {
+        println!("The cluster is upgraded to version {upgrade_to_version}");
+        info!(to_version = %upgrade_to_version, "Verified the upgrade");
+        return Ok(());
+    }
+
+    println!("The cluster is not fully upgraded to version {upgrade_to_version}:");
+    for discrepancy in discrepancies.iter() {
+        println!("  {discrepancy}");
+    }
+    UpgradeVerificationFailed {
+        upgrade_to_version,
+        discrepancies,
+    }
+    .fail()
+}
@@ -0,0 +1,123 @@
+use crate::{
+    common::{
+        api_stats::{LIST_PERSISTENT_VOLUMES, LIST_VOLUME_ATTACHMENTS},
+        error::{ListPersistentVolumes, ListVolumeAttachments, Result, VolumeAttachmentTimeout},
+        kube_client::KubeClientSet,
+    },
+    upgrade::timeouts::timeout_exceeded,
+};
+use kube::{api::ListParams, ResourceExt};
+use snafu::ResultExt;
+use std::{
+    collections::HashSet,
+    time::{Duration, Instant},
+};
+use tracing::{info, warn};
+
+/// Wait for the VolumeAttachments of the PersistentVolumes backed by the volumes 'volume_uuids'
+/// to be attached, i.e. for the workloads consuming the volumes to have their volumes attached
+/// again after the Node's io-engine Pod restart.
+pub(crate) async fn wait_for_volume_attachments(
+    node_name: &str,
+    volume_uuids: &[String],
+    k8s_client: &KubeClientSet,
+    timeout: Option<Duration>,
+) -> Result<()> {
+    if volume_uuids.is_empty() {
+        return Ok(());
+    }
+
+    let start = Instant::now();
+    loop {
+        let attachments = unattached_volume_attachments(volume_uuids, k8s_client).await?;
+        if attachments.is_empty() {
+            break;
+        }
+
+        if let Some(timeout) = timeout_exceeded(start, timeout) {
+            return VolumeAttachmentTimeout {
+                node_name: node_name.to_string(),
+                timeout,
+                attachments,
+            }
+            .fail();
+        }
+        info!(
+            node.name = %node_name,
+            ?attachments,
+            "Waiting for the VolumeAttachments of the Node's volumes to be attached"
+        );
+        tokio::time::sleep(Duration::from_secs(5_u64)).await;
+    }
+
+    info!(node.name = %node_name, "VolumeAttachments of the Node's volumes are attached");
+    Ok(())
+}
+
+/// Returns the names of the VolumeAttachments of the volumes 'volume_uuids' which aren't
+/// attached, or which have an attach error.
+async fn unattached_volume_attachments(
+    volume_uuids: &[String],
+    k8s_client: &KubeClientSet,
+) -> Result<Vec<String>> {
+    let persistent_volumes = k8s_client
+        .api_stats()
+        .timed(
+            LIST_PERSISTENT_VOLUMES,
+            k8s_client
+                .persistent_volumes_api()
+                .list(&ListParams::default()),
+        )
+        .await
+        .context(ListPersistentVolumes)?;
+
+    // The CSI volume handle of a PersistentVolume is the volume's UUID.
+    let persistent_volume_names: HashSet<String> = persistent_volumes
+        .iter()
+        .filter(|pv| {
+            pv.spec
+                .as_ref()
+                .and_then(|spec| spec.csi.as_ref())
+                .map(|csi| volume_uuids.contains(&csi.volume_handle))
+                .unwrap_or(false)
+        })
+        .map(|pv| pv.name_any())
+        .collect();
+
+    let volume_attachments = k8s_client
+        .api_stats()
+        .timed(
+            LIST_VOLUME_ATTACHMENTS,
+            k8s_client
+                .volume_attachments_api()
+                .list(&ListParams::default()),
+        )
+        .await
+        .context(ListVolumeAttachments)?;
+
+    Ok(volume_attachments
+        .iter()
+        .filter(|attachment| {
+            attachment
+                .spec
+                .source
+                .persistent_volume_name
+                .as_ref()
+                .map(|pv_name| persistent_volume_names.contains(pv_name))
+                .unwrap_or(false)
+        })
+        .filter(|attachment| {
+            let status = attachment.status.as_ref();
+            if let Some(error) = status.and_then(|status| status.attach_error.as_ref()) {
+                warn!(
+                    volume_attachment.name = %attachment.name_any(),
+                    error = ?error.message,
+                    "VolumeAttachment has an attach error"
+                );
+                return true;
+            }
+            !status.map(|status| status.attached).unwrap_or(false)
+        })
+        .map(|attachment| attachment.name_any())
+        .collect())
+}
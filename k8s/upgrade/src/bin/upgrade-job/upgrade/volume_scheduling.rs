@@ -0,0 +1,59 @@
+use crate::common::{
+    api_stats::{DELETE_VOLUME, PUT_VOLUME},
+    constants::PRODUCT,
+    error::{CreateTestVolume, DeleteTestVolume, Result},
+    rest_client::RestClientSet,
+};
+use openapi::{
+    apis::Uuid,
+    models::{CreateVolumeBody, VolumePolicy},
+};
+use snafu::ResultExt;
+use tracing::info;
+
+/// The size of the test volume, in bytes.
+const TEST_VOLUME_SIZE: u64 = 20 * 1024 * 1024;
+
+/// Verifies that the control plane is functional, by creating a small, thinly provisioned,
+/// single-replica test volume and deleting it again. This catches a control plane whose Pods
+/// are Ready but which can't actually service requests.
+pub(crate) async fn verify_volume_scheduling(rest_client: &RestClientSet) -> Result<()> {
+    let volume_uuid = Uuid::new_v4();
+    let body = CreateVolumeBody::new(VolumePolicy::new(false), 1, TEST_VOLUME_SIZE, true);
+
+    let created = rest_client
+        .call(PUT_VOLUME, |client| {
+            let body = body.clone();
+            async move { client.volumes_api().put_volume(&volume_uuid, body).await }
+        })
+        .await
+        .context(CreateTestVolume {
+            volume_uuid: volume_uuid.to_string(),
+        });
+
+    // The test volume may have been created even if the request failed, e.g. if the response
+    // was lost, so it is always deleted.
+    let deleted = rest_client
+        .call(DELETE_VOLUME, |client| async move {
+            client.volumes_api().del_volume(&volume_uuid).await
+        })
+        .await;
+
+    created?;
+    match deleted {
+        Ok(_) => {}
+        Err(openapi::clients::tower::Error::Response(response))
+            if response.status().as_u16() == 404 => {}
+        Err(error) => {
+            return Err(error).context(DeleteTestVolume {
+                volume_uuid: volume_uuid.to_string(),
+            })
+        }
+    }
+
+    info!(
+        volume.uuid = %volume_uuid,
+        "Verified that the {PRODUCT} control plane can create and delete volumes"
+    );
+    Ok(())
+}
@@ -0,0 +1,38 @@
+use serde::Serialize;
+
+/// These are the kinds of notable events which don't stop the data-plane upgrade.
+#[derive(Serialize, Clone, Copy, Debug, Eq, Ord, PartialEq, PartialOrd)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub(crate) enum UpgradeWarningKind {
+    /// A Kubernetes Event about a Node's upgrade couldn't be posted.
+    EventNotPosted,
+    /// A Node was left as it is, e.g. as it was upgraded already or it was removed.
+    NodeSkipped,
+    /// A volume replica's rebuild didn't progress for a while.
+    RebuildStalled,
+    /// A Node failed to upgrade, and the upgrade carried on with the rest of the Nodes.
+    NodeFailed,
+    /// A Node couldn't be uncordoned after its upgrade stopped.
+    UncordonFailed,
+}
+
+/// This is a notable event which doesn't stop the data-plane upgrade, for the upgrade report.
+#[derive(Serialize, Clone, Debug)]
+#[serde(rename_all(serialize = "camelCase"))]
+pub(crate) struct UpgradeWarning {
+    pub(crate) kind: UpgradeWarningKind,
+    /// The Node which the warning is about, if any.
+    node_name: Option<String>,
+    message: String,
+}
+
+impl UpgradeWarning {
+    /// This returns a warning of the kind 'kind', about the Node 'node_name' if any.
+    pub(crate) fn new(kind: UpgradeWarningKind, node_name: Option<&str>, message: String) -> Self {
+        Self {
+            kind,
+            node_name: node_name.map(ToString::to_string),
+            message,
+        }
+    }
+}
@@ -0,0 +1,140 @@
+use crate::{
+    common::{
+        api_stats::GET_K8S_NODE,
+        error::{GetK8sNode, Result},
+        kube_client::KubeClientSet,
+        rest_client::RestClientSet,
+    },
+    upgrade::{
+        pod_selection::NodePods,
+        utils::{list_volumes, nodes_hosting_volume},
+    },
+};
+use snafu::ResultExt;
+use std::collections::{HashMap, HashSet};
+use tracing::info;
+
+/// The label of the Kubernetes Node which identifies its failure domain.
+const ZONE_LABEL: &str = "topology.kubernetes.io/zone";
+
+/// This picks the Nodes which are upgraded together. Without concurrency, this is one Node at a
+/// time. With concurrency, it is at most one Node per zone, up to 'max_parallel' Nodes, none of
+/// which host the target or a replica of the same volume as any of the others. The Nodes which
+/// have no zone label are always upgraded on their own.
+pub(crate) struct ZoneWaves {
+    max_parallel: usize,
+    /// The zone of each of the Nodes which have been looked up so far.
+    zones: HashMap<String, Option<String>>,
+}
+
+impl ZoneWaves {
+    /// Creates a ZoneWaves which upgrades at most 'max_parallel' Nodes together.
+    pub(crate) fn new(max_parallel: usize) -> Self {
+        Self {
+            max_parallel: max_parallel.max(1),
+            zones: HashMap::new(),
+        }
+    }
+
+    /// Removes the Nodes which are upgraded next from the front of 'remaining', and returns
+    /// them. The order of the Nodes is retained, the first of the remaining Nodes is always
+    /// upgraded next.
+    pub(crate) async fn next_wave(
+        &mut self,
+        remaining: &mut Vec<NodePods>,
+        k8s_client: &KubeClientSet,
+        rest_client: &RestClientSet,
+    ) -> Result<Vec<NodePods>> {
+        if remaining.is_empty() {
+            return Ok(Vec::new());
+        }
+        if self.max_parallel == 1 {
+            return Ok(vec![remaining.remove(0)]);
+        }
+
+        let volume_nodes: Vec<HashSet<String>> = list_volumes(rest_client)
+            .await?
+            .iter()
+            .map(|volume| {
+                nodes_hosting_volume(volume)
+                    .into_iter()
+                    .map(ToString::to_string)
+                    .collect()
+            })
+            .collect();
+        let share_a_volume = |a: &str, b: &str| {
+            volume_nodes
+                .iter()
+                .any(|nodes| nodes.contains(a) && nodes.contains(b))
+        };
+
+        let mut wave: Vec<usize> = vec![0];
+        let mut wave_zones: HashSet<String> = HashSet::new();
+        match self
+            .zone(remaining[0].node_name.as_str(), k8s_client)
+            .await?
+        {
+            Some(zone) => wave_zones.insert(zone),
+            None => return Ok(vec![remaining.remove(0)]),
+        };
+
+        for index in 1 .. remaining.len() {
+            if wave.len() >= self.max_parallel {
+                break;
+            }
+            let node_name = remaining[index].node_name.clone();
+            let Some(zone) = self.zone(node_name.as_str(), k8s_client).await? else {
+                continue;
+            };
+            if wave_zones.contains(&zone)
+                || wave
+                    .iter()
+                    .any(|&i| share_a_volume(remaining[i].node_name.as_str(), &node_name))
+            {
+                continue;
+            }
+            wave_zones.insert(zone);
+            wave.push(index);
+        }
+
+        // The indices are removed from the back, so that the earlier ones remain valid.
+        let mut nodes: Vec<NodePods> = wave
+            .iter()
+            .rev()
+            .map(|&index| remaining.remove(index))
+            .collect();
+        nodes.reverse();
+
+        if nodes.len() > 1 {
+            let node_names: Vec<&str> = nodes.iter().map(|node| node.node_name.as_str()).collect();
+            info!(nodes = ?node_names, zones = ?wave_zones, "Upgrading nodes concurrently");
+        }
+        Ok(nodes)
+    }
+
+    /// Returns the zone of the Kubernetes Node, if it has a zone label.
+    async fn zone(
+        &mut self,
+        node_name: &str,
+        k8s_client: &KubeClientSet,
+    ) -> Result<Option<String>> {
+        if let Some(zone) = self.zones.get(node_name) {
+            return Ok(zone.clone());
+        }
+
+        let node = k8s_client
+            .api_stats()
+            .timed(GET_K8S_NODE, k8s_client.nodes_api().get(node_name))
+            .await
+            .context(GetK8sNode {
+                node_name: node_name.to_string(),
+            })?;
+        let zone = node
+            .metadata
+            .labels
+            .and_then(|mut labels| labels.remove(ZONE_LABEL));
+
+        self.zones.insert(node_name.to_string(), zone.clone());
+        Ok(zone)
+    }
+}
@@ -95,6 +95,7 @@ pub(crate) fn upgrade_job_cluster_role(
                     "create",
                     "get",
                     "list",
+                    "watch",
                     "delete",
                     "patch",
                     "deletecollection",
@@ -102,9 +103,27 @@ pub(crate) fn upgrade_job_cluster_role(
                 .into_vec(),
                 ..Default::default()
             },
+            PolicyRule {
+                api_groups: Some(vec![""].into_vec()),
+                resources: Some(vec!["pods/eviction"].into_vec()),
+                verbs: vec!["create"].into_vec(),
+                ..Default::default()
+            },
+            PolicyRule {
+                api_groups: Some(vec![""].into_vec()),
+                resources: Some(vec!["pods/log"].into_vec()),
+                verbs: vec!["get"].into_vec(),
+                ..Default::default()
+            },
             PolicyRule {
                 api_groups: Some(vec![""].into_vec()),
                 resources: Some(vec!["nodes"].into_vec()),
+                verbs: vec!["get", "list", "patch"].into_vec(),
+                ..Default::default()
+            },
+            PolicyRule {
+                api_groups: Some(vec!["storage.k8s.io"].into_vec()),
+                resources: Some(vec!["volumeattachments"].into_vec()),
                 verbs: vec!["get", "list"].into_vec(),
                 ..Default::default()
             },
@@ -114,6 +133,12 @@ pub(crate) fn upgrade_job_cluster_role(
                 verbs: vec!["get"].into_vec(),
                 ..Default::default()
             },
+            PolicyRule {
+                api_groups: Some(vec!["coordination.k8s.io"].into_vec()),
+                resources: Some(vec!["leases"].into_vec()),
+                verbs: vec!["get", "create", "update"].into_vec(),
+                ..Default::default()
+            },
             PolicyRule {
                 api_groups: Some(vec!["events.k8s.io"].into_vec()),
                 resources: Some(vec!["events"].into_vec()),